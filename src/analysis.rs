@@ -0,0 +1,646 @@
+use crate::binary::{self, Anchor};
+use crate::util::{self, EngineOptions, Engines, Function, Reference, TempDir};
+use r2pipe::open_pipe;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+/// longest an x86 instruction can be
+const MAX_INSTRUCTION: u64 = 15;
+/// bytes handed to capstone at a time, so a long function isn't decoded to its end on every
+/// branch
+const WINDOW: u64 = 256;
+/// what functions usually start with: endbr64, endbr32, then `push rbp; mov rbp, rsp` and
+/// `push ebp; mov ebp, esp`
+const PROLOGUES: &[&[u8]] = &[
+    &[0xf3, 0x0f, 0x1e, 0xfa],
+    &[0xf3, 0x0f, 0x1e, 0xfb],
+    &[0x55, 0x48, 0x89, 0xe5],
+    &[0x55, 0x89, 0xe5],
+];
+/// prefixes capstone puts in front of a mnemonic
+const PREFIXES: &[&str] = &["bnd", "notrack", "rep", "repz", "repe"];
+/// Ghidra post script writing every function the way r2's `aflj` lists them, with file offsets
+const GHIDRA_SCRIPT: &str = r#"import json
+from ghidra.program.model.block import BasicBlockModel
+
+def offset(address):
+    info = currentProgram.getMemory().getAddressSourceInfo(address)
+    return info.getFileOffset() if info else -1
+
+model = BasicBlockModel(currentProgram)
+functions = []
+for function in currentProgram.getFunctionManager().getFunctions(True):
+    entry = function.getEntryPoint()
+    if function.isExternal() or offset(entry) < 0:
+        continue
+    body = function.getBody()
+    blocks = model.getCodeBlocksContaining(body, monitor)
+    nbbs = 0
+    while blocks.hasNext():
+        blocks.next()
+        nbbs += 1
+    callrefs = []
+    for insn in currentProgram.getListing().getInstructions(body, True):
+        if insn.getFlowType().isCall():
+            for target in insn.getFlows():
+                if offset(target) >= 0:
+                    at = offset(insn.getAddress())
+                    callrefs.append({"addr": offset(target), "type": "CALL", "at": at})
+    functions.append({
+        "name": function.getName(),
+        "offset": offset(entry),
+        "size": body.getMaxAddress().subtract(entry) + 1,
+        "nbbs": nbbs,
+        "callrefs": callrefs,
+    })
+json.dump(functions, open(getScriptArgs()[0], "w"))
+"#;
+
+/// Something which finds the functions in a binary.
+pub trait Backend {
+    /// Every function in the file at `path` (whose contents are `data`), at file offsets.
+    fn functions(
+        &self,
+        path: &Path,
+        data: &[u8],
+        options: &EngineOptions,
+    ) -> Result<Vec<Function>, Box<dyn Error>>;
+}
+
+/// radare2's `aaa`, listed with `aflj`
+pub struct Radare2;
+
+impl Backend for Radare2 {
+    fn functions(
+        &self,
+        path: &Path,
+        data: &[u8],
+        _: &EngineOptions,
+    ) -> Result<Vec<Function>, Box<dyn Error>> {
+        let mut r2p = open_pipe!(Some(path.to_string_lossy()))?;
+        r2p.cmd("aaa")?;
+        let json = r2p.cmd("aflj")?;
+        Ok(at_file_offsets(serde_json::from_str(&json)?, data))
+    }
+}
+
+/// rizin, which lists functions the same way r2 does
+pub struct Rizin;
+
+impl Backend for Rizin {
+    fn functions(
+        &self,
+        path: &Path,
+        data: &[u8],
+        _: &EngineOptions,
+    ) -> Result<Vec<Function>, Box<dyn Error>> {
+        let json = util::run(
+            Command::new("rizin")
+                .args(["-q", "-c", "aaa; aflj"])
+                .arg(path),
+        )?;
+        Ok(at_file_offsets(serde_json::from_slice(&json)?, data))
+    }
+}
+
+/// Ghidra's `analyzeHeadless`, from `$GHIDRA_INSTALL_DIR/support` or the path, in a throwaway
+/// project
+pub struct Ghidra;
+
+impl Backend for Ghidra {
+    fn functions(
+        &self,
+        path: &Path,
+        _: &[u8],
+        _: &EngineOptions,
+    ) -> Result<Vec<Function>, Box<dyn Error>> {
+        let headless = std::env::var_os("GHIDRA_INSTALL_DIR")
+            .map(|x| Path::new(&x).join("support").join("analyzeHeadless"))
+            .unwrap_or_else(|| "analyzeHeadless".into());
+        // removed with the project and everything else in it once this returns
+        let dir = TempDir::new("ghidra")?;
+        let script = dir.join("ExportFunctions.py");
+        let output = dir.join("functions.json");
+        std::fs::write(&script, GHIDRA_SCRIPT)?;
+        util::run(
+            Command::new(&headless)
+                .arg(&dir.path)
+                .arg("project")
+                .arg("-import")
+                .arg(path)
+                .arg("-scriptPath")
+                .arg(&dir.path)
+                .arg("-postScript")
+                .arg("ExportFunctions.py")
+                .arg(&output)
+                .arg("-deleteProject"),
+        )?;
+        Ok(serde_json::from_slice(&std::fs::read(&output)?)?)
+    }
+}
+
+/// GNU objdump, which only knows the functions with symbols
+pub struct Objdump;
+
+impl Backend for Objdump {
+    fn functions(
+        &self,
+        path: &Path,
+        data: &[u8],
+        _: &EngineOptions,
+    ) -> Result<Vec<Function>, Box<dyn Error>> {
+        let text = util::run(Command::new("objdump").args(["-d", "-w"]).arg(path))?;
+        let functions = parse_objdump(&String::from_utf8_lossy(&text));
+        Ok(at_file_offsets(functions, data))
+    }
+}
+
+/// `functions` as a tool which loads the file (`data`) lists them, at virtual addresses, moved
+/// to file offsets.  Functions which don't start in the file are left out.  A file without
+/// segments, a raw blob, is loaded at 0.
+fn at_file_offsets(mut functions: Vec<Function>, data: &[u8]) -> Vec<Function> {
+    let segments = binary::segments(data);
+    if segments.is_empty() {
+        return functions;
+    }
+    let to_offset = |va: u64| {
+        segments
+            .iter()
+            .find(|(_, size, start)| (*start..start + size).contains(&va))
+            .map(|(offset, _, start)| va - start + offset)
+    };
+    functions.retain(|x| to_offset(x.offset as u64).is_some());
+    for function in &mut functions {
+        function.offset = to_offset(function.offset as u64).unwrap_or_default() as usize;
+        for reference in &mut function.callrefs {
+            reference.addr = to_offset(reference.addr).unwrap_or(reference.addr);
+            reference.at = to_offset(reference.at).unwrap_or(reference.at);
+        }
+    }
+    functions
+}
+
+/// `discover`, which needs nothing installed
+pub struct Native;
+
+impl Backend for Native {
+    fn functions(
+        &self,
+        _: &Path,
+        data: &[u8],
+        options: &EngineOptions,
+    ) -> Result<Vec<Function>, Box<dyn Error>> {
+        Ok(discover(data, &Engines::new(options.clone())?))
+    }
+}
+
+/// Which backend finds the functions, `--backend` or `backend` in the config.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    R2,
+    Rizin,
+    Ghidra,
+    Objdump,
+    Native,
+}
+
+impl BackendKind {
+    pub fn backend(self) -> Box<dyn Backend> {
+        match self {
+            BackendKind::R2 => Box::new(Radare2),
+            BackendKind::Rizin => Box::new(Rizin),
+            BackendKind::Ghidra => Box::new(Ghidra),
+            BackendKind::Objdump => Box::new(Objdump),
+            BackendKind::Native => Box::new(Native),
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r2" | "radare2" => Ok(BackendKind::R2),
+            "rizin" => Ok(BackendKind::Rizin),
+            "ghidra" => Ok(BackendKind::Ghidra),
+            "objdump" => Ok(BackendKind::Objdump),
+            "native" => Ok(BackendKind::Native),
+            _ => Err(format!(
+                "{} isn't a backend, try r2, rizin, ghidra, objdump or native",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BackendKind::R2 => "r2",
+            BackendKind::Rizin => "rizin",
+            BackendKind::Ghidra => "ghidra",
+            BackendKind::Objdump => "objdump",
+            BackendKind::Native => "native",
+        })
+    }
+}
+
+/// Functions in `objdump -d -w` output, at the virtual addresses it prints.  Basic blocks are
+/// counted from the jumps within each function.
+fn parse_objdump(text: &str) -> Vec<Function> {
+    let hex = |x: &str| u64::from_str_radix(x, 16).ok();
+    let mut functions = vec![];
+    // the function being read and where its blocks start
+    let mut current: Option<(Function, BTreeSet<u64>)> = None;
+    let mut finish = |current: Option<(Function, BTreeSet<u64>)>| {
+        if let Some((mut function, blocks)) = current {
+            let end = function.offset as u64 + function.size as u64;
+            function.nbbs = blocks.range(function.offset as u64..end).count();
+            functions.push(function);
+        }
+    };
+    for line in text.lines() {
+        // 0000000000001139 <main>:
+        if let Some(header) = line.strip_suffix(">:") {
+            let mut parts = header.splitn(2, " <");
+            if let (Some(start), Some(name)) = (parts.next().and_then(hex), parts.next()) {
+                finish(current.take());
+                let function = Function {
+                    name: name.to_string(),
+                    offset: start as usize,
+                    size: 0,
+                    nbbs: 0,
+                    callrefs: vec![],
+                    outside_file: false,
+                };
+                current = Some((function, std::iter::once(start).collect()));
+            }
+            continue;
+        }
+        //     113d:\te8 ee fe ff ff       \tcall   1030 <puts@plt>
+        let (function, blocks) = match current.as_mut() {
+            Some(current) => current,
+            None => continue,
+        };
+        let mut fields = line.trim_start().splitn(3, '\t');
+        let at = match fields.next().and_then(|x| hex(x.strip_suffix(':')?)) {
+            Some(at) => at,
+            None => continue,
+        };
+        let len = fields.next().unwrap_or_default().split_whitespace().count() as u64;
+        let text = fields.next().unwrap_or_default();
+        let next = at + len;
+        function.size = function.size.max((next - function.offset as u64) as usize);
+        let target = text.split_whitespace().nth(1).and_then(hex);
+        match flow(text) {
+            Flow::Call(_) => function.callrefs.extend(target.map(|addr| Reference {
+                addr,
+                kind: "CALL".to_string(),
+                at,
+            })),
+            Flow::Jump(_) => blocks.extend(target),
+            Flow::Branch(_) => blocks.extend(target.into_iter().chain(Some(next))),
+            _ => {}
+        }
+    }
+    finish(current);
+    functions
+}
+
+/// Where control goes after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    /// on to the next instruction
+    Next,
+    /// nowhere this can follow: a return, a trap or a byte which isn't code
+    Stop,
+    /// to the target if it's known, and nowhere else
+    Jump(Option<u64>),
+    /// to the target if it's known, or on to the next instruction
+    Branch(Option<u64>),
+    /// into the target if it's known and then on to the next instruction
+    Call(Option<u64>),
+}
+
+fn flow(text: &str) -> Flow {
+    let mut words = text.split_whitespace().skip_while(|x| PREFIXES.contains(x));
+    let mnemonic = words.next().unwrap_or_default();
+    // indirect targets (registers, memory) don't parse
+    let target = words.next().and_then(util::parse_address);
+    match mnemonic {
+        ".byte" | "hlt" | "ud2" | "int3" => Flow::Stop,
+        x if x.starts_with("ret") || x.starts_with("iret") => Flow::Stop,
+        "jmp" | "jmpq" | "ljmp" => Flow::Jump(target),
+        x if x.starts_with('j') || x.starts_with("loop") => Flow::Branch(target),
+        "call" | "callq" | "lcall" => Flow::Call(target),
+        _ => Flow::Next,
+    }
+}
+
+/// where a direct jump, branch or call goes, None for anything else
+pub fn branch_target(text: &str) -> Option<u64> {
+    match flow(text) {
+        Flow::Jump(target) | Flow::Branch(target) | Flow::Call(target) => target,
+        _ => None,
+    }
+}
+
+/// Function discovery without radare2, by following the code from the entry point and every
+/// symbol, the functions they call, and then anything left over which starts like a function.
+struct Finder<'a> {
+    data: &'a [u8],
+    engines: &'a Engines,
+    /// executable parts of the file, as their start and end offsets
+    ranges: Vec<(u64, u64)>,
+    /// every start seen so far, jumps to one of these are tail calls rather than followed
+    starts: BTreeSet<u64>,
+    functions: BTreeMap<u64, Function>,
+}
+
+impl<'a> Finder<'a> {
+    /// the end of the executable range `offset` is in
+    fn range_end(&self, offset: u64) -> Option<u64> {
+        self.ranges
+            .iter()
+            .find(|(start, end)| (*start..*end).contains(&offset))
+            .map(|(_, end)| *end)
+    }
+
+    /// whether `offset` is inside a function found already
+    fn covers(&self, offset: u64) -> bool {
+        self.functions
+            .range(..=offset)
+            .next_back()
+            .is_some_and(|(start, x)| offset < start + x.size as u64)
+    }
+
+    /// traverse each of `pending` and everything they call
+    fn follow(&mut self, mut pending: Vec<u64>) {
+        self.starts.extend(pending.iter().copied());
+        while let Some(start) = pending.pop() {
+            if self.functions.contains_key(&start) {
+                continue;
+            }
+            let end = match self.range_end(start) {
+                Some(end) => end,
+                None => continue,
+            };
+            let function = self.traverse(start, end);
+            for call in function.calls() {
+                if self.range_end(call).is_some() && self.starts.insert(call) {
+                    pending.push(call);
+                }
+            }
+            self.functions.insert(start, function);
+        }
+    }
+
+    /// Every instruction reachable from `start` without leaving the range ending at `end` or
+    /// jumping into another function, as an unnamed function from `start` to the furthest of
+    /// them.
+    fn traverse(&self, start: u64, end: u64) -> Function {
+        let mut blocks = BTreeSet::new();
+        let mut decoded = BTreeSet::new();
+        let mut callrefs = vec![];
+        let mut furthest = start;
+        let mut pending = vec![start];
+        let followed = |target: &u64| {
+            (start..end).contains(target) && (*target == start || !self.starts.contains(target))
+        };
+        while let Some(block) = pending.pop() {
+            if !blocks.insert(block) {
+                continue;
+            }
+            let mut at = block;
+            'block: while at < end {
+                let window_end = (at + WINDOW).min(end);
+                let decoded_window = self
+                    .engines
+                    .disassemble(&self.data[at as usize..window_end as usize], at);
+                if decoded_window.is_empty() {
+                    break;
+                }
+                for (bytes, text, _) in decoded_window {
+                    let next = at + bytes.len() as u64;
+                    // the window may have cut the last few instructions short, they're decoded
+                    // again at the start of the next one
+                    if window_end < end && next + MAX_INSTRUCTION > window_end {
+                        continue 'block;
+                    }
+                    // the rest of the block has been seen from another one
+                    if !decoded.insert(at) {
+                        break 'block;
+                    }
+                    furthest = furthest.max(next);
+                    match flow(&text) {
+                        Flow::Next => {}
+                        Flow::Stop => break 'block,
+                        Flow::Jump(target) => {
+                            pending.extend(target.filter(followed));
+                            break 'block;
+                        }
+                        Flow::Branch(target) => {
+                            pending.extend(target.filter(followed));
+                            pending.push(next);
+                            break 'block;
+                        }
+                        Flow::Call(target) => {
+                            if let Some(addr) = target {
+                                callrefs.push(Reference {
+                                    addr,
+                                    kind: "CALL".to_string(),
+                                    at,
+                                });
+                            }
+                        }
+                    }
+                    at = next;
+                }
+            }
+        }
+        Function {
+            name: String::new(),
+            offset: start as usize,
+            size: (furthest - start) as usize,
+            nbbs: blocks.len(),
+            callrefs,
+            outside_file: false,
+        }
+    }
+
+    /// offsets matching one of `PROLOGUES` in the executable ranges which no function covers
+    fn prologues(&self) -> Vec<u64> {
+        let mut hits = vec![];
+        for (start, end) in &self.ranges {
+            for offset in *start..*end {
+                let rest = &self.data[offset as usize..*end as usize];
+                if PROLOGUES.iter().any(|x| rest.starts_with(x)) && !self.covers(offset) {
+                    hits.push(offset);
+                }
+            }
+        }
+        hits
+    }
+}
+
+/// Find the functions in `data` without radare2: the entry point, symbols and what they call
+/// are followed instruction by instruction, then the gaps between them are scanned for
+/// prologues.  Offsets are file offsets, and functions without a symbol are named `fcn.` and
+/// their virtual address as r2 would.
+pub fn discover(data: &[u8], engines: &Engines) -> Vec<Function> {
+    let len = data.len() as u64;
+    let mut ranges = binary::code_ranges(data)
+        .into_iter()
+        .map(|(start, size)| (start.min(len), (start + size).min(len)))
+        .collect::<Vec<_>>();
+    // not an object file, it could all be code
+    if ranges.is_empty() {
+        ranges.push((0, len));
+    }
+    let segments = binary::segments(data);
+    let to_offset = |va: u64| {
+        segments
+            .iter()
+            .find(|(_, size, start)| (*start..start + size).contains(&va))
+            .map(|(offset, _, start)| va - start + offset)
+    };
+
+    let mut names = BTreeMap::new();
+    for (name, va) in binary::symbols(data) {
+        if let Some(offset) = to_offset(va) {
+            names.entry(offset).or_insert(name);
+        }
+    }
+    let entry = binary::anchor_addresses(data, Anchor::Entry).unwrap_or_default();
+    for offset in entry.into_iter().filter_map(to_offset) {
+        names.entry(offset).or_insert_with(|| "entry0".to_string());
+    }
+
+    let mut finder = Finder {
+        data,
+        engines,
+        ranges,
+        starts: BTreeSet::new(),
+        functions: BTreeMap::new(),
+    };
+    finder.follow(names.keys().copied().collect());
+    // each is checked again as the one before may have run into it
+    for offset in finder.prologues() {
+        if !finder.covers(offset) {
+            finder.follow(vec![offset]);
+        }
+    }
+
+    let starts = finder.functions.keys().copied().collect::<Vec<_>>();
+    let vas = binary::offsets_to_vas(data, &starts);
+    let mut functions = finder.functions.into_values().collect::<Vec<_>>();
+    for (i, function) in functions.iter_mut().enumerate() {
+        let offset = function.offset as u64;
+        // blocks are followed past the start of the next function, which owns them
+        if let Some(next) = starts.get(i + 1) {
+            function.size = function.size.min((next - offset) as usize);
+        }
+        function.name = match names.get(&offset) {
+            Some(name) => name.clone(),
+            None => format!("fcn.{:08x}", vas[i].unwrap_or(offset)),
+        };
+    }
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow() {
+        assert_eq!(Flow::Jump(Some(0x40)), flow("jmp 0x40"));
+        assert_eq!(Flow::Jump(None), flow("bnd jmp qword ptr [rip + 0x2fe2]"));
+        assert_eq!(Flow::Branch(Some(0x12)), flow("jne 0x12"));
+        assert_eq!(Flow::Call(None), flow("call rax"));
+        assert_eq!(Flow::Stop, flow("ret "));
+        assert_eq!(Flow::Next, flow("mov eax, 1"));
+    }
+
+    #[test]
+    fn test_parse_objdump() {
+        let text = "\
+Disassembly of section .text:
+
+0000000000001139 <main>:
+    1139:\t55                   \tpush   %rbp
+    113a:\te8 f1 fe ff ff       \tcall   1030 <puts@plt>
+    113f:\t74 01                \tje     1142 <main+0x9>
+    1141:\t90                   \tnop
+    1142:\tc3                   \tret    
+
+0000000000001143 <helper>:
+    1143:\tff e0                \tjmp    *%rax
+";
+        let functions = parse_objdump(text);
+        let found = functions
+            .iter()
+            .map(|x| (x.name.as_str(), x.offset, x.size, x.nbbs))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![("main", 0x1139, 0xa, 3), ("helper", 0x1143, 0x2, 1)],
+            found
+        );
+        assert_eq!(vec![0x1030], functions[0].calls().collect::<Vec<_>>());
+        assert_eq!(Ok(BackendKind::R2), "radare2".parse());
+        assert!("ida".parse::<BackendKind>().is_err());
+    }
+
+    #[test]
+    fn test_at_file_offsets() {
+        // as r2's aflj lists them: main at 0x401000 calling 0x401004, and a function past the
+        // end of the file
+        let json = r#"[
+            {"name": "main", "offset": 4198400, "size": 4,
+             "callrefs": [{"addr": 4198404, "type": "CALL", "at": 4198400}]},
+            {"name": "bss", "offset": 6291456, "size": 1}
+        ]"#;
+        let functions: Vec<Function> = serde_json::from_str(json).unwrap();
+        let data = binary::test_elf(0x400000, &[0xc3; 8]);
+        let moved = at_file_offsets(functions.clone(), &data);
+        assert_eq!(1, moved.len());
+        assert_eq!(0x1000, moved[0].offset);
+        assert_eq!(vec![0x1004], moved[0].calls().collect::<Vec<_>>());
+        assert_eq!(0x1000, moved[0].callrefs[0].at);
+        // a raw blob is loaded at 0
+        assert_eq!(2, at_file_offsets(functions, &[0xc3; 8]).len());
+    }
+
+    #[test]
+    fn test_discover() {
+        let data = [
+            // push rbp; mov rbp, rsp; call 0x10; pop rbp; ret, then padding
+            &b"\x55\x48\x89\xe5\xe8\x07\x00\x00\x00\x5d\xc3\xcc\xcc\xcc\xcc\xcc"[..],
+            // xor eax, eax; je 0x15; nop; ret, then padding
+            b"\x31\xc0\x74\x01\x90\xc3\xcc\xcc",
+            // endbr64; ret
+            b"\xf3\x0f\x1e\xfa\xc3",
+        ]
+        .concat();
+        let functions = discover(&data, &Engines::default());
+        let found = functions
+            .iter()
+            .map(|x| (x.name.as_str(), x.offset, x.size, x.nbbs))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("fcn.00000000", 0x0, 0xb, 1),
+                ("fcn.00000010", 0x10, 0x6, 3),
+                ("fcn.00000018", 0x18, 0x5, 1)
+            ],
+            found
+        );
+        assert_eq!(vec![0x10], functions[0].calls().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where an annotation is attached: a function name and the offset of the instruction from the
+/// start of that function.  Keying by name rather than address keeps annotations on the right
+/// code when a recompile moves functions around.
+pub type Location = (String, u64);
+
+/// Bookmarks and comments the user has attached to instructions.
+#[derive(Debug, Default, Clone)]
+pub struct Annotations {
+    pub bookmarks: BTreeSet<Location>,
+    pub comments: BTreeMap<Location, String>,
+}
+
+impl Annotations {
+    /// bookmark `location`, or remove the bookmark if it already has one; returns true if it's
+    /// now bookmarked
+    pub fn toggle_bookmark(&mut self, location: Location) -> bool {
+        if self.bookmarks.remove(&location) {
+            false
+        } else {
+            self.bookmarks.insert(location);
+            true
+        }
+    }
+
+    pub fn is_bookmarked(&self, location: &Location) -> bool {
+        self.bookmarks.contains(location)
+    }
+
+    /// attach `text` to `location`, an empty `text` removes the comment
+    pub fn set_comment(&mut self, location: Location, text: &str) {
+        if text.is_empty() {
+            self.comments.remove(&location);
+        } else {
+            self.comments.insert(location, text.to_string());
+        }
+    }
+
+    pub fn comment(&self, location: &Location) -> Option<&str> {
+        self.comments.get(location).map(|x| x.as_str())
+    }
+
+    /// move the annotations on function `old` over to `new`, after it's been renamed
+    pub fn rename_function(&mut self, old: &str, new: &str) {
+        let moved = |(function, offset): &Location| {
+            let function = if function == old { new } else { function };
+            (function.to_string(), *offset)
+        };
+        self.bookmarks = self.bookmarks.iter().map(moved).collect();
+        self.comments = self
+            .comments
+            .iter()
+            .map(|(location, text)| (moved(location), text.clone()))
+            .collect();
+    }
+
+    /// drop annotations on functions which no longer exist, returning how many were lost
+    pub fn retain_functions(&mut self, exists: impl Fn(&str) -> bool) -> usize {
+        let before = self.bookmarks.len() + self.comments.len();
+        self.bookmarks.retain(|(function, _)| exists(function));
+        self.comments.retain(|(function, _), _| exists(function));
+        before - self.bookmarks.len() - self.comments.len()
+    }
+}
@@ -1,208 +1,1165 @@
-use crate::util::{from_hexstring, Mode, Column};
+use crate::analysis::{self, BackendKind};
+use crate::annotations::{Annotations, Location};
+use crate::baseline::Baseline;
+use crate::binary::{self, Anchor, Hardening, Relocation};
+use crate::branch;
+use crate::bundle::{Bundle, Checksum};
+use crate::byteclass::{self, Class};
+use crate::cave::{self, Destination};
+use crate::command::Command;
+use crate::completion;
+use crate::config::{self, Settings};
+use crate::debugger::{self, Launch};
+use crate::diff::{self, DiffLine};
+use crate::dwarf::LineTable;
+use crate::encoder::Encoding;
+use crate::export;
+use crate::filter::{Candidate, Filter};
+use crate::fingerprint::{self, Fingerprint};
+use crate::got::{self, GotMap, Instruction};
+use crate::hooks::{self, Outcome, Running};
+use crate::interop::{self, Notes};
+use crate::jumptable::{self, JumpTable, Segments};
+use crate::layout::{self, Overlay, Struct};
+use crate::linear::{self, Linear};
+use crate::listing::{self, Highlight, Listing};
+use crate::literal;
+use crate::lock::Lock;
+use crate::macros::Macros;
+use crate::minimap::Mark;
+use crate::overlay;
+use crate::overview::{self, Overview};
+use crate::palette::{self, Kind, Palette, Place, Target};
+use crate::pointer::Targets;
+use crate::procmaps;
+use crate::project::{self, Autosave, Project};
+use crate::report::{self, Hunk, Line, Report};
+use crate::rewrite::{self, Extension, Symbol, SymbolTableEdit};
+use crate::runner::Runner;
+use crate::scratchpad::Scratchpad;
+use crate::session::{Entry, Operation, Recorder};
+use crate::signatures;
+use crate::snippet;
+use crate::source::Source;
+use crate::stats::Stats;
+use crate::symbols::{self, PdbSymbol};
+use crate::syscalls;
+use crate::trace::Trace;
+use crate::util::{
+    from_hexstring, Column, EngineOptions, EngineSetting, Engines, Grouping, InstructionRow, Mode,
+    Patch, Radix, SortOrder, TempDir,
+};
+use crate::vtable::{self, Explorer, Image};
+use crate::wizard::{Step, Template, Wizard};
+use crate::yara;
 use crate::{util, Function};
 use core::option::Option::{None, Some};
 use core::result::Result::Ok;
-use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
-use r2pipe::{open_pipe, R2Pipe};
-use std::collections::HashMap;
-use std::io::{Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use termion::event::Key;
 use tui::widgets::ListState;
 
+/// length of a `jmp rel32`
+const JMP_SIZE: usize = 5;
+
+/// instruction rows by the name of their function
+type Rows = HashMap<String, Vec<InstructionRow>>;
+/// what each edit which changed anything wrote over, by its index
+type Overwritten = Vec<(usize, Vec<u8>)>;
+
+/// How long each phase of loading the file took, for `--timings`.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    /// the backend which found the functions
+    pub backend: String,
+    /// why the chosen backend couldn't be used, the native one stands in for it then
+    pub fallback: Option<String>,
+    /// finding the functions
+    pub analysis: Duration,
+    /// disassembling every function, nothing in low memory mode
+    pub disassembly: Duration,
+    pub function_count: usize,
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.analysis + self.disassembly;
+        writeln!(
+            f,
+            "{:<18} {:>8.3}s  {} functions",
+            format!("analysis ({})", self.backend),
+            self.analysis.as_secs_f32(),
+            self.function_count
+        )?;
+        writeln!(
+            f,
+            "disassembly        {:>8.3}s",
+            self.disassembly.as_secs_f32()
+        )?;
+        write!(f, "total              {:>8.3}s", total.as_secs_f32())
+    }
+}
+
+/// Another build of the binary to diff functions against.
+pub struct Comparison {
+    pub file: PathBuf,
+    rows: HashMap<String, Vec<InstructionRow>>,
+}
+
+/// Tab completion in progress, pressing Tab again moves on to the next candidate.
+struct Completion {
+    /// where the completed word starts in the row's text
+    start: usize,
+    candidates: Vec<&'static str>,
+    index: usize,
+}
+
+/// A YARA string match, `va` is None if the match isn't in a loaded part of the file.
+pub struct YaraHit {
+    pub rule: String,
+    pub identifier: String,
+    pub va: Option<u64>,
+}
+
+/// A line of the call tree, `target` is the function it names.
+pub struct CallTreeEntry {
+    pub label: String,
+    pub target: Option<u64>,
+}
+
+/// A running process with the file loaded, `slide` is how far from its file address everything
+/// ended up.
+pub struct Attachment {
+    pub pid: u32,
+    pub slide: u64,
+}
+
+/// The second function shown in split view.  Only the focused pane is editable, switching focus
+/// swaps this with the main selection.
+pub struct Pane {
+    pub function: String,
+    pub editor_state: ListState,
+}
+
 pub struct Application {
+    /// the file being edited, a temporary copy when the input can't be edited in place
     pub file: PathBuf,
-    pub state: ListState,
+    /// contents of `file` when it was loaded, what `:bundle` and `:export` diff against
+    baseline: Baseline,
+    /// where writes end up if not `file`
+    pub output: Option<PathBuf>,
+    /// where `file` was fetched from, writes are stored back there
+    pub source: Option<Source>,
+    /// held on the file being edited (or what it was fetched from) for the whole session
+    pub lock: Option<Lock>,
+    pub settings: Settings,
+    /// the `--config` file, choices which are remembered are written back to it (or the default
+    /// one without it)
+    pub config: Option<PathBuf>,
+    /// built from `settings.engines`, `:engine` rebuilds them
+    pub engines: Engines,
     pub functions: Vec<Function>,
-    pub bytes: HashMap<String, Vec<String>>,
-    pub disasm: HashMap<String, Vec<String>>,
+    pub rows: HashMap<String, Vec<InstructionRow>>,
+    /// only the current function's rows are kept in memory, for images too big for all of them
+    pub low_memory: bool,
+    /// what finds the functions, used again to reload and compare
+    pub backend: BackendKind,
+    /// how long loading the file took
+    pub timings: Timings,
+    /// edits to functions whose rows were dropped in low memory mode, as bytes to write at file
+    /// offsets
+    deltas: BTreeMap<u64, Vec<u8>>,
+    /// well known constants found in (or referenced by) each function
+    pub signatures: HashMap<String, Vec<&'static str>>,
+    /// places the loader writes to, sorted by address
+    relocations: Vec<Relocation>,
+    /// the PDB symbols were loaded from, reloading loads it again
+    pub pdb: Option<PathBuf>,
+    /// functions and globals named by the PDB, by address
+    pdb_symbols: BTreeMap<u64, PdbSymbol>,
+    /// edits outside of any function, applied after the functions on write
+    pub patches: Vec<Patch>,
+    /// new segment holding code which didn't fit where it was written
+    pub extension: Option<Extension>,
+    /// symbol table change to make at the end of the next write
+    pub symbol_edit: Option<SymbolTableEdit>,
+    pub annotations: Annotations,
+    /// modification time of the file when it was last loaded or written
+    disk_mtime: Option<SystemTime>,
+    /// the file was changed by something else since it was loaded, `r` reloads it
+    pub changed_on_disk: bool,
+    /// log of every edit made, when recording a session
+    pub recorder: Option<Recorder>,
+    /// set by `--project`, the session is snapshotted there every `settings.autosave` seconds
+    pub autosave: Option<Autosave>,
+    /// an injected import rewrote the dynamic tables, another one has to wait for a write
+    import_pending: bool,
+    /// the kind of code signature the file had when it was loaded
+    pub code_signature: Option<&'static str>,
+    /// writing would break the signature and the user has been told so
+    signing_warned: bool,
     pub function_state: ListState,
+    /// set by `:filter`, functions which don't match aren't listed
+    pub filter: Option<Filter>,
+    /// name, file offset and size of each section, read the first time a filter is set
+    sections: Vec<(String, u64, u64)>,
     pub editor_state: ListState,
+    /// format, sections and mitigations of the file, None if it couldn't be read
+    pub overview: Option<Overview>,
+    /// whether the overview is shown instead of the editor
+    pub show_overview: bool,
+    /// set by `:stats`, shown instead of the editor until a key is pressed
+    pub stats: Option<Stats>,
+    /// after write hooks still running
+    pub hooks: Option<Running>,
+    /// set while before write hooks run, the write happens once they've all passed
+    write_pending: bool,
+    /// how the hooks of the last write went
+    pub hook_outcomes: Vec<Outcome>,
+    /// set by `:hooks`, shown instead of the editor until a key is pressed
+    pub show_hooks: bool,
+    /// the file run by `!`, its output is shown instead of the editor until Esc
+    pub runner: Option<Runner>,
+    /// set by `:debug`, the interface hands it the terminal before drawing again
+    pub launch: Option<Launch>,
+    /// set by `:linear`, shown instead of the editor until Esc
+    pub linear: Option<Linear>,
+    /// opened by Ctrl-P, shown instead of the editor while typing into it
+    pub palette: Option<Palette>,
+    /// opened by `:trampoline`, its questions are answered on the command line
+    pub wizard: Option<Wizard>,
+    /// byte ranges as (start, end) file offsets marked as data, which functions show as `db`
+    /// rows instead of whatever they happen to disassemble to
+    pub data: Vec<(u64, u64)>,
+    /// switch statements found by `:switches`
+    pub jump_tables: Vec<JumpTable>,
+    /// functions defined or resized by `af`, as (name, offset, size), defined again on reload
+    defined: Vec<(String, u64, u64)>,
+    /// struct layouts from `:struct` and `:structs`
+    structs: Vec<Struct>,
+    /// the struct shown over data by `:struct <name>`
+    pub struct_view: Option<Overlay>,
+    /// what pointers in data rows can point at, to name and follow them
+    pointers: Targets,
+    /// set by `:vtables`, shown instead of the editor until Esc
+    pub vtables: Option<Explorer>,
+    /// set by `:got`, shown instead of the editor until Esc
+    pub got: Option<GotMap>,
+    /// where each jump (Enter, `:seek`, `:offset`) was made from, as the function, row, column
+    /// and cursor, Backspace goes back
+    back: Vec<(String, usize, Column, isize)>,
+    /// the unfocused pane while in split view
+    pub split: Option<Pane>,
+    /// set by `:compare`, the current function is diffed against its namesake in here
+    pub comparison: Option<Comparison>,
+    /// matches from the last `:yara` scan, `:yara` without rules steps through them
+    pub yara_hits: Vec<YaraHit>,
+    yara_index: usize,
+    /// whether the call tree panel is shown
+    pub show_calls: bool,
+    pub call_state: ListState,
+    /// DWARF line info, None when the file has none
+    pub lines: Option<LineTable>,
+    /// whether the source panel is shown
+    pub show_lines: bool,
+    pub line_state: ListState,
+    /// source files the source panel has read, by path
+    source_text: HashMap<PathBuf, Vec<String>>,
+    /// assembly and hex typed in to try out, kept while the panel is hidden
+    pub scratchpad: Scratchpad,
+    /// whether the scratchpad panel is shown
+    pub show_scratchpad: bool,
+    /// keyboard macros recorded with `Q`, kept across reloads
+    pub macros: Macros,
+    /// bytes of the instructions last yanked, one entry each, with where they were yanked from
+    /// so relative operands can be fixed up (None for data, which is pasted as it is)
+    yanked: Vec<(Option<u64>, String)>,
+    /// rows an edit of the current row is repeated at, as function and file offset
+    pub cursors: BTreeSet<(String, u64)>,
+    /// the function, file offset and bytes of the current row before it was typed into while
+    /// there were cursors, leaving edit mode repeats the edit at them
+    cursor_edit: Option<(String, u64, String)>,
+    /// edits made at cursors, newest last, each the bytes of the rows it changed as they were
+    /// before it (function, file offset and bytes), `u` takes the last one back
+    undo: Vec<Vec<(String, u64, String)>>,
+    /// emulation started by `:trace`, the registers panel is shown while there is one
+    pub trace: Option<Trace>,
+    /// addresses a running trace stops at
+    pub breakpoints: BTreeSet<u64>,
+    /// set by `:attach`, addresses are shown and accepted as they are in that process
+    pub attached: Option<Attachment>,
     pub selected: Column,
     pub mode: Mode,
+    pub sort: SortOrder,
+    /// how immediates are written in the Disasm column
+    pub radix: Radix,
+    /// how the Hex column splits bytes into rows
+    pub grouping: Grouping,
+    /// whether the Hex column ends each row with its bytes as ascii
+    pub show_ascii: bool,
+    /// whether the Hex column colors bytes by their class, see `byteclass`
+    pub show_classes: bool,
+    /// contents of the command line while in Command mode
+    pub command: String,
+    /// result of the last command, shown in the status bar
+    pub message: Option<String>,
     cursor_index: isize,
+    completion: Option<Completion>,
+    /// text last cut by Ctrl-w, Ctrl-u or Ctrl-k while editing, Ctrl-y pastes it
+    killed: String,
     pub column_width: isize,
+    /// how many characters the selected Hex or Disasm column is scrolled right by
+    scroll: usize,
 }
 
 impl Application {
-    pub fn new<P: AsRef<str>>(path: P) -> Self {
-        let mut r2p = open_pipe!(Some(&path)).unwrap();
-        r2p.cmd("aaa").unwrap();
-        let x = r2p.cmd("aflj").unwrap();
-        let functions = serde_json::from_str::<Vec<Function>>(&x).unwrap_or_else(|_| vec![]);
-
-        let program = std::fs::read(path.as_ref()).unwrap();
-
-        type InstructionPair = (String, Vec<String>);
-
-        let (bytes, disasm): (Vec<InstructionPair>, Vec<InstructionPair>) = functions
-            .iter()
-            .map(|function| {
-                let (bytes, disasm): (Vec<Vec<u8>>, Vec<String>) =
-                    util::disassemble(&program[function.offset..function.offset + function.size])
-                        .into_iter()
-                        .unzip();
-                (
-                    (
-                        function.name.clone(),
-                        bytes.iter().map(|x| util::to_hexstring(x)).collect(),
-                    ),
-                    (function.name.clone(), disasm),
-                )
-            })
-            .unzip();
+    /// Analyze the file at `path` with `backend`.  With `low_memory` no function is
+    /// disassembled until it's visited, see `page_in`.
+    pub fn new<P: AsRef<str>>(
+        path: P,
+        engines: Engines,
+        backend: BackendKind,
+        low_memory: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (functions, rows, timings) =
+            analyze(path.as_ref(), &engines.options, backend, low_memory)?;
+        let message = timings.fallback.clone();
+        let data = util::map(Path::new(path.as_ref())).ok();
+        let data = data.as_deref().unwrap_or_default();
+        let signatures = signatures::scan(data, &functions, &rows);
+        let code_signature = binary::code_signature(data);
+        let lines = LineTable::parse(data).ok();
+        let relocations = binary::relocations(data).unwrap_or_default();
+        let overview = overview::read(data).ok();
+        let baseline = Baseline::new(data.len() as u64);
 
-        Application {
+        let mut app = Application {
             file: PathBuf::from(path.as_ref()),
-            state: ListState::default(),
+            baseline,
+            output: None,
+            source: None,
+            lock: None,
+            settings: Settings::default(),
+            config: None,
+            engines,
             functions,
-            bytes: bytes.into_iter().collect(),
-            disasm: disasm.into_iter().collect(),
+            rows,
+            low_memory,
+            backend,
+            timings,
+            deltas: BTreeMap::new(),
+            signatures,
+            relocations,
+            pdb: None,
+            pdb_symbols: BTreeMap::new(),
+            patches: vec![],
+            extension: None,
+            symbol_edit: None,
+            annotations: Annotations::default(),
+            disk_mtime: mtime(Path::new(path.as_ref())),
+            changed_on_disk: false,
+            recorder: None,
+            autosave: None,
+            import_pending: false,
+            code_signature,
+            signing_warned: false,
+            overview,
+            show_overview: false,
+            stats: None,
+            hooks: None,
+            write_pending: false,
+            hook_outcomes: vec![],
+            show_hooks: false,
+            runner: None,
+            launch: None,
+            linear: None,
+            palette: None,
+            wizard: None,
+            data: vec![],
+            jump_tables: vec![],
+            defined: vec![],
+            structs: vec![],
+            struct_view: None,
+            pointers: Targets::load(data),
+            vtables: None,
+            got: None,
+            back: vec![],
             function_state: ListState::default(),
+            filter: None,
+            sections: vec![],
             editor_state: ListState::default(),
+            split: None,
+            comparison: None,
+            yara_hits: vec![],
+            yara_index: 0,
+            show_calls: false,
+            call_state: ListState::default(),
+            lines,
+            show_lines: false,
+            line_state: ListState::default(),
+            source_text: HashMap::new(),
+            scratchpad: Scratchpad::default(),
+            show_scratchpad: false,
+            macros: Macros::default(),
+            yanked: vec![],
+            cursors: BTreeSet::new(),
+            cursor_edit: None,
+            undo: vec![],
+            trace: None,
+            breakpoints: BTreeSet::new(),
+            attached: None,
             selected: Column::Function,
             mode: Mode::Viewing,
+            sort: SortOrder::Name,
+            radix: Radix::Hex,
+            grouping: Grouping::Instruction,
+            show_ascii: false,
+            show_classes: false,
+            command: String::new(),
+            message,
             cursor_index: 0,
+            killed: String::new(),
+            completion: None,
             column_width: 0,
-        }
+            scroll: 0,
+        };
+        app.sort_functions();
+        Ok(app)
     }
 
-    pub fn get(&self, function: String, i: usize) -> Option<(&String, &String)> {
-        if i < self.bytes.len() && self.bytes.contains_key(&function) {
-            let bytes = self.bytes.get(&function).unwrap();
-            let disasm = self.disasm.get(&function).unwrap();
-            Some((&bytes[i], &disasm[i]))
-        } else {
-            None
+    pub fn get(&self, function: &str, i: usize) -> Option<&InstructionRow> {
+        self.rows.get(function).and_then(|rows| rows.get(i))
+    }
+
+    /// whether the current function can be edited, see `Function::outside_file`
+    pub fn editable(&self) -> bool {
+        self.functions
+            .get(self.function_state.selected().unwrap_or(0))
+            .map(|x| !x.outside_file)
+            .unwrap_or(false)
+    }
+
+    /// `editable` as an error for commands which change the current function
+    fn check_editable(&self) -> Result<(), Box<dyn Error>> {
+        if !self.editable() {
+            return Err("this function isn't all in the file".into());
         }
+        Ok(())
+    }
+
+    /// the row under the editor cursor in the current function
+    pub fn current_row(&self) -> Option<&InstructionRow> {
+        self.get(
+            &self.get_current_function()?.name,
+            self.editor_state.selected().unwrap_or(0),
+        )
+    }
+
+    pub fn current_rows(&self) -> &[InstructionRow] {
+        self.get_current_function()
+            .and_then(|x| self.rows.get(&x.name))
+            .map(|x| x.as_slice())
+            .unwrap_or(&[])
+    }
+
+    fn current_rows_mut(&mut self) -> &mut [InstructionRow] {
+        self.current_rows_and_engines().0
+    }
+
+    /// the current function's rows along with the engines to rebuild them with
+    fn current_rows_and_engines(&mut self) -> (&mut [InstructionRow], &Engines) {
+        let function = self
+            .get_current_function()
+            .map_or_else(String::new, |x| x.name.clone());
+        let rows = match self.rows.get_mut(&function) {
+            Some(rows) => rows.as_mut_slice(),
+            None => &mut [],
+        };
+        (rows, &self.engines)
     }
 
     pub fn rebuild_asm(&mut self) {
-        let function = self.get_current_function().name.clone();
-
-        let bytes = self
-            .bytes
-            .get_mut(&function)
-            .expect("current function doesn't exist in map?");
-        let disasm_vec = self
-            .disasm
-            .get_mut(&function)
-            .expect("current function doesn't exist in map?");
-        for i in 0..bytes.len() {
-            disasm_vec[i] = util::disassemble(&util::from_hexstring(&bytes[i]))
-                .first()
-                .map(|x| x.1.clone())
-                .unwrap_or_else(|| "INVALID".to_string());
+        let mut edited = vec![];
+        let (rows, engines) = self.current_rows_and_engines();
+        for row in rows.iter_mut().filter(|x| x.dirty) {
+            row.rebuild_text(engines);
+            edited.push((row.offset, row.bytes.clone()));
         }
+        self.record_edits(edited);
     }
 
     pub fn rebuild_bytes(&mut self) {
-        let function = self.get_current_function().name.clone();
+        let mut edited = vec![];
+        let (rows, engines) = self.current_rows_and_engines();
+        for row in rows.iter_mut().filter(|x| x.dirty) {
+            row.rebuild_bytes(engines);
+            if row.valid {
+                edited.push((row.offset, row.bytes.clone()));
+            }
+        }
+        self.record_edits(edited);
+    }
+
+    /// Replace the bytes of the current row, returning its new text.  Nothing is recorded since
+    /// commands are recorded as a whole.
+    fn set_current_bytes(&mut self, bytes: &[u8]) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let index = self.editor_state.selected().unwrap_or(0);
+        let (rows, engines) = self.current_rows_and_engines();
+        let row = rows.get_mut(index).ok_or("no instruction selected")?;
+        row.bytes = util::to_hexstring(bytes);
+        row.rebuild_text(engines);
+        Ok(row.text.clone())
+    }
+
+    /// swap the conditional jump on the current row for the opposite condition
+    pub fn invert_branch(&mut self) -> Result<String, Box<dyn Error>> {
+        let row = self.current_row().ok_or("no instruction selected")?;
+        let inverted =
+            branch::invert(&from_hexstring(&row.bytes)).ok_or("not a conditional jump")?;
+        let text = self.set_current_bytes(&inverted)?;
+        let va = self.current_row().map(|x| x.offset).unwrap_or(0);
+        self.record_edits(vec![(va, util::to_hexstring(&inverted))]);
+        Ok(text)
+    }
+
+    /// Overwrite `count` instructions from the current one with nops of the same length and move
+    /// to the one after them.
+    pub fn nop(&mut self, count: usize) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let index = self.editor_state.selected().unwrap_or(0);
+        let (rows, engines) = self.current_rows_and_engines();
+        if index >= rows.len() {
+            return Err("no instruction selected".into());
+        }
+        let end = index.saturating_add(count).min(rows.len());
+        let mut edited = vec![];
+        for row in &mut rows[index..end] {
+            row.bytes = util::to_hexstring(&vec![0x90; from_hexstring(&row.bytes).len()]);
+            row.rebuild_text(engines);
+            edited.push((row.offset, row.bytes.clone()));
+        }
+        let last = rows.len() - 1;
+        self.record_edits(edited);
+        self.editor_state.select(Some(end.min(last)));
+        Ok(format!("nopped {} instruction(s)", end - index))
+    }
+
+    /// copy `count` instructions from the current one for `paste`
+    pub fn yank(&mut self, count: usize) -> String {
+        let index = self.editor_state.selected().unwrap_or(0);
+        self.yanked = self
+            .current_rows()
+            .iter()
+            .skip(index)
+            .take(count)
+            .map(|x| (Some(x.offset).filter(|_| !x.data), x.bytes.clone()))
+            .collect();
+        format!("yanked {} instruction(s)", self.yanked.len())
+    }
+
+    /// Overwrite instructions from the current one with the bytes of those yanked, one each, and
+    /// move to the one after them.  Branches and rip relative operands still reach what they did
+    /// where they were yanked from.
+    pub fn paste(&mut self) -> Result<String, Box<dyn Error>> {
+        if self.yanked.is_empty() {
+            return Err("nothing has been yanked".into());
+        }
+        self.check_editable()?;
+        let index = self.editor_state.selected().unwrap_or(0);
+        let yanked = self.yanked.clone();
+        let intel = Engines::intel(self.engines.options.bits)?;
+        let (rows, engines) = self.current_rows_and_engines();
+        if index >= rows.len() {
+            return Err("no instruction selected".into());
+        }
+        // all of them first, so nothing is pasted if one can't reach from its new place
+        let relocated = rows[index..]
+            .iter()
+            .zip(&yanked)
+            .map(|(row, (from, bytes))| match from {
+                Some(from) => cave::relocate(&intel, &from_hexstring(bytes), *from, row.offset),
+                None => Ok(from_hexstring(bytes)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut edited = vec![];
+        for (row, bytes) in rows[index..].iter_mut().zip(relocated) {
+            row.bytes = util::to_hexstring(&bytes);
+            row.rebuild_text(engines);
+            edited.push((row.offset, row.bytes.clone()));
+        }
+        let pasted = edited.len();
+        let last = rows.len() - 1;
+        self.record_edits(edited);
+        self.editor_state.select(Some((index + pasted).min(last)));
+        Ok(format!("pasted {} instruction(s)", pasted))
+    }
+
+    fn record_edits(&mut self, edited: Vec<(u64, String)>) {
+        // a row typed into with cursors is recorded along with the edits at them
+        if self.cursor_edit.is_some() {
+            return;
+        }
+        let function = match self.get_current_function() {
+            Some(function) => function.name.clone(),
+            None => return,
+        };
+        for (va, bytes) in edited {
+            self.record(Operation::Edit {
+                function: function.clone(),
+                va,
+                bytes,
+            });
+        }
+    }
 
-        let bytes = self
-            .bytes
-            .get_mut(&function)
-            .expect("current function doesn't exist in map?");
-        let disasm = self
-            .disasm
-            .get_mut(&function)
-            .expect("current function doesn't exist in map?");
-        for i in 0..bytes.len() {
-            // TODO if the assembly is invalid we should handle that.  prob leave it alone?
-            // eprintln!("{:?}", disasm[i].trim().to_string());
-            if let Ok(b) = &util::assemble(disasm[i].clone()) {
-                bytes[i] = util::to_hexstring(b);
+    fn record(&mut self, operation: Operation) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(e) = recorder.record(operation) {
+                self.message = Some(format!("error: couldn't record the session: {}", e));
             }
         }
     }
 
-    pub fn values(&self, function: String) -> impl Iterator<Item = (String, String)> {
-        self.bytes
-            .get(&function)
-            .cloned()
-            .unwrap_or_else(|| vec![])
-            .into_iter()
-            .zip(
-                self.disasm
-                    .get(&function)
-                    .cloned()
-                    .unwrap_or_else(|| vec![])
-                    .into_iter(),
-            )
+    /// Redo a recorded session against this file, e.g. a fresh copy of the one it was recorded
+    /// on.  Returns how many operations were replayed.
+    pub fn replay(&mut self, entries: &[Entry]) -> Result<usize, Box<dyn Error>> {
+        for entry in entries {
+            self.replay_operation(&entry.operation)?;
+        }
+        Ok(entries.len())
+    }
+
+    fn replay_operation(&mut self, operation: &Operation) -> Result<(), Box<dyn Error>> {
+        match operation {
+            Operation::Edit {
+                function,
+                va,
+                bytes,
+            } => {
+                self.load_rows(function)?;
+                let row = self
+                    .rows
+                    .get_mut(function)
+                    .and_then(|rows| rows.iter_mut().find(|x| x.offset == *va))
+                    .ok_or_else(|| format!("no instruction at {:#x} in {}", va, function))?;
+                row.bytes = bytes.clone();
+                row.rebuild_text(&self.engines);
+                self.page_in();
+            }
+            Operation::Command {
+                function,
+                va,
+                byte,
+                line,
+            } => {
+                if !self.seek(function) {
+                    return Err(format!("couldn't find {}", function).into());
+                }
+                let row = self.current_rows().iter().position(|x| x.offset == *va);
+                self.editor_state.select(row);
+                if let Some(byte) = byte {
+                    self.select(Column::Hex);
+                    self.cursor_index = 3 * *byte as isize;
+                }
+                let command = line.parse::<Command>()?;
+                self.execute(command)
+                    .map_err(|e| format!("{}: {}", line, e))?;
+            }
+            Operation::Write => {
+                self.write()?;
+            }
+            Operation::Batch { edits } => {
+                for edit in edits {
+                    self.replay_operation(edit)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// true if any row of the current function has been edited since the last rebuild
+    pub fn is_dirty(&self) -> bool {
+        self.current_rows().iter().any(|x| x.dirty)
+    }
+
+    /// the selected function, None when the backend found none
+    pub fn get_current_function(&self) -> Option<&Function> {
+        self.functions
+            .get(self.function_state.selected().unwrap_or(0))
+    }
+
+    /// make the function at `index` the current one
+    pub fn select_function(&mut self, index: Option<usize>) {
+        self.function_state.select(index);
+        self.page_in();
+    }
+
+    /// In low memory mode keep only the current function's rows in memory: the rest are dropped,
+    /// keeping any edits to them as a delta, and the current function's are read from the file
+    /// again with its delta applied.
+    fn page_in(&mut self) {
+        let current = match self.get_current_function() {
+            Some(current) if self.low_memory => current.clone(),
+            _ => return,
+        };
+        let split = self.split.as_ref().map(|x| &x.function);
+        let leaving = self
+            .rows
+            .iter()
+            // rows still being typed into stay until they've been rebuilt, the split pane's stay
+            // while it's open
+            .filter(|(name, _)| **name != current.name && Some(*name) != split)
+            .filter(|(_, rows)| !rows.iter().any(|x| x.dirty))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for name in leaving {
+            let rows = self.rows.remove(&name).unwrap_or_default();
+            let edit = self
+                .functions
+                .iter()
+                .find(|x| x.name == name)
+                .and_then(|function| self.function_edit(function, &rows));
+            if let Some((offset, bytes)) = edit {
+                self.deltas.insert(offset, bytes);
+            }
+        }
+        if let Err(e) = self.load_rows(&current.name) {
+            self.message = Some(format!("error: {}", e));
+        }
+    }
+
+    /// Read the rows of the function named `name` in if they were dropped in low memory mode,
+    /// its delta goes back into them.
+    fn load_rows(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if !self.low_memory || self.rows.contains_key(name) {
+            return Ok(());
+        }
+        let function = match self.functions.iter().find(|x| x.name == name) {
+            Some(function) => function,
+            None => return Ok(()),
+        };
+        let rows = self.stream_rows(&util::map(&self.file)?, function);
+        if let Some(offset) = self.delta_of(function).map(|(offset, _)| *offset) {
+            self.deltas.remove(&offset);
+        }
+        self.rows.insert(name.to_string(), rows);
+        Ok(())
+    }
+
+    /// the delta kept for `function` in low memory mode, if it has one
+    fn delta_of(&self, function: &Function) -> Option<(&u64, &Vec<u8>)> {
+        let start = function.offset as u64;
+        self.deltas
+            .range(start..start + function.size as u64)
+            .next()
+    }
+
+    /// `function`'s rows disassembled from `program` (the file) with its delta applied
+    fn stream_rows(&self, program: &[u8], function: &Function) -> Vec<InstructionRow> {
+        let end = (function.offset + function.size).min(program.len());
+        let start = function.offset.min(end);
+        let mut code = program[start..end].to_vec();
+        if let Some((offset, bytes)) = self.delta_of(function) {
+            splice(&mut code, *offset as usize - start, bytes);
+        }
+        let original = self.baseline.range(program, start as u64, end as u64);
+        let mut rows = code_rows(&self.engines, &code, start as u64, &self.data);
+        for row in &mut rows {
+            let at = (row.offset as usize - start).min(original.len());
+            let len = from_hexstring(&row.bytes).len();
+            row.original = util::to_hexstring(&original[at..(at + len).min(original.len())]);
+        }
+        rows
+    }
+
+    /// `function`'s rows, read from the file if they've been dropped in low memory mode
+    fn rows_of(&self, function: &Function) -> Cow<'_, [InstructionRow]> {
+        match self.rows.get(&function.name) {
+            Some(rows) => Cow::Borrowed(rows),
+            None if self.low_memory => Cow::Owned(
+                util::map(&self.file)
+                    .map(|program| self.stream_rows(&program, function))
+                    .unwrap_or_default(),
+            ),
+            None => Cow::Borrowed(&[]),
+        }
+    }
+
+    /// select the column right of the current one (or left, unless `forward`), wrapping around
+    pub fn cycle_column(&mut self, forward: bool) {
+        let columns = [Column::Function, Column::Hex, Column::Disasm, Column::Calls]
+            .iter()
+            .copied()
+            .filter(|x| self.shown(*x))
+            .collect::<Vec<_>>();
+        let index = columns
+            .iter()
+            .position(|x| *x == self.selected)
+            .unwrap_or(0) as isize;
+        let step = if forward { 1 } else { -1 };
+        let column = columns[(index + step).rem_euclid(columns.len() as isize) as usize];
+        self.select(column);
+        if column == Column::Calls {
+            self.call_state.select(Some(0));
+        }
     }
 
-    pub fn get_current_function(&self) -> &Function {
-        &self.functions[self.function_state.selected().unwrap_or(0)]
+    /// put the cursor on the last character of the current row
+    pub fn cursor_to_end(&mut self) {
+        let len = self
+            .current_row()
+            .map(|x| match self.selected {
+                Column::Disasm => util::grapheme_count(&x.text),
+                Column::Hex => util::grapheme_count(&x.bytes),
+                _ => 0,
+            })
+            .unwrap_or(0) as isize;
+        if len > 0 {
+            self.set_cursor(len - 1)
+        }
     }
 
-    pub fn next_column(&mut self) {
-        self.increment_selected_column(1)
+    pub fn next_column(&mut self, count: usize) {
+        self.increment_selected_column(count as isize)
     }
 
-    pub fn previous_column(&mut self) {
-        self.increment_selected_column(-1)
+    pub fn previous_column(&mut self, count: usize) {
+        self.increment_selected_column(-(count as isize))
     }
 
     fn increment_selected_column(&mut self, val: isize) {
-        let current_func_name = self.get_current_function().name.clone();
+        if self.selected == Column::Function && self.filter.is_some() {
+            let visible = self.visible_functions();
+            let position = self
+                .function_state
+                .selected()
+                .and_then(|x| visible.iter().position(|y| *y == x));
+            let next = match position {
+                Some(position) => {
+                    (position as isize + val).rem_euclid(visible.len() as isize) as usize
+                }
+                None => 0,
+            };
+            if let Some(&index) = visible.get(next) {
+                self.select_function(Some(index));
+            }
+            return;
+        }
         let len = match self.selected {
             Column::Function => self.functions.len() as isize,
-            Column::Hex | Column::Disasm => self
-                .bytes
-                .get(&current_func_name)
-                .map(|x| x.len())
-                .unwrap_or(0) as isize,
+            Column::Hex | Column::Disasm => self.current_rows().len() as isize,
+            Column::Calls => self.call_tree().len() as isize,
         };
         let current_state = match self.selected {
             Column::Function => &mut self.function_state,
             Column::Hex | Column::Disasm => &mut self.editor_state,
+            Column::Calls => &mut self.call_state,
         };
 
         let next = (current_state.selected().unwrap_or(0) as isize + val).rem_euclid(len) as usize;
 
         current_state.select(Some(next));
+        if self.selected == Column::Function {
+            self.page_in();
+        }
     }
 
     pub fn apply_key(&mut self, key: Key) {
-        let current_func_name = self.get_current_function().name.clone();
-
         let current_state = match self.selected {
-            Column::Function => &mut self.function_state,
+            Column::Function | Column::Calls => &mut self.function_state,
             Column::Hex | Column::Disasm => &mut self.editor_state,
         }
         .selected()
         .unwrap_or(0);
 
-        let mut empty: Vec<String> = vec![];
-
-        let current_str = match self.selected {
-            Column::Hex => {
-                &mut self.bytes.get_mut(&current_func_name).unwrap_or(&mut empty)[current_state]
+        let selected = self.selected;
+        // while editing the cursor sits after the character it's on, -1 is before the first
+        let at = (self.cursor_index + 1).max(0) as usize;
+        let mut killed = std::mem::take(&mut self.killed);
+        let row = match self.current_rows_mut().get_mut(current_state) {
+            Some(row) => row,
+            None => {
+                self.killed = killed;
+                return;
             }
-            Column::Disasm => &mut self
-                .disasm
-                .get_mut(&current_func_name)
-                .unwrap_or(&mut empty)[current_state],
-            Column::Function => panic!(
+        };
+
+        // what the row was before, should this be the edit repeated at cursors
+        let original = (row.offset, row.bytes.clone());
+        let current_str = match selected {
+            Column::Hex => &mut row.bytes,
+            Column::Disasm => &mut row.text,
+            Column::Function | Column::Calls => panic!(
                 "trying to edit on a col which should never happen, means my logic is broken"
             ),
         };
 
-        match key {
-            Key::Char(c) => {
-                current_str.insert(self.cursor_index as usize + 1, c);
-                self.cursor_index += 1;
+        // the cursor counts grapheme clusters, pasted symbols can be anything
+        let before = current_str.clone();
+        let at = util::edit_text(current_str, at, key, &mut killed);
+        let changed = *current_str != before;
+        row.dirty |= changed;
+        self.killed = killed;
+        let at = match at {
+            Some(at) => at,
+            None => return,
+        };
+        self.cursor_index = at as isize - 1;
+        if changed {
+            self.completion = None;
+            if self.cursor_edit.is_none() && !self.cursors.is_empty() {
+                self.cursor_edit = self
+                    .get_current_function()
+                    .map(|x| (x.name.clone(), original.0, original.1));
+            }
+        }
+    }
+
+    /// Put a cursor on the current row, or take it off if there's one already.
+    pub fn toggle_cursor(&mut self) {
+        let function = match self.get_current_function() {
+            Some(function) => function.name.clone(),
+            None => return,
+        };
+        let va = match self.current_row() {
+            Some(row) => row.offset,
+            None => return,
+        };
+        if !self.cursors.remove(&(function.clone(), va)) {
+            self.cursors.insert((function, va));
+        }
+        self.message = Some(format!("{} cursor(s)", self.cursors.len()));
+    }
+
+    /// Put a cursor on every instruction whose disassembly contains `text` (ignoring case) in a
+    /// function which is all in the file, or remove them all if `text` is empty.
+    pub fn place_cursors(&mut self, text: &str) -> Result<String, Box<dyn Error>> {
+        if text.trim().is_empty() {
+            self.cursors.clear();
+            return Ok("removed every cursor".to_string());
+        }
+        let text = text.to_lowercase();
+        let mut cursors = BTreeSet::new();
+        for function in self.functions.iter().filter(|x| !x.outside_file) {
+            let rows = self.rows_of(function);
+            for row in rows
+                .iter()
+                .filter(|x| !x.data && x.text.to_lowercase().contains(&text))
+            {
+                cursors.insert((function.name.clone(), row.offset));
+            }
+        }
+        self.cursors = cursors;
+        if self.cursors.is_empty() {
+            return Err(format!("no instruction contains {}", text).into());
+        }
+        let functions = self.cursors.iter().map(|x| &x.0).collect::<BTreeSet<_>>();
+        Ok(format!(
+            "{} cursor(s) in {} function(s)",
+            self.cursors.len(),
+            functions.len()
+        ))
+    }
+
+    /// Leave edit mode, making the edit at every cursor too if the row was typed into.
+    pub fn stop_editing(&mut self) {
+        self.mode = Mode::Viewing;
+        self.cursor_index = self.cursor_index.max(0);
+        if self.cursor_edit.is_none() {
+            return;
+        }
+        if self.is_dirty() {
+            self.rebuild();
+        }
+        let original = self.cursor_edit.take();
+        self.message = Some(match self.apply_to_cursors(original) {
+            Ok(message) => message,
+            Err(e) => format!("error: {}", e),
+        });
+    }
+
+    /// Make the current row's instruction, which was `original` (function, file offset and
+    /// bytes) before it was edited, the one at every cursor too.  Disassembly is assembled again
+    /// at each cursor, so relative operands point where they did from the current row, hex is
+    /// copied as it is.  Cursors where that doesn't work are left alone, the rest is recorded
+    /// along with the edit of the current row as one operation, which `u` undoes.
+    fn apply_to_cursors(
+        &mut self,
+        original: Option<(String, u64, String)>,
+    ) -> Result<String, Box<dyn Error>> {
+        let current = self.current_row().ok_or("no instruction selected")?.clone();
+        if !current.valid {
+            return Err("the edit doesn't assemble, nothing was repeated".into());
+        }
+        let function = self.get_current_function().ok_or("no function selected")?;
+        let here = (function.name.clone(), current.offset);
+        let disasm = self.selected == Column::Disasm;
+        let mut edits = vec![];
+        let mut undo = vec![];
+        if let Some((function, va, bytes)) = original.filter(|x| x.2 != current.bytes) {
+            edits.push(Operation::Edit {
+                function: function.clone(),
+                va,
+                bytes: current.bytes.clone(),
+            });
+            undo.push((function, va, bytes));
+        }
+        let mut edited = 0;
+        let mut failed = vec![];
+        // in low memory mode the functions with cursors are read in for the edit and dropped
+        // again, keeping it as a delta, once it's made
+        let names = self
+            .cursors
+            .iter()
+            .map(|x| x.0.clone())
+            .collect::<BTreeSet<_>>();
+        for name in &names {
+            self.load_rows(name)?;
+        }
+        for (function, va) in self.cursors.clone() {
+            if (function.clone(), va) == here {
+                continue;
             }
-            Key::Delete => {
-                current_str.remove(self.cursor_index as usize + 1);
+            let in_file = self
+                .functions
+                .iter()
+                .any(|x| x.name == function && !x.outside_file);
+            let row = self
+                .rows
+                .get_mut(&function)
+                .and_then(|rows| rows.iter_mut().find(|x| x.offset == va))
+                .filter(|x| in_file && !x.data);
+            let row = match row {
+                Some(row) => row,
+                None => {
+                    failed.push(va);
+                    continue;
+                }
+            };
+            let before = row.clone();
+            if disasm {
+                row.text = current.text.clone();
+                row.rebuild_bytes(&self.engines);
+            } else {
+                row.bytes = current.bytes.clone();
+                row.rebuild_text(&self.engines);
+            }
+            if !row.valid {
+                *row = before;
+                failed.push(va);
+                continue;
             }
-            Key::Backspace if self.cursor_index > 0 => {
-                current_str.remove(self.cursor_index as usize);
-                self.cursor_index -= 1;
+            edits.push(Operation::Edit {
+                function: function.clone(),
+                va,
+                bytes: row.bytes.clone(),
+            });
+            undo.push((function, va, before.bytes));
+            edited += 1;
+        }
+        if !edits.is_empty() {
+            self.record(Operation::Batch { edits });
+            self.undo.push(undo);
+        }
+        let mut message = format!("made the same edit at {} more cursor(s)", edited);
+        if !failed.is_empty() {
+            let failed = failed
+                .iter()
+                .map(|x| format!("{:#x}", x))
+                .collect::<Vec<_>>();
+            message.push_str(&format!(", not at {}", failed.join(", ")));
+        }
+        self.page_in();
+        Ok(message)
+    }
+
+    /// Take back the last edit made at cursors, at every row it changed.
+    pub fn undo(&mut self) -> Result<String, Box<dyn Error>> {
+        let undo = self.undo.pop().ok_or("no edit at cursors to undo")?;
+        let mut edits = vec![];
+        for (function, va, bytes) in &undo {
+            self.load_rows(function)?;
+            let row = self
+                .rows
+                .get_mut(function)
+                .and_then(|rows| rows.iter_mut().find(|x| x.offset == *va))
+                .ok_or_else(|| format!("no instruction at {:#x} in {}", va, function))?;
+            row.bytes = bytes.clone();
+            row.rebuild_text(&self.engines);
+            edits.push(Operation::Edit {
+                function: function.clone(),
+                va: *va,
+                bytes: bytes.clone(),
+            });
+        }
+        self.record(Operation::Batch { edits });
+        self.page_in();
+        Ok(format!("undid the edit at {} row(s)", undo.len()))
+    }
+
+    /// Complete the word before the cursor in the Disasm column from the mnemonics (first word)
+    /// or operands for the architecture, or replace the last completion with the next candidate.
+    pub fn complete(&mut self) {
+        if self.selected != Column::Disasm {
+            return;
+        }
+        let index = self.editor_state.selected().unwrap_or(0);
+        let at = (self.cursor_index + 1).max(0) as usize;
+        let previous = self.completion.take();
+        let row = match self.current_rows_mut().get_mut(index) {
+            Some(row) => row,
+            None => return,
+        };
+        // characters are inserted after the cursor, so that's where the word ends
+        let end = util::grapheme_offset(&row.text, at);
+
+        let completion = match previous {
+            Some(c) if row.text.get(c.start..end) == Some(c.candidates[c.index]) => Completion {
+                index: (c.index + 1) % c.candidates.len(),
+                ..c
+            },
+            _ => {
+                let start = row.text[..end]
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| !c.is_ascii_alphanumeric())
+                    .map_or(0, |(i, c)| i + c.len_utf8());
+                let prefix = row.text[start..end].to_lowercase();
+                let first_word = row.text[..start].trim().is_empty();
+                let candidates = completion::X86_64.candidates(&prefix, first_word);
+                if prefix.is_empty() || candidates.is_empty() {
+                    return;
+                }
+                Completion {
+                    start,
+                    candidates,
+                    index: 0,
+                }
             }
-            _ => {}
         };
+
+        let word = completion.candidates[completion.index];
+        row.text.replace_range(completion.start..end, word);
+        row.dirty = true;
+        let end = completion.start + word.len();
+        self.cursor_index = util::grapheme_count(&row.text[..end]) as isize - 1;
+        self.message = Some(format!(
+            "{} ({}/{})",
+            word,
+            completion.index + 1,
+            completion.candidates.len()
+        ));
+        self.completion = Some(completion);
     }
 
     pub fn rebuild(&mut self) {
@@ -213,91 +1170,4645 @@ impl Application {
             Column::Disasm => {
                 self.rebuild_bytes();
             }
-            Column::Function => {
+            Column::Function | Column::Calls => {
                 panic!("should never call rebuild when current column is function");
             }
         }
+        if let Some(warning) = self
+            .relocation_warning()
+            .or_else(|| self.extension_warning())
+        {
+            self.message = Some(warning);
+        }
     }
 
-    pub fn write(&self) -> Result<(), std::io::Error> {
-        let mut file = std::fs::OpenOptions::new()
-            .write(true)
-            .open(self.file.as_path())?;
-        for function in &self.functions {
-            file.seek(SeekFrom::Start(function.offset as u64));
-            file.write(
+    /// warn if the current row was changed to an instruction the target can't run
+    fn extension_warning(&self) -> Option<String> {
+        let row = self.current_row().filter(|x| x.valid && x.modified())?;
+        let extension = row.uses(&self.settings.forbidden_extensions)?;
+        Some(format!(
+            "warning: {} needs {}, which the target doesn't have",
+            row.text.trim(),
+            extension
+        ))
+    }
+
+    /// how much of the binary already uses the forbidden extensions
+    fn forbidden_summary(&self) -> String {
+        let forbidden = &self.settings.forbidden_extensions;
+        if forbidden.is_empty() {
+            return "all instruction set extensions are allowed".to_string();
+        }
+        let uses = self
+            .rows
+            .values()
+            .map(|rows| rows.iter().filter(|x| x.uses(forbidden).is_some()).count())
+            .filter(|x| *x != 0)
+            .collect::<Vec<_>>();
+        format!(
+            "forbade {}, {} instruction(s) in {} function(s) use them",
+            forbidden.join(", "),
+            uses.iter().sum::<usize>(),
+            uses.len()
+        )
+    }
+
+    /// relocations which overlap `start..end`
+    fn relocations_in(&self, start: u64, end: u64) -> impl Iterator<Item = &Relocation> {
+        // relocations are at most 8 bytes wide
+        let first = self.relocations.partition_point(|x| x.va + 8 <= start);
+        let last = self.relocations.partition_point(|x| x.va < end);
+        self.relocations[first..last.max(first)]
+            .iter()
+            .filter(move |x| x.va + x.width > start)
+    }
+
+    /// Warn if the current row's edit changes bytes the loader relocates, since the loader will
+    /// write over them and the patch will mysteriously not be there at runtime.
+    fn relocation_warning(&self) -> Option<String> {
+        let row = self.current_row()?;
+        let bytes = from_hexstring(&row.bytes);
+        let original = from_hexstring(&row.original);
+        let end = row.offset + bytes.len().max(original.len()) as u64;
+        let clobbered = self.relocations_in(row.offset, end).find(|x| {
+            (x.va.max(row.offset)..(x.va + x.width).min(end)).any(|va| {
+                let i = (va - row.offset) as usize;
+                bytes.get(i) != original.get(i)
+            })
+        })?;
+        Some(format!(
+            "warning: {:#x} is relocated ({}), the loader will overwrite this edit",
+            clobbered.va, clobbered.kind
+        ))
+    }
+
+    /// The file's contents with every pending edit applied, along with the extension as it will
+    /// be once written.
+    fn render(&self) -> Result<(Vec<u8>, Option<Extension>), Box<dyn Error>> {
+        let mut data = util::map(&self.file)?.to_vec();
+        for function in self.functions.iter().filter(|x| !x.outside_file) {
+            splice(
+                &mut data,
+                function.offset,
                 &self
-                    .bytes
+                    .rows
                     .get(&function.name)
-                    .map(|x| x.clone())
-                    .unwrap_or_else(|| vec![])
+                    .map(|x| x.as_slice())
+                    .unwrap_or(&[])
                     .iter()
-                    .map(|x| from_hexstring(x))
-                    .map(|x| x.into_iter())
-                    .flatten()
+                    .flat_map(|x| from_hexstring(&x.bytes))
                     .collect::<Vec<u8>>(),
-            )?;
+            );
         }
-        Ok(())
+        for (offset, bytes) in &self.deltas {
+            splice(&mut data, *offset as usize, bytes);
+        }
+        // the extension has to exist before patches start pointing headers into it
+        let mut extension = self.extension.clone();
+        if let Some(extension) = extension.as_mut() {
+            extension.apply(&mut data)?;
+        }
+        for patch in &self.patches {
+            splice(&mut data, patch.offset as usize, &patch.bytes);
+        }
+        if let Some(edit) = &self.symbol_edit {
+            edit.apply(&mut data)?;
+        }
+        Ok((data, extension))
     }
 
-    pub fn select(&mut self, column: Column) {
-        self.selected = column;
-        self.cursor_index = 0;
+    /// the file as it was loaded
+    fn original(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self.baseline.restore(&util::map(&self.file)?))
     }
 
-    pub fn get_cursor(&self) -> isize {
-        self.cursor_index
+    /// Every pending edit as bytes to write at a file offset, in the order `render` applies
+    /// them.  Functions only contribute from their first row which was edited or written over
+    /// before (it may have been changed back) on, since rows after an edit which changed size
+    /// have moved.  None when the file's layout changes (a new segment or symbol table) and it
+    /// has to be rewritten as a whole.
+    fn edits(&self) -> Option<Vec<(u64, Vec<u8>)>> {
+        if self.symbol_edit.is_some() {
+            return None;
+        }
+        let mut edits = self
+            .functions
+            .iter()
+            .filter(|x| !x.outside_file)
+            .filter_map(|function| self.function_edit(function, self.rows.get(&function.name)?))
+            .collect::<Vec<_>>();
+        edits.extend(
+            self.deltas
+                .iter()
+                .map(|(offset, bytes)| (*offset, bytes.clone())),
+        );
+        if let Some(extension) = &self.extension {
+            edits.extend(extension.written()?);
+        }
+        edits.extend(self.patches.iter().map(|x| (x.offset, x.bytes.clone())));
+        Some(edits)
     }
 
-    pub fn set_cursor(&mut self, cursor: isize) {
-        let (len, alt_len) = self
-            .get(
-                self.get_current_function().clone().name,
-                self.editor_state.selected().unwrap_or(0),
+    /// The pending edit to `function` given its `rows`, from the first row which was edited or
+    /// written over before on (see `edits`).
+    fn function_edit(
+        &self,
+        function: &Function,
+        rows: &[InstructionRow],
+    ) -> Option<(u64, Vec<u8>)> {
+        let mut offset = function.offset as u64;
+        let mut start = None;
+        let mut bytes = vec![];
+        for row in rows {
+            let row_bytes = from_hexstring(&row.bytes);
+            let len = row_bytes.len().max(from_hexstring(&row.original).len());
+            if start.is_none()
+                && (row.modified() || self.baseline.changed_in(offset, offset + len as u64))
+            {
+                start = Some(offset);
+            }
+            if start.is_some() {
+                bytes.extend(&row_bytes);
+            }
+            offset += row_bytes.len() as u64;
+        }
+        Some((start?, bytes))
+    }
+
+    /// Write `edits` over `path` in place, only touching the span of each one which differs
+    /// from what's there.  Returns how many bytes changed, and what each edit which changed
+    /// anything wrote over by its index, for the baseline once the write has gone through.
+    fn write_edits(
+        path: &Path,
+        edits: &[(u64, Vec<u8>)],
+    ) -> Result<(usize, Overwritten), Box<dyn Error>> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut changed = 0;
+        let mut overwritten = vec![];
+        for (index, (offset, bytes)) in edits.iter().enumerate() {
+            // like `splice`, edits running past the end grow the file
+            let mut old = vec![];
+            file.seek(SeekFrom::Start(*offset))?;
+            (&mut file).take(bytes.len() as u64).read_to_end(&mut old)?;
+            let differs = |i: &usize| old.get(*i) != bytes.get(*i);
+            let first = match (0..bytes.len()).find(differs) {
+                Some(first) => first,
+                None => continue,
+            };
+            let last = (0..bytes.len()).rev().find(differs).unwrap_or(first);
+            changed += (first..=last).filter(differs).count();
+            file.seek(SeekFrom::Start(*offset + first as u64))?;
+            file.write_all(&bytes[first..=last])?;
+            overwritten.push((index, old));
+        }
+        Ok((changed, overwritten))
+    }
+
+    /// Write every pending edit once the before write hooks have all passed, which happens in
+    /// the background when there are any.  Returns what the bar should say.
+    pub fn save(&mut self) -> Result<String, Box<dyn Error>> {
+        if self.write_pending {
+            return Err("the last write is still waiting on its hooks".into());
+        }
+        self.hooks = None;
+        self.hook_outcomes.clear();
+        if self.settings.before_write.is_empty() {
+            let changed = self.write()?;
+            return Ok(self.wrote(changed));
+        }
+        let commands = self.settings.before_write.clone();
+        let count = commands.len();
+        self.hooks = Some(Running::spawn(commands, self.file.clone()));
+        self.write_pending = true;
+        Ok(format!("writing once {} before write hook(s) pass", count))
+    }
+
+    /// what the bar says after a write which changed `changed` bytes
+    fn wrote(&self, changed: usize) -> String {
+        let destination = match &self.source {
+            Some(source) => source.destination(),
+            None => self.written_path().display().to_string(),
+        };
+        format!("wrote {}, {} byte(s) changed", destination, changed)
+    }
+
+    /// write every pending edit to the file, returning how many bytes changed
+    pub fn write(&mut self) -> Result<usize, Box<dyn Error>> {
+        if let Some(reason) = self.overgrown() {
+            return Err(reason.into());
+        }
+        // only the part of the file functions cover is written, edits to the rest would be lost
+        if let Some(function) = self
+            .functions
+            .iter()
+            .find(|x| x.outside_file && self.is_modified(x))
+        {
+            return Err(format!(
+                "{} isn't all in the file, its edits can't be written",
+                function.name
             )
-            .map(|x| match self.selected {
-                Column::Disasm => (x.1.len(), x.0.len()),
-                Column::Hex => (x.0.len(), x.1.len()),
-                _ => (0, 0),
+            .into());
+        }
+        let file = self.file.clone();
+        self.hooks = None;
+        // a lock belongs to the file rather than its path, so when it's on the file being
+        // replaced it has to move to the copy before that's renamed over it
+        let mut lock = self.lock.take();
+        let moving = lock.as_ref().map(|x| &x.path) == file.canonicalize().ok().as_ref();
+        let mut relock = |path: &Path| match lock.as_mut() {
+            Some(lock) if moving => lock.transfer(path),
+            _ => Ok(()),
+        };
+        let changed = match self.edits() {
+            Some(edits) => util::replace_atomically(&file, |copy| {
+                let written = Application::write_edits(copy, &edits)?;
+                relock(copy)?;
+                Ok(written)
             })
-            .map(|(a, b)| (a as isize, b as isize))
-            .unwrap_or((0, 0));
-        let cursor = if cursor >= len {
-            self.select(match self.selected {
-                Column::Disasm => Column::Hex,
-                Column::Hex => Column::Disasm,
-                Column::Function => Column::Function, // this should never happen but idk i don't wanna crash
-            });
-            cursor - len
-        } else if cursor < 0 {
-            self.select(match self.selected {
-                Column::Disasm => Column::Hex,
-                Column::Hex => Column::Disasm,
-                Column::Function => Column::Function, // this should never happen but idk i don't wanna crash
-            });
-            alt_len + cursor
-        } else {
-            cursor
+            .map(|(changed, overwritten)| {
+                // only now is the file on disk what the baseline says it was written to
+                for (index, old) in overwritten {
+                    let (offset, bytes) = &edits[index];
+                    self.baseline.record(*offset, &old, bytes);
+                }
+                changed
+            }),
+            None => self.render().and_then(|(data, extension)| {
+                let old = util::map(&self.file)?;
+                util::replace_atomically(&file, |copy| {
+                    // the copy starts out as the old file, only what differs needs writing
+                    OpenOptions::new()
+                        .write(true)
+                        .open(copy)?
+                        .set_len(data.len() as u64)?;
+                    Application::write_edits(copy, &differences(&old, &data))?;
+                    Ok(relock(copy)?)
+                })?;
+                self.baseline.record(0, &old, &data);
+                self.extension = extension;
+                Ok((0..old.len().max(data.len()))
+                    .filter(|&i| old.get(i) != data.get(i))
+                    .count())
+            }),
         };
-        self.cursor_index = ((cursor % len) + len) % len;
+        if changed.is_err() {
+            // the copy it moved to is gone
+            let _ = relock(&file);
+        }
+        self.lock = lock;
+        let changed = changed?;
+        self.symbol_edit = None;
+        self.import_pending = false;
+        if let Some(hook) = &self.settings.post_write {
+            // the path goes after whatever arguments it gave itself, and it has to finish before
+            // the file is copied anywhere
+            let outcome = hooks::run(&format!("{} \"$1\"", hook), &self.file);
+            let summary = outcome.summary();
+            let success = outcome.success();
+            self.hook_outcomes.push(outcome);
+            if !success {
+                return Err(format!("post write hook {} (:hooks shows why)", summary).into());
+            }
+        }
+        if let Some(output) = &self.output {
+            std::fs::copy(&self.file, output)?;
+        }
+        if let Some(source) = &self.source {
+            source.store(&self.file)?;
+        }
+        self.record(Operation::Write);
+        self.retarget_autosave();
+        if !self.settings.after_write.is_empty() {
+            let commands = self.settings.after_write.clone();
+            self.hooks = Some(Running::spawn(commands, self.file.clone()));
+        }
+        // don't mistake our own write for someone else's
+        self.disk_mtime = mtime(&self.file);
+        self.changed_on_disk = false;
+        Ok(changed)
     }
 
-    pub fn get_bar(&self) -> String {
-        format!("Mode: {}", self.mode)
+    /// Gather every edited run of instructions, each with the rows around it and its
+    /// disassembly from before the edit, along with the comments and patches outside functions.
+    pub fn review_report(&self) -> Report {
+        const CONTEXT: usize = 2;
+        let mut hunks = vec![];
+        let mut comments = vec![];
+        for function in &self.functions {
+            let rows = self.rows_of(function);
+            let location =
+                |row: &InstructionRow| (function.name.clone(), row.offset - function.offset as u64);
+            // runs of edited rows, merged when their context would overlap
+            let mut runs: Vec<(usize, usize)> = vec![];
+            for (i, _) in rows.iter().enumerate().filter(|(_, x)| x.modified()) {
+                match runs.last_mut() {
+                    Some((_, end)) if i <= *end + 2 * CONTEXT + 1 => *end = i,
+                    _ => runs.push((i, i)),
+                }
+            }
+            let mut covered = BTreeSet::new();
+            for (first, last) in runs {
+                let start = first.saturating_sub(CONTEXT);
+                let end = (last + CONTEXT).min(rows.len() - 1);
+                let mut lines = vec![];
+                let mut changed = vec![];
+                let flush = |lines: &mut Vec<Line>, changed: &mut Vec<&InstructionRow>| {
+                    if let Some(first) = changed.first() {
+                        let original = changed
+                            .iter()
+                            .flat_map(|x| from_hexstring(&x.original))
+                            .collect::<Vec<_>>();
+                        let mut va = first.offset;
+                        for (bytes, text, _) in self.engines.disassemble(&original, va) {
+                            lines.push(Line::Removed(format!("{:#x}  {}", va, text)));
+                            va += bytes.len() as u64;
+                        }
+                        lines.extend(
+                            changed
+                                .drain(..)
+                                .map(|x| Line::Added(format!("{:#x}  {}", x.offset, x.text))),
+                        );
+                    }
+                };
+                for row in &rows[start..=end] {
+                    covered.insert(location(row));
+                    if row.modified() {
+                        changed.push(row);
+                    } else {
+                        flush(&mut lines, &mut changed);
+                        lines.push(Line::Context(format!("{:#x}  {}", row.offset, row.text)));
+                    }
+                }
+                flush(&mut lines, &mut changed);
+                let va = rows[first].offset;
+                let last = &rows[last];
+                hunks.push(Hunk {
+                    function: function.name.clone(),
+                    va,
+                    len: last.offset + from_hexstring(&last.bytes).len() as u64 - va,
+                    lines,
+                    comments: rows[start..=end]
+                        .iter()
+                        .filter_map(|row| {
+                            let comment = self.annotations.comment(&location(row))?;
+                            Some((row.offset, comment.to_string()))
+                        })
+                        .collect(),
+                });
+            }
+            comments.extend(rows.iter().filter_map(|row| {
+                let location = location(row);
+                let text = self.annotations.comment(&location)?;
+                if covered.contains(&location) {
+                    return None;
+                }
+                Some(report::Comment {
+                    function: function.name.clone(),
+                    va: row.offset,
+                    instruction: row.text.clone(),
+                    text: text.to_string(),
+                })
+            }));
+        }
+        Report {
+            file: self.file.display().to_string(),
+            author: std::env::var("USER").unwrap_or_default(),
+            hunks,
+            comments,
+            patches: self
+                .patches
+                .iter()
+                .map(|x| (x.offset, x.bytes.clone()))
+                .collect(),
+        }
     }
 
-    pub fn get_functions(&self, filter: &str) -> Vec<String> {
-        if filter == "" {
-            self.functions.iter().map(|x| x.name.clone()).collect()
+    /// Save the difference between the file as loaded and as last written to `path`.  Edits
+    /// which haven't been written yet aren't part of it.
+    pub fn save_bundle(&self, path: &Path, notes: String) -> Result<String, Box<dyn Error>> {
+        let patched = util::map(&self.file)?;
+        if !self.baseline.changed(patched.len() as u64) {
+            return Err("nothing has been written since loading".into());
+        }
+        let original = self.baseline.restore(&patched);
+        let mut bundle = Bundle::create(&original, &patched, binary::arch(&patched)?, notes);
+        // the functions the hunks are in, so `rebase` can find them in another build
+        let functions = self.fingerprints(|name| {
+            self.functions.iter().any(|x| {
+                x.name == name
+                    && bundle
+                        .hunks
+                        .iter()
+                        .any(|hunk| (x.offset..x.offset + x.size).contains(&(hunk.offset as usize)))
+            })
+        });
+        for hunk in &mut bundle.hunks {
+            hunk.function = functions
+                .iter()
+                .find(|x| (x.offset..x.offset + x.size).contains(&hunk.offset))
+                .cloned();
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&bundle)?)?;
+        let unwritten = self
+            .functions
+            .iter()
+            .filter(|x| !x.outside_file)
+            .any(|function| {
+                let bytes = self
+                    .rows
+                    .get(&function.name)
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|x| from_hexstring(&x.bytes))
+                    .collect::<Vec<u8>>();
+                patched.get(function.offset..function.offset + bytes.len()) != Some(&bytes[..])
+            });
+        Ok(format!(
+            "saved {} hunk(s) to {}{}",
+            bundle.hunks.len(),
+            path.display(),
+            if unwritten {
+                ", unwritten edits aren't included"
+            } else {
+                ""
+            }
+        ))
+    }
+
+    /// Explain that writing will invalidate the file's signature the first time it's about to be
+    /// written, unless there's a post write hook to sign it again.
+    pub fn signing_warning(&mut self) -> Option<String> {
+        let kind = self.code_signature?;
+        if self.signing_warned || self.settings.post_write.is_some() {
+            return None;
+        }
+        self.signing_warned = true;
+        Some(format!(
+            "{} has a {} which writing will invalidate, press w again to write anyway",
+            self.file.display(),
+            kind
+        ))
+    }
+
+    /// where the last write went
+    pub fn written_path(&self) -> &Path {
+        self.output.as_deref().unwrap_or(&self.file)
+    }
+
+    /// notice if something else (e.g. a recompile) has changed the file since it was loaded
+    pub fn check_file(&mut self) {
+        if self.changed_on_disk || mtime(&self.file) == self.disk_mtime {
+            return;
+        }
+        self.changed_on_disk = true;
+        self.message = Some(format!(
+            "{} changed on disk, press r to reload",
+            self.file.display()
+        ));
+    }
+
+    /// Reanalyze the file from scratch.  Pending edits are dropped since they were made against
+    /// the old contents, but annotations carry over to functions which still exist and the same
+    /// function stays selected.
+    pub fn reload(&mut self) -> Result<String, Box<dyn Error>> {
+        let engines = Engines::new(self.engines.options.clone())?;
+        let mut app = Application::new(
+            self.file.to_string_lossy(),
+            engines,
+            self.backend,
+            self.low_memory,
+        )?;
+        app.settings = self.settings.clone();
+        app.config = self.config.clone();
+        app.output = self.output.clone();
+        app.source = self.source.clone();
+        app.lock = self.lock.take();
+        app.show_calls = self.show_calls;
+        app.show_lines = self.show_lines && app.lines.is_some();
+        app.sort = self.sort;
+        app.sort_functions();
+        app.radix = self.radix;
+        app.data = std::mem::take(&mut self.data);
+        for range in app.data.clone() {
+            // nothing has been edited yet
+            let _ = app.resplit(range);
+        }
+        for (name, offset, size) in std::mem::take(&mut self.defined) {
+            // bounds which no longer fit the file are dropped
+            let _ = app.define_function(Some(offset), Some(size), Some(name));
+        }
+        // low memory mode only keeps the current function's rows, so go by the functions
+        let names = app
+            .functions
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<HashSet<_>>();
+        let mut annotations = std::mem::take(&mut self.annotations);
+        let lost = annotations.retain_functions(|name| names.contains(name));
+        app.annotations = annotations;
+        if !self.jump_tables.is_empty() {
+            let _ = app.detect_jump_tables();
+        }
+        app.grouping = self.grouping;
+        app.show_ascii = self.show_ascii;
+        app.show_classes = self.show_classes;
+        app.filter = self.filter.take();
+        app.sections = std::mem::take(&mut self.sections);
+        app.scratchpad = std::mem::take(&mut self.scratchpad);
+        app.show_scratchpad = self.show_scratchpad;
+        app.macros = std::mem::take(&mut self.macros);
+        app.yanked = std::mem::take(&mut self.yanked);
+        app.autosave = self.autosave.take();
+        app.hooks = self.hooks.take();
+        app.write_pending = self.write_pending;
+        app.recorder = self.recorder.take();
+        app.hook_outcomes = std::mem::take(&mut self.hook_outcomes);
+        app.structs = std::mem::take(&mut self.structs);
+        if let Some(view) = &self.struct_view {
+            // laid again, over the bytes the file has now
+            let _ = app.lay_struct(&view.layout.name, Some(view.address));
+        }
+        if let Some(pdb) = &self.pdb {
+            // a PDB which no longer matches is dropped with the rest of the old analysis
+            let _ = app.load_pdb(pdb);
+        }
+        app.select_function(Some(0));
+        app.editor_state.select(Some(0));
+        if let Some(function) = self
+            .functions
+            .get(self.function_state.selected().unwrap_or(0))
+        {
+            if app.seek(&function.name) {
+                let row = self.editor_state.selected().unwrap_or(0);
+                app.editor_state
+                    .select(Some(row.min(app.current_rows().len().saturating_sub(1))));
+            }
+        }
+        app.selected = self.selected;
+        app.column_width = self.column_width;
+        // last, so the snapshot it saves has everything carried over
+        app.retarget_autosave();
+        *self = app;
+        Ok(match lost {
+            0 => format!("reloaded {}", self.file.display()),
+            lost => format!(
+                "reloaded {}, dropped {} annotation(s) on functions which no longer exist",
+                self.file.display(),
+                lost
+            ),
+        })
+    }
+
+    /// Everything `--project` keeps: edits which have been rebuilt, annotations and the cursor.
+    fn snapshot(&self, target: Checksum) -> Project {
+        let mut edits = self
+            .rows
+            .iter()
+            .flat_map(|(function, rows)| {
+                rows.iter()
+                    .filter(|x| !x.dirty && x.modified())
+                    .map(move |row| project::Edit {
+                        function: function.clone(),
+                        va: row.offset,
+                        bytes: row.bytes.clone(),
+                        original: row.original.clone(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        // functions whose rows were dropped in low memory mode
+        for function in self.functions.iter().filter(|x| self.delta_of(x).is_some()) {
+            let rows = self.rows_of(function);
+            edits.extend(
+                rows.iter()
+                    .filter(|x| x.modified())
+                    .map(|row| project::Edit {
+                        function: function.name.clone(),
+                        va: row.offset,
+                        bytes: row.bytes.clone(),
+                        original: row.original.clone(),
+                    }),
+            );
+        }
+        // rows are in a HashMap, sorting keeps identical sessions from looking changed
+        edits.sort_unstable_by(|a, b| (&a.function, a.va).cmp(&(&b.function, b.va)));
+        let data = util::map(&self.file).ok();
+        let data = data.as_deref().unwrap_or_default();
+        let project = Project {
+            target,
+            edits,
+            patches: self
+                .patches
+                .iter()
+                .map(|x| (x.offset, util::to_hexstring(&x.bytes)))
+                .collect(),
+            originals: self
+                .patches
+                .iter()
+                .map(|x| {
+                    let end = x.offset + x.bytes.len() as u64;
+                    util::to_hexstring(&self.baseline.range(data, x.offset, end))
+                })
+                .collect(),
+            comments: self
+                .annotations
+                .comments
+                .iter()
+                .map(|(location, text)| (location.clone(), text.clone()))
+                .collect(),
+            bookmarks: self.annotations.bookmarks.iter().cloned().collect(),
+            data: self.data.clone(),
+            cursor: self.current_location(),
+            functions: vec![],
+        };
+        let mut attached = BTreeSet::new();
+        attached.extend(project.edits.iter().map(|x| x.function.as_str()));
+        attached.extend(project.comments.iter().map(|((x, _), _)| x.as_str()));
+        attached.extend(project.bookmarks.iter().map(|(x, _)| x.as_str()));
+        attached.extend(project.cursor.iter().map(|(x, _)| x.as_str()));
+        let patched = project
+            .patches
+            .iter()
+            .map(|(offset, bytes)| (*offset, offset + from_hexstring(bytes).len() as u64));
+        for (start, end) in project.data.iter().copied().chain(patched) {
+            attached.extend(
+                self.functions
+                    .iter()
+                    .filter(|x| (x.offset as u64) < end && start < (x.offset + x.size) as u64)
+                    .map(|x| x.name.as_str()),
+            );
+        }
+        let functions = self.fingerprints(|x| attached.contains(x));
+        Project {
+            functions,
+            ..project
+        }
+    }
+
+    /// fingerprints of the functions whose name is `wanted`, from the file as it was loaded
+    pub fn fingerprints(&self, wanted: impl Fn(&str) -> bool) -> Vec<Fingerprint> {
+        let data = util::map(&self.file).ok();
+        let data = data.as_deref().unwrap_or_default();
+        self.functions
+            .iter()
+            .filter(|x| !x.outside_file && wanted(&x.name))
+            .map(|x| {
+                let (start, end) = (x.offset as u64, (x.offset + x.size) as u64);
+                let code = self.baseline.range(data, start, end);
+                Fingerprint {
+                    name: x.name.clone(),
+                    offset: start,
+                    size: end - start,
+                    hash: fingerprint::hash(&self.engines, &code, start),
+                }
+            })
+            .collect()
+    }
+
+    /// `project`, saved against another build of the file, moved onto this one by finding its
+    /// functions again (see `fingerprint::relocate`).  Edits only carry over to functions whose
+    /// code hasn't changed, annotations to any function found.  The hash only covers mnemonics,
+    /// so an edit or patch also needs the bytes it went over to be at its new place, like
+    /// `Bundle::rebase`.  Patches outside every function stay where they were if those bytes
+    /// still are.  Returns how many things were left behind too.
+    fn port_project(&self, project: Project, target: Checksum) -> (Project, usize) {
+        let matches = fingerprint::relocate(&project.functions, &self.fingerprints(|_| true));
+        let moved = |(function, offset): &Location| {
+            let found = matches.get(function)?;
+            Some((found.name.clone(), *offset))
+        };
+        let shift = |at: u64, delta: i64| (at as i64 + delta) as u64;
+        let file = util::map(&self.file).ok();
+        let file = file.as_deref().unwrap_or_default();
+        let fits = |at: u64, original: &[u8]| {
+            self.baseline.range(file, at, at + original.len() as u64) == original
+        };
+        let edits = project
+            .edits
+            .iter()
+            .filter_map(|edit| {
+                let found = matches.get(&edit.function).filter(|x| x.same_code)?;
+                let va = shift(edit.va, found.delta);
+                // projects from before originals were kept can only go by the hash
+                Some(project::Edit {
+                    function: found.name.clone(),
+                    va,
+                    ..edit.clone()
+                })
+                .filter(|_| fits(va, &from_hexstring(&edit.original)))
+            })
+            .collect::<Vec<_>>();
+        let mut patches = vec![];
+        let mut originals = vec![];
+        for (i, (offset, bytes)) in project.patches.iter().enumerate() {
+            let original = match project.originals.get(i).map(|x| from_hexstring(x)) {
+                Some(original) if !original.is_empty() => original,
+                _ => continue,
+            };
+            // where its function went, otherwise where it was
+            let moved = project
+                .functions
+                .iter()
+                .find(|x| (x.offset..x.offset + x.size).contains(offset))
+                .and_then(|x| matches.get(&x.name))
+                .map(|x| shift(*offset, x.delta));
+            if let Some(at) = moved
+                .into_iter()
+                .chain(Some(*offset))
+                .find(|x| fits(*x, &original))
+            {
+                patches.push((at, bytes.clone()));
+                originals.push(util::to_hexstring(&original));
+            }
+        }
+        let data = project
+            .data
+            .iter()
+            .filter_map(|(start, end)| {
+                let old = project
+                    .functions
+                    .iter()
+                    .find(|x| (x.offset..x.offset + x.size).contains(start))?;
+                let found = matches.get(&old.name).filter(|x| x.same_code)?;
+                Some((shift(*start, found.delta), shift(*end, found.delta)))
+            })
+            .collect::<Vec<_>>();
+        let comments = project
+            .comments
+            .iter()
+            .filter_map(|(location, text)| Some((moved(location)?, text.clone())))
+            .collect::<Vec<_>>();
+        let bookmarks = project
+            .bookmarks
+            .iter()
+            .filter_map(moved)
+            .collect::<Vec<_>>();
+        let before = project.edits.len()
+            + project.patches.len()
+            + project.data.len()
+            + project.comments.len()
+            + project.bookmarks.len();
+        let lost =
+            before - edits.len() - patches.len() - data.len() - comments.len() - bookmarks.len();
+        let ported = Project {
+            target,
+            edits,
+            patches,
+            originals,
+            comments,
+            bookmarks,
+            data,
+            cursor: project.cursor.as_ref().and_then(moved),
+            functions: vec![],
+        };
+        (ported, lost)
+    }
+
+    /// Snapshot the session to `path` from now on, first restoring the snapshot already there
+    /// if there is one.  A snapshot of some other version of the file is refused rather than
+    /// applied to code it doesn't describe.
+    pub fn open_project(&mut self, path: &Path) -> Result<String, Box<dyn Error>> {
+        let target = Checksum::of(&util::map(&self.file)?);
+        let mut autosave = Autosave::new(path.to_path_buf(), target.clone());
+        if !path.exists() {
+            self.autosave = Some(autosave);
+            return Ok(format!("saving the session to {}", path.display()));
+        }
+        let mut project = Project::load(path)?;
+        // another build can be followed if the project says which functions things are in
+        let mut ported = None;
+        if project.target != target {
+            if project.functions.is_empty() {
+                return Err(format!(
+                    "{} was saved against a different version of {}",
+                    path.display(),
+                    self.file.display()
+                )
+                .into());
+            }
+            let (moved, lost) = self.port_project(project, target.clone());
+            project = moved;
+            ported = Some(lost);
+        }
+        // edits are made to rows as they were split when saving
+        self.data = project.data.clone();
+        for range in &project.data {
+            self.resplit(*range)?;
+        }
+        for edit in &project.edits {
+            self.load_rows(&edit.function)?;
+            let row = self
+                .rows
+                .get_mut(&edit.function)
+                .and_then(|rows| rows.iter_mut().find(|x| x.offset == edit.va))
+                .ok_or_else(|| format!("no instruction at {:#x} in {}", edit.va, edit.function))?;
+            row.bytes = edit.bytes.clone();
+            row.rebuild_text(&self.engines);
+            self.page_in();
+        }
+        for (offset, bytes) in &project.patches {
+            self.patches
+                .push(Patch::new(*offset, from_hexstring(bytes)));
+        }
+        for (location, text) in &project.comments {
+            self.annotations.set_comment(location.clone(), text);
+        }
+        self.annotations
+            .bookmarks
+            .extend(project.bookmarks.iter().cloned());
+        if let Some((function, offset)) = &project.cursor {
+            if let Some(start) = self.functions.iter().find(|x| &x.name == function) {
+                let va = start.offset as u64 + offset;
+                self.select_row(function, va);
+            }
+        }
+        let mut message = format!(
+            "restored {} edit(s) and {} annotation(s) from {}",
+            project.edits.len() + project.patches.len(),
+            project.comments.len() + project.bookmarks.len(),
+            path.display()
+        );
+        if let Some(lost) = ported {
+            message.push_str(&format!(
+                ", saved against another build, {} couldn't be found again",
+                lost
+            ));
+            project = self.snapshot(target);
+        }
+        autosave.save(project)?;
+        self.autosave = Some(autosave);
+        Ok(message)
+    }
+
+    /// Snapshot the session if it's been `settings.autosave` seconds since the last one, or
+    /// regardless when `force`d.  A failed snapshot is shown rather than interrupting editing.
+    pub fn autosave(&mut self, force: bool) {
+        let target = match &self.autosave {
+            Some(autosave) if force || autosave.due(self.settings.autosave) => {
+                autosave.target.clone()
+            }
+            _ => return,
+        };
+        let project = self.snapshot(target);
+        if let Some(autosave) = &mut self.autosave {
+            if let Err(e) = autosave.save(project) {
+                self.message = Some(format!(
+                    "couldn't save the session to {}: {}",
+                    autosave.path.display(),
+                    e
+                ));
+            }
+        }
+    }
+
+    /// The file on disk changed under the snapshot, by a write or a reload.  It's saved again
+    /// straight away, since the last one is for a file which is gone and couldn't be restored.
+    fn retarget_autosave(&mut self) {
+        if let (Some(autosave), Ok(data)) = (&mut self.autosave, util::map(&self.file)) {
+            autosave.target = Checksum::of(&data);
+            self.autosave(true);
+        }
+    }
+
+    pub fn select(&mut self, column: Column) {
+        // flush pending edits while we still know which column they were made in
+        if self.selected.editable() && self.is_dirty() {
+            self.rebuild();
+        }
+        // one of Hex and Disasm is always shown, the other stands in for it
+        self.selected = match column {
+            Column::Hex if !self.shown(column) => Column::Disasm,
+            Column::Disasm if !self.shown(column) => Column::Hex,
+            column => column,
+        };
+        self.cursor_index = 0;
+    }
+
+    /// whether `column` is on screen
+    pub fn shown(&self, column: Column) -> bool {
+        match column {
+            Column::Function => true,
+            Column::Hex => self.settings.show_hex,
+            Column::Disasm => self.settings.show_disasm,
+            Column::Calls => self.show_calls,
+        }
+    }
+
+    /// Show or hide the Hex or Disasm column, the others share its width while it's hidden.
+    /// Which are shown is remembered in the config.
+    pub fn toggle_column(&mut self, column: Column) -> Result<String, Box<dyn Error>> {
+        let (name, key, other) = match column {
+            Column::Hex => ("Hex", "show_hex", Column::Disasm),
+            Column::Disasm => ("Disasm", "show_disasm", Column::Hex),
+            Column::Function | Column::Calls => return Err("only Hex and Disasm hide".into()),
+        };
+        let shown = !self.shown(column);
+        if !shown && !self.shown(other) {
+            return Err(format!("{} is all that's left to edit in", name).into());
+        }
+        match column {
+            Column::Hex => self.settings.show_hex = shown,
+            _ => self.settings.show_disasm = shown,
+        }
+        if self.selected == column {
+            self.select(column);
+        }
+        let done = format!("{} {}", if shown { "showing" } else { "hid" }, name);
+        config::remember(self.config.as_deref(), key, shown.into())
+            .map_err(|e| format!("{} but couldn't remember it: {}", done, e))?;
+        Ok(done)
+    }
+
+    pub fn get_cursor(&self) -> isize {
+        self.cursor_index
+    }
+
+    /// Scroll the selected column sideways so the cursor is inside it, given the length of the
+    /// line it's on as shown.  While editing the cursor sits after the character it's on.
+    pub fn follow_cursor(&mut self, len: usize) {
+        let width = (self.column_width - 2).max(0) as usize;
+        let column = (self.cursor_index + (self.mode == Mode::Editing) as isize).max(0) as usize;
+        self.scroll = util::scroll_to(column, self.scroll, width, len);
+    }
+
+    /// how far `column` is scrolled, only the selected one ever is
+    pub fn scroll_of(&self, column: Column) -> usize {
+        if column == self.selected {
+            self.scroll
+        } else {
+            0
+        }
+    }
+
+    /// where the cursor is shown inside the selected column's borders
+    pub fn cursor_column(&self) -> u16 {
+        let column = (self.cursor_index + (self.mode == Mode::Editing) as isize).max(0) as usize;
+        column.saturating_sub(self.scroll) as u16
+    }
+
+    pub fn set_cursor(&mut self, cursor: isize) {
+        let (len, alt_len) = self
+            .current_row()
+            .map(|x| {
+                let (text, bytes) = (
+                    util::grapheme_count(&x.text),
+                    util::grapheme_count(&x.bytes),
+                );
+                match self.selected {
+                    Column::Disasm => (text, bytes),
+                    Column::Hex => (bytes, text),
+                    _ => (0, 0),
+                }
+            })
+            .map(|(a, b)| (a as isize, b as isize))
+            .unwrap_or((0, 0));
+        let cursor = if cursor >= len {
+            self.select(match self.selected {
+                Column::Disasm => Column::Hex,
+                Column::Hex => Column::Disasm,
+                column => column, // this should never happen but idk i don't wanna crash
+            });
+            cursor - len
+        } else if cursor < 0 {
+            self.select(match self.selected {
+                Column::Disasm => Column::Hex,
+                Column::Hex => Column::Disasm,
+                column => column, // this should never happen but idk i don't wanna crash
+            });
+            alt_len + cursor
+        } else {
+            cursor
+        };
+        self.cursor_index = ((cursor % len) + len) % len;
+    }
+
+    pub fn get_bar(&self) -> String {
+        let mut mode = match self.macros.recording() {
+            Some(register) => format!("{} (recording @{})", self.mode, register),
+            None => self.mode.to_string(),
+        };
+        if self.hooks.is_some() {
+            mode.push_str(" (hooks running)");
+        }
+        if !self.cursors.is_empty() {
+            mode.push_str(&format!(" ({} cursors)", self.cursors.len()));
+        }
+        match (&self.mode, &self.message) {
+            (Mode::Command, _) => format!(":{}", self.command),
+            (Mode::Wizard, message) => format!(
+                "{}{} {}",
+                message
+                    .as_ref()
+                    .map_or(String::new(), |x| format!("{} | ", x)),
+                self.wizard.as_ref().map_or(String::new(), |x| x.question()),
+                self.command
+            ),
+            (Mode::Palette, _) => format!(
+                "goto {}",
+                self.palette.as_ref().map_or("", |x| x.query.as_str())
+            ),
+            (_, Some(message)) => format!("Mode: {} | {}", mode, message),
+            (_, None) => format!("Mode: {}", mode),
+        }
+    }
+
+    /// Show how any hooks which have finished since the last tick went, and write once before
+    /// write hooks have all passed.
+    pub fn poll_hooks(&mut self) {
+        let running = match &mut self.hooks {
+            Some(running) => running,
+            None => return,
+        };
+        let finished = running.finished();
+        let done = running.done();
+        let failed = finished.iter().find(|x| !x.success()).map(|x| x.summary());
+        for outcome in finished {
+            self.message = Some(outcome.summary());
+            self.hook_outcomes.push(outcome);
+        }
+        if done {
+            self.hooks = None;
+            if std::mem::take(&mut self.write_pending) {
+                self.message = Some(match failed {
+                    Some(summary) => format!("{}, nothing was written (:hooks shows why)", summary),
+                    None => match self.write() {
+                        Ok(changed) => self.wrote(changed),
+                        Err(e) => format!("error: {}", e),
+                    },
+                });
+            }
+        }
+    }
+
+    /// Run the file in a pseudo terminal, with `args` or else `settings.run_args`.  With pending
+    /// edits it's a copy with them applied which runs, so they can be tried before writing.
+    pub fn run_target(&mut self, args: Option<Vec<String>>) -> Result<String, Box<dyn Error>> {
+        let args = args.unwrap_or_else(|| self.settings.run_args.clone());
+        // the last run is killed before its copy could be overwritten
+        self.runner = None;
+        let (program, copy) = self.executable("run")?;
+        let pending = if copy.is_some() {
+            " with pending edits"
+        } else {
+            ""
+        };
+        let runner = Runner::spawn(&program, &args, copy)?;
+        let message = format!("running {}{}, Esc stops it", runner.command, pending);
+        self.runner = Some(runner);
+        Ok(message)
+    }
+
+    /// The file to run: itself if nothing's pending, otherwise an executable copy with the
+    /// pending edits applied, in a directory named after `purpose` which goes when it's dropped.
+    fn executable(&self, purpose: &str) -> Result<(PathBuf, Option<TempDir>), Box<dyn Error>> {
+        let (data, _) = self.render()?;
+        if data[..] == util::map(&self.file)?[..] {
+            return Ok((self.file.canonicalize()?, None));
+        }
+        let directory = TempDir::new(purpose)?;
+        let name = self.file.file_name().unwrap_or_default().to_string_lossy();
+        let (copy, mut file) = directory.create(&name)?;
+        file.write_all(&data)?;
+        Ok((copy, Some(directory)))
+    }
+
+    /// Get a debugger ready to start on the file with a breakpoint at the selected instruction,
+    /// at its virtual address as the debugger sees it before the program runs.  Position
+    /// independent files are refused, a breakpoint set before they're loaded would never hit.
+    pub fn debug(&mut self, args: Option<Vec<String>>) -> Result<String, Box<dyn Error>> {
+        let args = args.unwrap_or_else(|| self.settings.run_args.clone());
+        let offset = match &self.linear {
+            Some(linear) => linear.selected().ok_or("nothing selected")?.1,
+            None => self.current_row().ok_or("nothing selected")?.offset,
+        };
+        let program = util::map(&self.file)?;
+        if binary::position_independent(&program) {
+            return Err(format!(
+                "{} is position independent, where {:#x} will be isn't known before it runs",
+                self.file.display(),
+                offset
+            )
+            .into());
+        }
+        let address = binary::offsets_to_vas(&program, &[offset])[0]
+            .ok_or_else(|| format!("{:#x} isn't loaded", offset))?;
+        let (program, copy) = self.executable("debug")?;
+        let command = debugger::expand(&self.settings.debugger, &program, address, &args);
+        self.launch = Some(Launch { command, copy });
+        Ok(format!("debugging with a breakpoint at {:#x}", address))
+    }
+
+    /// Show what the running file printed since the last tick, and how it exited once it has.
+    pub fn poll_runner(&mut self) {
+        if let Some(runner) = &mut self.runner {
+            if runner.poll() {
+                self.message = Some(format!("{} {}", runner.command, runner.describe_status()));
+            }
+        }
+    }
+
+    /// what each hook of the last write printed, under how it exited
+    pub fn hook_lines(&self) -> Vec<String> {
+        let mut lines = self
+            .hook_outcomes
+            .iter()
+            .flat_map(|x| x.lines())
+            .collect::<Vec<_>>();
+        if self.hooks.is_some() {
+            lines.push("(still running)".to_string());
+        }
+        lines
+    }
+
+    /// What plain mode reads out, a line each: the status bar and the selection, or the stats
+    /// while they're shown.
+    pub fn announcements(&self) -> Vec<String> {
+        if self.show_hooks {
+            let mut lines = self.hook_lines();
+            lines.push("press any key to go back".to_string());
+            return lines;
+        }
+        if let Some(runner) = &self.runner {
+            let mut lines = vec![self.get_bar(), runner.describe_status()];
+            lines.extend(runner.transcript.all());
+            return lines;
+        }
+        if let Some(stats) = &self.stats {
+            let mut lines = vec![format!("mnemonics, {} instructions", stats.instructions)];
+            lines.extend(stats.mnemonic_labels());
+            lines.push("most called".to_string());
+            lines.extend(stats.call_labels());
+            lines.push(format!("function sizes, {} functions", stats.functions));
+            lines.extend(stats.size_labels());
+            lines.push("press any key to go back".to_string());
+            return lines;
+        }
+        let mut bar = self.get_bar();
+        if let Some((size, original)) = self.byte_budget() {
+            bar = format!("{} | {} bytes (orig {})", bar, size, original);
+        }
+        if let Some((va, _, name)) = self.pointer_at_cursor() {
+            bar = format!("{} | -> {} ({:#x})", bar, name, va);
+        }
+        if let Some(linear) = &self.linear {
+            return vec![bar, linear.selected_label().unwrap_or_default()];
+        }
+        if let Some(palette) = &self.palette {
+            let selected = palette.state.selected().unwrap_or(0);
+            let label = palette.labels().into_iter().nth(selected);
+            return vec![bar, label.unwrap_or_else(|| "no matches".to_string())];
+        }
+        let function = match self.get_current_function() {
+            Some(function) => function,
+            None => return vec![bar, "no functions found".to_string()],
+        };
+        let selection = match self.selected {
+            Column::Function => format!(
+                "function {}, {} of {}, {:#x} bytes",
+                function.name,
+                self.function_state.selected().unwrap_or(0) + 1,
+                self.functions.len(),
+                function.size
+            ),
+            Column::Calls => self
+                .call_tree()
+                .into_iter()
+                .nth(self.call_state.selected().unwrap_or(0))
+                .map(|x| format!("calls {}", x.label))
+                .unwrap_or_default(),
+            Column::Hex | Column::Disasm => {
+                let index = self.editor_state.selected().unwrap_or(0);
+                let column = match self.selected {
+                    Column::Hex => "hex",
+                    _ => "disasm",
+                };
+                match self.current_row() {
+                    Some(row) => format!(
+                        "{} {} {} of {}: {} | {}{}",
+                        column,
+                        function.name,
+                        index + 1,
+                        self.current_rows().len(),
+                        row.bytes,
+                        self.disasm_labels(&function.name).swap_remove(index),
+                        if row.valid { "" } else { " (invalid)" }
+                    ),
+                    None => format!("{} is empty", function.name),
+                }
+            }
+        };
+        vec![bar, selection]
+    }
+
+    /// While editing a Disasm row: how many bytes it assembles to and how many it had originally.
+    /// Rows are reassembled on every tick, so this trails typing by at most one tick.
+    pub fn byte_budget(&self) -> Option<(usize, usize)> {
+        if self.mode != Mode::Editing || self.selected != Column::Disasm {
+            return None;
+        }
+        let row = self.current_row().filter(|x| x.valid)?;
+        Some((
+            from_hexstring(&row.bytes).len(),
+            from_hexstring(&row.original).len(),
+        ))
+    }
+
+    /// run whatever is on the command line and go back to Viewing mode
+    pub fn run_command(&mut self) {
+        // commands act on the selected row, so that's part of what gets recorded
+        let selected = self
+            .current_row()
+            .and_then(|x| Some((self.get_current_function()?.name.clone(), x.offset)));
+        let result = self
+            .command
+            .parse::<Command>()
+            .map_err(|e| e.into())
+            .and_then(|command| self.execute(command));
+        // the wizard records the command it ends up running rather than itself
+        if let (Ok(_), Some((function, va)), None) = (&result, selected, &self.wizard) {
+            let line = self.command.clone();
+            let byte = Some(self.cursor_byte()).filter(|_| self.selected == Column::Hex);
+            self.record(Operation::Command {
+                function,
+                va,
+                byte,
+                line,
+            });
+        }
+        self.message = Some(result.unwrap_or_else(|e| format!("error: {}", e)));
+        self.command.clear();
+        self.mode = match self.wizard {
+            Some(_) => Mode::Wizard,
+            None => Mode::Viewing,
+        };
+    }
+
+    /// Take what was typed as the answer to the wizard's question.  Once it's confirmed the
+    /// wizard runs (and so records) the `trampoline` command its answers add up to.
+    pub fn answer_wizard(&mut self) {
+        let answer = std::mem::take(&mut self.command);
+        self.message = None;
+        let wizard = match self.wizard.as_mut() {
+            Some(wizard) => wizard,
+            None => return,
+        };
+        match wizard.step {
+            Step::Stub => match self.resolve(answer.trim()) {
+                Some(stub) => {
+                    if let Some(wizard) = self.wizard.as_mut() {
+                        wizard.set_stub(stub);
+                    }
+                }
+                None => self.message = Some(format!("error: couldn't find {}", answer.trim())),
+            },
+            Step::Template => {
+                if let Err(e) = wizard.set_template(&answer) {
+                    self.message = Some(format!("error: {}", e));
+                    return;
+                }
+                let saving = wizard.prelude().map_or(0, |x| x.len());
+                self.message = Some(match self.hook_span(0) {
+                    Ok((end, covered)) => format!(
+                        "{} instruction(s) around the call, then {} displaced instruction(s) \
+                         ({} bytes) and a jmp back",
+                        saving, end, covered
+                    ),
+                    Err(e) => format!("error: {}", e),
+                });
+            }
+            Step::Confirm => {
+                let wizard = self.wizard.take();
+                self.mode = Mode::Viewing;
+                match (
+                    answer.trim(),
+                    wizard.and_then(|x| Some((x.stub?, x.template))),
+                ) {
+                    ("y", Some((stub, template))) | ("yes", Some((stub, template))) => {
+                        self.command = format!("trampoline {:#x} {}", stub, template);
+                        self.run_command();
+                    }
+                    _ => self.message = Some("no trampoline added".to_string()),
+                }
+            }
+        }
+    }
+
+    fn execute(&mut self, command: Command) -> Result<String, Box<dyn Error>> {
+        match command {
+            Command::Comment(text) => {
+                let location = self.current_location().ok_or("no instruction selected")?;
+                self.annotations.set_comment(location, &text);
+                Ok(if text.is_empty() {
+                    "removed comment".to_string()
+                } else {
+                    "added comment".to_string()
+                })
+            }
+            Command::Reload => self.reload(),
+            Command::Filter(query) => self.set_filter(&query),
+            Command::Cursors(text) => self.place_cursors(&text),
+            Command::Engine(Some(setting)) => self.set_engine_option(setting),
+            Command::Engine(None) => Ok(self.engine_summary()),
+            Command::Run(args) => self.run_target(args),
+            Command::Debug(args) => self.debug(args),
+            Command::Hooks => {
+                if self.hook_outcomes.is_empty() {
+                    return Err("no hooks have run".into());
+                }
+                self.show_hooks = true;
+                Ok(format!("{} hook(s)", self.hook_outcomes.len()))
+            }
+            Command::Stats => {
+                let stats = Stats::new(&self.functions, &self.rows);
+                let message = format!(
+                    "{} instruction(s) in {} function(s)",
+                    stats.instructions, stats.functions
+                );
+                self.stats = Some(stats);
+                Ok(message)
+            }
+            Command::Goto(anchor) => self.goto(anchor),
+            Command::Seek(target) => {
+                let from = self.location();
+                if !self.seek(&target) {
+                    return Err(format!("couldn't find {}", target).into());
+                }
+                self.back.push(from);
+                let function = self.get_current_function().map_or("", |x| &x.name);
+                Ok(format!("in {}", function))
+            }
+            Command::Offset(offset) => {
+                let from = self.location();
+                let message = self.seek_offset(offset)?;
+                self.back.push(from);
+                Ok(message)
+            }
+            Command::Yara(Some(rules)) => {
+                let matches = yara::scan(std::path::Path::new(&rules), &self.file)?;
+                let vas = binary::offsets_to_vas(
+                    &util::map(&self.file)?,
+                    &matches.iter().map(|x| x.offset).collect::<Vec<_>>(),
+                );
+                self.yara_hits = matches
+                    .into_iter()
+                    .zip(vas)
+                    .map(|(x, va)| YaraHit {
+                        rule: x.rule,
+                        identifier: x.identifier,
+                        va,
+                    })
+                    .collect();
+                if self.yara_hits.is_empty() {
+                    return Ok("no matches".to_string());
+                }
+                self.yara_index = self.yara_hits.len() - 1;
+                self.next_yara_hit()
+            }
+            Command::Yara(None) => self.next_yara_hit(),
+            Command::Bundle { path, notes } => self.save_bundle(Path::new(&path), notes),
+            Command::InjectC { path, function } => {
+                self.inject_c(Path::new(&path), function.as_deref())
+            }
+            Command::Encode { encoding, len } => self.encode(encoding, len),
+            Command::Harden { hardening, enable } => self.harden(hardening, enable),
+            Command::Forbid(extensions) => {
+                self.settings.forbidden_extensions = extensions;
+                Ok(self.forbidden_summary())
+            }
+            Command::Attach(Some(pid)) => {
+                let base =
+                    procmaps::load_base(&procmaps::read(pid)?, &self.file).ok_or_else(|| {
+                        format!("{} doesn't have {} mapped", pid, self.file.display())
+                    })?;
+                let slide = base.wrapping_sub(binary::image_base(&util::map(&self.file)?)?);
+                self.attached = Some(Attachment { pid, slide });
+                Ok(format!("attached to {}, loaded at {:#x}", pid, base))
+            }
+            Command::Attach(None) => {
+                self.attached = None;
+                Ok("detached".to_string())
+            }
+            Command::Dump(path) => {
+                let bytes = self
+                    .current_rows()
+                    .iter()
+                    .flat_map(|x| from_hexstring(&x.bytes))
+                    .collect::<Vec<_>>();
+                std::fs::write(&path, &bytes)?;
+                Ok(format!("wrote {} bytes to {}", bytes.len(), path))
+            }
+            Command::Load(path) => self.load_function(Path::new(&path)),
+            Command::Force { taken } => {
+                let row = self.current_row().ok_or("no instruction selected")?;
+                let bytes = from_hexstring(&row.bytes);
+                let forced = if taken {
+                    branch::force_jump(&bytes)
+                } else {
+                    branch::fallthrough(&bytes)
+                };
+                let text = self.set_current_bytes(&forced.ok_or("not a conditional jump")?)?;
+                Ok(format!("replaced with {}", text))
+            }
+            Command::Trace(true) => {
+                let pc = self.current_row().ok_or("no instruction selected")?.offset;
+                let (data, _) = self.render()?;
+                self.trace = Some(Trace::start(&data, pc)?);
+                Ok(format!(
+                    "tracing from {:#x}, n steps, g runs to a breakpoint, p sets one",
+                    pc
+                ))
+            }
+            Command::Trace(false) => {
+                self.trace = None;
+                Ok("stopped tracing".to_string())
+            }
+            Command::Export(path) => {
+                let path = Path::new(&path);
+                let format = export::Format::from_path(path)?;
+                let (patched, _) = self.render()?;
+                let original = self.original()?;
+                std::fs::write(path, format.encode(&original, &patched)?)?;
+                Ok(format!("exported {}", path.display()))
+            }
+            Command::Annotations { path, export } => {
+                if export {
+                    self.export_annotations(Path::new(&path))
+                } else {
+                    // an r2 project can be given by name
+                    let path = interop::r2_project(&path).unwrap_or_else(|_| PathBuf::from(&path));
+                    self.import_annotations(&path)
+                }
+            }
+            Command::Report(path) => {
+                let path = Path::new(&path);
+                let format = report::Format::from_path(path)?;
+                let report = self.review_report();
+                std::fs::write(path, report.render(format)?)?;
+                Ok(format!(
+                    "reported {} hunk(s) to {}",
+                    report.hunks.len(),
+                    path.display()
+                ))
+            }
+            Command::Linear => self.toggle_linear(),
+            Command::Vtables => self.toggle_vtables(),
+            Command::Slot(target) => self.patch_slot(&target),
+            Command::Got(path) => self.map_imports(path.as_deref()),
+            Command::Data(len) => self.mark_data(len),
+            Command::Switches => self.detect_jump_tables(),
+            Command::Define { offset, size, name } => self.define_function(offset, size, name),
+            Command::Capture(path) => {
+                let path = Path::new(&path);
+                let format = listing::Format::from_path(path)?;
+                let listing = self.listing();
+                std::fs::write(path, listing.render(format))?;
+                Ok(format!(
+                    "captured {} row(s) of {} to {}",
+                    listing.rows.len(),
+                    listing.title,
+                    path.display()
+                ))
+            }
+            Command::Overlay(path) => {
+                let path = Path::new(&path);
+                let (patched, _) = self.render()?;
+                let name = self.file.file_name().unwrap_or_default().to_string_lossy();
+                let source = overlay::source(&self.original()?, &patched, &name)?;
+                overlay::build(&source, path, &self.settings)?;
+                Ok(format!("wrote overlay {}", path.display()))
+            }
+            Command::Compare(None) => {
+                self.comparison = None;
+                Ok("stopped comparing".to_string())
+            }
+            Command::Compare(Some(file)) => {
+                if !std::path::Path::new(&file).is_file() {
+                    return Err(format!("{} doesn't exist", file).into());
+                }
+                let (functions, rows, _) =
+                    analyze(&file, &self.engines.options, self.backend, false)?;
+                self.comparison = Some(Comparison {
+                    file: PathBuf::from(&file),
+                    rows,
+                });
+                Ok(format!(
+                    "comparing against {} ({} functions)",
+                    file,
+                    functions.len()
+                ))
+            }
+            Command::Redirect { import, target } => self.redirect_import(&import, &target),
+            Command::Detour => self.detour(),
+            Command::Claim => self.claim_padding(),
+            Command::Poke(bytes) => self.poke(&bytes),
+            Command::DefineStruct(source) => {
+                let (names, skipped) = self.define_structs(&source)?;
+                Ok(format!("defined {}{}", names.join(", "), skipped))
+            }
+            Command::Structs(path) => {
+                let (names, skipped) = self.define_structs(&std::fs::read_to_string(&path)?)?;
+                Ok(format!(
+                    "{} struct(s) from {}{}",
+                    names.len(),
+                    path,
+                    skipped
+                ))
+            }
+            Command::Struct { name: None, .. } => {
+                self.struct_view = None;
+                Ok("stopped showing the struct".to_string())
+            }
+            Command::Struct {
+                name: Some(name),
+                address,
+            } => self.lay_struct(&name, address),
+            Command::Field { name, value } => self.set_field(&name, &value),
+            Command::EditString {
+                address,
+                text,
+                relocate,
+            } => self.edit_string(address, &text, relocate),
+            Command::Duplicate {
+                destination,
+                redirect,
+            } => self.duplicate(destination, redirect),
+            Command::Trampoline {
+                stub: None,
+                template,
+            } => {
+                self.check_editable()?;
+                let data = util::map(&self.file)?;
+                let arch = binary::arch(&data).unwrap_or_default();
+                let template =
+                    template.unwrap_or_else(|| Template::detect(&arch, data.starts_with(b"MZ")));
+                let function = self.get_current_function().ok_or("no function selected")?;
+                let wizard = Wizard::new(function.name.clone(), template);
+                self.editor_state.select(Some(0));
+                self.wizard = Some(wizard);
+                Ok("Esc to cancel".to_string())
+            }
+            Command::Trampoline {
+                stub: Some(stub),
+                template,
+            } => self.trampoline(&stub, template),
+            Command::Import { library, function } => {
+                self.inject_import(&library, function.as_deref())
+            }
+            Command::Strip => {
+                self.symbol_edit = Some(SymbolTableEdit::Strip);
+                Ok("the symbol table will be stripped on write".to_string())
+            }
+            Command::Unstrip => {
+                let symbols = self
+                    .functions
+                    .iter()
+                    .filter(|x| !x.name.starts_with("sym.imp."))
+                    .map(|x| Symbol {
+                        name: x.name.trim_start_matches("sym.").to_string(),
+                        va: x.offset as u64,
+                        size: x.size as u64,
+                    })
+                    .collect::<Vec<_>>();
+                let message = format!("{} symbols will be added on write", symbols.len());
+                self.symbol_edit = Some(SymbolTableEdit::Add(symbols));
+                Ok(message)
+            }
+        }
+    }
+
+    /// Flip the header bits for `hardening` on the next write.  The overview is updated to show
+    /// the file as it will be written.
+    pub fn harden(&mut self, hardening: Hardening, enable: bool) -> Result<String, Box<dyn Error>> {
+        let (data, _) = self.render()?;
+        self.patches.push(binary::harden(&data, hardening, enable)?);
+        let (data, _) = self.render()?;
+        self.overview = overview::read(&data).ok();
+        let state = if enable { "on" } else { "off" };
+        let base = binary::image_base(&data)?;
+        Ok(match (hardening, enable) {
+            (Hardening::Pie, true) if base != 0 => format!(
+                "{} will be {} after writing, but it's linked at {:#x} so absolute addresses will \
+                 be wrong once it's moved",
+                hardening, state, base
+            ),
+            (Hardening::Pie, false) if base == 0 => format!(
+                "{} will be {} after writing, but it's linked at 0 and will fail to load",
+                hardening, state
+            ),
+            _ => format!("{} will be {} after writing", hardening, state),
+        })
+    }
+
+    /// add `library` (and `function` from it) to the imports, the tables live in the extension
+    pub fn inject_import(
+        &mut self,
+        library: &str,
+        function: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        if self.import_pending {
+            return Err("write the file before injecting another import".into());
+        }
+        let data = util::map(&self.file)?;
+        if self.extension.is_none() {
+            self.extension = Some(Extension::plan(&data)?);
+        }
+        let extension = self.extension.as_mut().ok_or("no extension segment")?;
+        let (patches, slot) = rewrite::inject_import(&data, extension, library, function)?;
+        self.patches.extend(patches);
+        self.import_pending = true;
+        Ok(match (function, slot) {
+            (Some(function), Some(slot)) => {
+                format!(
+                    "imported {} from {}, its address is at {:#x}",
+                    function, library, slot
+                )
+            }
+            _ => format!("added {} to the needed libraries", library),
+        })
+    }
+
+    /// Move the current row's instructions into the extension segment, replacing it (and as many
+    /// following rows as needed to make room) with a jmp there.  The displaced instructions are
+    /// reassembled after the new code, followed by a jmp back.
+    pub fn detour(&mut self) -> Result<String, Box<dyn Error>> {
+        let (target, moved) = self.hook(&[])?;
+        Ok(format!("moved {} instruction(s) to {:#x}", moved, target))
+    }
+
+    /// which byte of the current row the Hex cursor is on, each takes two digits and a space
+    fn cursor_byte(&self) -> usize {
+        self.cursor_index.max(0) as usize / 3
+    }
+
+    /// Overwrite the bytes from the Hex cursor (or the start of the row from the Disasm column)
+    /// with `bytes`, carrying on into the following rows if they don't fit in this one.
+    pub fn poke(&mut self, bytes: &[u8]) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let index = self.editor_state.selected().unwrap_or(0);
+        let skip = match self.selected {
+            Column::Hex => self.cursor_byte(),
+            _ => 0,
+        };
+        let (rows, engines) = self.current_rows_and_engines();
+        let start = rows.get(index).ok_or("no instruction selected")?.offset + skip as u64;
+        let room = rows[index..]
+            .iter()
+            .map(|x| from_hexstring(&x.bytes).len())
+            .sum::<usize>();
+        if skip + bytes.len() > room {
+            return Err(format!(
+                "{} byte(s) from {:#x} run past the end of the function",
+                bytes.len(),
+                start
+            )
+            .into());
+        }
+        let mut at = 0;
+        let mut skip = skip;
+        for row in &mut rows[index..] {
+            if at == bytes.len() {
+                break;
+            }
+            let mut row_bytes = from_hexstring(&row.bytes);
+            let len = (row_bytes.len() - skip).min(bytes.len() - at);
+            row_bytes[skip..skip + len].copy_from_slice(&bytes[at..at + len]);
+            at += len;
+            // only the first row starts part way in
+            skip = 0;
+            row.bytes = util::to_hexstring(&row_bytes);
+            row.rebuild_text(engines);
+        }
+        Ok(format!(
+            "wrote {} at {:#x}",
+            util::to_hexstring(bytes),
+            start
+        ))
+    }
+
+    /// Replace the NUL terminated string at `address`, or the first one the current row refers
+    /// to, with `text`.  It's written over the original, which has to have room for it, unless
+    /// `relocate`, when a longer one goes in a code cave or the new segment and the instructions
+    /// which referred to the original refer to it instead.  Pointers to it in data aren't
+    /// followed.
+    pub fn edit_string(
+        &mut self,
+        address: Option<u64>,
+        text: &[u8],
+        relocate: bool,
+    ) -> Result<String, Box<dyn Error>> {
+        let (rendered, _) = self.render()?;
+        let original = self.original()?;
+        let string_at = |va: u64| {
+            let offset = binary::va_to_offset(&rendered, va)?;
+            literal::read(&rendered, offset).map(|_| (va, offset))
+        };
+        let (from, offset) = match address {
+            Some(va) => string_at(va).ok_or_else(|| format!("there's no string at {:#x}", va))?,
+            None => {
+                let row = self.current_row().ok_or("no instruction selected")?;
+                signatures::references(std::slice::from_ref(row))
+                    .into_iter()
+                    .find_map(string_at)
+                    .ok_or("the current row doesn't refer to a string, give its @address")?
+            }
+        };
+        // a string shortened earlier still has all of its original room
+        let room = [&rendered, &original]
+            .iter()
+            .filter_map(|x| literal::read(x, offset))
+            .map(|x| x.len() + 1)
+            .max()
+            .unwrap_or(0);
+        let mut bytes = text.to_vec();
+        bytes.push(0);
+        if bytes.len() <= room {
+            bytes.resize(room, 0);
+            self.patches.push(Patch::new(offset, bytes));
+            return Ok(format!(
+                "replaced the string at {:#x} ({} of {} byte(s))",
+                from,
+                text.len() + 1,
+                room
+            ));
+        }
+        if !relocate {
+            return Err(format!(
+                "{} byte(s) don't fit in the {} the string at {:#x} has, `string move` puts it \
+                 somewhere with room",
+                text.len() + 1,
+                room,
+                from
+            )
+            .into());
+        }
+        if self.low_memory {
+            return Err(
+                "repointing code at the string needs every function's rows, which low memory \
+                 mode doesn't keep"
+                    .into(),
+            );
+        }
+        let data = util::map(&self.file)?;
+        // past the first byte, which may be the NUL of a string moved there before
+        let cave = self
+            .free_caves(&data)?
+            .into_iter()
+            .map(|(start, size)| start + 1..start + size)
+            .find(|x| x.end - x.start >= bytes.len() as u64);
+        let (to, place) = match cave {
+            Some(cave) => {
+                let to = binary::offsets_to_vas(&data, &[cave.start])[0]
+                    .ok_or_else(|| format!("{:#x} isn't loaded", cave.start))?;
+                self.patches.push(Patch::new(cave.start, bytes));
+                (to, format!("the cave at {:#x}", cave.start))
+            }
+            None => {
+                if self.extension.is_none() {
+                    self.extension = Some(Extension::plan(&data)?);
+                }
+                let extension = self.extension.as_mut().ok_or("no extension segment")?;
+                (extension.push(&bytes)?, "the new segment".to_string())
+            }
+        };
+        let engines = &self.engines;
+        let mut repointed = 0;
+        for row in self.rows.values_mut().flatten().filter(|x| !x.data) {
+            let bytes = from_hexstring(&row.bytes);
+            let moved = engines
+                .disassemble(&bytes, row.offset)
+                .first()
+                .and_then(|(_, text, _)| literal::repoint(&bytes, text, row.offset, from, to));
+            if let Some(moved) = moved {
+                row.bytes = util::to_hexstring(&moved);
+                row.rebuild_text(engines);
+                repointed += 1;
+            }
+        }
+        Ok(format!(
+            "moved the string at {:#x} to {:#x} in {}, {} instruction(s) now refer to it \
+             (pointers to it in data still point at the original)",
+            from, to, place, repointed
+        ))
+    }
+
+    /// Define the structs in `source`, C, replacing any by the same names.  Returns their names
+    /// and which were skipped as the end of a message, empty if none were.
+    fn define_structs(&mut self, source: &str) -> Result<(Vec<String>, String), Box<dyn Error>> {
+        let layout::Header { structs, skipped } = layout::parse(source, self.pointers.width())?;
+        let skipped = skipped
+            .iter()
+            .map(|(name, e)| format!("{} ({})", name, e))
+            .collect::<Vec<_>>();
+        if structs.is_empty() {
+            return Err(match skipped.as_slice() {
+                [] => "no structs there".into(),
+                _ => format!("no struct could be laid out: {}", skipped.join(", ")).into(),
+            });
+        }
+        let names = structs.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
+        self.structs.retain(|x| !names.contains(&x.name));
+        self.structs.extend(structs);
+        let skipped = match skipped.as_slice() {
+            [] => String::new(),
+            _ => format!(", skipped {}", skipped.join(", ")),
+        };
+        Ok((names, skipped))
+    }
+
+    /// Show the struct `name` laid over the data at `address`, or at the cursor: the Hex
+    /// cursor's byte or the start of the current row.
+    pub fn lay_struct(
+        &mut self,
+        name: &str,
+        address: Option<u64>,
+    ) -> Result<String, Box<dyn Error>> {
+        let layout = self
+            .structs
+            .iter()
+            .find(|x| x.name == name)
+            .cloned()
+            .ok_or_else(|| format!("no struct {}, define it with :struct or :structs", name))?;
+        let data = util::map(&self.file)?;
+        let (address, offset) = match address {
+            Some(va) => (
+                va,
+                binary::va_to_offset(&data, va)
+                    .ok_or_else(|| format!("{:#x} isn't loaded from the file", va))?,
+            ),
+            None => {
+                let row = self.current_row().ok_or("no row selected")?;
+                let offset = match self.selected {
+                    Column::Hex => row.offset + self.cursor_byte() as u64,
+                    _ => row.offset,
+                };
+                let va = binary::offsets_to_vas(&data, &[offset])[0]
+                    .ok_or_else(|| format!("{:#x} isn't loaded", offset))?;
+                (va, offset)
+            }
+        };
+        let bytes = data
+            .get(offset as usize..)
+            .map_or(&[][..], |x| &x[..x.len().min(layout.size)])
+            .to_vec();
+        let message = format!(
+            "{} ({:#x} bytes) at {:#x}",
+            layout.name, layout.size, address
+        );
+        self.struct_view = Some(Overlay {
+            layout,
+            address,
+            offset,
+            bytes,
+        });
+        Ok(message)
+    }
+
+    /// Title and lines of the struct panel: each field of the struct shown by `:struct` with its
+    /// value, pending edits included.
+    pub fn struct_panel(&self) -> Option<(String, Vec<String>)> {
+        let view = self.struct_view.as_ref()?;
+        let bytes = self.with_edits(view.offset, view.bytes.clone());
+        let title = format!("struct {} at {:#x}", view.layout.name, view.address);
+        Some((title, view.layout.describe(&bytes)))
+    }
+
+    /// Set the field `name` of the struct shown by `:struct` to `value`.
+    pub fn set_field(&mut self, name: &str, value: &str) -> Result<String, Box<dyn Error>> {
+        let view = self
+            .struct_view
+            .as_ref()
+            .ok_or("no struct is shown, see :struct")?;
+        let (at, bytes) = view.layout.encode(name, value)?;
+        let (offset, message) = (
+            view.offset + at as u64,
+            format!("{}.{} = {}", view.layout.name, name, value),
+        );
+        self.write_at(offset, &bytes);
+        Ok(message)
+    }
+
+    /// `bytes`, as read from file `offset` when loaded, with the pending edits over them
+    /// applied: the rows of functions there (or their deltas in low memory mode) and patches.
+    fn with_edits(&self, offset: u64, mut bytes: Vec<u8>) -> Vec<u8> {
+        let end = offset + bytes.len() as u64;
+        let mut copy = |start: u64, edit: &[u8]| {
+            for (i, byte) in edit.iter().enumerate() {
+                let at = start + i as u64;
+                if (offset..end).contains(&at) {
+                    bytes[(at - offset) as usize] = *byte;
+                }
+            }
+        };
+        let over = self.functions.iter().filter(|x| {
+            !x.outside_file && (x.offset as u64) < end && offset < (x.offset + x.size) as u64
+        });
+        for function in over {
+            for row in self.rows.get(&function.name).into_iter().flatten() {
+                copy(row.offset, &from_hexstring(&row.bytes));
+            }
+        }
+        // only functions whose rows were dropped have one
+        for (start, delta) in &self.deltas {
+            copy(*start, delta);
+        }
+        for patch in &self.patches {
+            copy(patch.offset, &patch.bytes);
+        }
+        bytes
+    }
+
+    /// Write `bytes` at file `offset`: into the rows of the function there, so it shows them,
+    /// or as a patch when no function's rows hold them all.
+    fn write_at(&mut self, offset: u64, bytes: &[u8]) {
+        let end = offset + bytes.len() as u64;
+        let function = self
+            .functions
+            .iter()
+            .find(|x| {
+                !x.outside_file && x.offset as u64 <= offset && end <= (x.offset + x.size) as u64
+            })
+            .map(|x| x.name.clone())
+            .filter(|x| self.rows.contains_key(x));
+        let function = match function {
+            Some(function) => function,
+            None => return self.patches.push(Patch::new(offset, bytes.to_vec())),
+        };
+        let engines = &self.engines;
+        for row in self.rows.get_mut(&function).into_iter().flatten() {
+            let mut row_bytes = from_hexstring(&row.bytes);
+            let (start, row_end) = (row.offset, row.offset + row_bytes.len() as u64);
+            if row_end <= offset || end <= start {
+                continue;
+            }
+            for at in offset.max(start)..end.min(row_end) {
+                row_bytes[(at - start) as usize] = bytes[(at - offset) as usize];
+            }
+            row.bytes = util::to_hexstring(&row_bytes);
+            row.rebuild_text(engines);
+        }
+    }
+
+    /// Add the alignment padding after the current function to its end, as rows which can be
+    /// edited like the rest of it.  Writing checks functions against their size, which now
+    /// includes the padding.
+    pub fn claim_padding(&mut self) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let index = self.function_state.selected().unwrap_or(0);
+        let function = self.functions.get(index).ok_or("no function selected")?;
+        let (start, len) = self
+            .padding_after(function)
+            .ok_or_else(|| format!("there's no padding after {}", function.name))?;
+        let padding = Function {
+            name: function.name.clone(),
+            offset: start as usize,
+            size: len,
+            nbbs: 0,
+            callrefs: vec![],
+            outside_file: false,
+        };
+        let rows = self.stream_rows(&util::map(&self.file)?, &padding);
+        self.rows
+            .entry(padding.name.clone())
+            .or_default()
+            .extend(rows);
+        self.functions[index].size += len;
+        Ok(format!(
+            "{} has {} more byte(s) to grow into, up to {:#x}",
+            padding.name,
+            len,
+            start + len as u64
+        ))
+    }
+
+    /// the alignment padding between the end of `function` and the next one, as its file
+    /// offset and length
+    fn padding_after(&self, function: &Function) -> Option<(u64, usize)> {
+        let end = (function.offset + function.size) as u64;
+        let next = self
+            .functions
+            .iter()
+            .map(|x| x.offset as u64)
+            .filter(|x| *x >= end)
+            .min()?;
+        let data = util::map(&self.file).ok()?;
+        let bytes = data.get(end as usize..next as usize)?;
+        Some((end, cave::padding(&self.engines, bytes, end))).filter(|(_, len)| *len > 0)
+    }
+
+    /// A function whose rows have grown past its size and would write over whatever follows
+    /// it, as a reason not to write.
+    fn overgrown(&self) -> Option<String> {
+        self.functions
+            .iter()
+            .filter(|x| !x.outside_file)
+            .find_map(|function| {
+                let len = match self.rows.get(&function.name) {
+                    Some(rows) => rows
+                        .iter()
+                        .map(|x| from_hexstring(&x.bytes).len())
+                        .sum::<usize>(),
+                    // a delta runs from its first edited row to the end of the rows
+                    None => {
+                        let (start, delta) = self.delta_of(function)?;
+                        (*start - function.offset as u64) as usize + delta.len()
+                    }
+                };
+                let claim = match self.padding_after(function) {
+                    Some(_) => ", :claim the padding after it",
+                    None => "",
+                };
+                Some(format!(
+                    "{} has grown to {} bytes but only has room for {}{} or :detour some of it, \
+                     nothing was written",
+                    function.name, len, function.size, claim
+                ))
+                .filter(|_| len > function.size)
+            })
+    }
+
+    /// Copy the current function, as edited so far, to `destination` with its branches and rip
+    /// relative operands fixed up to still reach what they did (see `cave::relocate`).  With
+    /// `redirect` direct calls and jumps to the function go to the copy instead.
+    pub fn duplicate(
+        &mut self,
+        destination: Destination,
+        redirect: bool,
+    ) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        if redirect && self.low_memory {
+            return Err(
+                "redirecting callers needs every function's rows, which low memory mode \
+                        doesn't keep"
+                    .into(),
+            );
+        }
+        let function = self
+            .get_current_function()
+            .ok_or("no function selected")?
+            .clone();
+        let code = self
+            .current_rows()
+            .iter()
+            .flat_map(|x| from_hexstring(&x.bytes))
+            .collect::<Vec<_>>();
+        let data = util::map(&self.file)?;
+        let from = binary::offsets_to_vas(&data, &[function.offset as u64])[0]
+            .ok_or_else(|| format!("{} isn't loaded", function.name))?;
+        let len = code.len() as u64;
+        let align = |x: u64| (x + 15) & !15;
+
+        let (to, place) = match destination {
+            Destination::Extension => {
+                if self.extension.is_none() {
+                    self.extension = Some(Extension::plan(&data)?);
+                }
+                let next = self.extension.as_ref().map(|x| x.next_va()).unwrap_or(0);
+                let copy = cave::relocate(&self.engines, &code, from, align(next))?;
+                let extension = self.extension.as_mut().ok_or("no extension segment")?;
+                (
+                    extension.push_aligned(&copy, 16)?,
+                    "the new segment".to_string(),
+                )
+            }
+            Destination::Cave | Destination::At(_) => {
+                let caves = self.free_caves(&data)?;
+                let offset = match destination {
+                    Destination::At(offset) => Some(offset).filter(|offset| {
+                        caves
+                            .iter()
+                            .any(|(start, size)| start <= offset && offset + len <= start + size)
+                    }),
+                    _ => caves
+                        .iter()
+                        .map(|(start, size)| (align(*start), start + size))
+                        .find(|(start, end)| start + len <= *end)
+                        .map(|(start, _)| start),
+                }
+                .ok_or_else(|| format!("no cave there has room for {:#x} bytes", len))?;
+                let to = binary::offsets_to_vas(&data, &[offset])[0]
+                    .ok_or_else(|| format!("{:#x} isn't loaded", offset))?;
+                let copy = cave::relocate(&self.engines, &code, from, to)?;
+                self.patches.push(Patch::new(offset, copy));
+                (to, format!("the cave at {:#x}", offset))
+            }
+        };
+        let mut message = format!(
+            "copied {} ({:#x} bytes) to {:#x} in {}",
+            function.name, len, to, place
+        );
+        if redirect {
+            let redirected =
+                self.redirect_callers(function.offset as u64, to as i64 - from as i64)?;
+            message.push_str(&format!(", {} caller(s) now go there", redirected));
+        }
+        Ok(message)
+    }
+
+    /// Code caves (see `cave::caves`) in the file as loaded, `data`, which are outside every
+    /// function.  Earlier copies have filled their caves in, and the new segment is no cave.
+    fn free_caves(&self, data: &[u8]) -> Result<Vec<(u64, u64)>, Box<dyn Error>> {
+        let (rendered, _) = self.render()?;
+        Ok(cave::caves(&rendered, &binary::code_ranges(&rendered))
+            .into_iter()
+            .filter(|(start, size)| {
+                start + size <= data.len() as u64
+                    && !self.functions.iter().any(|x| {
+                        let (offset, end) = (x.offset as u64, (x.offset + x.size) as u64);
+                        offset < start + size && *start < end
+                    })
+            })
+            .collect())
+    }
+
+    /// Move every direct call or jmp (rel32) to `target` by `delta`.  Returns how many moved.
+    fn redirect_callers(&mut self, target: u64, delta: i64) -> Result<usize, Box<dyn Error>> {
+        let engines = &self.engines;
+        let mut redirected = 0;
+        for row in self.rows.values_mut().flatten().filter(|x| !x.data) {
+            let mut bytes = from_hexstring(&row.bytes);
+            let len = bytes.len();
+            if len < 5 || ![0xe8, 0xe9].contains(&bytes[len - 5]) {
+                continue;
+            }
+            let goes_to = engines
+                .disassemble(&bytes, row.offset)
+                .first()
+                .and_then(|(_, text, _)| analysis::branch_target(text));
+            if goes_to != Some(target) {
+                continue;
+            }
+            let mut displacement = [0; 4];
+            displacement.copy_from_slice(&bytes[len - 4..]);
+            let displacement = i32::try_from(i32::from_le_bytes(displacement) as i64 + delta)
+                .map_err(|_| {
+                    format!("the copy is out of reach of the call at {:#x}", row.offset)
+                })?;
+            bytes[len - 4..].copy_from_slice(&displacement.to_le_bytes());
+            row.bytes = util::to_hexstring(&bytes);
+            row.rebuild_text(engines);
+            redirected += 1;
+        }
+        Ok(redirected)
+    }
+
+    /// Hook the current function's entry with a call to `stub` (a function or an address),
+    /// saving state around it as `template` does, by default the usual one for the file.
+    pub fn trampoline(
+        &mut self,
+        stub: &str,
+        template: Option<Template>,
+    ) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let address = self
+            .resolve(stub)
+            .ok_or_else(|| format!("couldn't find {}", stub))?;
+        let template = match template {
+            Some(template) => template,
+            None => {
+                let data = util::map(&self.file)?;
+                Template::detect(&binary::arch(&data)?, data.starts_with(b"MZ"))
+            }
+        };
+        self.editor_state.select(Some(0));
+        let (target, moved) = self.hook(&template.call(address))?;
+        Ok(format!(
+            "{} calls {:#x} from a {} trampoline at {:#x}, {} instruction(s) displaced",
+            self.get_current_function().map_or("", |x| &x.name),
+            address,
+            template,
+            target,
+            moved
+        ))
+    }
+
+    /// the address of a function by name or an address as it is
+    fn resolve(&self, target: &str) -> Option<u64> {
+        self.find_function(target)
+            .map(|x| self.functions[x].offset as u64)
+            .or_else(|| util::parse_address(target))
+    }
+
+    /// compile the C file at `path` and call `function` from it (or its first function) at the
+    /// current row
+    pub fn inject_c(
+        &mut self,
+        path: &Path,
+        function: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        let snippet = snippet::compile(path, function, &self.settings)?;
+        // the snippet's room is given back if the call to it can't be hooked in
+        let before = self.extension.clone();
+        if self.extension.is_none() {
+            self.extension = Some(Extension::plan(&util::map(&self.file)?)?);
+        }
+        let start = self
+            .extension
+            .as_mut()
+            .ok_or("no extension segment")?
+            .push_aligned(&snippet.code, 16)?;
+        let entry = start + snippet.entry;
+        if let Err(e) = self.call_from_current_row(entry) {
+            self.extension = before;
+            return Err(e);
+        }
+        Ok(format!(
+            "injected {} bytes from {}, called at {:#x}",
+            snippet.code.len(),
+            path.display(),
+            entry
+        ))
+    }
+
+    /// Obfuscate the code after the current row with `encoding` and hook a decoder in at the
+    /// current row, which restores it the first time it runs.  `len` bytes are encoded, by
+    /// default up to the end of the function.
+    pub fn encode(
+        &mut self,
+        encoding: Encoding,
+        len: Option<u64>,
+    ) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let index = self.editor_state.selected().unwrap_or(0);
+        let (_, covered) = self.hook_span(index)?;
+        let rows = self.current_rows();
+        // the rows the hook moves stay readable, everything after them can be encoded
+        let start = rows[index].offset + covered as u64;
+        let function_end = rows
+            .last()
+            .map(|x| x.offset + from_hexstring(&x.bytes).len() as u64)
+            .unwrap_or(start);
+        let len = len.unwrap_or_else(|| function_end.saturating_sub(start));
+        if len == 0 || start + len > function_end {
+            return Err(format!(
+                "{:#x} bytes from {:#x} isn't inside the function",
+                len, start
+            )
+            .into());
+        }
+        let data = util::map(&self.file)?;
+        Encoding::check_target(&data)?;
+
+        // everything which can fail comes before the code is encoded, the extension is only
+        // kept once the decoder is hooked in
+        let before = self.extension.clone();
+        let mut extension = match before.clone() {
+            Some(extension) => extension,
+            None => Extension::plan(&data)?,
+        };
+        let flag = extension.push_writable(&[0], 1)?;
+        let (code, entry) = encoding.decoder(extension.next_va(), flag, start, len)?;
+        extension.push(&code)?;
+        self.extension = Some(extension);
+        if let Err(e) = self.call_from_current_row(entry) {
+            self.extension = before;
+            return Err(e);
+        }
+
+        let (rows, engines) = self.current_rows_and_engines();
+        for row in rows.iter_mut().filter(|x| x.offset >= start) {
+            let mut bytes = from_hexstring(&row.bytes);
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                if (start..start + len).contains(&(row.offset + i as u64)) {
+                    encoding.encode(std::slice::from_mut(byte));
+                }
+            }
+            row.bytes = util::to_hexstring(&bytes);
+            row.rebuild_text(engines);
+        }
+        Ok(format!(
+            "encoded {:#x} bytes from {:#x}, decoder at {:#x}",
+            len, start, entry
+        ))
+    }
+
+    /// Hook a call to `entry` in at the current row.  Registers and flags are saved around it so
+    /// the callee can't disturb the code it's spliced into.
+    fn call_from_current_row(&mut self, entry: u64) -> Result<(), Box<dyn Error>> {
+        self.hook(&Template::SysV64.call(entry))?;
+        Ok(())
+    }
+
+    /// the end of the rows (from `index`) a jmp has to overwrite and how many bytes they cover
+    fn hook_span(&self, index: usize) -> Result<(usize, usize), Box<dyn Error>> {
+        let rows = self.current_rows();
+        let mut end = index;
+        let mut covered = 0;
+        while covered < JMP_SIZE {
+            let row = rows
+                .get(end)
+                .ok_or("not enough room before the end of the function for a jmp")?;
+            covered += from_hexstring(&row.original).len();
+            end += 1;
+        }
+        Ok((end, covered))
+    }
+
+    /// Move the instructions at the current row into the extension behind `prelude`, with a jmp
+    /// there in their place and one back after them.  Returns where the moved code starts and how
+    /// many instructions were moved.
+    fn hook(&mut self, prelude: &[String]) -> Result<(u64, usize), Box<dyn Error>> {
+        if self.extension.is_none() {
+            self.extension = Some(Extension::plan(&util::map(&self.file)?)?);
+        }
+        let index = self.editor_state.selected().unwrap_or(0);
+        let (end, covered) = self.hook_span(index)?;
+        let rows = self.current_rows();
+        let site = rows[index].offset;
+        let resume = site + covered as u64;
+
+        // the prelude and the text reassembled below are intel syntax
+        let intel = Engines::intel(self.engines.options.bits)?;
+        let mut va = self.extension.as_ref().map(|x| x.next_va()).unwrap_or(0);
+        let target = va;
+        let mut code = vec![];
+        for line in prelude {
+            let bytes = intel.assemble(line.clone(), va)?;
+            va += bytes.len() as u64;
+            code.extend(bytes);
+        }
+        for row in &rows[index..end] {
+            // data is moved as it is, code has its relative operands fixed up unless a short
+            // branch can't reach from there, then it's assembled again to get a longer one
+            let bytes = from_hexstring(&row.bytes);
+            let bytes = if row.data {
+                bytes
+            } else {
+                match cave::relocate(&intel, &bytes, row.offset, va) {
+                    Ok(bytes) => bytes,
+                    Err(_) => {
+                        let text = intel
+                            .disassemble(&bytes, row.offset)
+                            .into_iter()
+                            .next()
+                            .map(|(_, text, _)| text)
+                            .ok_or_else(|| format!("{} isn't an instruction", row.bytes))?;
+                        intel.assemble(text, va)?
+                    }
+                }
+            };
+            va += bytes.len() as u64;
+            code.extend(bytes);
+        }
+        code.extend(intel.assemble(format!("jmp {:#x}", resume), va)?);
+
+        let mut jmp = intel.assemble(format!("jmp {:#x}", target), site)?;
+        jmp.resize(covered, 0x90);
+        let original = rows[index..end]
+            .iter()
+            .flat_map(|x| from_hexstring(&x.original))
+            .collect::<Vec<_>>();
+        let mut replacement = vec![InstructionRow::new(
+            site,
+            &jmp[..JMP_SIZE],
+            format!("jmp {:#x}", target),
+        )];
+        for i in JMP_SIZE..covered {
+            replacement.push(InstructionRow::new(
+                site + i as u64,
+                &jmp[i..=i],
+                "nop".to_string(),
+            ));
+        }
+        let mut start = 0;
+        for row in &mut replacement {
+            let len = from_hexstring(&row.bytes).len();
+            row.original = util::to_hexstring(&original[start..start + len]);
+            start += len;
+        }
+
+        self.extension
+            .as_mut()
+            .ok_or("no extension segment")?
+            .push(&code)?;
+        let function = self.get_current_function().ok_or("no function selected")?;
+        if let Some(rows) = self.rows.get_mut(&function.name.clone()) {
+            rows.splice(index..end, replacement);
+        }
+        Ok((target, end - index))
+    }
+
+    /// Replace the current function with the contents of `path`, e.g. a replacement compiled
+    /// elsewhere.  It can't be any longer than the function, shorter ones are padded with nops.
+    pub fn load_function(&mut self, path: &Path) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let function = self
+            .get_current_function()
+            .ok_or("no function selected")?
+            .clone();
+        let original = self
+            .current_rows()
+            .iter()
+            .flat_map(|x| from_hexstring(&x.original))
+            .collect::<Vec<_>>();
+        let mut bytes = std::fs::read(path)?;
+        if bytes.len() > original.len() {
+            return Err(format!(
+                "{} is {} bytes but {} only has room for {}",
+                path.display(),
+                bytes.len(),
+                function.name,
+                original.len()
+            )
+            .into());
+        }
+        let padding = original.len() - bytes.len();
+        bytes.resize(original.len(), 0x90);
+
+        let mut start = 0;
+        let rows = self
+            .engines
+            .disassemble(&bytes, function.offset as u64)
+            .into_iter()
+            .map(|(bytes, text, extensions)| {
+                let offset = function.offset as u64 + start as u64;
+                let mut row = InstructionRow::new(offset, &bytes, text);
+                row.extensions = extensions;
+                row.original = util::to_hexstring(&original[start..start + bytes.len()]);
+                start += bytes.len();
+                row
+            })
+            .collect();
+        self.rows.insert(function.name, rows);
+        self.editor_state.select(Some(0));
+        Ok(format!(
+            "loaded {} bytes from {}, padded with {} nop(s)",
+            original.len() - padding,
+            path.display(),
+            padding
+        ))
+    }
+
+    /// make calls through the GOT slot(s) of `import` land on `target` instead
+    pub fn redirect_import(
+        &mut self,
+        import: &str,
+        target: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let address = self
+            .resolve(target)
+            .ok_or_else(|| format!("couldn't find {}", target))?;
+        let import = import.trim_start_matches("sym.imp.");
+        let slots = binary::import_slots(&util::map(&self.file)?)?
+            .into_iter()
+            .filter(|x| x.name == import)
+            .collect::<Vec<_>>();
+        if slots.is_empty() {
+            return Err(format!("no import named {}", import).into());
+        }
+        for slot in &slots {
+            self.patches.extend(slot.redirect(address)?);
+        }
+        Ok(format!(
+            "redirected {} to {:#x} via {}",
+            import,
+            address,
+            slots
+                .iter()
+                .map(|x| format!("{:#x}", x.va))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+
+    /// the current row as a function name and offset into that function
+    pub fn current_location(&self) -> Option<Location> {
+        let function = self
+            .functions
+            .get(self.function_state.selected().unwrap_or(0))?;
+        let row = self.current_row()?;
+        Some((function.name.clone(), row.offset - function.offset as u64))
+    }
+
+    pub fn toggle_bookmark(&mut self) {
+        if let Some(location) = self.current_location() {
+            self.message = Some(if self.annotations.toggle_bookmark(location) {
+                "bookmarked".to_string()
+            } else {
+                "removed bookmark".to_string()
+            });
+        }
+    }
+
+    /// disassembly of `function` followed by any bookmark (`*`), comment, system call name or
+    /// YARA rule matching on each row
+    pub fn disasm_labels(&self, function: &str) -> Vec<String> {
+        let start = self
+            .functions
+            .iter()
+            .find(|x| x.name == function)
+            .map(|x| x.offset as u64)
+            .unwrap_or(0);
+        let rows = self.rows.get(function).map(|x| x.as_slice()).unwrap_or(&[]);
+        // shown on the last row, the padding isn't part of the function until it's claimed
+        let last = rows.last().map_or(0, |x| x.offset);
+        let padding = self
+            .functions
+            .iter()
+            .find(|x| x.name == function)
+            .and_then(|x| self.padding_after(x))
+            .map(|(_, len)| format!("then {} byte(s) of padding", len));
+        rows.iter()
+            .zip(syscalls::annotate(rows))
+            .map(|(row, syscall)| {
+                let location = (function.to_string(), row.offset - start);
+                let end = row.offset + from_hexstring(&row.bytes).len() as u64;
+                let switch = self.jump_table_notes(row.offset);
+                let notes = [
+                    Some("*").filter(|_| self.annotations.is_bookmarked(&location)),
+                    Some("+")
+                        .filter(|_| self.cursors.contains(&(function.to_string(), row.offset))),
+                    Some("break").filter(|_| self.breakpoints.contains(&row.offset)),
+                    self.annotations.comment(&location),
+                    syscall,
+                ]
+                .iter()
+                .flatten()
+                .copied()
+                .chain(
+                    self.yara_hits
+                        .iter()
+                        .filter(|x| matches!(x.va, Some(va) if (row.offset..end).contains(&va)))
+                        .map(|x| x.rule.as_str()),
+                )
+                .chain(
+                    signatures::references(std::slice::from_ref(row))
+                        .iter()
+                        .filter_map(|x| self.pdb_symbols.get(x))
+                        .map(|x| x.name.as_str()),
+                )
+                .chain(
+                    self.relocations_in(row.offset, end)
+                        .map(|x| x.kind.as_str()),
+                )
+                .chain(switch.iter().map(|x| x.as_str()))
+                .chain(
+                    padding
+                        .iter()
+                        .filter(|_| row.offset == last)
+                        .map(|x| x.as_str()),
+                )
+                .collect::<Vec<_>>();
+                let text = self.display_text(row);
+                if notes.is_empty() {
+                    text.into_owned()
+                } else {
+                    format!("{}  ; {}", text, notes.join(" "))
+                }
+            })
+            .collect()
+    }
+
+    /// what the row at `va` is to any jump table: the jmp, an entry or a case
+    fn jump_table_notes(&self, va: u64) -> Vec<String> {
+        let mut notes = vec![];
+        for table in &self.jump_tables {
+            if table.jump == va {
+                notes.push(format!(
+                    "switch {:#x} ({} cases)",
+                    table.table,
+                    table.targets.len()
+                ));
+            }
+            if let Some(case) = table.entry_at(va) {
+                notes.push(format!("case {} -> {:#x}", case, table.targets[case]));
+            }
+            let cases = table.cases_to(va);
+            if !cases.is_empty() {
+                let cases = cases.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                notes.push(format!("case {}", cases.join(",")));
+            }
+        }
+        notes
+    }
+
+    /// From a switch's jmp go to its table, from an entry of the table go to its case.
+    pub fn follow_jump_table(&mut self) -> Result<String, Box<dyn Error>> {
+        let va = self.current_row().ok_or("no instruction selected")?.offset;
+        let (offset, message) = self
+            .jump_tables
+            .iter()
+            .find_map(|table| {
+                if table.jump == va {
+                    let message = format!("table of {} case(s)", table.targets.len());
+                    return Some((table.table, message));
+                }
+                let case = table.entry_at(va)?;
+                Some((table.targets[case], format!("case {}", case)))
+            })
+            .ok_or("not a switch or one of its entries")?;
+        let from = self.location();
+        let column = self.selected;
+        self.seek_offset(offset)?;
+        self.select(column);
+        self.back.push(from);
+        Ok(format!("{} at {:#x}", message, offset))
+    }
+
+    /// The pointer the Hex cursor is on in a data row, as the address and file offset it points
+    /// at and a name for it (see `Targets::name`).
+    pub fn pointer_at_cursor(&self) -> Option<(u64, u64, String)> {
+        if self.mode != Mode::Viewing || self.selected != Column::Hex {
+            return None;
+        }
+        let index = self.editor_state.selected().unwrap_or(0);
+        let rows = self.current_rows();
+        if !rows.get(index)?.data {
+            return None;
+        }
+        // a pointer can carry on into the next row
+        let bytes = rows[index..]
+            .iter()
+            .flat_map(|x| from_hexstring(&x.bytes))
+            .skip(self.cursor_byte())
+            .take(8)
+            .collect::<Vec<_>>();
+        let (va, offset) = self.pointers.decode(&bytes)?;
+        Some((va, offset, self.pointers.name(va, offset)))
+    }
+
+    /// Go to where the pointer under the Hex cursor points.
+    pub fn follow_pointer(&mut self) -> Result<String, Box<dyn Error>> {
+        let (va, offset, name) = self
+            .pointer_at_cursor()
+            .ok_or("the cursor isn't on a pointer")?;
+        let from = self.location();
+        self.seek_offset(offset)
+            .map_err(|e| format!("{} ({:#x}): {}", name, va, e))?;
+        self.back.push(from);
+        Ok(format!("{} at {:#x}", name, va))
+    }
+
+    /// where the cursor is, see `back`
+    fn location(&self) -> (String, usize, Column, isize) {
+        (
+            self.get_current_function()
+                .map_or_else(String::new, |x| x.name.clone()),
+            self.editor_state.selected().unwrap_or(0),
+            self.selected,
+            self.cursor_index,
+        )
+    }
+
+    /// Go back to where the last jump was made from.
+    pub fn go_back(&mut self) -> Result<String, Box<dyn Error>> {
+        let (name, row, column, cursor) = self.back.pop().ok_or("nowhere to go back to")?;
+        let index = self
+            .functions
+            .iter()
+            .position(|x| x.name == name)
+            .ok_or_else(|| format!("{} is gone", name))?;
+        self.select(Column::Function);
+        self.select_function(Some(index));
+        let last = self.current_rows().len().saturating_sub(1);
+        self.editor_state.select(Some(row.min(last)));
+        self.select(column);
+        self.cursor_index = cursor;
+        Ok(format!("back in {}", name))
+    }
+
+    /// The current function as the Hex and Disasm columns show it, highlighting and all.
+    pub fn listing(&self) -> Listing {
+        let function = self.get_current_function().map_or("", |x| &x.name);
+        let slide = self.attached.as_ref().map_or(0, |x| x.slide);
+        let pc = self.trace.as_ref().map(|x| x.pc);
+        let forbidden = &self.settings.forbidden_extensions;
+        let rows = self
+            .current_rows()
+            .iter()
+            .zip(self.disasm_labels(function))
+            .map(|(row, label)| {
+                let (text, notes) = match label.find("  ; ") {
+                    Some(i) => (label[..i].to_string(), Some(label[i + 4..].to_string())),
+                    None => (label, None),
+                };
+                let highlight = if pc == Some(row.offset) {
+                    Highlight::Pc
+                } else if !row.valid {
+                    Highlight::Invalid
+                } else if row.uses(forbidden).is_some() {
+                    Highlight::Forbidden
+                } else {
+                    Highlight::Normal
+                };
+                listing::Row {
+                    address: row.offset.wrapping_add(slide),
+                    bytes: row.bytes.clone(),
+                    text: text.trim_end().to_string(),
+                    notes,
+                    highlight,
+                }
+            })
+            .collect();
+        Listing {
+            title: function.to_string(),
+            rows,
+        }
+    }
+
+    /// the current function's disassembly diffed against the same function in the comparison
+    /// file, None if not comparing or it has no function by that name
+    pub fn compare_current(&self) -> Option<Vec<DiffLine>> {
+        let other = self
+            .comparison
+            .as_ref()?
+            .rows
+            .get(&self.get_current_function()?.name)?;
+        let text =
+            |rows: &[InstructionRow]| rows.iter().map(|x| x.text.clone()).collect::<Vec<_>>();
+        Some(diff::diff(&text(self.current_rows()), &text(other)))
+    }
+
+    /// Callers of the current function followed by its callees, each expanded to
+    /// `settings.call_tree_depth` levels.  Recursion stops at functions already on the path.
+    pub fn call_tree(&self) -> Vec<CallTreeEntry> {
+        let function = match self.get_current_function() {
+            Some(function) => function,
+            None => return vec![],
+        };
+        let mut entries = vec![CallTreeEntry {
+            label: format!("callers of {}", function.name),
+            target: None,
+        }];
+        self.walk_calls(function.offset as u64, true, 1, &mut vec![], &mut entries);
+        entries.push(CallTreeEntry {
+            label: format!("called by {}", function.name),
+            target: None,
+        });
+        self.walk_calls(function.offset as u64, false, 1, &mut vec![], &mut entries);
+        entries
+    }
+
+    fn walk_calls(
+        &self,
+        address: u64,
+        callers: bool,
+        level: usize,
+        path: &mut Vec<u64>,
+        entries: &mut Vec<CallTreeEntry>,
+    ) {
+        if level > self.settings.call_tree_depth || path.contains(&address) {
+            return;
+        }
+        let mut next = if callers {
+            self.functions
+                .iter()
+                .filter(|x| x.calls().any(|x| x == address))
+                .map(|x| x.offset as u64)
+                .collect::<Vec<_>>()
+        } else {
+            self.functions
+                .iter()
+                .find(|x| x.offset as u64 == address)
+                .map(|x| x.calls().collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+        next.sort_unstable();
+        next.dedup();
+
+        path.push(address);
+        for target in next {
+            let name = self
+                .functions
+                .iter()
+                .find(|x| x.offset as u64 == target)
+                .map(|x| x.name.clone())
+                .unwrap_or_else(|| format!("{:#x}", target));
+            entries.push(CallTreeEntry {
+                label: format!(
+                    "{}{} {}",
+                    "  ".repeat(level),
+                    if callers { "<-" } else { "->" },
+                    name
+                ),
+                target: Some(target),
+            });
+            self.walk_calls(target, callers, level + 1, path, entries);
+        }
+        path.pop();
+    }
+
+    /// show or hide the call tree, moving focus back to the functions if it was in the tree
+    pub fn toggle_calls(&mut self) {
+        self.show_calls = !self.show_calls;
+        if !self.show_calls && self.selected == Column::Calls {
+            self.select(Column::Function);
+        }
+    }
+
+    /// Name functions and globals from the PDB at `path`.  Functions at a symbol's address take
+    /// its name, and operands referring to any symbol are annotated with it.
+    pub fn load_pdb(&mut self, path: &Path) -> Result<String, Box<dyn Error>> {
+        let data = util::map(&self.file)?;
+        let base = binary::image_base(&data)?;
+        self.pdb_symbols = symbols::load(path)?
+            .into_iter()
+            .map(|(rva, symbol)| (base + rva as u64, symbol))
+            .collect();
+        // functions are at file offsets and symbols at the addresses they're loaded at
+        let offsets = self
+            .functions
+            .iter()
+            .map(|x| x.offset as u64)
+            .collect::<Vec<_>>();
+        let vas = binary::offsets_to_vas(&data, &offsets);
+        let mut renamed = 0;
+        for (i, va) in vas.into_iter().enumerate() {
+            let name = match va.and_then(|va| self.pdb_symbols.get(&va)) {
+                Some(symbol) if symbol.function => symbol.name.clone(),
+                _ => continue,
+            };
+            if self.rename_function(i, name) {
+                renamed += 1;
+            }
+        }
+        self.pdb = Some(path.to_path_buf());
+        self.sort_functions();
+        Ok(format!(
+            "loaded {} symbol(s) from {}, renamed {} function(s)",
+            self.pdb_symbols.len(),
+            path.display(),
+            renamed
+        ))
+    }
+
+    /// Rename the function at `index` along with its rows, tags and annotations, returning false
+    /// if it already has that name or another function does.
+    fn rename_function(&mut self, index: usize, name: String) -> bool {
+        if self.functions.iter().any(|x| x.name == name) {
+            return false;
+        }
+        let old = std::mem::replace(&mut self.functions[index].name, name.clone());
+        if let Some(rows) = self.rows.remove(&old) {
+            self.rows.insert(name.clone(), rows);
+        }
+        if let Some(tags) = self.signatures.remove(&old) {
+            self.signatures.insert(name.clone(), tags);
+        }
+        self.annotations.rename_function(&old, &name);
+        true
+    }
+
+    /// Take function names, comments and bookmarks from another tool's export.  Names only
+    /// replace r2's and comments only go where there isn't one already, so nothing typed here
+    /// is lost.
+    pub fn import_annotations(&mut self, path: &Path) -> Result<String, Box<dyn Error>> {
+        let format = interop::Format::from_path(path)?;
+        let data = util::map(&self.file)?;
+        let base = binary::image_base(&data).unwrap_or(0);
+        let notes = interop::read(format, &std::fs::read_to_string(path)?, base)?;
+        // the other tool's addresses are where the file is loaded, functions are at file offsets
+        let offset = |address: u64| binary::va_to_offset(&data, address);
+        let mut renamed = 0;
+        for (address, name) in notes.names {
+            let index = offset(address).and_then(|offset| {
+                self.functions
+                    .iter()
+                    .position(|x| x.offset as u64 == offset)
+            });
+            if let Some(index) = index {
+                if self.rename_function(index, name) {
+                    renamed += 1;
+                }
+            }
+        }
+        self.sort_functions();
+
+        // anything outside every function has nowhere to go
+        let location = |address: u64| {
+            let offset = offset(address)?;
+            self.functions
+                .iter()
+                .find(|x| (x.offset as u64..(x.offset + x.size) as u64).contains(&offset))
+                .map(|x| (x.name.clone(), offset - x.offset as u64))
+        };
+        let mut comments = vec![];
+        for (address, text) in notes.comments {
+            comments.extend(location(address).map(|x| (x, text)));
+        }
+        let mut bookmarks = vec![];
+        for (address, description) in notes.bookmarks {
+            bookmarks.extend(location(address).map(|x| (x, description)));
+        }
+        let (commented, bookmarked) = (comments.len(), bookmarks.len());
+        for (location, text) in comments {
+            if self.annotations.comment(&location).is_none() {
+                self.annotations.set_comment(location, &text);
+            }
+        }
+        for (location, description) in bookmarks {
+            if self.annotations.comment(&location).is_none() {
+                self.annotations.set_comment(location.clone(), &description);
+            }
+            self.annotations.bookmarks.insert(location);
+        }
+        Ok(format!(
+            "renamed {} function(s), {} comment(s) and {} bookmark(s) from {}",
+            renamed,
+            commented,
+            bookmarked,
+            path.display()
+        ))
+    }
+
+    /// Write function names, comments and bookmarks for another tool.  Names r2 made up
+    /// (`fcn.`, `sub.`...) aren't worth carrying over.
+    pub fn export_annotations(&self, path: &Path) -> Result<String, Box<dyn Error>> {
+        let format = interop::Format::from_path(path)?;
+        let data = util::map(&self.file)?;
+        let base = binary::image_base(&data).unwrap_or(0);
+        let mut names = vec![];
+        for function in &self.functions {
+            if !["fcn.", "sub.", "loc.", "entry"]
+                .iter()
+                .any(|x| function.name.starts_with(x))
+            {
+                let name = function.name.trim_start_matches("sym.");
+                names.push((function.offset as u64, name.to_string()));
+            }
+        }
+        let offset = |(function, offset): &Location| {
+            let start = self.functions.iter().find(|x| x.name == *function)?.offset;
+            Some(start as u64 + offset)
+        };
+        let comments = self
+            .annotations
+            .comments
+            .iter()
+            .filter_map(|(location, text)| Some((offset(location)?, text.clone())))
+            .collect::<Vec<_>>();
+        let bookmarks = self
+            .annotations
+            .bookmarks
+            .iter()
+            .filter_map(|location| Some((offset(location)?, String::new())))
+            .collect::<Vec<_>>();
+        // the other tool wants the addresses the file is loaded at
+        let at_vas = |entries: Vec<(u64, String)>| {
+            let offsets = entries.iter().map(|x| x.0).collect::<Vec<_>>();
+            let vas = binary::offsets_to_vas(&data, &offsets);
+            entries
+                .into_iter()
+                .zip(vas)
+                .filter_map(|((_, x), va)| Some((va?, x)))
+                .collect()
+        };
+        let notes = Notes {
+            names: at_vas(names),
+            comments: at_vas(comments),
+            bookmarks: at_vas(bookmarks),
+        };
+        std::fs::write(path, interop::write(format, &notes, base)?)?;
+        Ok(format!(
+            "exported {} name(s), {} comment(s) and {} bookmark(s) to {}",
+            notes.names.len(),
+            notes.comments.len(),
+            notes.bookmarks.len(),
+            path.display()
+        ))
+    }
+
+    /// switch between the overview and the editor
+    pub fn toggle_overview(&mut self) {
+        if self.overview.is_none() {
+            self.message = Some("couldn't read the file's headers".to_string());
+            return;
+        }
+        self.show_overview = !self.show_overview;
+    }
+
+    /// show or hide the source panel, which needs DWARF line info
+    pub fn toggle_lines(&mut self) {
+        if self.lines.is_none() {
+            self.message = Some("no DWARF line info".to_string());
+            return;
+        }
+        self.show_lines = !self.show_lines;
+    }
+
+    /// Show the scratchpad and start typing into it.  Instructions are placed at the current row
+    /// so relative jumps come out as they would there.
+    pub fn open_scratchpad(&mut self) {
+        if let Some(row) = self.current_row() {
+            self.scratchpad.address = row.offset;
+        }
+        self.show_scratchpad = true;
+        self.mode = Mode::Scratch;
+    }
+
+    /// Title and contents of the source panel, the file the current row was compiled from with
+    /// its line selected.
+    pub fn source_panel(&mut self) -> (String, Vec<String>) {
+        let location = self
+            .current_row()
+            .zip(self.lines.as_ref())
+            .and_then(|(row, lines)| lines.lookup(row.offset))
+            .map(|(file, line)| (file.to_path_buf(), line));
+        let (file, line) = match location {
+            Some(location) => location,
+            None => {
+                self.line_state.select(None);
+                return ("Source".to_string(), vec!["no line info".to_string()]);
+            }
+        };
+        let title = format!("{}:{}", file.display(), line);
+        let text = self.source_text.entry(file).or_insert_with_key(|file| {
+            std::fs::read_to_string(file)
+                .map(|x| x.lines().map(|x| x.replace('\t', "    ")).collect())
+                .unwrap_or_default()
+        });
+        if text.is_empty() {
+            self.line_state.select(None);
+            return (title, vec!["source isn't available".to_string()]);
+        }
+        self.line_state
+            .select(Some(line.saturating_sub(1) as usize));
+        (title, text.clone())
+    }
+
+    /// select the function under the cursor in the call tree, staying in the tree
+    pub fn follow_call(&mut self) {
+        let target = self
+            .call_tree()
+            .get(self.call_state.selected().unwrap_or(0))
+            .and_then(|x| x.target);
+        if let Some(target) = target {
+            let from = self.location();
+            if self.seek(&format!("{:#x}", target)) {
+                self.selected = Column::Calls;
+                self.call_state.select(Some(0));
+                self.back.push(from);
+            }
+        }
+    }
+
+    /// execute one instruction of the trace, or run it to the next breakpoint if `run`
+    pub fn advance_trace(&mut self, run: bool) {
+        let limit = self.settings.trace_steps;
+        let trace = match self.trace.as_mut() {
+            Some(trace) => trace,
+            None => return,
+        };
+        let result = if run {
+            trace.run(&self.breakpoints, limit)
+        } else {
+            trace
+                .step()
+                .map(|_| format!("step {}, pc {:#x}", trace.steps, trace.pc))
+        };
+        let pc = trace.pc;
+        let column = self.selected;
+        if self.seek(&format!("{:#x}", pc)) && column.editable() {
+            self.selected = column;
+        }
+        self.message = Some(result.unwrap_or_else(|e| format!("error: {}", e)));
+    }
+
+    /// set a breakpoint on the current row, or remove the one already there
+    pub fn toggle_breakpoint(&mut self) {
+        if let Some(va) = self.current_row().map(|x| x.offset) {
+            if !self.breakpoints.remove(&va) {
+                self.breakpoints.insert(va);
+            }
+        }
+    }
+
+    /// show a second pane starting on the current function, or close it
+    pub fn toggle_split(&mut self) {
+        self.split = match (&self.split, self.get_current_function()) {
+            (None, Some(function)) => Some(Pane {
+                function: function.name.clone(),
+                editor_state: self.editor_state.clone(),
+            }),
+            _ => None,
+        };
+    }
+
+    /// move focus to the other pane of the split view
+    pub fn switch_pane(&mut self) {
+        let index = match &self.split {
+            Some(pane) => match self.functions.iter().position(|x| x.name == pane.function) {
+                Some(index) => index,
+                None => return,
+            },
+            None => return,
+        };
+        if self.selected.editable() && self.is_dirty() {
+            self.rebuild();
+        }
+        let current = match self.get_current_function() {
+            Some(current) => current.name.clone(),
+            None => return,
+        };
+        if let Some(pane) = self.split.as_mut() {
+            pane.function = current;
+            std::mem::swap(&mut pane.editor_state, &mut self.editor_state);
+        }
+        self.select_function(Some(index));
+        self.cursor_index = 0;
+    }
+
+    /// select the function at `anchor`, listing every address if there's more than one
+    pub fn goto(&mut self, anchor: Anchor) -> Result<String, Box<dyn Error>> {
+        let addresses = match (anchor, self.find_function("main")) {
+            (Anchor::Main, Some(main)) => vec![self.functions[main].offset as u64],
+            (Anchor::Main, None) => vec![self.find_main()?],
+            (anchor, _) => binary::anchor_addresses(&util::map(&self.file)?, anchor)?,
+        };
+        let first = *addresses.first().ok_or("the array is empty")?;
+        // PLT stubs each get their own function, so the start of the section may not be in one
+        let target = match self.find_function(&format!("{:#x}", first)) {
+            Some(_) => first,
+            None => self
+                .functions
+                .iter()
+                .map(|x| x.offset as u64)
+                .filter(|x| *x >= first)
+                .min()
+                .ok_or_else(|| format!("no function at or after {:#x}", first))?,
+        };
+        self.seek(&format!("{:#x}", target));
+        Ok(addresses
+            .iter()
+            .map(|x| format!("{:#x}", x))
+            .collect::<Vec<_>>()
+            .join(", "))
+    }
+
+    /// Address of main in a stripped binary, from the argument the entry point passes to
+    /// `__libc_start_main`: the last `lea rdi, [rip + x]` or `mov rdi, x` before the first call.
+    fn find_main(&self) -> Result<u64, Box<dyn Error>> {
+        let entry = binary::anchor_addresses(&util::map(&self.file)?, Anchor::Entry)?[0];
+        let function = self
+            .find_function(&format!("{:#x}", entry))
+            .ok_or("no function at the entry point")?;
+        let rows = self
+            .rows
+            .get(&self.functions[function].name)
+            .ok_or("no function at the entry point")?;
+        let mut main = None;
+        for row in rows.iter().skip_while(|x| x.offset < entry) {
+            let text = row.text.trim();
+            if text.starts_with("call") {
+                break;
+            }
+            let next = row.offset + from_hexstring(&row.bytes).len() as u64;
+            if let Some(operand) = text.strip_prefix("lea rdi, [rip + ") {
+                main = util::parse_address(operand.trim_end_matches(']')).map(|x| next + x);
+            } else if let Some(operand) = text.strip_prefix("mov rdi, ") {
+                main = util::parse_address(operand).or(main);
+            }
+        }
+        main.ok_or_else(|| "couldn't find main from the entry point".into())
+    }
+
+    /// Go to the next YARA match: the instruction it's in, or for matches in data the first
+    /// instruction referring to it.
+    pub fn next_yara_hit(&mut self) -> Result<String, Box<dyn Error>> {
+        if self.yara_hits.is_empty() {
+            return Err("no yara matches, run :yara <rules> first".into());
+        }
+        self.yara_index = (self.yara_index + 1) % self.yara_hits.len();
+        let hit = &self.yara_hits[self.yara_index];
+        let description = format!(
+            "{} {} ({}/{})",
+            hit.rule,
+            hit.identifier,
+            self.yara_index + 1,
+            self.yara_hits.len()
+        );
+        let va = match hit.va {
+            Some(va) => va,
+            None => return Ok(format!("{}, not in a loaded segment", description)),
+        };
+        if self.seek(&format!("{:#x}", va)) {
+            return Ok(format!("{} at {:#x}", description, va));
+        }
+        match self.referrer(va) {
+            Some(referrer) => {
+                self.seek(&format!("{:#x}", referrer));
+                Ok(format!("{} at {:#x}, referenced here", description, va))
+            }
+            None => Ok(format!(
+                "{} at {:#x}, nothing refers to it",
+                description, va
+            )),
+        }
+    }
+
+    /// the first instruction of any function which refers to `va`
+    fn referrer(&self, va: u64) -> Option<u64> {
+        self.functions.iter().find_map(|function| {
+            self.rows_of(function)
+                .iter()
+                .find(|row| signatures::references(std::slice::from_ref(row)).contains(&va))
+                .map(|row| row.offset)
+        })
+    }
+
+    /// Open the goto palette over every function, symbol, string (in data sections), section
+    /// and bookmark.
+    pub fn open_palette(&mut self) -> Result<String, Box<dyn Error>> {
+        let data = util::map(&self.file)?;
+        if self.sections.is_empty() {
+            self.sections = binary::sections(&data);
+        }
+        let mut targets = self
+            .functions
+            .iter()
+            .map(|x| {
+                let place = Place::Row(x.name.clone(), x.offset as u64);
+                Target::new(Kind::Function, x.name.clone(), place)
+            })
+            .collect::<Vec<_>>();
+        let symbols = binary::symbols(&data).into_iter().chain(
+            self.pdb_symbols
+                .iter()
+                .map(|(va, symbol)| (symbol.name.clone(), *va)),
+        );
+        targets
+            .extend(symbols.map(|(name, va)| Target::new(Kind::Symbol, name, Place::Address(va))));
+
+        let data_sections = self
+            .sections
+            .iter()
+            .filter(|(name, _, _)| [".rodata", ".rdata", ".data"].contains(&name.as_str()))
+            .map(|(_, offset, size)| (*offset, offset + size))
+            .collect::<Vec<_>>();
+        let ranges = if self.sections.is_empty() {
+            vec![(0, data.len() as u64)]
+        } else {
+            data_sections
+        };
+        let mut strings = vec![];
+        for (start, end) in ranges {
+            let end = end.min(data.len() as u64);
+            for (offset, text) in palette::strings(&data[start as usize..end as usize]) {
+                strings.push((start + offset, text));
+            }
+        }
+        let offsets = strings.iter().map(|x| x.0).collect::<Vec<_>>();
+        let vas = binary::offsets_to_vas(&data, &offsets);
+        for ((_, text), va) in strings.into_iter().zip(vas) {
+            if let Some(va) = va {
+                targets.push(Target::new(Kind::String, text, Place::Address(va)));
+            }
+        }
+
+        let offsets = self.sections.iter().map(|x| x.1).collect::<Vec<_>>();
+        let vas = binary::offsets_to_vas(&data, &offsets);
+        for ((name, _, _), va) in self.sections.iter().zip(vas) {
+            if let Some(va) = va {
+                targets.push(Target::new(Kind::Section, name.clone(), Place::Address(va)));
+            }
+        }
+        for location in &self.annotations.bookmarks {
+            let (function, offset) = location;
+            // bookmarks are kept relative to the start of their function
+            let start = match self.functions.iter().find(|x| x.name == *function) {
+                Some(function) => function.offset as u64,
+                None => continue,
+            };
+            let name = match self.annotations.comments.get(location) {
+                Some(comment) => format!("{}+{:#x} {}", function, offset, comment),
+                None => format!("{}+{:#x}", function, offset),
+            };
+            let place = Place::Row(function.clone(), start + offset);
+            targets.push(Target::new(Kind::Bookmark, name, place));
+        }
+
+        let palette = Palette::new(targets);
+        let message = format!("{} places to go, Enter goes, Esc cancels", palette.total);
+        self.palette = Some(palette);
+        self.mode = Mode::Palette;
+        Ok(message)
+    }
+
+    /// Close the palette on whatever's selected in it.  Addresses outside every function go to
+    /// the first instruction referring to them instead.
+    pub fn leave_palette(&mut self) -> Result<String, Box<dyn Error>> {
+        let palette = self.palette.take();
+        self.mode = Mode::Viewing;
+        let target = palette
+            .as_ref()
+            .and_then(|x| x.selected())
+            .ok_or("nothing matches")?;
+        match &target.place {
+            Place::Row(function, va) => {
+                if !self.select_row(function, *va) {
+                    return Err(format!("{} is gone", function).into());
+                }
+                if target.kind == Kind::Bookmark {
+                    self.select(Column::Disasm);
+                }
+                Ok(target.name.clone())
+            }
+            Place::Address(va) => {
+                if self.seek(&format!("{:#x}", va)) {
+                    self.select(Column::Disasm);
+                    return Ok(format!("{} at {:#x}", target.name, va));
+                }
+                match self.referrer(*va) {
+                    Some(referrer) => {
+                        self.seek(&format!("{:#x}", referrer));
+                        self.select(Column::Disasm);
+                        Ok(format!("{} at {:#x}, referenced here", target.name, va))
+                    }
+                    None => Err(format!(
+                        "{} at {:#x} isn't in a function and nothing refers to it",
+                        target.name, va
+                    )
+                    .into()),
+                }
+            }
+        }
+    }
+
+    /// select the function `target` (see `find_function`), returning false if nothing matched
+    pub fn seek(&mut self, target: &str) -> bool {
+        if let Some(va) = self.line_address(target) {
+            return self.seek(&format!("{:#x}", va));
+        }
+        let index = match self.find_function(target) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.select(Column::Function);
+        self.select_function(Some(index));
+        let row = util::parse_address(target)
+            .map(|addr| self.file_address(addr))
+            .and_then(|addr| self.current_rows().iter().rposition(|x| x.offset <= addr))
+            .unwrap_or(0);
+        self.editor_state.select(Some(row));
+        true
+    }
+
+    /// Put the Hex cursor on the byte at file `offset`.  Unlike `seek` the offset is never
+    /// translated from the attached process.
+    pub fn seek_offset(&mut self, offset: u64) -> Result<String, Box<dyn Error>> {
+        let index = self
+            .functions
+            .iter()
+            .position(|x| (x.offset as u64..(x.offset + x.size) as u64).contains(&offset))
+            .filter(|&x| !self.functions[x].outside_file)
+            .ok_or_else(|| format!("no function contains file offset {:#x}", offset))?;
+        // flushes edits to the function being left
+        self.select(Column::Hex);
+        self.select_function(Some(index));
+        let row = self
+            .current_rows()
+            .iter()
+            .rposition(|x| x.offset <= offset)
+            .unwrap_or(0);
+        self.editor_state.select(Some(row));
+        let start = self.current_row().map_or(offset, |x| x.offset);
+        // each byte takes two digits and a space
+        self.cursor_index = 3 * (offset - start) as isize;
+        Ok(format!(
+            "file offset {:#x} is in {}",
+            offset,
+            self.get_current_function().map_or("", |x| &x.name)
+        ))
+    }
+
+    /// Index of the function named `target` (with or without r2's `sym.` prefix) or containing the
+    /// address `target`.  While attached the address can be a runtime one too.
+    pub fn find_function(&self, target: &str) -> Option<usize> {
+        match util::parse_address(target) {
+            Some(addr) => {
+                let addr = self.file_address(addr);
+                self.functions
+                    .iter()
+                    .position(|x| (x.offset as u64..(x.offset + x.size) as u64).contains(&addr))
+            }
+            None => self
+                .functions
+                .iter()
+                .position(|x| x.name == target || x.name == format!("sym.{}", target)),
+        }
+    }
+
+    /// the address of `file:line` according to the line info
+    fn line_address(&self, target: &str) -> Option<u64> {
+        let (file, line) = target.split_at(target.rfind(':')?);
+        let line = line[1..].parse().ok()?;
+        self.lines.as_ref()?.address_of(Path::new(file), line)
+    }
+
+    /// `addr` as an address in the file, translated from the attached process if it's in a
+    /// function there
+    pub fn file_address(&self, addr: u64) -> u64 {
+        match &self.attached {
+            Some(attached) => {
+                let translated = addr.wrapping_sub(attached.slide);
+                let in_function = self
+                    .functions
+                    .iter()
+                    .any(|x| (x.offset as u64..(x.offset + x.size) as u64).contains(&translated));
+                if in_function {
+                    translated
+                } else {
+                    addr
+                }
+            }
+            None => addr,
+        }
+    }
+
+    /// true if any instruction of the function differs from what was loaded from disk
+    pub fn is_modified(&self, function: &Function) -> bool {
+        match self.rows.get(&function.name) {
+            Some(rows) => rows.iter().any(|x| x.modified()),
+            None => self.delta_of(function).is_some(),
+        }
+    }
+
+    /// show immediates in the next radix, rows with pending edits are left as typed
+    pub fn cycle_radix(&mut self) {
+        self.radix = self.radix.next();
+        self.message = Some(format!("immediates in {}", self.radix));
+    }
+
+    pub fn cycle_grouping(&mut self) {
+        self.grouping = self.grouping.next();
+        self.message = Some(match self.grouping {
+            Grouping::Instruction => "hex grouped by instruction".to_string(),
+            fixed => format!("hex in rows of {}, switch back to edit it", fixed),
+        });
+    }
+
+    pub fn toggle_ascii(&mut self) {
+        self.show_ascii = !self.show_ascii;
+    }
+
+    pub fn toggle_classes(&mut self) {
+        self.show_classes = !self.show_classes;
+        self.message = Some(if self.show_classes {
+            "hex colored by byte: zero, printable, high bit and prefix".to_string()
         } else {
-            let matcher = SkimMatcherV2::default();
-            self.functions
+            "hex uncolored".to_string()
+        });
+    }
+
+    /// What the minimap marks each of the current function's rows for.
+    pub fn minimap_marks(&self) -> Vec<Option<Mark>> {
+        let function = match self.get_current_function() {
+            Some(function) => function,
+            None => return vec![],
+        };
+        let start = function.offset as u64;
+        self.current_rows()
+            .iter()
+            .map(|row| {
+                let location = (function.name.clone(), row.offset - start);
+                let end = row.offset + from_hexstring(&row.bytes).len() as u64;
+                let hit = self
+                    .yara_hits
+                    .iter()
+                    .any(|x| matches!(x.va, Some(va) if (row.offset..end).contains(&va)));
+                [
+                    Some(Mark::Bookmark).filter(|_| self.annotations.is_bookmarked(&location)),
+                    Some(Mark::Hit).filter(|_| hit),
+                    Some(Mark::Modified).filter(|_| row.modified()),
+                ]
                 .iter()
-                .map(|x| (x, matcher.fuzzy_match(&x.name.clone(), filter).unwrap_or(0)))
-                .filter(|(a, b)| *b > 5)
-                .map(|(a, _)| a.name.clone())
-                .collect()
+                .flatten()
+                .max()
+                .copied()
+            })
+            .collect()
+    }
+
+    /// The class of each byte on each of the Hex column's rows, lined up with `hex_labels`.
+    pub fn hex_classes(&self) -> Vec<Vec<Class>> {
+        let rows = self.current_rows();
+        match self.grouping {
+            Grouping::Instruction => rows
+                .iter()
+                .map(|x| byteclass::classify(&from_hexstring(&x.bytes), !x.data && x.valid))
+                .collect(),
+            Grouping::Fixed(width) => rows
+                .iter()
+                .flat_map(|x| from_hexstring(&x.bytes))
+                .collect::<Vec<_>>()
+                .chunks(width)
+                .map(|x| byteclass::classify(x, false))
+                .collect(),
+        }
+    }
+
+    /// The Hex column's rows for the current function, with the ascii sidebar lined up against
+    /// the right edge of the column when it's shown.
+    pub fn hex_labels(&self) -> Vec<String> {
+        let rows = self.current_rows();
+        let (labels, chunks) = match self.grouping {
+            Grouping::Instruction => (
+                rows.iter().map(|x| x.bytes.clone()).collect::<Vec<_>>(),
+                rows.iter()
+                    .map(|x| from_hexstring(&x.bytes))
+                    .collect::<Vec<_>>(),
+            ),
+            Grouping::Fixed(width) => {
+                let bytes = rows
+                    .iter()
+                    .flat_map(|x| from_hexstring(&x.bytes))
+                    .collect::<Vec<_>>();
+                (
+                    util::fixed_rows(&bytes, rows.first().map_or(0, |x| x.offset), width),
+                    bytes.chunks(width).map(|x| x.to_vec()).collect(),
+                )
+            }
+        };
+        if !self.show_ascii {
+            return labels;
+        }
+        let ascii = chunks
+            .iter()
+            .map(|x| util::printable(x))
+            .collect::<Vec<_>>();
+        let ascii_width = ascii.iter().map(|x| x.len()).max().unwrap_or(0);
+        // inside the borders
+        let inner = (self.column_width - 2).max(0) as usize;
+        labels
+            .into_iter()
+            .zip(ascii)
+            .map(|(label, ascii)| {
+                let hex_width = inner.saturating_sub(ascii_width + 1).max(label.len());
+                format!("{:<hex$} {}", label, ascii, hex = hex_width)
+            })
+            .collect()
+    }
+
+    /// `row`'s text with immediates in the radix they're shown in.  Rows always hold hex, which
+    /// everything parsing them expects, rows with pending edits are shown as typed.
+    fn display_text<'a>(&self, row: &'a InstructionRow) -> Cow<'a, str> {
+        if self.radix == Radix::Hex || row.dirty || !row.valid {
+            return Cow::Borrowed(&row.text);
+        }
+        Cow::Owned(util::format_immediates(&row.text, self.radix))
+    }
+
+    pub fn cycle_sort(&mut self) {
+        self.sort = self.sort.next();
+        if self.sort == SortOrder::File && self.lines.is_none() {
+            self.sort = self.sort.next();
+        }
+        self.sort_functions();
+    }
+
+    /// reorder the function list by the current sort order, keeping the same function selected
+    fn sort_functions(&mut self) {
+        let current = self
+            .functions
+            .get(self.function_state.selected().unwrap_or(0))
+            .map(|x| x.name.clone());
+        match self.sort {
+            SortOrder::Name => self.functions.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortOrder::Address => self.functions.sort_by_key(|x| x.offset),
+            SortOrder::Size => self.functions.sort_by_key(|x| Reverse(x.size)),
+            SortOrder::File => {
+                let lines = self.lines.as_ref();
+                // functions without line info go last
+                self.functions.sort_by_cached_key(|x| {
+                    let location = lines.and_then(|lines| lines.lookup(x.offset as u64));
+                    (
+                        location.is_none(),
+                        location.map(|(file, line)| (file.to_path_buf(), line)),
+                    )
+                })
+            }
+        }
+        if let Some(current) = current {
+            let index = self.functions.iter().position(|x| x.name == current);
+            self.select_function(index);
+        }
+    }
+
+    /// Rebuild the engines with `setting` changed.  Rows are disassembled again to follow the new
+    /// syntax, but options which change where instructions start need the file reanalyzed, which
+    /// would drop pending edits.
+    pub fn set_engine_option(&mut self, setting: EngineSetting) -> Result<String, Box<dyn Error>> {
+        let mut options = self.engines.options.clone();
+        setting.apply(&mut options);
+        let resplit = options.bits != self.engines.options.bits
+            || options.skipdata != self.engines.options.skipdata;
+        if resplit && self.functions.iter().any(|x| self.is_modified(x)) {
+            return Err("that changes where instructions start, write and :reload first".into());
+        }
+        self.engines = Engines::new(options.clone())?;
+        self.settings.engines = options;
+        if resplit {
+            return self.reload();
+        }
+        let engines = &self.engines;
+        for row in self.rows.values_mut().flatten() {
+            // text still being typed is reassembled with the new syntax on the next tick
+            if !row.dirty {
+                row.rebuild_text(engines);
+            }
+        }
+        Ok(self.engine_summary())
+    }
+
+    fn engine_summary(&self) -> String {
+        let options = &self.engines.options;
+        format!(
+            "{} bit, {} syntax, assembling {} syntax, detail {}, skipdata {}",
+            options.bits,
+            options.syntax,
+            options.asm_syntax,
+            if options.detail { "on" } else { "off" },
+            if options.skipdata { "on" } else { "off" }
+        )
+    }
+
+    /// Select `function` and the row at `va` in it (or its first row), returning false if
+    /// there's no function by that name.
+    fn select_row(&mut self, function: &str, va: u64) -> bool {
+        let index = match self.functions.iter().position(|x| x.name == function) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.select(Column::Function);
+        self.select_function(Some(index));
+        let row = self.current_rows().iter().position(|x| x.offset == va);
+        self.editor_state.select(Some(row.unwrap_or(0)));
+        true
+    }
+
+    /// file offsets of the code section, or of everything from the first function to the end of
+    /// the last when there's no section by that name
+    fn code_range(&mut self) -> Result<(u64, u64), Box<dyn Error>> {
+        if self.sections.is_empty() {
+            self.sections = binary::sections(&util::map(&self.file)?);
+        }
+        if let Some((_, offset, size)) = self
+            .sections
+            .iter()
+            .find(|(name, _, _)| name == ".text" || name == "__text")
+        {
+            return Ok((*offset, offset + size));
+        }
+        let functions = self.functions.iter().filter(|x| !x.outside_file);
+        let start = functions.clone().map(|x| x.offset as u64).min();
+        let end = functions.map(|x| (x.offset + x.size) as u64).max();
+        start
+            .zip(end)
+            .ok_or_else(|| "there's no code section".into())
+    }
+
+    /// Define a function of `size` bytes at file offset `offset`, or move and resize the one
+    /// called `name` (or starting at `offset`) when analysis got its bounds wrong.  Its rows are
+    /// disassembled again from the file, so it can't have pending edits.
+    ///
+    /// Without an offset the function starts at the cursor of the linear view (or the current
+    /// row), and without a size it runs to its first `ret` or wherever the next function starts.
+    pub fn define_function(
+        &mut self,
+        offset: Option<u64>,
+        size: Option<u64>,
+        name: Option<String>,
+    ) -> Result<String, Box<dyn Error>> {
+        let program = util::map(&self.file)?;
+        let offset = match offset {
+            Some(offset) => offset,
+            None => match &self.linear {
+                Some(linear) => linear.selected().ok_or("nothing selected")?.1,
+                None => self.current_row().ok_or("nothing selected")?.offset,
+            },
+        };
+        let size = match size {
+            Some(size) => size,
+            None => {
+                let next = self
+                    .functions
+                    .iter()
+                    .map(|x| x.offset as u64)
+                    .filter(|&x| x > offset)
+                    .min();
+                let end = next.unwrap_or(program.len() as u64);
+                linear::function_size(&program, offset, end, &self.engines)
+            }
+        };
+        if size == 0 || offset.saturating_add(size) > program.len() as u64 {
+            return Err(format!("{:#x} bytes at {:#x} aren't in the file", size, offset).into());
+        }
+        let existing = match &name {
+            Some(name) => self.functions.iter().position(|x| &x.name == name),
+            None => self
+                .functions
+                .iter()
+                .position(|x| x.offset as u64 == offset),
+        };
+        let function = match existing {
+            Some(index) => {
+                let name = &self.functions[index].name;
+                if self.is_modified(&self.functions[index]) {
+                    return Err(format!("{} has pending edits, write them first", name).into());
+                }
+                Function {
+                    offset: offset as usize,
+                    size: size as usize,
+                    outside_file: false,
+                    ..self.functions[index].clone()
+                }
+            }
+            None => Function {
+                name: name.unwrap_or_else(|| format!("fcn.{:08x}", offset)),
+                offset: offset as usize,
+                size: size as usize,
+                nbbs: 1,
+                callrefs: vec![],
+                outside_file: false,
+            },
+        };
+
+        let rows = function_rows(&self.engines, &program, &function, &self.data);
+        let name = function.name.clone();
+        self.rows.insert(name.clone(), rows);
+        self.defined.retain(|x| x.0 != name);
+        self.defined.push((name.clone(), offset, size));
+        let message = match existing {
+            Some(index) => {
+                self.functions[index] = function;
+                format!("{} is now {:#x} bytes at {:#x}", name, size, offset)
+            }
+            None => {
+                self.functions.push(function);
+                format!("defined {}, {:#x} bytes at {:#x}", name, size, offset)
+            }
+        };
+        self.sort_functions();
+        self.select_row(&name, offset);
+        if self.linear.is_some() {
+            self.refresh_linear(offset)?;
+        }
+        Ok(message)
+    }
+
+    /// Mark `len` bytes from the current row as data, or with None turn the data at the current
+    /// row back into code.  Functions over those bytes are split into rows again from the file, so
+    /// they can't have pending edits.
+    pub fn mark_data(&mut self, len: Option<u64>) -> Result<String, Box<dyn Error>> {
+        self.check_editable()?;
+        let va = self.current_row().ok_or("no instruction selected")?.offset;
+        let mut data = self.data.clone();
+        let (range, message) = match len {
+            Some(0) => return Err("nothing to mark".into()),
+            Some(len) => {
+                let function = self.get_current_function().ok_or("no function selected")?;
+                let end = va
+                    .saturating_add(len)
+                    .min((function.offset + function.size) as u64);
+                data.push((va, end));
+                // merge ranges which overlap or touch
+                data.sort_unstable();
+                data.dedup_by(|next, previous| {
+                    if next.0 > previous.1 {
+                        return false;
+                    }
+                    previous.1 = previous.1.max(next.1);
+                    true
+                });
+                let message = format!("marked {:#x} byte(s) at {:#x} as data", end - va, va);
+                ((va, end), message)
+            }
+            None => {
+                let index = data
+                    .iter()
+                    .position(|(start, end)| (*start..*end).contains(&va))
+                    .ok_or_else(|| format!("{:#x} isn't marked as data", va))?;
+                let range = data.remove(index);
+                let message = format!("{:#x}..{:#x} is code again", range.0, range.1);
+                (range, message)
+            }
+        };
+        let previous = std::mem::replace(&mut self.data, data);
+        if let Err(e) = self.resplit(range) {
+            self.data = previous;
+            return Err(e);
+        }
+        let function = self.get_current_function().ok_or("no function selected")?;
+        let function = function.name.clone();
+        self.select_row(&function, range.0);
+        self.select(Column::Disasm);
+        if self.linear.is_some() {
+            self.refresh_linear(range.0)?;
+        }
+        Ok(message)
+    }
+
+    /// Find the switch statements of every function and mark their tables as data, an entry a
+    /// row, where they're inside a function.  Functions with pending edits are left as they are.
+    pub fn detect_jump_tables(&mut self) -> Result<String, Box<dyn Error>> {
+        let program = util::map(&self.file)?;
+        let segments = binary::segments(&program);
+        let mut tables = vec![];
+        for function in self.functions.iter().filter(|x| !x.outside_file) {
+            let rows = self
+                .rows
+                .get(&function.name)
+                .map_or(&[][..], |x| x.as_slice());
+            let bounds = (
+                function.offset as u64,
+                (function.offset + function.size) as u64,
+            );
+            tables.extend(jumptable::detect(
+                rows,
+                &program,
+                &Segments(&segments),
+                bounds,
+            ));
+        }
+
+        let location = self.current_row().map(|x| x.offset);
+        let column = self.selected;
+        let mut marked = 0;
+        for table in &tables {
+            let inside = self.functions.iter().any(|x| {
+                !x.outside_file
+                    && (x.offset as u64..(x.offset + x.size) as u64).contains(&table.table)
+            });
+            if !inside {
+                continue;
+            }
+            let previous = self.data.clone();
+            for i in 0..table.targets.len() as u64 {
+                let start = table.table + i * table.entry_size;
+                let entry = (start, start + table.entry_size);
+                if !self.data.contains(&entry) {
+                    self.data.push(entry);
+                }
+            }
+            match self.resplit((table.table, table.table + table.size())) {
+                Ok(()) => marked += 1,
+                Err(_) => self.data = previous,
+            }
+        }
+        let cases = tables.iter().map(|x| x.targets.len()).sum::<usize>();
+        let found = tables.len();
+        self.jump_tables = tables;
+
+        if let (Some(va), Some(function)) = (location, self.get_current_function()) {
+            let function = function.name.clone();
+            self.select_row(&function, va);
+            self.select(column);
+        }
+        if let Some(linear) = &self.linear {
+            let va = linear.selected().map_or(0, |x| x.1);
+            self.refresh_linear(va)?;
+        }
+        Ok(format!(
+            "found {} jump table(s) with {} case(s), {} marked as data",
+            found, cases, marked
+        ))
+    }
+
+    /// Split every function over the file offsets `start..end` into rows again from the file,
+    /// following `data`.  Refused if any of them has pending edits.
+    fn resplit(&mut self, (start, end): (u64, u64)) -> Result<(), Box<dyn Error>> {
+        let functions = self
+            .functions
+            .iter()
+            .filter(|x| !x.outside_file)
+            .filter(|x| (x.offset as u64) < end && start < (x.offset + x.size) as u64)
+            .collect::<Vec<_>>();
+        if let Some(function) = functions.iter().find(|x| self.is_modified(x)) {
+            return Err(format!("{} has pending edits, write them first", function.name).into());
         }
+        let program = util::map(&self.file)?;
+        for function in functions {
+            // the rest are split as they're visited
+            if self.low_memory && !self.rows.contains_key(&function.name) {
+                continue;
+            }
+            let rows = function_rows(&self.engines, &program, function, &self.data);
+            self.rows.insert(function.name.clone(), rows);
+        }
+        Ok(())
+    }
+
+    /// Show the code section as one disassembly in address order, starting at the current row,
+    /// or go back to showing it function by function.
+    pub fn toggle_linear(&mut self) -> Result<String, Box<dyn Error>> {
+        if self.linear.take().is_some() {
+            return Ok("back to functions".to_string());
+        }
+        let va = self.current_row().map_or(0, |x| x.offset);
+        self.refresh_linear(va)?;
+        let lines = self.linear.as_ref().map_or(0, |x| x.lines.len());
+        Ok(format!(
+            "{} line(s), Enter edits the function at the cursor",
+            lines
+        ))
+    }
+
+    /// build the linear view again from the rows as they are now, with `va` selected
+    pub fn refresh_linear(&mut self, va: u64) -> Result<(), Box<dyn Error>> {
+        let range = self.code_range()?;
+        let mut linear = Linear::new(
+            &util::map(&self.file)?,
+            range,
+            &self.functions,
+            &self.rows,
+            &self.engines,
+        );
+        linear.select_address(va);
+        self.linear = Some(linear);
+        Ok(())
+    }
+
+    /// Close the linear view on the function at its cursor.
+    pub fn leave_linear(&mut self) -> Result<String, Box<dyn Error>> {
+        let (function, va) = match self.linear.as_ref().and_then(|x| x.selected()) {
+            Some((Some(function), va)) => (function.to_string(), va),
+            Some((None, va)) => return Err(format!("{:#x} isn't in a function", va).into()),
+            None => return Err("nothing selected".into()),
+        };
+        self.linear = None;
+        self.select_row(&function, va);
+        self.select(Column::Disasm);
+        Ok(format!("{} at {:#x}", function, va))
+    }
+
+    /// List the vtables in the file's data sections, or go back to the editor.
+    pub fn toggle_vtables(&mut self) -> Result<String, Box<dyn Error>> {
+        if self.vtables.take().is_some() {
+            return Ok("back to functions".to_string());
+        }
+        let data = util::map(&self.file)?;
+        // position independent files hold 0 where the loader puts the pointer
+        let relocated = self
+            .relocations
+            .iter()
+            .filter(|x| x.kind.ends_with("RELATIVE"))
+            .filter_map(|x| Some((x.va, x.addend? as u64)))
+            .collect::<HashMap<_, _>>();
+        let segments = binary::segments(&data);
+        let image = Image {
+            data: &data,
+            segments: &segments,
+            width: self.pointers.width(),
+            pe_base: Some(&data)
+                .filter(|x| x.starts_with(b"MZ"))
+                .and_then(|x| binary::image_base(x).ok()),
+            relocated: &relocated,
+        };
+        let code = binary::code_ranges(&data);
+        // pointers to constructors and destructors, not vtables
+        let ranges = binary::sections(&data)
+            .into_iter()
+            .filter(|(name, _, _)| !name.ends_with("_array") && !name.starts_with(".got"))
+            .filter(|(_, offset, _)| !code.iter().any(|(start, _)| start == offset))
+            .map(|(_, offset, size)| (offset, size))
+            .collect::<Vec<_>>();
+        let functions = self
+            .functions
+            .iter()
+            .map(|x| x.offset as u64)
+            .collect::<BTreeSet<_>>();
+        let tables = vtable::detect(&image, &ranges, &functions, &binary::symbols(&data));
+        if tables.is_empty() {
+            return Err("no vtables found".into());
+        }
+        let count = tables.len();
+        self.vtables = Some(Explorer::new(tables));
+        Ok(format!(
+            "{} vtable(s), Enter goes to a slot's function, :slot <function|address> repoints it",
+            count
+        ))
+    }
+
+    /// Close the vtable list on the function the selected slot points at.
+    pub fn follow_slot(&mut self) -> Result<String, Box<dyn Error>> {
+        let (target, slot) = self
+            .vtables
+            .as_ref()
+            .and_then(|x| x.selected())
+            .map(|(table, slot)| (table.slots[slot], slot))
+            .ok_or("nothing selected")?;
+        let offset = binary::va_to_offset(&util::map(&self.file)?, target)
+            .ok_or_else(|| format!("{:#x} isn't in the file", target))?;
+        let index = self
+            .functions
+            .iter()
+            .position(|x| x.offset as u64 == offset)
+            .ok_or_else(|| format!("no function starts at {:#x}", target))?;
+        let from = self.location();
+        self.vtables = None;
+        self.select(Column::Function);
+        self.select_function(Some(index));
+        self.editor_state.select(Some(0));
+        self.select(Column::Disasm);
+        self.back.push(from);
+        Ok(format!("slot {} is {}", slot, self.functions[index].name))
+    }
+
+    /// Point the selected vtable slot at a function or address instead.  In position independent
+    /// ELF files it's the relocation's addend which changes.
+    pub fn patch_slot(&mut self, target: &str) -> Result<String, Box<dyn Error>> {
+        let (address, slot) = self
+            .vtables
+            .as_ref()
+            .and_then(|x| x.selected())
+            .map(|(table, slot)| (table.address, slot))
+            .ok_or("no vtable selected, see :vtables")?;
+        let data = util::map(&self.file)?;
+        let va = match self.find_function(target) {
+            Some(function) => {
+                let offset = self.functions[function].offset as u64;
+                binary::offsets_to_vas(&data, &[offset])[0]
+                    .ok_or_else(|| format!("{} isn't loaded", target))?
+            }
+            None => util::parse_address(target)
+                .ok_or_else(|| format!("{} isn't a function or an address", target))?,
+        };
+        let width = self.pointers.width();
+        let at = address + (slot * width) as u64;
+        match binary::addend_offset(&data, at) {
+            Some(addend) => self.write_at(addend, &va.to_le_bytes()),
+            None => {
+                let offset = binary::va_to_offset(&data, at)
+                    .ok_or_else(|| format!("slot {:#x} isn't in the file", at))?;
+                self.write_at(offset, &va.to_le_bytes()[..width]);
+            }
+        }
+        let tables = self.vtables.iter_mut().flat_map(|x| x.tables.iter_mut());
+        for table in tables.filter(|x| x.address == address) {
+            table.slots[slot] = va;
+        }
+        Ok(format!("slot {} at {:#x} points at {:#x}", slot, at, va))
+    }
+
+    /// List every call through a GOT or IAT slot and the import it resolves to, or write them to
+    /// `path` as JSON.
+    pub fn map_imports(&mut self, path: Option<&str>) -> Result<String, Box<dyn Error>> {
+        if path.is_none() && self.got.take().is_some() {
+            return Ok("back to functions".to_string());
+        }
+        let data = util::map(&self.file)?;
+        let slots = binary::import_slots(&data)?
+            .into_iter()
+            .map(|x| (x.va, x.name))
+            .collect::<HashMap<_, _>>();
+        if slots.is_empty() {
+            return Err("nothing is imported".into());
+        }
+        let rows = self
+            .functions
+            .iter()
+            .map(|x| (x, self.rows_of(x)))
+            .collect::<Vec<_>>();
+        let code = rows
+            .iter()
+            .flat_map(|(function, rows)| rows.iter().map(move |x| (function, x)))
+            .filter(|(_, row)| !row.data && row.valid)
+            .collect::<Vec<_>>();
+        let offsets = code.iter().map(|(_, row)| row.offset).collect::<Vec<_>>();
+        let instructions = code
+            .iter()
+            .zip(binary::offsets_to_vas(&data, &offsets))
+            .filter_map(|((function, row), va)| {
+                Some(Instruction {
+                    function: &function.name,
+                    offset: row.offset,
+                    va: va?,
+                    len: from_hexstring(&row.bytes).len() as u64,
+                    text: &row.text,
+                })
+            })
+            .collect::<Vec<_>>();
+        let sites = got::find(&instructions, &slots);
+        let imports = sites
+            .iter()
+            .map(|x| &x.import)
+            .collect::<BTreeSet<_>>()
+            .len();
+        if let Some(path) = path {
+            std::fs::write(path, serde_json::to_string_pretty(&got::to_json(&sites))?)?;
+            return Ok(format!(
+                "wrote {} call site(s) through {} import(s) to {}",
+                sites.len(),
+                imports,
+                path
+            ));
+        }
+        if sites.is_empty() {
+            return Err("nothing calls through the GOT or IAT".into());
+        }
+        let message = format!(
+            "{} call site(s) through {} import(s), Enter goes to one, :redirect <import> \
+             <function> interposes on it",
+            sites.len(),
+            imports
+        );
+        self.got = Some(GotMap::new(sites));
+        Ok(message)
+    }
+
+    /// Close the list of calls through imports on the selected call.
+    pub fn follow_got(&mut self) -> Result<String, Box<dyn Error>> {
+        let site = self
+            .got
+            .as_ref()
+            .and_then(|x| x.selected())
+            .cloned()
+            .ok_or("nothing selected")?;
+        let from = self.location();
+        if !self.select_row(&site.function, site.offset) {
+            return Err(format!("{} is gone", site.function).into());
+        }
+        self.got = None;
+        self.select(Column::Disasm);
+        self.back.push(from);
+        Ok(format!(
+            "{} calls {} at {:#x}",
+            site.function, site.import, site.va
+        ))
+    }
+
+    /// each function's name by the address it's loaded at, for the vtable list
+    pub fn function_names(&self) -> HashMap<u64, String> {
+        let data = match util::map(&self.file) {
+            Ok(data) => data,
+            Err(_) => return HashMap::new(),
+        };
+        let offsets = self
+            .functions
+            .iter()
+            .map(|x| x.offset as u64)
+            .collect::<Vec<_>>();
+        binary::offsets_to_vas(&data, &offsets)
+            .into_iter()
+            .zip(&self.functions)
+            .filter_map(|(va, function)| Some((va?, function.name.clone())))
+            .collect()
+    }
+
+    /// List only functions matching `query`, or every function if it's empty.  The selection
+    /// moves to the first match if the selected function doesn't match.
+    pub fn set_filter(&mut self, query: &str) -> Result<String, Box<dyn Error>> {
+        if query.trim().is_empty() {
+            self.filter = None;
+            return Ok(format!("listing all {} functions", self.functions.len()));
+        }
+        self.filter = Some(Filter::parse(query)?);
+        if self.sections.is_empty() {
+            self.sections = binary::sections(&util::map(&self.file)?);
+        }
+        let visible = self.visible_functions();
+        let selected = self.function_state.selected().unwrap_or(0);
+        if !visible.contains(&selected) {
+            if let Some(&first) = visible.first() {
+                self.select_function(Some(first));
+                self.editor_state.select(Some(0));
+                self.cursor_index = 0;
+            }
+        }
+        Ok(format!(
+            "{} of {} functions match",
+            visible.len(),
+            self.functions.len()
+        ))
+    }
+
+    /// indices into `functions` of the ones which are listed
+    pub fn visible_functions(&self) -> Vec<usize> {
+        let filter = match &self.filter {
+            Some(filter) => filter,
+            None => return (0..self.functions.len()).collect(),
+        };
+        (0..self.functions.len())
+            .filter(|&i| {
+                let function = &self.functions[i];
+                let offset = function.offset as u64;
+                let section = self
+                    .sections
+                    .iter()
+                    .find(|(_, start, size)| (*start..start + size).contains(&offset))
+                    .map(|(name, _, _)| name.as_str());
+                filter.matches(&Candidate {
+                    name: &function.name,
+                    size: function.size,
+                    section,
+                    modified: self.is_modified(function),
+                })
+            })
+            .collect()
+    }
+
+    /// function names along with their address, size, basic block count, a `*` if modified and
+    /// tags for any signatures found in them or if they aren't entirely in the file.  Only
+    /// functions matching the filter are labelled.
+    pub fn function_labels(&self) -> Vec<String> {
+        let width = self
+            .functions
+            .iter()
+            .map(|x| x.name.len())
+            .max()
+            .unwrap_or(0)
+            .min(32);
+        self.visible_functions()
+            .into_iter()
+            .map(|i| &self.functions[i])
+            .map(|x| {
+                let mut tags = self.signatures.get(&x.name).cloned().unwrap_or_default();
+                if x.outside_file {
+                    tags.insert(0, "not in file");
+                }
+                let mut tags = if tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", tags.join(", "))
+                };
+                // sorted by source file the list doubles as a picker for it
+                let location = self
+                    .lines
+                    .as_ref()
+                    .filter(|_| self.sort == SortOrder::File)
+                    .and_then(|lines| lines.lookup(x.offset as u64));
+                if let Some((file, line)) = location {
+                    let file = file.file_name().unwrap_or_default().to_string_lossy();
+                    tags = format!(" {}:{}{}", file, line, tags);
+                }
+                // while attached the list shows where functions are in the process
+                let slide = self.attached.as_ref().map(|x| x.slide).unwrap_or(0);
+                format!(
+                    "{}{:<width$} {:#08x} {:>6} {:>4}{}",
+                    if self.is_modified(x) { '*' } else { ' ' },
+                    x.name,
+                    (x.offset as u64).wrapping_add(slide),
+                    x.size,
+                    x.nbbs,
+                    tags,
+                    width = width
+                )
+            })
+            .collect()
+    }
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
+
+/// overwrite `data` at `offset` with `bytes`, growing it if they run past the end
+fn splice(data: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+    if data.len() < offset + bytes.len() {
+        data.resize(offset + bytes.len(), 0);
+    }
+    data[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// the runs of `new` which differ from `old`, as edits to write over it
+fn differences(old: &[u8], new: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    let mut edits: Vec<(u64, Vec<u8>)> = vec![];
+    for (i, byte) in new.iter().enumerate() {
+        if old.get(i) == Some(byte) {
+            continue;
+        }
+        match edits.last_mut() {
+            Some((offset, bytes)) if *offset as usize + bytes.len() == i => bytes.push(*byte),
+            _ => edits.push((i as u64, vec![*byte])),
+        }
+    }
+    edits
+}
+
+/// the functions `backend` finds in the file at `path` along with their disassembly, and how
+/// long finding and disassembling them took
+fn analyze(
+    path: &str,
+    options: &EngineOptions,
+    backend: BackendKind,
+    low_memory: bool,
+) -> Result<(Vec<Function>, Rows, Timings), Box<dyn Error>> {
+    let mut timings = Timings {
+        backend: backend.to_string(),
+        ..Timings::default()
+    };
+    let program = util::map(Path::new(path))?;
+    let start = Instant::now();
+    let found = backend
+        .backend()
+        .functions(Path::new(path), &program, options);
+    let mut functions = match found {
+        Ok(functions) => functions,
+        // whatever isn't installed, the functions can still be found by following the code
+        Err(e) => {
+            timings.backend = BackendKind::Native.to_string();
+            timings.fallback = Some(format!("{} failed ({}), using native analysis", backend, e));
+            analysis::discover(&program, &Engines::new(options.clone())?)
+        }
+    };
+    timings.analysis = start.elapsed();
+    timings.function_count = functions.len();
+
+    for function in &mut functions {
+        function.outside_file = function.offset + function.size > program.len();
+    }
+    if low_memory {
+        return Ok((functions, HashMap::new(), timings));
+    }
+    let start = Instant::now();
+
+    // engines can't be shared between threads, so each batch of functions rayon hands a thread
+    // gets its own rather than building one per function
+    let rows = functions
+        .par_iter()
+        .map_init(
+            || Engines::new(options.clone()).expect("the options were checked"),
+            |engines, function| {
+                (
+                    function.name.clone(),
+                    function_rows(engines, &program, function, &[]),
+                )
+            },
+        )
+        .collect();
+    timings.disassembly = start.elapsed();
+    Ok((functions, rows, timings))
+}
+
+/// disassemble the part of `program` `function` covers, with the `data` ranges in it as `db` rows
+fn function_rows(
+    engines: &Engines,
+    program: &[u8],
+    function: &Function,
+    data: &[(u64, u64)],
+) -> Vec<InstructionRow> {
+    let end = (function.offset + function.size).min(program.len());
+    let start = function.offset.min(end);
+    code_rows(engines, &program[start..end], start as u64, data)
+}
+
+/// disassemble `code`, which is at file offset `start`, with the `data` ranges in it as `db` rows
+fn code_rows(
+    engines: &Engines,
+    code: &[u8],
+    start: u64,
+    data: &[(u64, u64)],
+) -> Vec<InstructionRow> {
+    let end = start + code.len() as u64;
+    let mut data = data
+        .iter()
+        .filter(|(x, y)| *x < end && start < *y)
+        .copied()
+        .collect::<Vec<_>>();
+    data.sort_unstable();
+
+    let mut rows = vec![];
+    let mut offset = start;
+    // code up to each range, then the range, then code to the end
+    for (data_start, data_end) in data.into_iter().chain(std::iter::once((end, end))) {
+        let code_end = data_start.max(offset).min(end);
+        let code_range = (offset - start) as usize..(code_end - start) as usize;
+        for (bytes, text, extensions) in engines.disassemble(&code[code_range], offset) {
+            let mut row = InstructionRow::new(offset, &bytes, text);
+            row.extensions = extensions;
+            offset += bytes.len() as u64;
+            rows.push(row);
+        }
+        offset = offset.max(code_end);
+        let data_end = data_end.min(end);
+        while offset < data_end {
+            let len = (data_end - offset).min(util::DATA_ROW_SIZE as u64);
+            rows.push(InstructionRow::data(
+                offset,
+                &code[(offset - start) as usize..(offset - start + len) as usize],
+            ));
+            offset += len;
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an editor on an ELF of `code`, found by the native backend, in a directory of its own
+    fn open(code: &[u8], low_memory: bool) -> (TempDir, Application) {
+        let directory = TempDir::new("test").unwrap();
+        let path = directory.join("a.out");
+        std::fs::write(&path, binary::test_elf(0x400000, code)).unwrap();
+        let app = Application::new(
+            path.to_string_lossy(),
+            Engines::default(),
+            BackendKind::Native,
+            low_memory,
+        )
+        .unwrap();
+        (directory, app)
+    }
+
+    fn bytes(app: &Application, function: &str) -> Vec<String> {
+        app.rows[function].iter().map(|x| x.bytes.clone()).collect()
+    }
+
+    #[test]
+    fn test_cursors() {
+        let (_directory, mut app) = open(&[0x90, 0x90, 0x90, 0xc3], false);
+        assert_eq!(
+            "3 cursor(s) in 1 function(s)",
+            app.place_cursors("nop").unwrap()
+        );
+        assert!(app.seek("entry0"));
+        app.selected = Column::Hex;
+        app.editor_state.select(Some(1));
+        app.mode = Mode::Editing;
+        app.cursor_index = 1;
+        for key in &[
+            Key::Backspace,
+            Key::Backspace,
+            Key::Char('c'),
+            Key::Char('c'),
+        ] {
+            app.apply_key(*key);
+        }
+        app.stop_editing();
+        assert_eq!(vec!["cc", "cc", "cc", "c3"], bytes(&app, "entry0"));
+        assert_eq!(
+            Some("made the same edit at 2 more cursor(s)"),
+            app.message.as_deref()
+        );
+
+        // the edit typed in and the ones at the cursors go together
+        assert_eq!("undid the edit at 3 row(s)", app.undo().unwrap());
+        assert_eq!(vec!["90", "90", "90", "c3"], bytes(&app, "entry0"));
+        assert!(app.rows["entry0"].iter().all(|x| !x.modified()));
+        assert!(app.undo().is_err());
+    }
+
+    #[test]
+    fn test_annotations() {
+        // entry0 at 0x401000 calls a function at 0x401006, which are file offsets 0x1000 and 0x1006
+        let code = [0xe8, 0x01, 0x00, 0x00, 0x00, 0xc3, 0x90, 0x90, 0xc3];
+        let (directory, mut app) = open(&code, false);
+        let idc = directory.join("ida.idc");
+        let script = "set_name(0x401006, \"helper\");\nset_cmt(0x401000, \"start\", 0);\n";
+        std::fs::write(&idc, script).unwrap();
+        assert_eq!(
+            format!(
+                "renamed 1 function(s), 1 comment(s) and 0 bookmark(s) from {}",
+                idc.display()
+            ),
+            app.import_annotations(&idc).unwrap()
+        );
+        let helper = app.functions.iter().find(|x| x.name == "helper").unwrap();
+        assert_eq!(0x1006, helper.offset);
+        assert_eq!(
+            Some("start"),
+            app.annotations.comment(&("entry0".to_string(), 0))
+        );
+
+        let export = directory.join("export.idc");
+        app.export_annotations(&export).unwrap();
+        let export = std::fs::read_to_string(export).unwrap();
+        assert!(export.contains("set_name(0x401006, \"helper\");"));
+        assert!(export.contains("set_cmt(0x401000, \"start\", 0);"));
+    }
+
+    #[test]
+    fn test_r2_project() {
+        // r2 flags and comments are at addresses the file is loaded at too
+        let code = [0xe8, 0x01, 0x00, 0x00, 0x00, 0xc3, 0x90, 0x90, 0xc3];
+        let (directory, mut app) = open(&code, false);
+        let project = directory.join("rc.r2");
+        std::fs::write(&project, "afn helper 0x401006\n\"CC start @ 0x401000\"\n").unwrap();
+        app.import_annotations(&project).unwrap();
+        let helper = app.functions.iter().find(|x| x.name == "helper").unwrap();
+        assert_eq!(0x1006, helper.offset);
+        assert_eq!(
+            Some("start"),
+            app.annotations.comment(&("entry0".to_string(), 0))
+        );
+    }
+
+    #[test]
+    fn test_port_project() {
+        // entry0 calls nop; mov eax, 1; ret at 0x1006, with the mov edited and the nop patched
+        let (_old, mut app) = open(
+            &[0xe8, 1, 0, 0, 0, 0xc3, 0x90, 0xb8, 1, 0, 0, 0, 0xc3],
+            false,
+        );
+        let callee = app.functions[1].name.clone();
+        app.load_rows(&callee).unwrap();
+        let row = app.rows.get_mut(&callee).unwrap();
+        row[1].bytes = "b8 05 00 00 00".to_string();
+        app.patches.push(Patch::new(0x1006, vec![0xcc]));
+        let project = app.snapshot(Checksum::of(b""));
+        assert_eq!(vec!["90".to_string()], project.originals);
+
+        // rebuilt with the function a byte further on
+        let moved = [0xe8, 2, 0, 0, 0, 0xc3, 0x90, 0x90, 0xb8, 1, 0, 0, 0, 0xc3];
+        let (_new, app) = open(&moved, false);
+        let (ported, lost) = app.port_project(project.clone(), Checksum::of(b""));
+        assert_eq!(0, lost);
+        assert_eq!(0x1008, ported.edits[0].va);
+        assert_eq!(vec![(0x1007, "cc".to_string())], ported.patches);
+
+        // and with another constant, which hashes the same but isn't what was edited
+        let changed = [0xe8, 2, 0, 0, 0, 0xc3, 0x90, 0x90, 0xb8, 2, 0, 0, 0, 0xc3];
+        let (_new, app) = open(&changed, false);
+        let (ported, lost) = app.port_project(project, Checksum::of(b""));
+        assert_eq!(1, lost);
+        assert!(ported.edits.is_empty());
+        assert_eq!(1, ported.patches.len());
+    }
+
+    #[test]
+    fn test_structs() {
+        let (_directory, mut app) = open(&[0x90, 0x90, 0x90, 0xc3], false);
+        let (names, skipped) = app
+            .define_structs("struct pair { char a, b; }; struct odd { widget w; };")
+            .unwrap();
+        assert_eq!(vec!["pair".to_string()], names);
+        assert!(skipped.starts_with(", skipped odd ("));
+        assert!(app.define_structs("struct odd { widget w; };").is_err());
+        app.lay_struct("pair", Some(0x401000)).unwrap();
+
+        // both survive a reload, the view laid over the file as it is now
+        app.reload().unwrap();
+        assert_eq!(1, app.structs.len());
+        let view = app.struct_view.as_ref().unwrap();
+        assert_eq!(
+            (0x1000, vec![0x90, 0x90]),
+            (view.offset, view.bytes.clone())
+        );
+    }
+
+    #[test]
+    fn test_no_functions() {
+        // a backend can find nothing, e.g. objdump on a stripped binary
+        let (_directory, mut app) = open(&[0xc3], false);
+        app.functions.clear();
+        app.rows.clear();
+        assert!(app.get_current_function().is_none());
+        assert!(app.listing().rows.is_empty());
+        assert!(app.minimap_marks().is_empty());
+        assert_eq!("no functions found", app.announcements()[1]);
+        app.toggle_cursor();
+        app.toggle_split();
+        app.select(Column::Hex);
+        app.mode = Mode::Editing;
+        app.apply_key(Key::Char('9'));
+        assert!(app.mark_data(Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_differences() {
+        assert!(differences(b"abcd", b"abcd").is_empty());
+        assert_eq!(
+            vec![(1, b"x".to_vec()), (3, b"yzw".to_vec())],
+            differences(b"abcd", b"axcyzw")
+        );
+        // a shorter file is truncated rather than written
+        assert!(differences(b"abcd", b"ab").is_empty());
+    }
+
+    #[test]
+    fn test_low_memory() {
+        // entry0 calls a function of two nops and a ret
+        let code = [0xe8, 0x01, 0x00, 0x00, 0x00, 0xc3, 0x90, 0x90, 0xc3];
+        let (_directory, mut app) = open(&code, true);
+        let callee = app
+            .functions
+            .iter()
+            .find(|x| x.offset == 0x1006)
+            .unwrap()
+            .name
+            .clone();
+        assert!(app.seek(&callee));
+        assert!(!app.rows.contains_key("entry0"));
+        app.annotations
+            .set_comment((callee.clone(), 0), "called by entry0");
+
+        // the first nop becomes three bytes, so it no longer fits
+        let row = &mut app.rows.get_mut(&callee).unwrap()[0];
+        row.bytes = "cc cc cc".to_string();
+        row.rebuild_text(&app.engines);
+        assert!(app.seek("entry0"));
+        assert!(!app.rows.contains_key(&callee));
+        assert_eq!(
+            Some(&vec![0xcc, 0xcc, 0xcc, 0x90, 0xc3]),
+            app.deltas.get(&0x1006)
+        );
+        assert_eq!(
+            vec![0xcc, 0xcc, 0xcc],
+            app.with_edits(0x1006, vec![0x90, 0x90, 0xc3])
+        );
+        assert!(app.overgrown().unwrap().contains("has grown to 5 bytes"));
+
+        // and back, with the delta as rows
+        app.load_rows(&callee).unwrap();
+        assert!(app.deltas.is_empty());
+        assert_eq!(vec!["cc", "cc", "cc", "90", "c3"], bytes(&app, &callee));
+        assert!(app.overgrown().unwrap().contains("has grown to 5 bytes"));
+
+        // the reloaded editor only has entry0's rows, the comment stays all the same
+        assert!(app.reload().unwrap().starts_with("reloaded"));
+        assert_eq!(
+            Some("called by entry0"),
+            app.annotations.comment(&(callee.clone(), 0))
+        );
     }
 }
@@ -0,0 +1,238 @@
+use crate::util::{run, TempDir};
+use std::error::Error;
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+/// size of a newc cpio header, the magic followed by 13 8 digit hex fields
+const CPIO_HEADER: usize = 110;
+/// offsets of the filesize, namesize and checksum fields in a newc header
+const CPIO_FILESIZE: usize = 54;
+const CPIO_NAMESIZE: usize = 94;
+const CPIO_CHECK: usize = 102;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// static libraries
+    Ar,
+    Zip,
+    /// newc, as used by initramfs
+    Cpio,
+}
+
+impl Format {
+    fn detect(magic: &[u8]) -> Option<Format> {
+        if magic.starts_with(b"!<arch>\n") {
+            Some(Format::Ar)
+        } else if magic.starts_with(b"PK\x03\x04") {
+            Some(Format::Zip)
+        } else if magic.starts_with(b"070701") || magic.starts_with(b"070702") {
+            Some(Format::Cpio)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file inside an archive, `archive:member`.  It's extracted to edit and replaced in the archive
+/// on write, ar and zip archives through the system's ar and zip so indexes are kept up to date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub archive: PathBuf,
+    pub name: String,
+    pub format: Format,
+}
+
+impl Member {
+    /// None if `spec` isn't `archive:member` where archive is an existing archive
+    pub fn parse(spec: &str) -> Option<Member> {
+        spec.match_indices(':').find_map(|(index, _)| {
+            let archive = Path::new(&spec[..index]);
+            let name = &spec[index + 1..];
+            let mut magic = [0; 8];
+            std::fs::File::open(archive)
+                .ok()?
+                .read_exact(&mut magic)
+                .ok()?;
+            Some(Member {
+                archive: archive.to_path_buf(),
+                name: name.to_string(),
+                format: Format::detect(&magic).filter(|_| !name.is_empty())?,
+            })
+        })
+    }
+
+    pub fn fetch(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        let data = match self.format {
+            Format::Ar => run(Command::new("ar")
+                .arg("p")
+                .arg(&self.archive)
+                .arg(&self.name))?,
+            Format::Zip => run(Command::new("unzip")
+                .arg("-p")
+                .arg(&self.archive)
+                .arg(&self.name))?,
+            Format::Cpio => {
+                let archive = std::fs::read(&self.archive)?;
+                let entry = self.cpio_entry(&archive)?;
+                archive[entry.data].to_vec()
+            }
+        };
+        std::fs::write(local, data)?;
+        Ok(())
+    }
+
+    /// replace the member with `local`
+    pub fn store(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        if self.format == Format::Cpio {
+            let archive = std::fs::read(&self.archive)?;
+            let entry = self.cpio_entry(&archive)?;
+            let replaced = cpio_replace(&archive, &entry, &std::fs::read(local)?);
+            std::fs::write(&self.archive, replaced)?;
+            return Ok(());
+        }
+        // both tools take the member's name from the path they're given, so give it one
+        let inside = Path::new(&self.name)
+            .components()
+            .all(|x| matches!(x, Component::Normal(_) | Component::CurDir));
+        if !inside {
+            return Err(format!("{} isn't a relative path", self.name).into());
+        }
+        let directory = TempDir::new("member")?;
+        let copy = directory.join(&self.name);
+        std::fs::create_dir_all(copy.parent().unwrap_or(&directory.path))?;
+        std::fs::copy(local, &copy)?;
+        let archive = self.archive.canonicalize()?;
+        match self.format {
+            Format::Ar => run(Command::new("ar").arg("r").arg(&archive).arg(&copy))?,
+            _ => run(Command::new("zip")
+                .arg("-q")
+                .arg(&archive)
+                .arg(&self.name)
+                .current_dir(&directory.path))?,
+        };
+        Ok(())
+    }
+
+    fn cpio_entry(&self, archive: &[u8]) -> Result<CpioEntry, Box<dyn Error>> {
+        cpio_entries(archive)?
+            .into_iter()
+            .find(|x| x.name.trim_start_matches("./") == self.name.trim_start_matches("./"))
+            .ok_or_else(|| format!("{} isn't in {}", self.name, self.archive.display()).into())
+    }
+}
+
+impl std::fmt::Display for Member {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.archive.display(), self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CpioEntry {
+    /// offset of the header
+    header: usize,
+    name: String,
+    data: Range<usize>,
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// the 8 hex digit header field at `offset`
+fn cpio_field(archive: &[u8], offset: usize) -> Result<usize, Box<dyn Error>> {
+    let field = archive
+        .get(offset..offset + 8)
+        .ok_or("truncated cpio header")?;
+    Ok(usize::from_str_radix(std::str::from_utf8(field)?, 16)?)
+}
+
+fn cpio_entries(archive: &[u8]) -> Result<Vec<CpioEntry>, Box<dyn Error>> {
+    let mut entries = vec![];
+    let mut header = 0;
+    loop {
+        if Format::detect(archive.get(header..).unwrap_or_default()) != Some(Format::Cpio) {
+            return Err(format!("bad cpio header at {:#x}", header).into());
+        }
+        let name_size = cpio_field(archive, header + CPIO_NAMESIZE)?;
+        let name = archive
+            .get(header + CPIO_HEADER..header + CPIO_HEADER + name_size)
+            .ok_or("truncated cpio name")?;
+        let name = String::from_utf8_lossy(name)
+            .trim_end_matches('\0')
+            .to_string();
+        let start = align4(header + CPIO_HEADER + name_size);
+        let end = start + cpio_field(archive, header + CPIO_FILESIZE)?;
+        if name == "TRAILER!!!" {
+            return Ok(entries);
+        }
+        if end > archive.len() {
+            return Err(format!("{} runs past the end of the archive", name).into());
+        }
+        entries.push(CpioEntry {
+            header,
+            name,
+            data: start..end,
+        });
+        header = align4(end);
+    }
+}
+
+/// `archive` with the data of `entry` swapped for `data`, fixing up its size and checksum
+fn cpio_replace(archive: &[u8], entry: &CpioEntry, data: &[u8]) -> Vec<u8> {
+    let mut replaced = archive[..entry.data.start].to_vec();
+    let field = entry.header + CPIO_FILESIZE;
+    replaced[field..field + 8].copy_from_slice(format!("{:08x}", data.len()).as_bytes());
+    // 070702 archives carry a sum of the data's bytes
+    if archive[entry.header + 5] == b'2' {
+        let sum = data.iter().fold(0u32, |x, y| x.wrapping_add(*y as u32));
+        let field = entry.header + CPIO_CHECK;
+        replaced[field..field + 8].copy_from_slice(format!("{:08x}", sum).as_bytes());
+    }
+    replaced.extend_from_slice(data);
+    replaced.resize(align4(replaced.len()), 0);
+    replaced.extend_from_slice(&archive[align4(entry.data.end)..]);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpio_header(name: &str, size: usize) -> Vec<u8> {
+        let mut header = b"070701".to_vec();
+        for field in &[1, 0o100755, 0, 0, 1, 0, size, 0, 0, 0, 0, name.len() + 1, 0] {
+            header.extend_from_slice(format!("{:08x}", field).as_bytes());
+        }
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.resize(align4(header.len()), 0);
+        header
+    }
+
+    #[test]
+    fn test_cpio_replace() {
+        let mut archive = cpio_header("bin/sh", 3);
+        archive.extend_from_slice(&[1, 2, 3, 0]);
+        archive.extend(cpio_header("init", 1));
+        archive.extend_from_slice(&[4, 0, 0, 0]);
+        archive.extend(cpio_header("TRAILER!!!", 0));
+
+        let entries = cpio_entries(&archive).unwrap();
+        assert_eq!(
+            vec!["bin/sh", "init"],
+            entries.iter().map(|x| x.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![1, 2, 3], archive[entries[0].data.clone()].to_vec());
+
+        let replaced = cpio_replace(&archive, &entries[0], &[5, 6, 7, 8, 9]);
+        let entries = cpio_entries(&replaced).unwrap();
+        assert_eq!(
+            vec![5, 6, 7, 8, 9],
+            replaced[entries[0].data.clone()].to_vec()
+        );
+        assert_eq!(vec![4], replaced[entries[1].data.clone()].to_vec());
+    }
+}
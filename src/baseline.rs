@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+/// The file as it was loaded, kept as the original value of each byte a write changed rather than
+/// a copy of the whole file, so large files cost nothing until something is written.
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    /// length of the file when it was loaded
+    len: u64,
+    /// by file offset
+    bytes: BTreeMap<u64, u8>,
+}
+
+impl Baseline {
+    pub fn new(len: u64) -> Baseline {
+        Baseline {
+            len,
+            bytes: BTreeMap::new(),
+        }
+    }
+
+    /// Note what writing `new` over `old` at `offset` replaces.  Bytes which were already
+    /// written over keep their original value.
+    pub fn record(&mut self, offset: u64, old: &[u8], new: &[u8]) {
+        for (i, byte) in old.iter().enumerate() {
+            let at = offset + i as u64;
+            if at < self.len && new.get(i) != Some(byte) {
+                self.bytes.entry(at).or_insert(*byte);
+            }
+        }
+    }
+
+    /// whether any byte in `start..end` has been written over
+    pub fn changed_in(&self, start: u64, end: u64) -> bool {
+        self.bytes.range(start..end).next().is_some()
+    }
+
+    /// whether anything has been written since loading
+    pub fn changed(&self, current_len: u64) -> bool {
+        !self.bytes.is_empty() || current_len != self.len
+    }
+
+    /// the file as loaded, given what it contains now
+    pub fn restore(&self, current: &[u8]) -> Vec<u8> {
+        self.range(current, 0, self.len)
+    }
+
+    /// `start..end` of the file as loaded (cut off at its end), given what it contains now
+    pub fn range(&self, current: &[u8], start: u64, end: u64) -> Vec<u8> {
+        let end = end.min(self.len).max(start);
+        let available = (start as usize).min(current.len())..(end as usize).min(current.len());
+        let mut data = current[available].to_vec();
+        data.resize((end - start) as usize, 0);
+        for (&at, &byte) in self.bytes.range(start..end) {
+            data[(at - start) as usize] = byte;
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore() {
+        let original = vec![1, 2, 3, 4];
+        let mut baseline = Baseline::new(original.len() as u64);
+        assert!(!baseline.changed(4));
+
+        baseline.record(1, &[2, 3], &[9, 3]);
+        baseline.record(1, &[9], &[8]);
+        // grew, the appended bytes aren't part of the baseline
+        let current = vec![1, 8, 3, 4, 5, 6];
+        baseline.record(0, &[1, 8, 3, 4], &current);
+        assert!(baseline.changed(6));
+        assert!(baseline.changed_in(0, 2));
+        assert!(!baseline.changed_in(2, 4));
+        assert_eq!(original, baseline.restore(&current));
+        assert_eq!(vec![2, 3, 4], baseline.range(&current, 1, 6));
+
+        // shrank, what was cut off has to come back
+        let mut baseline = Baseline::new(original.len() as u64);
+        baseline.record(0, &original, &[1, 2]);
+        assert_eq!(original, baseline.restore(&[1, 2]));
+    }
+}
@@ -0,0 +1,716 @@
+use crate::util::Patch;
+use goblin::elf::dynamic::{DF_1_NOW, DF_BIND_NOW, DT_RELA};
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::reloc::{R_386_GLOB_DAT, R_386_JMP_SLOT, R_386_RELATIVE};
+use goblin::elf::reloc::{R_X86_64_GLOB_DAT, R_X86_64_JUMP_SLOT, R_X86_64_RELATIVE};
+use goblin::elf::section_header::SHT_NOBITS;
+use goblin::elf::Elf;
+use goblin::Object;
+use std::error::Error;
+
+/// The GOT (ELF) or IAT (PE) slot the loader fills in with the address of an imported function.
+#[derive(Debug, Clone)]
+pub struct ImportSlot {
+    pub name: String,
+    /// virtual address of the slot
+    pub va: u64,
+    /// file offset of the slot
+    pub offset: u64,
+    /// pointer width in bytes
+    pub width: usize,
+    /// file offset of the relocation entry which fills the slot in, ELF only
+    reloc_offset: Option<u64>,
+    rela: bool,
+    /// resolved on first call through the PLT rather than at startup
+    lazy: bool,
+}
+
+impl ImportSlot {
+    /// patches which make the loader resolve this slot to `target` instead of the import.
+    ///
+    /// A lazily bound slot only gets the load base added on startup, so it can simply be filled
+    /// in with `target` and the resolver never runs.  Otherwise the dynamic linker overwrites the
+    /// slot with the import's address, so the relocation is turned into a RELATIVE one which
+    /// resolves to `target` plus the load base instead.
+    pub fn redirect(&self, target: u64) -> Result<Vec<Patch>, Box<dyn Error>> {
+        let reloc_offset = self
+            .reloc_offset
+            .ok_or("redirecting PE imports isn't supported")?;
+        if self.lazy {
+            let mut bytes = target.to_le_bytes().to_vec();
+            bytes.truncate(self.width);
+            return Ok(vec![Patch::new(self.offset, bytes)]);
+        }
+        let mut patches = vec![];
+        match (self.width, self.rela) {
+            (8, true) => patches.push(Patch::new(
+                reloc_offset + 8,
+                [
+                    &(R_X86_64_RELATIVE as u64).to_le_bytes()[..],
+                    &target.to_le_bytes()[..],
+                ]
+                .concat(),
+            )),
+            (8, false) => {
+                patches.push(Patch::new(
+                    reloc_offset + 8,
+                    (R_X86_64_RELATIVE as u64).to_le_bytes().to_vec(),
+                ));
+                patches.push(Patch::new(self.offset, target.to_le_bytes().to_vec()));
+            }
+            (4, true) => patches.push(Patch::new(
+                reloc_offset + 4,
+                [
+                    &R_386_RELATIVE.to_le_bytes()[..],
+                    &(target as u32).to_le_bytes()[..],
+                ]
+                .concat(),
+            )),
+            (4, false) => {
+                patches.push(Patch::new(
+                    reloc_offset + 4,
+                    R_386_RELATIVE.to_le_bytes().to_vec(),
+                ));
+                patches.push(Patch::new(
+                    self.offset,
+                    (target as u32).to_le_bytes().to_vec(),
+                ));
+            }
+            _ => return Err("unsupported pointer width".into()),
+        }
+        Ok(patches)
+    }
+}
+
+/// every imported function along with where its address gets stored
+pub fn import_slots(data: &[u8]) -> Result<Vec<ImportSlot>, Box<dyn Error>> {
+    match Object::parse(data)? {
+        Object::Elf(elf) => Ok(elf_import_slots(&elf)),
+        Object::PE(pe) => {
+            let file_alignment = pe
+                .header
+                .optional_header
+                .map(|x| x.windows_fields.file_alignment)
+                .unwrap_or(0x200);
+            Ok(pe
+                .imports
+                .iter()
+                .filter_map(|import| {
+                    let offset = goblin::pe::utils::find_offset(
+                        import.offset,
+                        &pe.sections,
+                        file_alignment,
+                    )?;
+                    Some(ImportSlot {
+                        name: import.name.to_string(),
+                        va: (pe.image_base + import.offset) as u64,
+                        offset: offset as u64,
+                        width: import.size,
+                        reloc_offset: None,
+                        rela: false,
+                        lazy: false,
+                    })
+                })
+                .collect())
+        }
+        _ => Err("only ELF and PE files are supported".into()),
+    }
+}
+
+fn elf_import_slots(elf: &Elf) -> Vec<ImportSlot> {
+    let info = match elf.dynamic.as_ref() {
+        Some(dynamic) => &dynamic.info,
+        None => return vec![],
+    };
+    let width = if elf.is_64 { 8 } else { 4 };
+    let reloc_size = |rela: bool| match (elf.is_64, rela) {
+        (true, true) => 24,
+        (true, false) => 16,
+        (false, true) => 12,
+        (false, false) => 8,
+    };
+    let plt_rela = info.pltrel == DT_RELA;
+    let bind_now = info.flags & DF_BIND_NOW != 0 || info.flags_1 & DF_1_NOW != 0;
+    let tables = [
+        (&elf.pltrelocs, info.jmprel, plt_rela, !bind_now),
+        (&elf.dynrelas, info.rela, true, false),
+        (&elf.dynrels, info.rel, false, false),
+    ];
+
+    let mut slots = vec![];
+    for (relocs, table_offset, rela, lazy) in tables.iter() {
+        for (i, reloc) in relocs.iter().enumerate() {
+            let is_import = match reloc.r_type {
+                R_X86_64_JUMP_SLOT | R_X86_64_GLOB_DAT if elf.is_64 => true,
+                R_386_JMP_SLOT | R_386_GLOB_DAT if !elf.is_64 => true,
+                _ => false,
+            };
+            let name = elf
+                .dynsyms
+                .get(reloc.r_sym)
+                .and_then(|sym| elf.dynstrtab.get(sym.st_name))
+                .and_then(|name| name.ok());
+            let offset = elf_va_to_offset(elf, reloc.r_offset);
+            if let (true, Some(name), Some(offset)) = (is_import, name, offset) {
+                slots.push(ImportSlot {
+                    name: name.to_string(),
+                    va: reloc.r_offset,
+                    offset,
+                    width,
+                    reloc_offset: Some((table_offset + i * reloc_size(*rela)) as u64),
+                    rela: *rela,
+                    lazy: *lazy,
+                });
+            }
+        }
+    }
+    slots
+}
+
+/// the parts of the file which are loaded, as (file offset, size, virtual address)
+pub fn segments(data: &[u8]) -> Vec<(u64, u64, u64)> {
+    match Object::parse(data) {
+        Ok(Object::Elf(elf)) => elf
+            .program_headers
+            .iter()
+            .filter(|x| x.p_type == PT_LOAD)
+            .map(|x| (x.p_offset, x.p_filesz, x.p_vaddr))
+            .collect::<Vec<_>>(),
+        Ok(Object::PE(pe)) => pe
+            .sections
+            .iter()
+            .map(|x| {
+                (
+                    x.pointer_to_raw_data as u64,
+                    x.size_of_raw_data as u64,
+                    pe.image_base as u64 + x.virtual_address as u64,
+                )
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// the virtual address each file offset is loaded at, None for offsets which aren't loaded
+pub fn offsets_to_vas(data: &[u8], offsets: &[u64]) -> Vec<Option<u64>> {
+    let ranges = segments(data);
+    offsets
+        .iter()
+        .map(|offset| {
+            ranges
+                .iter()
+                .find(|(start, size, _)| (*start..start + size).contains(offset))
+                .map(|(start, _, va)| offset - start + va)
+        })
+        .collect()
+}
+
+/// the file offset virtual address `va` is loaded from, None if it isn't loaded from the file
+pub fn va_to_offset(data: &[u8], va: u64) -> Option<u64> {
+    segments(data)
+        .into_iter()
+        .find(|(_, size, start)| (*start..start + size).contains(&va))
+        .map(|(offset, _, start)| va - start + offset)
+}
+
+/// every section with contents in the file, as its name, file offset and size
+pub fn sections(data: &[u8]) -> Vec<(String, u64, u64)> {
+    match Object::parse(data) {
+        Ok(Object::Elf(elf)) => elf
+            .section_headers
+            .iter()
+            .filter(|x| x.sh_type != SHT_NOBITS && x.sh_size != 0)
+            .map(|x| {
+                let name = elf.shdr_strtab.get(x.sh_name).and_then(|x| x.ok());
+                (name.unwrap_or_default().to_string(), x.sh_offset, x.sh_size)
+            })
+            .collect(),
+        Ok(Object::PE(pe)) => pe
+            .sections
+            .iter()
+            .map(|x| {
+                (
+                    x.name().unwrap_or_default().to_string(),
+                    x.pointer_to_raw_data as u64,
+                    x.size_of_raw_data as u64,
+                )
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// the sections holding code, as their file offset and size
+pub fn code_ranges(data: &[u8]) -> Vec<(u64, u64)> {
+    use goblin::elf::section_header::SHF_EXECINSTR;
+    use goblin::pe::section_table::IMAGE_SCN_MEM_EXECUTE;
+    match Object::parse(data) {
+        Ok(Object::Elf(elf)) => elf
+            .section_headers
+            .iter()
+            .filter(|x| x.sh_type != SHT_NOBITS && x.sh_flags & SHF_EXECINSTR as u64 != 0)
+            .map(|x| (x.sh_offset, x.sh_size))
+            .collect(),
+        Ok(Object::PE(pe)) => pe
+            .sections
+            .iter()
+            .filter(|x| x.characteristics & IMAGE_SCN_MEM_EXECUTE != 0)
+            .map(|x| (x.pointer_to_raw_data as u64, x.size_of_raw_data as u64))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// every named symbol with an address, ELF symbols (static and dynamic) or PE exports, as its
+/// name and virtual address
+pub fn symbols(data: &[u8]) -> Vec<(String, u64)> {
+    let mut symbols = match Object::parse(data) {
+        Ok(Object::Elf(elf)) => {
+            let named = |strtab: &goblin::strtab::Strtab, x: goblin::elf::Sym| {
+                let name = strtab.get(x.st_name).and_then(|x| x.ok())?;
+                if name.is_empty() || x.st_value == 0 {
+                    return None;
+                }
+                Some((name.to_string(), x.st_value))
+            };
+            elf.syms
+                .iter()
+                .filter_map(|x| named(&elf.strtab, x))
+                .chain(elf.dynsyms.iter().filter_map(|x| named(&elf.dynstrtab, x)))
+                .collect::<Vec<_>>()
+        }
+        Ok(Object::PE(pe)) => pe
+            .exports
+            .iter()
+            .filter_map(|x| Some((x.name?.to_string(), (pe.image_base + x.rva) as u64)))
+            .collect(),
+        _ => vec![],
+    };
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+/// short name of the architecture `data` was built for, e.g. x86_64
+pub fn arch(data: &[u8]) -> Result<String, Box<dyn Error>> {
+    use goblin::elf::header::{machine_to_str, EM_386, EM_X86_64};
+    use goblin::pe::header::{COFF_MACHINE_X86, COFF_MACHINE_X86_64};
+    Ok(match Object::parse(data)? {
+        Object::Elf(elf) => match elf.header.e_machine {
+            EM_X86_64 => "x86_64".to_string(),
+            EM_386 => "x86".to_string(),
+            machine => machine_to_str(machine).to_lowercase(),
+        },
+        Object::PE(pe) => match pe.header.coff_header.machine {
+            COFF_MACHINE_X86_64 => "x86_64".to_string(),
+            COFF_MACHINE_X86 => "x86".to_string(),
+            machine => format!("pe-{:#x}", machine),
+        },
+        _ => return Err("only ELF and PE files are supported".into()),
+    })
+}
+
+/// A place the loader writes to when it loads the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub va: u64,
+    /// how many bytes at `va` are overwritten
+    pub width: u64,
+    /// e.g. R_X86_64_RELATIVE or DIR64
+    pub kind: String,
+    /// what's added to the symbol (or the load address, for relative ones), for ELF RELA
+    pub addend: Option<i64>,
+}
+
+/// Every dynamic relocation (ELF) or base relocation (PE), sorted by address.
+pub fn relocations(data: &[u8]) -> Result<Vec<Relocation>, Box<dyn Error>> {
+    use goblin::elf::reloc::{r_to_str, R_X86_64_32, R_X86_64_32S, R_X86_64_PC32, R_X86_64_PLT32};
+    let mut relocations = match Object::parse(data)? {
+        Object::Elf(elf) => elf
+            .dynrelas
+            .iter()
+            .chain(elf.dynrels.iter())
+            .chain(elf.pltrelocs.iter())
+            .map(|x| Relocation {
+                va: x.r_offset,
+                width: match x.r_type {
+                    R_X86_64_32 | R_X86_64_32S | R_X86_64_PC32 | R_X86_64_PLT32 => 4,
+                    _ if elf.is_64 => 8,
+                    _ => 4,
+                },
+                kind: format!("R_{}", r_to_str(x.r_type, elf.header.e_machine)),
+                addend: x.r_addend,
+            })
+            .collect(),
+        Object::PE(pe) => {
+            let optional_header = pe.header.optional_header.ok_or("no optional header")?;
+            let table = match optional_header.data_directories.get_base_relocation_table() {
+                Some(table) => *table,
+                None => return Ok(vec![]),
+            };
+            let start = goblin::pe::utils::find_offset(
+                table.virtual_address as usize,
+                &pe.sections,
+                optional_header.windows_fields.file_alignment,
+            )
+            .ok_or(".reloc isn't in the file")?;
+            let table = data
+                .get(start..start + table.size as usize)
+                .ok_or(".reloc runs past the end of the file")?;
+            pe_base_relocations(table, pe.image_base as u64)
+        }
+        _ => return Err("only ELF and PE files are supported".into()),
+    };
+    relocations.sort_by_key(|x| x.va);
+    Ok(relocations)
+}
+
+/// The file offset of the addend of the dynamic RELA relocation at `va`.  Changing a pointer in
+/// a position independent ELF means changing this, the loader writes over the pointer itself.
+pub fn addend_offset(data: &[u8], va: u64) -> Option<u64> {
+    let elf = match Object::parse(data).ok()? {
+        Object::Elf(elf) if elf.is_64 => elf,
+        _ => return None,
+    };
+    let info = &elf.dynamic.as_ref()?.info;
+    let entry = info.relaent.max(24);
+    (0..info.relasz as u64 / entry)
+        .map(|i| info.rela as u64 + i * entry)
+        .find(|at| {
+            let at = *at as usize;
+            data.get(at..at + 8) == Some(&va.to_le_bytes()[..])
+        })
+        .map(|at| at + 16)
+}
+
+/// Base relocations come in blocks per page, a u32 page RVA and block size then u16 entries
+/// holding the type in the top 4 bits and the offset into the page in the rest.
+fn pe_base_relocations(mut table: &[u8], image_base: u64) -> Vec<Relocation> {
+    let u32_at = |bytes: &[u8], at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+    };
+    let mut relocations = vec![];
+    while let (Some(page), Some(size)) = (u32_at(table, 0), u32_at(table, 4)) {
+        let size = (size as usize).min(table.len());
+        if size < 8 {
+            break;
+        }
+        for entry in table[8..size].chunks_exact(2) {
+            let entry = u16::from_le_bytes([entry[0], entry[1]]);
+            let (kind, width) = match entry >> 12 {
+                3 => ("HIGHLOW", 4),
+                10 => ("DIR64", 8),
+                // padding
+                0 => continue,
+                _ => ("other", 4),
+            };
+            relocations.push(Relocation {
+                va: image_base + page as u64 + (entry & 0xfff) as u64,
+                width,
+                kind: kind.to_string(),
+                addend: None,
+            });
+        }
+        table = &table[size..];
+    }
+    relocations
+}
+
+/// kernel modules carry their signature after the ELF, ending with this
+const MODULE_SIGNATURE: &[u8] = b"~Module signature appended~\n";
+
+/// What kind of signature `data` carries, None if it isn't signed.  Any edit invalidates it.
+pub fn code_signature(data: &[u8]) -> Option<&'static str> {
+    use goblin::mach::load_command::LC_CODE_SIGNATURE;
+    use goblin::mach::{Mach, MachO};
+    let codesigned = |macho: &MachO| {
+        macho
+            .load_commands
+            .iter()
+            .any(|x| x.command.cmd() == LC_CODE_SIGNATURE)
+    };
+    match Object::parse(data).ok()? {
+        Object::Elf(_) if data.ends_with(MODULE_SIGNATURE) => Some("kernel module signature"),
+        Object::Elf(elf) => elf
+            .section_headers
+            .iter()
+            .filter_map(|x| elf.shdr_strtab.get(x.sh_name).and_then(|x| x.ok()))
+            .any(|x| x == ".sig" || x == ".signature" || x == ".note.signature")
+            .then_some("embedded ELF signature"),
+        Object::PE(pe) => pe
+            .header
+            .optional_header?
+            .data_directories
+            .get_certificate_table()
+            .filter(|x| x.size != 0)
+            .map(|_| "Authenticode"),
+        Object::Mach(Mach::Binary(macho)) => Some("codesign").filter(|_| codesigned(&macho)),
+        Object::Mach(Mach::Fat(fat)) => Some("codesign").filter(|_| {
+            (0..fat.narches)
+                .filter_map(|i| fat.get(i).ok())
+                .any(|x| codesigned(&x))
+        }),
+        _ => None,
+    }
+}
+
+/// Whether the file is an ELF which is loaded wherever the loader likes, so the addresses its code
+/// ends up at aren't known until it runs.
+pub fn position_independent(data: &[u8]) -> bool {
+    use goblin::elf::header::ET_DYN;
+    matches!(Object::parse(data), Ok(Object::Elf(elf)) if elf.header.e_type == ET_DYN)
+}
+
+/// the address the start of the file is meant to be loaded at, 0 for position independent ELFs
+pub fn image_base(data: &[u8]) -> Result<u64, Box<dyn Error>> {
+    match Object::parse(data)? {
+        Object::Elf(elf) => elf
+            .program_headers
+            .iter()
+            .filter(|x| x.p_type == PT_LOAD)
+            .map(|x| x.p_vaddr & !0xfff)
+            .min()
+            .ok_or_else(|| "no loadable segments".into()),
+        Object::PE(pe) => Ok(pe.image_base as u64),
+        _ => Err("only ELF and PE files are supported".into()),
+    }
+}
+
+pub fn elf_va_to_offset(elf: &Elf, va: u64) -> Option<u64> {
+    elf.program_headers
+        .iter()
+        .filter(|x| x.p_type == PT_LOAD)
+        .find(|x| (x.p_vaddr..x.p_vaddr + x.p_filesz).contains(&va))
+        .map(|x| va - x.p_vaddr + x.p_offset)
+}
+
+/// Well known places in a binary which can be jumped to by name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    Entry,
+    Main,
+    /// functions listed in `.init_array`
+    Init,
+    /// functions listed in `.fini_array`
+    Fini,
+    Plt,
+}
+
+/// Addresses of `anchor` as recorded in the headers.  `Main` isn't recorded anywhere and has to
+/// be found from the entry point's code instead.
+pub fn anchor_addresses(data: &[u8], anchor: Anchor) -> Result<Vec<u64>, Box<dyn Error>> {
+    let elf = match (Object::parse(data)?, anchor) {
+        (Object::Elf(elf), _) => elf,
+        (Object::PE(pe), Anchor::Entry) => return Ok(vec![(pe.image_base + pe.entry) as u64]),
+        (Object::PE(_), _) => return Err(format!("{:?} is only supported for ELF", anchor).into()),
+        _ => return Err("only ELF and PE files are supported".into()),
+    };
+    let section = |names: &[&str]| {
+        elf.section_headers
+            .iter()
+            .find(|x| {
+                let name = elf.shdr_strtab.get(x.sh_name).and_then(|x| x.ok());
+                name.map(|name| names.contains(&name)).unwrap_or(false)
+            })
+            .ok_or_else(|| format!("no {} section", names.join(" or ")))
+    };
+    match anchor {
+        Anchor::Entry => Ok(vec![elf.header.e_entry]),
+        Anchor::Main => Err("main has to be found from the entry point".into()),
+        Anchor::Plt => Ok(vec![section(&[".plt.sec", ".plt"])?.sh_addr]),
+        Anchor::Init | Anchor::Fini => {
+            let header = section(if anchor == Anchor::Init {
+                &[".init_array"]
+            } else {
+                &[".fini_array"]
+            })?;
+            let width = if elf.is_64 { 8 } else { 4 };
+            let relative = if elf.is_64 {
+                R_X86_64_RELATIVE
+            } else {
+                R_386_RELATIVE
+            };
+            let mut addresses = vec![];
+            for i in (0..header.sh_size).step_by(width) {
+                let start = (header.sh_offset + i) as usize;
+                let slot = data
+                    .get(start..start + width)
+                    .ok_or("array runs past the end of the file")?;
+                let mut value = slot.iter().rev().fold(0u64, |acc, x| acc << 8 | *x as u64);
+                // position independent binaries leave the slot empty for a RELATIVE relocation
+                if value == 0 {
+                    let va = header.sh_addr + i;
+                    value = elf
+                        .dynrelas
+                        .iter()
+                        .find(|x| x.r_offset == va && x.r_type == relative)
+                        .and_then(|x| x.r_addend)
+                        .unwrap_or(0) as u64;
+                }
+                // glibc pads the arrays with 0 and -1 sentinels
+                if value != 0 && value != u64::MAX >> (64 - 8 * width) {
+                    addresses.push(value);
+                }
+            }
+            Ok(addresses)
+        }
+    }
+}
+
+/// dll characteristics bits from the PE optional header
+pub const HIGH_ENTROPY_VA: u16 = 0x0020;
+pub const DYNAMIC_BASE: u16 = 0x0040;
+pub const NX_COMPAT: u16 = 0x0100;
+pub const NO_SEH: u16 = 0x0400;
+pub const GUARD_CF: u16 = 0x4000;
+/// coff characteristics bit set when the image can only be loaded at its preferred base
+pub const RELOCS_STRIPPED: u16 = 0x0001;
+
+/// A mitigation which comes down to a header field and can be switched on or off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hardening {
+    /// ELF GNU_STACK permissions or PE NX_COMPAT
+    Nx,
+    /// PE DYNAMIC_BASE
+    Aslr,
+    /// ELF type, ET_DYN or ET_EXEC
+    Pie,
+}
+
+impl std::str::FromStr for Hardening {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nx" => Ok(Hardening::Nx),
+            "aslr" => Ok(Hardening::Aslr),
+            "pie" => Ok(Hardening::Pie),
+            _ => Err(format!("{} isn't nx, aslr or pie", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Hardening {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Hardening::Nx => write!(f, "NX"),
+            Hardening::Aslr => write!(f, "ASLR"),
+            Hardening::Pie => write!(f, "PIE"),
+        }
+    }
+}
+
+/// The patch which turns `hardening` on or off.  It only changes the header, whether the code
+/// can cope (e.g. an executable linked at a fixed address made PIE) is up to the caller.
+pub fn harden(data: &[u8], hardening: Hardening, enable: bool) -> Result<Patch, Box<dyn Error>> {
+    let set = |value: u64, flag: u64| if enable { value | flag } else { value & !flag };
+    match (Object::parse(data)?, hardening) {
+        (Object::Elf(elf), Hardening::Nx) => {
+            use goblin::elf::program_header::{PF_X, PT_GNU_STACK};
+            let index = elf
+                .program_headers
+                .iter()
+                .position(|x| x.p_type == PT_GNU_STACK)
+                .ok_or("there's no GNU_STACK header, the stack is executable")?;
+            let header = elf.header.e_phoff + index as u64 * elf.header.e_phentsize as u64;
+            // p_flags comes second in 64 bit headers and seventh in 32 bit ones
+            let offset = header + if elf.is_64 { 4 } else { 24 };
+            let flags = elf.program_headers[index].p_flags as u64;
+            // NX is the absence of PF_X
+            let flags = if enable {
+                flags & !(PF_X as u64)
+            } else {
+                flags | PF_X as u64
+            };
+            Ok(Patch::new(offset, encode(flags, 4, elf.little_endian)))
+        }
+        (Object::Elf(elf), Hardening::Pie) => {
+            use goblin::elf::header::{ET_DYN, ET_EXEC};
+            if elf.header.e_type != ET_DYN && elf.header.e_type != ET_EXEC {
+                return Err("only executables can be made position independent".into());
+            }
+            let kind = if enable { ET_DYN } else { ET_EXEC };
+            Ok(Patch::new(16, encode(kind as u64, 2, elf.little_endian)))
+        }
+        (Object::Elf(_), Hardening::Aslr) => {
+            Err("ELF files are moved when they're position independent, use pie".into())
+        }
+        (Object::PE(pe), hardening) => {
+            let flag = match hardening {
+                Hardening::Nx => NX_COMPAT,
+                Hardening::Aslr => DYNAMIC_BASE,
+                Hardening::Pie => return Err("PE files are moved by DYNAMIC_BASE, use aslr".into()),
+            };
+            let optional_header = pe.header.optional_header.ok_or("no optional header")?;
+            let relocatable = pe.header.coff_header.characteristics & RELOCS_STRIPPED == 0
+                && optional_header
+                    .data_directories
+                    .get_base_relocation_table()
+                    .is_some();
+            if hardening == Hardening::Aslr && enable && !relocatable {
+                return Err("there are no base relocations, the image can't be moved".into());
+            }
+            // the coff header follows the signature and the optional header follows that
+            let offset = pe.header.dos_header.pe_pointer as u64 + 4 + 20 + 70;
+            let characteristics = optional_header.windows_fields.dll_characteristics as u64;
+            Ok(Patch::new(
+                offset,
+                encode(set(characteristics, flag as u64), 2, true),
+            ))
+        }
+        _ => Err("only ELF and PE files are supported".into()),
+    }
+}
+
+/// the low `width` bytes of `value`
+fn encode(value: u64, width: usize, little_endian: bool) -> Vec<u8> {
+    if little_endian {
+        value.to_le_bytes()[..width].to_vec()
+    } else {
+        value.to_be_bytes()[8 - width..].to_vec()
+    }
+}
+
+/// A 64 bit ELF for tests: `code` at file offset 0x1000, loaded with the rest of the file as one
+/// segment at `base`.
+#[cfg(test)]
+pub fn test_elf(base: u64, code: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x7f, b'E', b'L', b'F', 2, 1, 1];
+    data.resize(16, 0);
+    data.extend(&2u16.to_le_bytes()); // ET_EXEC
+    data.extend(&0x3eu16.to_le_bytes()); // x86-64
+    data.extend(&1u32.to_le_bytes());
+    data.extend(&(base + 0x1000).to_le_bytes()); // entry
+    data.extend(&64u64.to_le_bytes()); // program headers
+    data.extend(&0u64.to_le_bytes()); // no section headers
+    data.extend(&0u32.to_le_bytes());
+    for half in &[64u16, 56, 1, 64, 0, 0] {
+        data.extend(&half.to_le_bytes());
+    }
+    let len = 0x1000 + code.len() as u64;
+    data.extend(&PT_LOAD.to_le_bytes());
+    data.extend(&5u32.to_le_bytes()); // PF_R | PF_X
+    for field in &[0, base, base, len, len, 0x1000] {
+        data.extend(&field.to_le_bytes());
+    }
+    data.resize(0x1000, 0);
+    data.extend(code);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_independent() {
+        let mut data = test_elf(0x400000, &[0xc3]);
+        assert!(!position_independent(&data));
+        let patch = harden(&data, Hardening::Pie, true).unwrap();
+        data[patch.offset as usize..][..patch.bytes.len()].copy_from_slice(&patch.bytes);
+        assert!(position_independent(&data));
+        assert!(!position_independent(b"not an ELF"));
+    }
+}
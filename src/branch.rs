@@ -0,0 +1,109 @@
+use std::convert::TryInto;
+
+/// branch hint prefixes which can sit in front of a conditional jump
+const HINTS: [u8; 2] = [0x2e, 0x3e];
+
+/// Index of the opcode byte holding the condition if `bytes` is a conditional jump, either
+/// `7x rel8` or `0f 8x rel32`.
+fn condition(bytes: &[u8]) -> Option<usize> {
+    let start = bytes.iter().take_while(|x| HINTS.contains(x)).count();
+    match bytes.get(start..)? {
+        [0x70..=0x7f, _] => Some(start),
+        [0x0f, 0x80..=0x8f, _, _, _, _] => Some(start + 1),
+        _ => None,
+    }
+}
+
+/// The opposite conditional jump (je to jne, jl to jge...) to the same target.  Conditions come in
+/// pairs which only differ in the lowest bit of the opcode, so the encoding keeps its length.
+pub fn invert(bytes: &[u8]) -> Option<Vec<u8>> {
+    let index = condition(bytes)?;
+    let mut inverted = bytes.to_vec();
+    inverted[index] ^= 1;
+    Some(inverted)
+}
+
+/// An unconditional jump to the same target, padded with nops to the original length.  The jump
+/// comes first so the row still reads as a jump, which moves its end and so its relative offset.
+/// When the offset would no longer fit the jump stays where it ends, behind the nops.
+pub fn force_jump(bytes: &[u8]) -> Option<Vec<u8>> {
+    let index = condition(bytes)?;
+    let short = bytes[index] & 0xf0 == 0x70;
+    let (opcode, displacement) = (if short { 0xeb } else { 0xe9 }, &bytes[index + 1..]);
+    let padding = bytes.len() - 1 - displacement.len();
+    let moved = if short {
+        (displacement[0] as i8)
+            .checked_add(padding as i8)
+            .map(|x| vec![x as u8])
+    } else {
+        i32::from_le_bytes(displacement.try_into().ok()?)
+            .checked_add(padding as i32)
+            .map(|x| x.to_le_bytes().to_vec())
+    };
+    let jump = match moved {
+        Some(moved) => {
+            let mut jump = vec![opcode];
+            jump.extend(moved);
+            jump.resize(bytes.len(), 0x90);
+            jump
+        }
+        None => {
+            let mut jump = vec![0x90; padding];
+            jump.push(opcode);
+            jump.extend(displacement);
+            jump
+        }
+    };
+    Some(jump)
+}
+
+/// nops in place of the conditional jump so execution always falls through
+pub fn fallthrough(bytes: &[u8]) -> Option<Vec<u8>> {
+    condition(bytes)?;
+    Some(vec![0x90; bytes.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invert() {
+        // je +5 / jne +5
+        assert_eq!(Some(vec![0x75, 0x05]), invert(&[0x74, 0x05]));
+        // jl rel32 / jge rel32
+        assert_eq!(
+            Some(vec![0x0f, 0x8d, 0x10, 0x00, 0x00, 0x00]),
+            invert(&[0x0f, 0x8c, 0x10, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(None, invert(&[0xeb, 0x05]));
+        assert_eq!(None, invert(&[0xe8, 0x00, 0x00, 0x00, 0x00]));
+    }
+
+    #[test]
+    fn test_force() {
+        assert_eq!(Some(vec![0xeb, 0x05]), force_jump(&[0x74, 0x05]));
+        assert_eq!(
+            Some(vec![0xe9, 0x11, 0x00, 0x00, 0x00, 0x90]),
+            force_jump(&[0x0f, 0x8c, 0x10, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(
+            Some(vec![0xeb, 0x7f, 0x90]),
+            force_jump(&[0x3e, 0x74, 0x7e])
+        );
+        // one more would overflow the rel8
+        assert_eq!(
+            Some(vec![0x90, 0xeb, 0x7f]),
+            force_jump(&[0x3e, 0x74, 0x7f])
+        );
+        assert_eq!(
+            Some(vec![0x90, 0xe9, 0xff, 0xff, 0xff, 0x7f]),
+            force_jump(&[0x0f, 0x8c, 0xff, 0xff, 0xff, 0x7f])
+        );
+        assert_eq!(
+            Some(vec![0x90, 0x90, 0x90]),
+            fallthrough(&[0x3e, 0x74, 0x05])
+        );
+        assert_eq!(None, fallthrough(&[0xc3]));
+    }
+}
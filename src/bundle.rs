@@ -0,0 +1,338 @@
+use crate::fingerprint::{self, Fingerprint};
+use crate::util::{from_hexstring, to_hexstring};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::path::Path;
+
+/// version of the `.tmogpatch` format written by `create`
+pub const FORMAT: u32 = 1;
+
+/// differing bytes closer together than this end up in the same hunk
+const MERGE_GAP: usize = 8;
+/// bytes kept from either side of a hunk, to find it again in another build
+const CONTEXT: usize = 16;
+
+/// A set of edits to one specific file, in a form which can be handed to someone else and
+/// applied with `transmogrify apply`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bundle {
+    pub format: u32,
+    /// the file the hunks were made against, nothing else gets patched
+    pub target: Checksum,
+    /// what the file looks like once patched
+    pub result: Checksum,
+    pub arch: String,
+    #[serde(default)]
+    pub notes: String,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Checksum {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl Checksum {
+    pub fn of(data: &[u8]) -> Self {
+        Checksum {
+            sha256: format!("{:x}", Sha256::digest(data)),
+            size: data.len() as u64,
+        }
+    }
+}
+
+/// `original` replaced by `bytes` at `offset`, both hex.  Either can be shorter than the other
+/// when the file grew or shrank.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub offset: u64,
+    pub original: String,
+    pub bytes: String,
+    /// the original bytes just before and after the hunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<(String, String)>,
+    /// the function the hunk is in, filled in by the editor since it needs the analysis
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<Fingerprint>,
+}
+
+/// Where `Bundle::rebase` put a hunk in the other build.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placement {
+    /// its function was found again, by hash or failing that by name, and the original bytes
+    /// are where they were in it
+    Function { name: String, offset: u64 },
+    /// the original bytes with those around them were found once
+    Context(u64),
+    /// the original bytes are still at the same offset
+    Unchanged(u64),
+    /// the original bytes were found nowhere, or in more than one place
+    Lost,
+}
+
+impl Placement {
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            Placement::Function { offset, .. }
+            | Placement::Context(offset)
+            | Placement::Unchanged(offset) => Some(*offset),
+            Placement::Lost => None,
+        }
+    }
+}
+
+/// the offsets `pattern` is at in `data`
+fn occurrences(data: &[u8], pattern: &[u8]) -> Vec<usize> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+    data.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, x)| *x == pattern)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+impl Bundle {
+    /// the changes which turn `original` into `patched`
+    pub fn create(original: &[u8], patched: &[u8], arch: String, notes: String) -> Self {
+        let len = original.len().max(patched.len());
+        let differs = |i: usize| original.get(i) != patched.get(i);
+        let mut ranges: Vec<(usize, usize)> = vec![];
+        for i in (0..len).filter(|i| differs(*i)) {
+            match ranges.last_mut() {
+                Some((_, end)) if i - *end < MERGE_GAP => *end = i + 1,
+                _ => ranges.push((i, i + 1)),
+            }
+        }
+        let slice = |data: &[u8], start: usize, end: usize| {
+            to_hexstring(&data[start.min(data.len())..end.min(data.len())])
+        };
+        let context = |start: usize, end: usize| {
+            Some((
+                slice(original, start.saturating_sub(CONTEXT), start),
+                slice(original, end, end + CONTEXT),
+            ))
+        };
+        Bundle {
+            format: FORMAT,
+            target: Checksum::of(original),
+            result: Checksum::of(patched),
+            arch,
+            notes,
+            hunks: ranges
+                .into_iter()
+                .map(|(start, end)| Hunk {
+                    offset: start as u64,
+                    original: slice(original, start, end),
+                    bytes: slice(patched, start, end),
+                    context: context(start, end),
+                    function: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let bundle: Bundle = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if bundle.format > FORMAT {
+            return Err(format!(
+                "{} is format {}, this build only understands up to {}",
+                path.display(),
+                bundle.format,
+                FORMAT
+            )
+            .into());
+        }
+        Ok(bundle)
+    }
+
+    /// `data` with the hunks applied, refusing anything but the exact file the bundle was made
+    /// against
+    pub fn apply(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let checksum = Checksum::of(data);
+        if checksum == self.result {
+            return Err("the bundle has already been applied".into());
+        }
+        if checksum != self.target {
+            return Err(format!(
+                "target doesn't match the bundle (sha256 {}, expected {})",
+                checksum.sha256, self.target.sha256
+            )
+            .into());
+        }
+        let mut patched = data.to_vec();
+        patched.resize(self.result.size as usize, 0);
+        for hunk in &self.hunks {
+            let bytes = from_hexstring(&hunk.bytes);
+            let start = hunk.offset as usize;
+            patched
+                .get_mut(start..start + bytes.len())
+                .ok_or_else(|| format!("hunk at {:#x} runs past the end of the file", start))?
+                .copy_from_slice(&bytes);
+        }
+        if Checksum::of(&patched) != self.result {
+            return Err("patched file doesn't match the bundle's result checksum".into());
+        }
+        Ok(patched)
+    }
+
+    /// Move the hunks onto `data`, another build of the target whose functions are `functions`.
+    /// A hunk goes where its function went if the original bytes are there, otherwise wherever
+    /// the original bytes and their context turn up exactly once, otherwise where it was if the
+    /// original bytes still are.  The bundle returned only has the hunks which were placed.
+    pub fn rebase(&self, data: &[u8], functions: &[Fingerprint]) -> (Bundle, Vec<Placement>) {
+        let mut placements = vec![];
+        for hunk in &self.hunks {
+            let original = from_hexstring(&hunk.original);
+            let fits = |offset: u64| {
+                let start = offset as usize;
+                !original.is_empty() && data.get(start..start + original.len()) == Some(&original)
+            };
+            let moved = hunk.function.as_ref().and_then(|function| {
+                let found = fingerprint::relocate(std::slice::from_ref(function), functions);
+                let found = found.get(&function.name)?;
+                let offset = (hunk.offset as i64 + found.delta) as u64;
+                Some(Placement::Function {
+                    name: found.name.clone(),
+                    offset,
+                })
+                .filter(|_| fits(offset))
+            });
+            let (before, after) = match &hunk.context {
+                Some((before, after)) => (from_hexstring(before), from_hexstring(after)),
+                None => (vec![], vec![]),
+            };
+            // the widest context first, an edit next to the hunk may have changed one side
+            let patterns = [
+                ([&before[..], &original, &after].concat(), before.len()),
+                ([&before[..], &original].concat(), before.len()),
+                ([&original[..], &after].concat(), 0),
+            ];
+            let context = || {
+                patterns.iter().find_map(|(pattern, skip)| {
+                    match occurrences(data, pattern).as_slice() {
+                        [at] if !original.is_empty() => {
+                            Some(Placement::Context((at + skip) as u64))
+                        }
+                        _ => None,
+                    }
+                })
+            };
+            let placement = moved
+                .or_else(context)
+                .or_else(|| Some(Placement::Unchanged(hunk.offset)).filter(|_| fits(hunk.offset)))
+                .unwrap_or(Placement::Lost);
+            placements.push(placement);
+        }
+
+        let mut patched = data.to_vec();
+        let mut hunks = vec![];
+        for (hunk, placement) in self.hunks.iter().zip(&placements) {
+            let offset = match placement.offset() {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let bytes = from_hexstring(&hunk.bytes);
+            let start = offset as usize;
+            if patched.len() < start + bytes.len() {
+                patched.resize(start + bytes.len(), 0);
+            }
+            patched[start..start + bytes.len()].copy_from_slice(&bytes);
+            let function = hunk.function.as_ref().and_then(|x| match placement {
+                Placement::Function { name, offset } => Some(Fingerprint {
+                    name: name.clone(),
+                    offset: (x.offset as i64 + *offset as i64 - hunk.offset as i64) as u64,
+                    ..x.clone()
+                }),
+                _ => None,
+            });
+            hunks.push(Hunk {
+                offset,
+                function,
+                ..hunk.clone()
+            });
+        }
+        let bundle = Bundle {
+            target: Checksum::of(data),
+            result: Checksum::of(&patched),
+            hunks,
+            ..self.clone()
+        };
+        (bundle, placements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let original = b"\x55\x48\x89\xe5\x31\xc0\x5d\xc3".to_vec();
+        let mut patched = original.clone();
+        patched[4..6].copy_from_slice(&[0xb0, 0x01]);
+        patched.extend_from_slice(&[0x90, 0x90]);
+        let bundle = Bundle::create(&original, &patched, "x86_64".to_string(), String::new());
+        assert_eq!(1, bundle.hunks.len());
+        assert_eq!("31 c0 5d c3", bundle.hunks[0].original);
+        assert_eq!(patched, bundle.apply(&original).unwrap());
+        assert!(bundle.apply(&patched).is_err());
+        assert!(bundle.apply(&original[1..]).is_err());
+    }
+
+    #[test]
+    fn test_rebase() {
+        // main and some padding, then another function
+        let original = [
+            &b"\xcc\x55\x48\x89\xe5\x31\xc0\x5d\xc3"[..],
+            &[0xcc; 12],
+            b"\x31\xc0\xc3",
+        ]
+        .concat();
+        let mut patched = original.clone();
+        patched[5..7].copy_from_slice(&[0xb0, 0x01]);
+        patched[21..23].copy_from_slice(&[0xb0, 0x02]);
+        let mut bundle = Bundle::create(&original, &patched, "x86_64".to_string(), String::new());
+        assert_eq!(2, bundle.hunks.len());
+        let function = Fingerprint {
+            name: "main".to_string(),
+            offset: 1,
+            size: 8,
+            hash: "aa".to_string(),
+        };
+        bundle.hunks[0].function = Some(function.clone());
+
+        // four bytes were inserted before main, which was renamed, and the rest changed
+        let mut rebuilt = b"\x90\x90\x90\x90".to_vec();
+        rebuilt.extend_from_slice(&original[..21]);
+        rebuilt.extend_from_slice(b"\x31\xdb\xc3");
+        let functions = [Fingerprint {
+            name: "fcn.00000005".to_string(),
+            offset: 5,
+            ..function
+        }];
+        let (rebased, placements) = bundle.rebase(&rebuilt, &functions);
+        assert_eq!(
+            vec![
+                Placement::Function {
+                    name: "fcn.00000005".to_string(),
+                    offset: 9
+                },
+                Placement::Lost
+            ],
+            placements
+        );
+        assert_eq!(1, rebased.hunks.len());
+        let applied = rebased.apply(&rebuilt).unwrap();
+        assert_eq!(&[0xb0, 0x01], &applied[9..11]);
+
+        // without the function the context finds it
+        bundle.hunks[0].function = None;
+        let (_, placements) = bundle.rebase(&rebuilt, &[]);
+        assert_eq!(Placement::Context(9), placements[0]);
+    }
+}
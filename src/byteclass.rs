@@ -0,0 +1,128 @@
+use crate::util;
+
+/// What kind of byte the Hex column colors a byte as, when coloring is on (`B`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Zero,
+    /// printable ASCII, space included
+    Printable,
+    /// 0x80 and up
+    High,
+    /// a legacy or REX prefix in front of an instruction's opcode
+    Prefix,
+    /// control characters and anything else left uncolored
+    Other,
+}
+
+/// x86 lock, repeat, segment override, operand size and address size prefixes
+const PREFIXES: &[u8] = &[
+    0xf0, 0xf2, 0xf3, 0x2e, 0x36, 0x3e, 0x26, 0x64, 0x65, 0x66, 0x67,
+];
+
+/// The class of each of `bytes`, which are one instruction if `instruction` is set and so can
+/// start with prefixes.
+pub fn classify(bytes: &[u8], instruction: bool) -> Vec<Class> {
+    let mut prefixes = if instruction {
+        bytes.iter().take_while(|x| PREFIXES.contains(x)).count()
+    } else {
+        0
+    };
+    // a REX prefix is only ever right before the opcode, 0x40 to 0x4f on their own are inc and
+    // dec in 32 bit code
+    if instruction && bytes.len() > prefixes + 1 && (0x40..=0x4f).contains(&bytes[prefixes]) {
+        prefixes += 1;
+    }
+    bytes
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| match byte {
+            _ if i < prefixes => Class::Prefix,
+            0 => Class::Zero,
+            0x20..=0x7e => Class::Printable,
+            0x80..=0xff => Class::High,
+            _ => Class::Other,
+        })
+        .collect()
+}
+
+/// `label`, a Hex column row, clipped like `util::clip` and split into runs of the same class:
+/// the first `classes.len()` two digit words are bytes with those classes, and the rest of the
+/// row (its address, spaces, the ascii sidebar) has none.
+pub fn runs(
+    label: &str,
+    classes: &[Class],
+    scroll: usize,
+    width: usize,
+) -> Vec<(String, Option<Class>)> {
+    let mut of_char = vec![];
+    let mut bytes = classes.iter();
+    for word in label.split_inclusive(' ') {
+        let digits = word.trim_end();
+        let byte = digits.len() == 2 && digits.chars().all(|x| x.is_ascii_hexdigit());
+        let class = if byte { bytes.next().copied() } else { None };
+        of_char.extend(digits.chars().map(|_| class));
+        of_char.extend(word[digits.len()..].chars().map(|_| None));
+    }
+    let mut runs: Vec<(String, Option<Class>)> = vec![];
+    let shown = util::clip(label, scroll, width);
+    for (c, class) in shown.chars().zip(of_char.into_iter().skip(scroll)) {
+        // the ellipses clipping leaves at either end aren't bytes
+        let class = class.filter(|_| c != '…');
+        match runs.last_mut() {
+            Some((run, last)) if *last == class => run.push(c),
+            _ => runs.push((c.to_string(), class)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Class::*;
+
+    #[test]
+    fn test_classify() {
+        // lock add dword ptr [rax], 0x41
+        assert_eq!(
+            vec![Prefix, High, Zero, Printable],
+            classify(&[0xf0, 0x83, 0x00, 0x41], true)
+        );
+        assert_eq!(
+            vec![Printable, Printable, High, Other],
+            classify(&[0x66, 0x48, 0x89, 0x07], false)
+        );
+        // mov rax, rdi and inc eax
+        assert_eq!(
+            vec![Prefix, High, High],
+            classify(&[0x48, 0x89, 0xf8], true)
+        );
+        assert_eq!(vec![Printable], classify(&[0x40], true));
+    }
+
+    #[test]
+    fn test_runs() {
+        let classes = [Zero, Zero, Printable];
+        assert_eq!(
+            vec![
+                ("00000010  ".to_string(), None),
+                ("00".to_string(), Some(Zero)),
+                (" ".to_string(), None),
+                ("00".to_string(), Some(Zero)),
+                (" ".to_string(), None),
+                ("41".to_string(), Some(Printable)),
+                (" ..A".to_string(), None),
+            ],
+            runs("00000010  00 00 41 ..A", &classes, 0, 80)
+        );
+        assert_eq!(
+            vec![
+                ("…".to_string(), None),
+                ("0".to_string(), Some(Zero)),
+                (" ".to_string(), None),
+                ("41".to_string(), Some(Printable)),
+            ],
+            runs("00 00 41", &classes, 3, 5)
+        );
+    }
+}
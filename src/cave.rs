@@ -0,0 +1,183 @@
+use crate::analysis;
+use crate::util::{self, Engines};
+use std::convert::TryFrom;
+
+/// shortest run of padding taken for a cave
+const MIN_CAVE: u64 = 16;
+/// what compilers and linkers pad code with
+const PADDING: &[u8] = &[0x00, 0x90, 0xcc];
+/// most bytes taken for the padding after a function, alignment never needs more
+const MAX_PADDING: usize = 256;
+/// x86 prefixes which can come before a branch's opcode
+const PREFIXES: &[u8] = &[0x2e, 0x3e, 0x66, 0x67, 0xf2, 0xf3];
+
+/// Where `duplicate` puts the copy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+    /// the new segment, see `rewrite::Extension`
+    Extension,
+    /// the first cave with room for it
+    Cave,
+    /// the cave at this file offset
+    At(u64),
+}
+
+/// Runs of at least `MIN_CAVE` of the same padding byte in `ranges` of `data` (file offsets and
+/// sizes, e.g. `binary::code_ranges`), as the offset and size of each.
+pub fn caves(data: &[u8], ranges: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut caves = vec![];
+    for &(start, size) in ranges {
+        let end = (start + size).min(data.len() as u64);
+        let mut at = start;
+        while at < end {
+            let byte = data[at as usize];
+            let len = data[at as usize..end as usize]
+                .iter()
+                .take_while(|x| **x == byte)
+                .count() as u64;
+            if PADDING.contains(&byte) && len >= MIN_CAVE {
+                caves.push((at, len));
+            }
+            at += len;
+        }
+    }
+    caves
+}
+
+/// How many bytes at the start of `bytes` (code at `va`) are padding: a run of zeroes, or of
+/// nops (of any length) and int3s.
+pub fn padding(engines: &Engines, bytes: &[u8], va: u64) -> usize {
+    let bytes = &bytes[..bytes.len().min(MAX_PADDING)];
+    if bytes.first() == Some(&0) {
+        return bytes.iter().take_while(|x| **x == 0).count();
+    }
+    engines
+        .disassemble(bytes, va)
+        .iter()
+        .take_while(|(_, text, _)| {
+            let mnemonic = text.split_whitespace().find(|x| *x != "data16");
+            matches!(mnemonic, Some(x) if x.starts_with("nop") || x == "int3")
+        })
+        .map(|(bytes, _, _)| bytes.len())
+        .sum()
+}
+
+/// whether `bytes` is a branch with an 8 bit displacement (jcc, jmp short, loop, jrcxz)
+fn short_branch(bytes: &[u8]) -> bool {
+    let opcode = bytes
+        .iter()
+        .find(|x| !PREFIXES.contains(x) && !(0x40..=0x4f).contains(*x));
+    matches!(opcode, Some(0x70..=0x7f) | Some(0xeb) | Some(0xe0..=0xe3))
+}
+
+/// the displacement of a rip relative operand, in Intel (`[rip + 0x10]`) or AT&T (`0x10(%rip)`)
+/// syntax
+pub fn rip_displacement(text: &str) -> Option<i64> {
+    let signed = |literal: &str| match literal.trim().strip_prefix('-') {
+        Some(literal) => util::parse_address(literal).map(|x| -(x as i64)),
+        None => util::parse_address(literal).map(|x| x as i64),
+    };
+    if let Some(at) = text.find("(%rip)") {
+        let literal = text[..at].rsplit([' ', ',']).next()?;
+        return if literal.is_empty() {
+            Some(0)
+        } else {
+            signed(literal)
+        };
+    }
+    let rest = &text[text.find("[rip")? + 4..];
+    let operand = rest.split(']').next().unwrap_or_default().trim();
+    match (operand.strip_prefix('+'), operand.strip_prefix('-')) {
+        (Some(x), _) => signed(x),
+        (_, Some(x)) => signed(x).map(|x| -x),
+        _ if operand.is_empty() => Some(0),
+        _ => None,
+    }
+}
+
+/// Copy the code at address `from` to address `to`.  Branches and rip relative operands which
+/// point outside the code are adjusted so they still reach the same place, ones within it are
+/// left alone so they stay within the copy.
+pub fn relocate(engines: &Engines, code: &[u8], from: u64, to: u64) -> Result<Vec<u8>, String> {
+    let end = from + code.len() as u64;
+    let delta = to.wrapping_sub(from) as i64;
+    let mut copy = code.to_vec();
+    let mut at = 0;
+    for (bytes, text, _) in engines.disassemble(code, from) {
+        let va = from + at as u64;
+        let len = bytes.len();
+        let (position, width, value) = match analysis::branch_target(&text) {
+            Some(target) if (from..end).contains(&target) => (0, 0, 0),
+            Some(target) => {
+                let width = if short_branch(&bytes) { 1 } else { 4 };
+                let next = va as i64 + delta + len as i64;
+                (len - width, width, target as i64 - next)
+            }
+            None => match rip_displacement(&text) {
+                Some(displacement) => {
+                    let pattern = (displacement as i32).to_le_bytes();
+                    let position = bytes
+                        .windows(4)
+                        .skip(2)
+                        .position(|x| x == pattern)
+                        .ok_or_else(|| format!("couldn't find the displacement of {}", text))?;
+                    (position + 2, 4, displacement - delta)
+                }
+                None => (0, 0, 0),
+            },
+        };
+        let fits = match width {
+            1 => i8::try_from(value).is_ok(),
+            _ => i32::try_from(value).is_ok(),
+        };
+        if !fits {
+            return Err(format!(
+                "`{}` at {:#x} can't reach that far from the copy",
+                text, va
+            ));
+        }
+        let start = at + position;
+        copy[start..start + width].copy_from_slice(&value.to_le_bytes()[..width]);
+        at += len;
+    }
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caves() {
+        let mut data = vec![0x55; 0x40];
+        data[0x08..0x20].fill(0xcc);
+        data[0x28..0x30].fill(0x00);
+        data[0x30..0x40].fill(0x90);
+        assert_eq!(vec![(0x08, 0x18), (0x30, 0x10)], caves(&data, &[(0, 0x40)]));
+        assert_eq!(vec![(0x08, 0x10)], caves(&data, &[(0, 0x18)]));
+    }
+
+    #[test]
+    fn test_padding() {
+        let engines = Engines::default();
+        let code = b"\xcc\xcc\x66\x0f\x1f\x44\x00\x00\x66\x2e\x0f\x1f\x84\x00\x00\x00\x00\x00\x55";
+        assert_eq!(18, padding(&engines, code, 0x1000));
+        assert_eq!(3, padding(&engines, b"\x00\x00\x00\x55", 0x1000));
+        assert_eq!(0, padding(&engines, b"\x55\xcc", 0x1000));
+    }
+
+    #[test]
+    fn test_relocate() {
+        let engines = Engines::default();
+        // call 0x2000; jmp to the next instruction; mov rax, [rip + 0x10]; ret
+        let code = b"\xe8\xfb\x0f\x00\x00\xeb\x00\x48\x8b\x05\x10\x00\x00\x00\xc3";
+        assert_eq!(
+            b"\xe8\xfb\xef\xff\xff\xeb\x00\x48\x8b\x05\x10\xe0\xff\xff\xc3".to_vec(),
+            relocate(&engines, code, 0x1000, 0x3000).unwrap()
+        );
+        // je 0x1012 leaves the code, the copy is too far for a short jump
+        assert!(relocate(&engines, b"\x74\x10\xc3", 0x1000, 0x3000).is_err());
+        assert_eq!(Some(-0x10), rip_displacement("lea -0x10(%rip), %rax"));
+        assert_eq!(Some(0), rip_displacement("mov rax, qword ptr [rip]"));
+    }
+}
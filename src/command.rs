@@ -0,0 +1,425 @@
+use crate::binary::{Anchor, Hardening};
+use crate::cave::Destination;
+use crate::encoder::Encoding;
+use crate::literal;
+use crate::scalar;
+use crate::util::{self, EngineSetting};
+use crate::wizard::Template;
+use std::str::FromStr;
+
+/// A command typed into the command line, entered with `:` from Viewing mode.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `redirect <import> <function|address>`
+    Redirect { import: String, target: String },
+    /// `import <library> [function]`, add a library (and function) to the binary's imports
+    Import {
+        library: String,
+        function: Option<String>,
+    },
+    /// `strip`, remove the symbol table on the next write
+    Strip,
+    /// `unstrip`, add a symbol table built from the analysis on the next write
+    Unstrip,
+    /// `detour`, move the current row into a new segment when it outgrew its original size
+    Detour,
+    /// `poke <type> <value>`, overwrite the bytes at the Hex cursor with an integer (`u8` to
+    /// `u64`, `i8` to `i64`) or a float (`f32`, `f64`), with a `be` suffix for big endian
+    Poke(Vec<u8>),
+    /// `string [move] [@address] <text>`, replace the NUL terminated string the current row
+    /// refers to, or the one at an address, with text (C escapes work, quotes keep spaces at the
+    /// ends), `move` puts a longer one in a code cave or the new segment and points code at it
+    EditString {
+        address: Option<u64>,
+        text: Vec<u8>,
+        relocate: bool,
+    },
+    /// `struct <name> { fields }`, define a struct in C, see `layout::parse`
+    DefineStruct(String),
+    /// `structs <header>`, define every struct in a C header
+    Structs(String),
+    /// `struct <name> [address]`, show the struct's fields over the data at an address or the
+    /// cursor, `struct` alone stops showing it
+    Struct {
+        name: Option<String>,
+        address: Option<u64>,
+    },
+    /// `field <name> <value>`, set a field of the struct being shown, `name[index]` for an
+    /// element of an array
+    Field { name: String, value: String },
+    /// `claim`, make the alignment padding after the current function part of it, as room to
+    /// grow into
+    Claim,
+    /// `comment [text]`, attach a comment to the current row, without text it removes the comment
+    Comment(String),
+    /// `reload`, reanalyze the file, dropping pending edits
+    Reload,
+    /// `compare [file]`, diff functions against their namesakes in another binary, without a
+    /// file it stops comparing
+    Compare(Option<String>),
+    /// `seek <function|address|file:line>`, jump to a function, an address or a source line
+    Seek(String),
+    /// `offset <offset>`, put the Hex cursor on the byte at a file offset, as reported by tools
+    /// which work on the file rather than a running process
+    Offset(u64),
+    /// `entry`, `main`, `init`, `fini` or `plt`, jump to that part of the binary
+    Goto(Anchor),
+    /// `yara [rules]`, scan the file with YARA rules, without rules go to the next match
+    Yara(Option<String>),
+    /// `bundle <path> [notes]`, save what's been written since loading as a `.tmogpatch`
+    Bundle { path: String, notes: String },
+    /// `export <path>`, save every edit including pending ones as an IPS, BPS or VCDIFF patch
+    /// against the file as loaded
+    Export(String),
+    /// `report <path>`, write comments and every edit including pending ones with before and
+    /// after disassembly, as Markdown or SARIF for reviewing a patch
+    Report(String),
+    /// `capture <path>`, write the current function as the Hex and Disasm columns show it, as
+    /// ANSI colored text or HTML for pasting into a writeup
+    Capture(String),
+    /// `overlay <path>`, build an LD_PRELOAD library which applies every edit in memory at load
+    /// time instead, or its C source if the path ends in `.c`
+    Overlay(String),
+    /// `trace`, start emulating from the current row, `trace stop` ends it
+    Trace(bool),
+    /// `force-jump` or `force-fallthrough`, make the conditional jump on the current row always or
+    /// never taken
+    Force { taken: bool },
+    /// `dump <path>`, write the current function's bytes to a file
+    Dump(String),
+    /// `load <path>`, replace the current function's bytes with a file's, which has to fit
+    Load(String),
+    /// `cc <file.c> [function]`, compile a C snippet and call it from the current row
+    InjectC {
+        path: String,
+        function: Option<String>,
+    },
+    /// `encode <xor|add> <key> [length]`, obfuscate the code after the current row and hook a
+    /// decoder for it in at the current row
+    Encode {
+        encoding: Encoding,
+        len: Option<u64>,
+    },
+    /// `harden <nx|aslr|pie> [off]`, set (or clear) the header bits for a mitigation on the next
+    /// write
+    Harden { hardening: Hardening, enable: bool },
+    /// `forbid [extension...]`, set the instruction set extensions the target lacks, without any
+    /// it allows them all again
+    Forbid(Vec<String>),
+    /// `attach <pid>`, show addresses as they are in a running process, `detach` stops
+    Attach(Option<u32>),
+    /// `stats`, show which instructions are used most, which functions are called most and how
+    /// big functions are
+    Stats,
+    /// `! [args]`, run the file with pending edits applied and show what it prints, without
+    /// arguments it gets `run_args` from the config
+    Run(Option<Vec<String>>),
+    /// `debug [args]`, hand the terminal to a debugger on the file, with a breakpoint at the
+    /// selected instruction, see `Settings::debugger`
+    Debug(Option<Vec<String>>),
+    /// `hooks`, show what the write hooks printed last time they ran
+    Hooks,
+    /// `linear`, show the whole code section as one disassembly in address order, with the bytes
+    /// between functions swept linearly, `linear` again goes back
+    Linear,
+    /// `vtables`, list the C++ vtables in data sections and the classes their RTTI names,
+    /// `vtables` again goes back
+    Vtables,
+    /// `slot <function|address>`, point the vtable slot selected in the list at another function
+    Slot(String),
+    /// `got [path]`, list every call through a GOT or IAT slot along with the import it resolves
+    /// to, `got` again goes back, with a path it writes them there as JSON instead
+    Got(Option<String>),
+    /// `af <offset> <size> [name]`, define a function at a file offset analysis missed, or move
+    /// and resize the function called `name` (or starting there) where it got the bounds wrong.
+    /// `af [size]` defines one at the cursor of the linear view, up to its first `ret` if no size
+    /// is given.
+    Define {
+        offset: Option<u64>,
+        size: Option<u64>,
+        name: Option<String>,
+    },
+    /// `data <length>`, show `length` bytes from the current row as `db` rows, which are never
+    /// assembled or disassembled, `code` turns the data at the current row back into instructions
+    Data(Option<u64>),
+    /// `switches`, find the jump tables of switch statements, marking those inside functions as
+    /// data, and label their cases
+    Switches,
+    /// `cursors [text]`, put a cursor on every row whose disassembly contains `text`, an edit
+    /// made at the current row is then made at each of them too, without text they're removed
+    Cursors(String),
+    /// `filter [query]`, only list functions matching the query, see `Filter`, without a query
+    /// every function is listed again
+    Filter(String),
+    /// `engine [option value]`, change how instructions are disassembled and assembled, see
+    /// `EngineSetting`, without an option it shows them
+    Engine(Option<EngineSetting>),
+    /// `annotations <import|export> <path>`, take function names, comments and bookmarks from a
+    /// Ghidra (.xml, .sarif) or IDA (.idc, .txt) export or an r2 project, or write ours out for
+    /// them
+    Annotations { path: String, export: bool },
+    /// `duplicate [cave|offset] [redirect]`, copy the current function into the new segment, the
+    /// first code cave with room or the cave at a file offset, optionally pointing direct calls
+    /// to it at the copy
+    Duplicate {
+        destination: Destination,
+        redirect: bool,
+    },
+    /// `trampoline [stub [template]]`, hook the current function's entry with a call to a stub
+    /// which saves and restores state around it, without a stub it asks for each part
+    Trampoline {
+        stub: Option<String>,
+        template: Option<Template>,
+    },
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // like vim, `!` doesn't need a space after it
+        if let Some(args) = s.trim_start().strip_prefix('!') {
+            let args = args.split_whitespace().map(|x| x.to_string());
+            return Ok(Command::Run(
+                Some(args.collect::<Vec<_>>()).filter(|x| !x.is_empty()),
+            ));
+        }
+        let args = s.split_whitespace().collect::<Vec<_>>();
+        match args.as_slice() {
+            ["redirect", import, target] => Ok(Command::Redirect {
+                import: import.to_string(),
+                target: target.to_string(),
+            }),
+            ["detour"] => Ok(Command::Detour),
+            ["claim"] => Ok(Command::Claim),
+            ["poke", kind, value] => Ok(Command::Poke(scalar::encode(kind, value)?)),
+            ["poke", ..] => Err("usage: poke <u8..u64|i8..i64|f32|f64>[be] <value>".to_string()),
+            ["struct", ..] if s.contains('{') => Ok(Command::DefineStruct(s.trim().to_string())),
+            ["struct"] => Ok(Command::Struct {
+                name: None,
+                address: None,
+            }),
+            ["struct", name] => Ok(Command::Struct {
+                name: Some(name.to_string()),
+                address: None,
+            }),
+            ["struct", name, address] => Ok(Command::Struct {
+                name: Some(name.to_string()),
+                address: Some(
+                    util::parse_address(address)
+                        .ok_or_else(|| format!("{} isn't an address", address))?,
+                ),
+            }),
+            ["struct", ..] => {
+                Err("usage: struct <name> { fields } or struct [name [address]]".to_string())
+            }
+            ["structs", path] => Ok(Command::Structs(path.to_string())),
+            ["structs", ..] => Err("usage: structs <header>".to_string()),
+            ["field", name, value @ ..] if !value.is_empty() => Ok(Command::Field {
+                name: name.to_string(),
+                value: value.join(" "),
+            }),
+            ["field", ..] => Err("usage: field <name> <value>".to_string()),
+            ["string", rest @ ..] => {
+                let usage = || "usage: string [move] [@address] <text>".to_string();
+                // the text is taken as typed, runs of spaces and all
+                let mut text = s.trim_start()["string".len()..].trim_start();
+                let relocate = rest.first() == Some(&"move");
+                if relocate {
+                    text = text["move".len()..].trim_start();
+                }
+                let address = match text.split_whitespace().next() {
+                    Some(word) if word.starts_with('@') => {
+                        text = text[word.len()..].trim_start();
+                        Some(
+                            util::parse_address(&word[1..])
+                                .ok_or_else(|| format!("{} isn't an address", &word[1..]))?,
+                        )
+                    }
+                    _ => None,
+                };
+                let text = text.trim_end();
+                let text = match text.strip_prefix('"').and_then(|x| x.strip_suffix('"')) {
+                    Some(quoted) => quoted,
+                    None if text.is_empty() => return Err(usage()),
+                    None => text,
+                };
+                Ok(Command::EditString {
+                    address,
+                    text: literal::unescape(text)?,
+                    relocate,
+                })
+            }
+            ["strip"] => Ok(Command::Strip),
+            ["unstrip"] => Ok(Command::Unstrip),
+            ["reload"] => Ok(Command::Reload),
+            ["stats"] => Ok(Command::Stats),
+            ["hooks"] => Ok(Command::Hooks),
+            ["debug", args @ ..] => Ok(Command::Debug(
+                Some(args.iter().map(|x| x.to_string()).collect::<Vec<_>>())
+                    .filter(|x| !x.is_empty()),
+            )),
+            ["linear"] => Ok(Command::Linear),
+            ["vtables"] => Ok(Command::Vtables),
+            ["slot", target] => Ok(Command::Slot(target.to_string())),
+            ["slot", ..] => Err("usage: slot <function|address>".to_string()),
+            ["got"] => Ok(Command::Got(None)),
+            ["got", path] => Ok(Command::Got(Some(path.to_string()))),
+            ["got", ..] => Err("usage: got [path]".to_string()),
+            ["switches"] => Ok(Command::Switches),
+            ["af"] => Ok(Command::Define {
+                offset: None,
+                size: None,
+                name: None,
+            }),
+            ["af", size] => Ok(Command::Define {
+                offset: None,
+                size: Some(
+                    util::parse_address(size).ok_or_else(|| format!("{} isn't a size", size))?,
+                ),
+                name: None,
+            }),
+            ["af", offset, size, name @ ..] if name.len() <= 1 => Ok(Command::Define {
+                offset: Some(
+                    util::parse_address(offset)
+                        .ok_or_else(|| format!("{} isn't an offset", offset))?,
+                ),
+                size: Some(
+                    util::parse_address(size).ok_or_else(|| format!("{} isn't a size", size))?,
+                ),
+                name: name.first().map(|x| x.to_string()),
+            }),
+            ["af", ..] => Err("usage: af [[offset] size [name]]".to_string()),
+            ["data", len] => Ok(Command::Data(Some(
+                util::parse_address(len).ok_or_else(|| format!("{} isn't a length", len))?,
+            ))),
+            ["data", ..] => Err("usage: data <length>".to_string()),
+            ["code"] => Ok(Command::Data(None)),
+            ["filter", query @ ..] => Ok(Command::Filter(query.join(" "))),
+            ["cursors", text @ ..] => Ok(Command::Cursors(text.join(" "))),
+            ["engine"] => Ok(Command::Engine(None)),
+            ["engine", option, value] => {
+                Ok(Command::Engine(Some(EngineSetting::parse(option, value)?)))
+            }
+            ["engine", ..] => Err("usage: engine [option value]".to_string()),
+            ["annotations", direction @ "import", path]
+            | ["annotations", direction @ "export", path] => Ok(Command::Annotations {
+                path: path.to_string(),
+                export: *direction == "export",
+            }),
+            ["annotations", ..] => Err("usage: annotations <import|export> <path>".to_string()),
+            ["trampoline"] => Ok(Command::Trampoline {
+                stub: None,
+                template: None,
+            }),
+            ["trampoline", stub] => Ok(Command::Trampoline {
+                stub: Some(stub.to_string()),
+                template: None,
+            }),
+            ["trampoline", stub, template] => Ok(Command::Trampoline {
+                stub: Some(stub.to_string()),
+                template: Some(template.parse()?),
+            }),
+            ["trampoline", ..] => Err("usage: trampoline [stub [sysv64|win64|x86]]".to_string()),
+            ["duplicate", rest @ ..] => {
+                let (redirect, rest) = match rest {
+                    [rest @ .., "redirect"] => (true, rest),
+                    rest => (false, rest),
+                };
+                let destination = match rest {
+                    [] => Destination::Extension,
+                    ["cave"] => Destination::Cave,
+                    [offset] => Destination::At(
+                        util::parse_address(offset)
+                            .ok_or_else(|| format!("{} isn't an offset", offset))?,
+                    ),
+                    _ => return Err("usage: duplicate [cave|offset] [redirect]".to_string()),
+                };
+                Ok(Command::Duplicate {
+                    destination,
+                    redirect,
+                })
+            }
+            ["yara"] => Ok(Command::Yara(None)),
+            ["yara", rules] => Ok(Command::Yara(Some(rules.to_string()))),
+            ["seek", target] => Ok(Command::Seek(target.to_string())),
+            ["seek", ..] => Err("usage: seek <function|address|file:line>".to_string()),
+            ["offset", offset] => Ok(Command::Offset(
+                util::parse_address(offset).ok_or_else(|| format!("{} isn't an offset", offset))?,
+            )),
+            ["offset", ..] => Err("usage: offset <file offset>".to_string()),
+            ["entry"] => Ok(Command::Goto(Anchor::Entry)),
+            ["main"] => Ok(Command::Goto(Anchor::Main)),
+            ["init"] => Ok(Command::Goto(Anchor::Init)),
+            ["fini"] => Ok(Command::Goto(Anchor::Fini)),
+            ["plt"] => Ok(Command::Goto(Anchor::Plt)),
+            ["compare"] => Ok(Command::Compare(None)),
+            ["compare", file] => Ok(Command::Compare(Some(file.to_string()))),
+            ["bundle", path, notes @ ..] => Ok(Command::Bundle {
+                path: path.to_string(),
+                notes: notes.join(" "),
+            }),
+            ["comment", text @ ..] => Ok(Command::Comment(text.join(" "))),
+            ["import", library] => Ok(Command::Import {
+                library: library.to_string(),
+                function: None,
+            }),
+            ["import", library, function] => Ok(Command::Import {
+                library: library.to_string(),
+                function: Some(function.to_string()),
+            }),
+            ["cc", path] => Ok(Command::InjectC {
+                path: path.to_string(),
+                function: None,
+            }),
+            ["cc", path, function] => Ok(Command::InjectC {
+                path: path.to_string(),
+                function: Some(function.to_string()),
+            }),
+            ["encode", kind, key] => Ok(Command::Encode {
+                encoding: Encoding::parse(kind, key)?,
+                len: None,
+            }),
+            ["encode", kind, key, len] => Ok(Command::Encode {
+                encoding: Encoding::parse(kind, key)?,
+                len: Some(util::parse_address(len).ok_or("length isn't a number")?),
+            }),
+            ["encode", ..] => Err("usage: encode <xor|add> <key> [length]".to_string()),
+            ["attach", pid] => Ok(Command::Attach(Some(
+                pid.parse().map_err(|_| format!("{} isn't a pid", pid))?,
+            ))),
+            ["detach"] => Ok(Command::Attach(None)),
+            ["dump", path] => Ok(Command::Dump(path.to_string())),
+            ["load", path] => Ok(Command::Load(path.to_string())),
+            ["force-jump"] => Ok(Command::Force { taken: true }),
+            ["force-fallthrough"] => Ok(Command::Force { taken: false }),
+            ["trace"] => Ok(Command::Trace(true)),
+            ["trace", "stop"] => Ok(Command::Trace(false)),
+            ["export", path] => Ok(Command::Export(path.to_string())),
+            ["export", ..] => Err("usage: export <path.ips|.bps|.vcdiff>".to_string()),
+            ["report", path] => Ok(Command::Report(path.to_string())),
+            ["report", ..] => Err("usage: report <path.md|.sarif>".to_string()),
+            ["capture", path] => Ok(Command::Capture(path.to_string())),
+            ["capture", ..] => Err("usage: capture <path.ans|.html>".to_string()),
+            ["harden", hardening] => Ok(Command::Harden {
+                hardening: hardening.parse()?,
+                enable: true,
+            }),
+            ["harden", hardening, "off"] => Ok(Command::Harden {
+                hardening: hardening.parse()?,
+                enable: false,
+            }),
+            ["forbid", extensions @ ..] => Ok(Command::Forbid(
+                extensions.iter().map(|x| x.to_lowercase()).collect(),
+            )),
+            ["harden", ..] => Err("usage: harden <nx|aslr|pie> [off]".to_string()),
+            ["overlay", path] => Ok(Command::Overlay(path.to_string())),
+            ["overlay", ..] => Err("usage: overlay <path.so|.c>".to_string()),
+            ["bundle"] => Err("usage: bundle <path> [notes]".to_string()),
+            ["import", ..] => Err("usage: import <library> [function]".to_string()),
+            ["redirect", ..] => Err("usage: redirect <import> <function|address>".to_string()),
+            [name, ..] => Err(format!("unknown command: {}", name)),
+            [] => Err("no command given".to_string()),
+        }
+    }
+}
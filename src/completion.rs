@@ -0,0 +1,53 @@
+/// Words offered when completing assembly, for one architecture.
+pub struct Table {
+    pub mnemonics: &'static [&'static str],
+    /// register names plus the keywords which show up in memory operands
+    pub operands: &'static [&'static str],
+}
+
+impl Table {
+    /// words starting with `prefix`, mnemonics if it's the first word on the line and operands
+    /// otherwise
+    pub fn candidates(&self, prefix: &str, first_word: bool) -> Vec<&'static str> {
+        let words = if first_word {
+            self.mnemonics
+        } else {
+            self.operands
+        };
+        words
+            .iter()
+            .copied()
+            .filter(|x| x.starts_with(prefix))
+            .collect()
+    }
+}
+
+/// matches what `util::assemble` targets
+pub const X86_64: Table = Table {
+    mnemonics: &[
+        "adc", "add", "and", "bsf", "bsr", "bswap", "bt", "btc", "btr", "bts", "call", "cbw",
+        "cdq", "cdqe", "clc", "cld", "cmc", "cmova", "cmovae", "cmovb", "cmovbe", "cmove", "cmovg",
+        "cmovge", "cmovl", "cmovle", "cmovne", "cmovns", "cmovs", "cmp", "cmpsb", "cmpxchg",
+        "cpuid", "cqo", "cwd", "cwde", "dec", "div", "endbr64", "enter", "hlt", "idiv", "imul",
+        "inc", "int", "int3", "ja", "jae", "jb", "jbe", "jc", "jcxz", "je", "jecxz", "jg", "jge",
+        "jl", "jle", "jmp", "jna", "jnae", "jnb", "jnbe", "jnc", "jne", "jng", "jnge", "jnl",
+        "jnle", "jno", "jnp", "jns", "jnz", "jo", "jp", "jpe", "jpo", "jrcxz", "js", "jz", "lea",
+        "leave", "lock", "lodsb", "loop", "mov", "movabs", "movaps", "movd", "movdqa", "movdqu",
+        "movq", "movsb", "movsd", "movss", "movsx", "movsxd", "movups", "movzx", "mul", "neg",
+        "nop", "not", "or", "pop", "popf", "pxor", "push", "pushf", "rcl", "rcr", "rdtsc", "rep",
+        "repe", "repne", "ret", "rol", "ror", "sal", "sar", "sbb", "scasb", "seta", "setae",
+        "setb", "setbe", "sete", "setg", "setge", "setl", "setle", "setne", "shl", "shld", "shr",
+        "shrd", "stc", "std", "stosb", "stosd", "stosq", "sub", "syscall", "test", "ud2", "xadd",
+        "xchg", "xor", "xorps",
+    ],
+    operands: &[
+        "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12",
+        "r13", "r14", "r15", "eax", "ebx", "ecx", "edx", "esi", "edi", "ebp", "esp", "r8d", "r9d",
+        "r10d", "r11d", "r12d", "r13d", "r14d", "r15d", "ax", "bx", "cx", "dx", "si", "di", "bp",
+        "sp", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w", "r14w", "r15w", "al", "bl", "cl", "dl",
+        "sil", "dil", "bpl", "spl", "ah", "bh", "ch", "dh", "r8b", "r9b", "r10b", "r11b", "r12b",
+        "r13b", "r14b", "r15b", "rip", "cs", "ds", "es", "fs", "gs", "ss", "xmm0", "xmm1", "xmm2",
+        "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13",
+        "xmm14", "xmm15", "byte", "word", "dword", "qword", "ptr",
+    ],
+};
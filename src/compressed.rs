@@ -0,0 +1,142 @@
+use crate::export;
+use crate::util::run;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// u-boot legacy image magic and header length
+const UIMAGE_MAGIC: [u8; 4] = [0x27, 0x05, 0x19, 0x56];
+const UIMAGE_HEADER: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    fn detect(magic: &[u8]) -> Option<Compression> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn tool(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+/// A compressed file, optionally behind a wrapper header such as a u-boot image's.  It's
+/// decompressed to edit and compressed again on write with the same tool, keeping the header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compressed {
+    pub path: PathBuf,
+    pub compression: Compression,
+    /// everything before the compressed stream
+    pub header: Vec<u8>,
+    /// xz's integrity check, the kernel's decompressor only understands crc32
+    check: Option<&'static str>,
+}
+
+impl Compressed {
+    /// None if `path` isn't compressed with anything we know how to recompress
+    pub fn detect(path: &Path) -> Option<Compressed> {
+        let mut start = vec![0; UIMAGE_HEADER + 8];
+        let len = File::open(path).ok()?.read(&mut start).ok()?;
+        start.truncate(len);
+        let offset = if start.starts_with(&UIMAGE_MAGIC) {
+            UIMAGE_HEADER
+        } else {
+            0
+        };
+        let magic = start.get(offset..)?;
+        let compression = Compression::detect(magic)?;
+        let check = match (compression, magic.get(7)) {
+            (Compression::Xz, Some(0x00)) => Some("none"),
+            (Compression::Xz, Some(0x01)) => Some("crc32"),
+            (Compression::Xz, Some(0x0a)) => Some("sha256"),
+            _ => None,
+        };
+        Some(Compressed {
+            path: path.to_path_buf(),
+            compression,
+            header: start[..offset].to_vec(),
+            check,
+        })
+    }
+
+    pub fn fetch(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        let mut stream = File::open(&self.path)?;
+        stream.seek(SeekFrom::Start(self.header.len() as u64))?;
+        let data = run(Command::new(self.compression.tool())
+            .arg("-dc")
+            .stdin(stream))?;
+        std::fs::write(local, data)?;
+        Ok(())
+    }
+
+    pub fn store(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        let mut command = Command::new(self.compression.tool());
+        match self.compression {
+            Compression::Gzip => command.args(["-9", "-n"]),
+            Compression::Xz => command.arg(format!("--check={}", self.check.unwrap_or("crc64"))),
+            Compression::Zstd => command.args(["-19", "-q"]),
+        };
+        let stream = run(command.arg("-c").arg(local))?;
+        let mut data = self.header.clone();
+        if data.starts_with(&UIMAGE_MAGIC) {
+            uimage_fixup(&mut data, &stream);
+        }
+        data.extend(stream);
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Compressed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.path.display().fmt(f)
+    }
+}
+
+/// update a u-boot image header's data size and checksums for new `data`
+fn uimage_fixup(header: &mut [u8], data: &[u8]) {
+    header[12..16].copy_from_slice(&(data.len() as u32).to_be_bytes());
+    header[24..28].copy_from_slice(&export::crc32(data).to_be_bytes());
+    // the header checksum covers the header with itself zeroed
+    header[4..8].copy_from_slice(&[0; 4]);
+    let crc = export::crc32(header);
+    header[4..8].copy_from_slice(&crc.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uimage_fixup() {
+        let mut header = UIMAGE_MAGIC.to_vec();
+        header.resize(UIMAGE_HEADER, 0xff);
+        let data = [0x1f, 0x8b, 0x08, 0x00];
+        uimage_fixup(&mut header, &data);
+        assert_eq!([0, 0, 0, 4], header[12..16]);
+        assert_eq!(export::crc32(&data).to_be_bytes(), header[24..28]);
+        let crc = header[4..8].to_vec();
+        header[4..8].copy_from_slice(&[0; 4]);
+        assert_eq!(export::crc32(&header).to_be_bytes().to_vec(), crc);
+        assert_eq!(Some(Compression::Gzip), Compression::detect(&data));
+    }
+}
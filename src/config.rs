@@ -0,0 +1,212 @@
+use crate::analysis::BackendKind;
+use crate::keymap::Keymap;
+use crate::theme::Theme;
+use crate::util::{self, EngineOptions};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// User settings, read from `$XDG_CONFIG_HOME/transmogrify/config.json` (or `~/.config/...`).
+/// Missing keys fall back to their defaults so a config only has to mention what it changes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// how often (in milliseconds) pending edits are reassembled/disassembled
+    pub tick_rate: u64,
+    /// how many levels of callers and callees the call tree shows
+    pub call_tree_depth: usize,
+    /// how many instructions running a trace executes before giving up on hitting a breakpoint
+    pub trace_steps: usize,
+    /// compiler `:cc` runs, e.g. a cross compiler for the binary's architecture
+    pub cc: String,
+    /// flags for `cc`, the snippet has to come out position independent and self contained
+    pub cflags: Vec<String>,
+    /// docker compatible command `--image` copies files in and out of images with
+    pub container_runtime: String,
+    /// shell command run on the file after every write, with its path appended, e.g.
+    /// `codesign -f -s -` to sign it again
+    pub post_write: Option<String>,
+    /// shell commands run one after another in the background before every write, with the
+    /// file's path as `$1`, the first to fail stops the write
+    pub before_write: Vec<String>,
+    /// shell commands run in the background after every write, e.g. `make test`, with the file's
+    /// path as `$1`.  How each exits is shown in the bar and `:hooks` shows what they printed.
+    pub after_write: Vec<String>,
+    /// arguments `!` runs the file with, `:! args` overrides them for one run
+    pub run_args: Vec<String>,
+    /// how `:debug` starts a debugger: `{file}`, `{address}` and `{args}` (`run_args`) are filled
+    /// in and it's run with `sh -c`, e.g. for lldb
+    /// `lldb -o 'breakpoint set -a {address}' -o run -- {file} {args}`.  The breakpoint is set
+    /// before the file is loaded, so position independent files are refused.
+    pub debugger: String,
+    /// instruction set extensions the target CPU lacks as capstone names them (avx, avx2, sse41,
+    /// avx512...), instructions which need one are highlighted and assembling one warns
+    pub forbidden_extensions: Vec<String>,
+    /// show the file's format, sections and mitigations on startup, `h` shows them again
+    pub overview: bool,
+    /// capstone and keystone options, `:engine` changes them for the session
+    pub engines: EngineOptions,
+    /// `default` or `vim` (hjkl, i/a to edit, dd, yy and p), see `Keymap`
+    pub keymap: Keymap,
+    /// `default` or `high-contrast`
+    pub theme: Theme,
+    /// write what changes as plain lines for screen readers instead of drawing the interface,
+    /// `--plain` turns it on for one session
+    pub plain: bool,
+    /// seconds between snapshots of the session to the `--project` file, 0 only saves on quit
+    pub autosave: u64,
+    /// whether the Hex column is shown, `S` toggles it and remembers the choice here
+    pub show_hex: bool,
+    /// whether the Disasm column is shown, `D` toggles it and remembers the choice here
+    pub show_disasm: bool,
+    /// whether the Disasm column has a minimap of the function on its right edge, marking
+    /// modified rows, YARA hits, bookmarks and where the cursor is
+    pub minimap: bool,
+    /// what finds the functions: `r2`, `rizin`, `ghidra`, `objdump` or `native`, `--backend`
+    /// picks another for one session
+    pub backend: BackendKind,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            tick_rate: 250,
+            call_tree_depth: 2,
+            trace_steps: 10000,
+            cc: "cc".to_string(),
+            cflags: [
+                "-Os",
+                "-fPIC",
+                "-ffreestanding",
+                "-fno-stack-protector",
+                "-fno-asynchronous-unwind-tables",
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect(),
+            container_runtime: "docker".to_string(),
+            post_write: None,
+            before_write: vec![],
+            after_write: vec![],
+            run_args: vec![],
+            debugger: "gdb -q -ex 'break *{address}' -ex run --args {file} {args}".to_string(),
+            forbidden_extensions: vec![],
+            overview: true,
+            engines: EngineOptions::default(),
+            keymap: Keymap::Default,
+            theme: Theme::Default,
+            plain: false,
+            autosave: 30,
+            show_hex: true,
+            show_disasm: true,
+            minimap: true,
+            backend: BackendKind::R2,
+        }
+    }
+}
+
+impl Settings {
+    /// The config at `path` (or the default one), the defaults if there is none.  A config which
+    /// can't be read is an error naming it, ignoring it would quietly drop its hooks.
+    pub fn load(path: Option<&Path>) -> Result<Settings, Box<dyn Error>> {
+        let path = match path.map(PathBuf::from).or_else(default_path) {
+            Some(path) => path,
+            None => return Ok(Settings::default()),
+        };
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Settings::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+        };
+        Ok(serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?)
+    }
+
+    /// `tick_rate`, at least a millisecond so the tick thread doesn't spin
+    pub fn tick_rate(&self) -> Duration {
+        Duration::from_millis(self.tick_rate.max(1))
+    }
+}
+
+/// Set `key` to `value` in the config at `path` (or the default one), creating it if need be.
+/// The rest of the file is left as it was rather than filled in with defaults.
+pub fn remember(
+    path: Option<&Path>,
+    key: &str,
+    value: serde_json::Value,
+) -> Result<(), Box<dyn Error>> {
+    let path = path
+        .map(PathBuf::from)
+        .or_else(default_path)
+        .ok_or("nowhere to keep the config")?;
+    let mut config = match std::fs::read_to_string(&path) {
+        Ok(json) => {
+            serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        Err(_) => serde_json::json!({}),
+    };
+    config
+        .as_object_mut()
+        .ok_or_else(|| format!("{} isn't a JSON object", path.display()))?
+        .insert(key.to_string(), value);
+    let json = serde_json::to_string_pretty(&config)?;
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::File::create(&path)?;
+    }
+    // a crash part way through mustn't leave the rest of the config half written
+    util::replace_atomically(&path, |copy| Ok(std::fs::write(copy, json)?))
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("transmogrify").join("config.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    #[test]
+    fn test_remember() {
+        let directory = TempDir::new("test").unwrap();
+        let path = directory.join("transmogrify").join("config.json");
+        let read = || -> serde_json::Value {
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap()
+        };
+        remember(Some(&path), "show_hex", false.into()).unwrap();
+        assert_eq!(serde_json::json!({"show_hex": false}), read());
+        // the rest is left alone
+        std::fs::write(&path, "{\"tick_rate\": 100, \"show_hex\": false}").unwrap();
+        remember(Some(&path), "show_hex", true.into()).unwrap();
+        assert_eq!(
+            serde_json::json!({"tick_rate": 100, "show_hex": true}),
+            read()
+        );
+        // nothing's left next to it
+        assert_eq!(
+            1,
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count()
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let directory = TempDir::new("test").unwrap();
+        let path = directory.join("config.json");
+        assert_eq!(250, Settings::load(Some(&path)).unwrap().tick_rate);
+        std::fs::write(&path, "{\"tick_rate\": 0}").unwrap();
+        let settings = Settings::load(Some(&path)).unwrap();
+        assert_eq!(Duration::from_millis(1), settings.tick_rate());
+        // a typo isn't taken for an empty config
+        std::fs::write(&path, "{\"before_write\": [\"sign\"],}").unwrap();
+        let e = Settings::load(Some(&path)).unwrap_err().to_string();
+        assert!(e.starts_with(&path.display().to_string()));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::util;
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// A file inside a container image.  Writes don't touch the image itself, they commit the
+/// patched file as a new layer on top of it under a `-patched` tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    /// docker or anything with the same command line, e.g. podman
+    pub runtime: String,
+    pub image: String,
+    pub path: String,
+}
+
+impl Image {
+    pub fn fetch(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        let container = self.create()?;
+        // -L so a path that's a symlink in the image, like /bin/sh often is, gives the file it
+        // points at rather than a dangling link
+        let copied = self.run(&[
+            "cp",
+            "-L",
+            &format!("{}:{}", container, self.path),
+            &local.to_string_lossy(),
+        ]);
+        self.run(&["rm", &container])?;
+        copied.map(|_| ())
+    }
+
+    /// commit `local` over the original file as a new image named by `patched`
+    pub fn commit(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        let container = self.create()?;
+        let patched = self.patched();
+        let committed = self
+            .run(&[
+                "cp",
+                &local.to_string_lossy(),
+                &format!("{}:{}", container, self.path),
+            ])
+            .and_then(|_| self.run(&["commit", &container, &patched]));
+        self.run(&["rm", &container])?;
+        committed.map(|_| ())
+    }
+
+    /// the tag patched images are committed under, `name:tag-patched`
+    pub fn patched(&self) -> String {
+        // a colon before the last slash is a registry port rather than a tag
+        let name = &self.image[self.image.rfind('/').map(|x| x + 1).unwrap_or(0)..];
+        if name.contains(':') {
+            format!("{}-patched", self.image)
+        } else {
+            format!("{}:latest-patched", self.image)
+        }
+    }
+
+    /// a stopped container of the image to copy files in and out of
+    fn create(&self) -> Result<String, Box<dyn Error>> {
+        self.run(&["create", &self.image])
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        let output = util::run(Command::new(&self.runtime).args(args))?;
+        Ok(String::from_utf8_lossy(&output).trim().to_string())
+    }
+}
+
+impl std::fmt::Display for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in {}", self.path, self.image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(image: &str) -> Image {
+        Image {
+            runtime: "docker".to_string(),
+            image: image.to_string(),
+            path: "/bin/sh".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_patched() {
+        assert_eq!("alpine:3.12-patched", image("alpine:3.12").patched());
+        assert_eq!("alpine:latest-patched", image("alpine").patched());
+        assert_eq!(
+            "localhost:5000/app:latest-patched",
+            image("localhost:5000/app").patched()
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let echo = Image {
+            runtime: "echo".to_string(),
+            ..image("alpine")
+        };
+        assert_eq!("create alpine", echo.create().unwrap());
+        let fails = Image {
+            runtime: "false".to_string(),
+            ..image("alpine")
+        };
+        assert!(fails.fetch(Path::new("/nonexistent")).is_err());
+        assert!(fails.commit(Path::new("/nonexistent")).is_err());
+    }
+}
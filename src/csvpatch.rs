@@ -0,0 +1,173 @@
+use crate::util;
+use std::error::Error;
+
+/// A row of a patch CSV, `offset,bytes` as fuzzers and binary diffing tools write them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// line of the CSV the row came from, for reporting
+    pub line: usize,
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// What applying a CSV did.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub rows: usize,
+    /// rows whose bytes were already in the file
+    pub unchanged: usize,
+    /// bytes which actually differ now
+    pub changed: usize,
+}
+
+/// `0x` prefixed or bare hex, bytes may be separated by spaces
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .unwrap_or(text)
+        .chars()
+        .filter(|x| !x.is_whitespace())
+        .collect::<Vec<_>>();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|x| u8::from_str_radix(&x.iter().collect::<String>(), 16).ok())
+        .collect()
+}
+
+/// Every row of `text`.  Blank lines and `#` comments are skipped, as is a header on the first
+/// line.  All the bad rows are reported at once rather than the first.
+pub fn parse(text: &str) -> Result<Vec<Row>, Box<dyn Error>> {
+    let mut rows = vec![];
+    let mut errors = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split(',').map(|x| x.trim()).collect::<Vec<_>>();
+        let row = match fields.as_slice() {
+            [offset, bytes] => util::parse_address(offset)
+                .ok_or_else(|| format!("{} isn't an offset", offset))
+                .and_then(|offset| {
+                    parse_hex(bytes)
+                        .map(|bytes| (offset, bytes))
+                        .ok_or_else(|| format!("{} isn't hex bytes", bytes))
+                }),
+            _ => Err(format!(
+                "expected offset,bytes but got {} field(s)",
+                fields.len()
+            )),
+        };
+        match row {
+            Ok((offset, bytes)) => rows.push(Row {
+                line: i + 1,
+                offset,
+                bytes,
+            }),
+            Err(_) if i == 0 => {}
+            Err(e) => errors.push(format!("line {}: {}", i + 1, e)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+    Ok(rows)
+}
+
+/// Write every row into `data`, or nothing if any row runs past the end of it or overlaps
+/// another with different bytes.
+pub fn apply(rows: &[Row], data: &mut [u8]) -> Result<Summary, Box<dyn Error>> {
+    let mut errors = vec![];
+    for row in rows {
+        if row.offset.saturating_add(row.bytes.len() as u64) > data.len() as u64 {
+            errors.push(format!(
+                "line {}: {:#x}+{:#x} is past the end of the file ({:#x} bytes)",
+                row.line,
+                row.offset,
+                row.bytes.len(),
+                data.len()
+            ));
+        }
+    }
+    let mut sorted = rows.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|x| x.offset);
+    let end = |row: &Row| row.offset + row.bytes.len() as u64;
+    let byte = |row: &Row, offset: u64| row.bytes[(offset - row.offset) as usize];
+    // each row is checked against whichever before it reaches furthest
+    let mut furthest: Option<&Row> = None;
+    for b in sorted {
+        if let Some(a) = furthest {
+            if (b.offset..end(a).min(end(b))).any(|offset| byte(a, offset) != byte(b, offset)) {
+                errors.push(format!(
+                    "lines {} and {} write different bytes at {:#x}",
+                    a.line, b.line, b.offset
+                ));
+            }
+        }
+        if furthest.is_none_or(|a| end(b) > end(a)) {
+            furthest = Some(b);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+
+    let mut summary = Summary {
+        rows: rows.len(),
+        ..Summary::default()
+    };
+    for row in rows {
+        let range = row.offset as usize..row.offset as usize + row.bytes.len();
+        let changed = data[range.clone()]
+            .iter()
+            .zip(&row.bytes)
+            .filter(|(a, b)| a != b)
+            .count();
+        if changed == 0 {
+            summary.unchanged += 1;
+        }
+        summary.changed += changed;
+        data[range].copy_from_slice(&row.bytes);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let rows = parse("offset,bytes\n0x1,9090\n\n# already patched\n3, 0x03\n").unwrap();
+        assert_eq!(2, rows.len());
+        assert_eq!((2, 0x1), (rows[0].line, rows[0].offset));
+        let mut data = vec![0, 1, 2, 3];
+        let summary = apply(&rows, &mut data).unwrap();
+        assert_eq!(vec![0, 0x90, 0x90, 3], data);
+        assert_eq!(
+            Summary {
+                rows: 2,
+                unchanged: 1,
+                changed: 2,
+            },
+            summary
+        );
+
+        let errors = parse("0x1,9090\nzz,90\n2,909\n").unwrap_err().to_string();
+        assert_eq!(
+            "line 2: zz isn't an offset\nline 3: 909 isn't hex bytes",
+            errors
+        );
+
+        let rows = parse("0,0102\n1,03\n3,0405").unwrap();
+        let errors = apply(&rows, &mut data).unwrap_err().to_string();
+        assert!(errors.contains("lines 1 and 2 write different bytes at 0x1"));
+        assert!(errors.contains("line 3: 0x3+0x2 is past the end"));
+        // nothing was written
+        assert_eq!(vec![0, 0x90, 0x90, 3], data);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::util::TempDir;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// A debugger waiting to be handed the terminal.
+#[derive(Debug)]
+pub struct Launch {
+    /// the expanded template, run with `sh -c`
+    pub command: String,
+    /// where a copy with pending edits applied is, removed once the debugger exits
+    pub copy: Option<TempDir>,
+}
+
+/// `text` quoted for sh
+pub fn quote(text: &str) -> String {
+    if !text.is_empty()
+        && text
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || "/._-+=:,@".contains(x))
+    {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Fill in a debugger command template: `{file}` is the path, `{address}` where to break (in
+/// hex) and `{args}` the arguments to run it with, all quoted for sh.
+pub fn expand(template: &str, file: &Path, address: u64, args: &[String]) -> String {
+    let args = args.iter().map(|x| quote(x)).collect::<Vec<_>>().join(" ");
+    template
+        .replace("{file}", &quote(&file.to_string_lossy()))
+        .replace("{address}", &format!("{:#x}", address))
+        .replace("{args}", &args)
+}
+
+/// Run `command` in the foreground with the terminal back in its cooked mode `cooked` (and on
+/// the main screen unless `plain`), then put the interface's raw mode and screen back.  Keys
+/// have to be left unread while it runs.
+pub fn run_in_terminal(
+    command: &str,
+    cooked: &libc::termios,
+    plain: bool,
+) -> io::Result<ExitStatus> {
+    let mut stdout = io::stdout();
+    let mut raw: libc::termios = unsafe { std::mem::zeroed() };
+    unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut raw) };
+    if !plain {
+        // mouse reporting off, as MouseTerminal turned it on
+        write!(
+            stdout,
+            "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l{}{}",
+            termion::screen::ToMainScreen,
+            termion::cursor::Show
+        )?;
+        stdout.flush()?;
+    }
+    unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, cooked) };
+
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    // the file may have been piped in, the debugger wants the keyboard
+    if !termion::is_tty(&io::stdin()) {
+        shell.stdin(Stdio::from(termion::get_tty()?));
+    }
+    let status = shell.status();
+
+    unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &raw) };
+    if !plain {
+        write!(
+            stdout,
+            "{}\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h",
+            termion::screen::ToAlternateScreen
+        )?;
+        stdout.flush()?;
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let template = "gdb -ex 'break *{address}' --args {file} {args}";
+        let args = ["-v".to_string(), "it's".to_string()];
+        assert_eq!(
+            r"gdb -ex 'break *0x401136' --args '/tmp/a b' -v 'it'\''s'",
+            expand(template, Path::new("/tmp/a b"), 0x401136, &args)
+        );
+        assert_eq!("''", quote(""));
+        assert_eq!("./a.out", quote("./a.out"));
+    }
+}
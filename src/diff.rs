@@ -0,0 +1,92 @@
+/// One line of a diff between two listings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    /// only in the first listing
+    Removed(String),
+    /// only in the second listing
+    Added(String),
+    /// the first listing's line was replaced by the second's
+    Changed(String, String),
+}
+
+/// Line diff of `old` against `new` via their longest common subsequence.  A run of removals
+/// directly followed by additions is paired up into changes, which is how an edited instruction
+/// usually shows up.
+pub fn diff(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    // lcs[i][j] is the length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut removed, mut added) = (vec![], vec![]);
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            flush(&mut lines, &mut removed, &mut added);
+            lines.push(DiffLine::Same(old[i].clone()));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(new[j].clone());
+            j += 1;
+        } else {
+            removed.push(old[i].clone());
+            i += 1;
+        }
+    }
+    flush(&mut lines, &mut removed, &mut added);
+    lines
+}
+
+fn flush(lines: &mut Vec<DiffLine>, removed: &mut Vec<String>, added: &mut Vec<String>) {
+    let changed = removed.len().min(added.len());
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+    for (old, new) in removed.by_ref().zip(added.by_ref()).take(changed) {
+        lines.push(DiffLine::Changed(old, new));
+    }
+    lines.extend(removed.map(DiffLine::Removed));
+    lines.extend(added.map(DiffLine::Added));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(x: &[&str]) -> Vec<String> {
+        x.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = lines(&["push rbp", "mov rbp, rsp", "xor eax, eax", "pop rbp", "ret"]);
+        let new = lines(&[
+            "push rbp",
+            "mov rbp, rsp",
+            "mov eax, 1",
+            "nop",
+            "pop rbp",
+            "ret",
+        ]);
+        assert_eq!(
+            vec![
+                DiffLine::Same("push rbp".to_string()),
+                DiffLine::Same("mov rbp, rsp".to_string()),
+                DiffLine::Changed("xor eax, eax".to_string(), "mov eax, 1".to_string()),
+                DiffLine::Added("nop".to_string()),
+                DiffLine::Same("pop rbp".to_string()),
+                DiffLine::Same("ret".to_string()),
+            ],
+            diff(&old, &new)
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use gimli::{EndianSlice, LittleEndian, SectionId};
+use goblin::elf::section_header::SHF_COMPRESSED;
+use goblin::elf::Elf;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+type Slice<'a> = EndianSlice<'a, LittleEndian>;
+
+/// One row of the line number matrix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Row {
+    address: u64,
+    /// index into `LineTable::files`
+    file: usize,
+    line: u32,
+    /// the first address past the end of a sequence, which doesn't map to anything
+    end: bool,
+}
+
+/// The `.debug_line` programs of every compilation unit run and merged into one table, so any
+/// address can be mapped back to the source line it was compiled from.
+#[derive(Debug, Default)]
+pub struct LineTable {
+    files: Vec<PathBuf>,
+    /// sorted by address
+    rows: Vec<Row>,
+}
+
+impl LineTable {
+    pub fn parse(data: &[u8]) -> Result<LineTable, Box<dyn Error>> {
+        let elf = Elf::parse(data)?;
+        if !elf.little_endian {
+            return Err("big endian DWARF isn't supported".into());
+        }
+        let section = |name| -> Result<&[u8], Box<dyn Error>> {
+            let header = elf
+                .section_headers
+                .iter()
+                .find(|x| elf.shdr_strtab.get(x.sh_name).and_then(|x| x.ok()) == Some(name));
+            match header {
+                None => Ok(&[]),
+                Some(x) if x.sh_flags & SHF_COMPRESSED as u64 != 0 => {
+                    Err(format!("{} is compressed, which isn't supported", name).into())
+                }
+                Some(x) => x
+                    .sh_offset
+                    .checked_add(x.sh_size)
+                    .and_then(|end| data.get(x.sh_offset as usize..end as usize))
+                    .ok_or_else(|| format!("{} runs past the end of the file", name).into()),
+            }
+        };
+        LineTable::from_sections(section)
+    }
+
+    /// The line programs of every compilation unit in `.debug_info`, with `section` giving the
+    /// contents of each DWARF section by name, empty if the file doesn't have it.
+    fn from_sections<'a>(
+        section: impl Fn(&'static str) -> Result<&'a [u8], Box<dyn Error>>,
+    ) -> Result<LineTable, Box<dyn Error>> {
+        if section(SectionId::DebugLine.name())?.is_empty() {
+            return Err("no DWARF line info".into());
+        }
+        let dwarf = gimli::Dwarf::load(
+            |id: SectionId| Ok::<_, Box<dyn Error>>(Slice::new(section(id.name())?, LittleEndian)),
+            |_| Ok(Slice::new(&[], LittleEndian)),
+        )?;
+        let mut table = LineTable::default();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let mut unit = dwarf.unit(header)?;
+            if let Some(program) = unit.line_program.take() {
+                table.run(&dwarf, &unit, program)?;
+            }
+        }
+        // where one sequence ends and another starts at the same address the start wins
+        table.rows.sort_by_key(|x| (x.address, !x.end));
+        Ok(table)
+    }
+
+    /// the source file and line `va` was compiled from
+    pub fn lookup(&self, va: u64) -> Option<(&Path, u32)> {
+        let index = self.rows.partition_point(|x| x.address <= va);
+        let row = self.rows.get(index.checked_sub(1)?)?;
+        if row.end {
+            return None;
+        }
+        Some((&self.files[row.file], row.line))
+    }
+
+    /// The lowest address compiled from `line` of a file whose path ends with `file`, or from the
+    /// next line after it which has any code.
+    pub fn address_of(&self, file: &Path, line: u32) -> Option<u64> {
+        self.rows
+            .iter()
+            .filter(|x| !x.end && x.line >= line && self.files[x.file].ends_with(file))
+            .min_by_key(|x| (x.line, x.address))
+            .map(|x| x.address)
+    }
+
+    fn intern(&mut self, path: PathBuf) -> usize {
+        match self.files.iter().position(|x| *x == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path);
+                self.files.len() - 1
+            }
+        }
+    }
+
+    /// run one unit's line program, adding its rows
+    fn run(
+        &mut self,
+        dwarf: &gimli::Dwarf<Slice>,
+        unit: &gimli::Unit<Slice>,
+        program: gimli::IncompleteLineProgram<Slice>,
+    ) -> Result<(), Box<dyn Error>> {
+        // the index into `files` of each of the unit's file numbers
+        let mut files: HashMap<u64, Option<usize>> = HashMap::new();
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            let file = match files.get(&row.file_index()) {
+                Some(file) => *file,
+                None => {
+                    let file = match header.file(row.file_index()) {
+                        Some(entry) => {
+                            // relative directories are relative to the compilation directory
+                            let mut path = PathBuf::new();
+                            if let Some(directory) = &unit.comp_dir {
+                                path.push(&*directory.to_string_lossy());
+                            }
+                            if let Some(directory) = entry.directory(header) {
+                                path.push(&*dwarf.attr_string(unit, directory)?.to_string_lossy());
+                            }
+                            path.push(
+                                &*dwarf
+                                    .attr_string(unit, entry.path_name())?
+                                    .to_string_lossy(),
+                            );
+                            Some(self.intern(path))
+                        }
+                        None => None,
+                    };
+                    files.insert(row.file_index(), file);
+                    file
+                }
+            };
+            let file = match file {
+                Some(file) => file,
+                // end rows aren't looked up, so their file doesn't matter
+                None if row.end_sequence() => 0,
+                None => continue,
+            };
+            self.rows.push(Row {
+                address: row.address(),
+                file,
+                line: row.line().unwrap_or(0) as u32,
+                end: row.end_sequence(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a DWARF 4 line table for src/a.c running `program`
+    fn unit(program: &[u8]) -> Vec<u8> {
+        let mut unit = vec![
+            0x04, 0x00, // version
+            0x00, 0x00, 0x00, 0x00, // header length, filled in below
+            0x01, // minimum instruction length
+            0x01, // maximum operations per instruction
+            0x01, // default is_stmt
+            0xfb, // line base -5
+            0x0e, // line range 14
+            0x0d, // opcode base 13
+            0x00, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01,
+        ];
+        unit.extend_from_slice(b"src\0\0a.c\0\x01\x00\x00\0");
+        let header_len = unit.len() - 6;
+        unit[2..6].copy_from_slice(&(header_len as u32).to_le_bytes());
+        unit.extend_from_slice(program);
+        let mut debug_line = (unit.len() as u32).to_le_bytes().to_vec();
+        debug_line.extend(unit);
+        debug_line
+    }
+
+    #[test]
+    fn test_line_program() {
+        // line 3 at 0x1000, line 4 at 0x1004, ending at 0x1008
+        let debug_line = unit(&[
+            0x00, 0x09, 0x02, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // set address
+            0x03, 0x02, // advance line by 2
+            0x01, // copy
+            0x4b, // special: address +4, line +1
+            0x02, 0x04, // advance pc by 4
+            0x00, 0x01, 0x01, // end sequence
+        ]);
+        let table = LineTable::from_sections(sections(&debug_line)).unwrap();
+        let a = Path::new("src/a.c");
+        assert_eq!(None, table.lookup(0xfff));
+        assert_eq!(Some((a, 3)), table.lookup(0x1000));
+        assert_eq!(Some((a, 3)), table.lookup(0x1003));
+        assert_eq!(Some((a, 4)), table.lookup(0x1007));
+        assert_eq!(None, table.lookup(0x1008));
+        assert_eq!(Some(0x1004), table.address_of(Path::new("a.c"), 4));
+        assert_eq!(Some(0x1000), table.address_of(a, 1));
+        assert_eq!(None, table.address_of(Path::new("b.c"), 4));
+        assert_eq!(None, table.address_of(a, 5));
+    }
+
+    #[test]
+    fn test_malformed() {
+        // extended opcodes with no room for the opcode or for set address' operand
+        assert!(LineTable::from_sections(sections(&unit(&[0x00, 0x00, 0x02]))).is_err());
+        assert!(LineTable::from_sections(sections(&unit(&[0x00, 0x01, 0x02]))).is_err());
+        // a header running past the end of the section
+        let truncated = [0x1a, 0x00, 0x00, 0x00, 0x04, 0x00, 0xff, 0xff, 0xff, 0x7f];
+        assert!(LineTable::from_sections(sections(&truncated)).is_err());
+        assert!(LineTable::from_sections(sections(&[])).is_err());
+    }
+
+    /// the sections of one DWARF 4 compilation unit whose line program is `debug_line`
+    fn sections<'a>(
+        debug_line: &'a [u8],
+    ) -> impl Fn(&'static str) -> Result<&'a [u8], Box<dyn Error>> + 'a {
+        move |name| {
+            Ok(match name {
+                // a compile unit with only a DW_AT_stmt_list of 0, as a sec_offset
+                ".debug_abbrev" => &[0x01, 0x11, 0x00, 0x10, 0x17, 0x00, 0x00, 0x00],
+                ".debug_info" => &[
+                    0x0c, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x01, 0x00,
+                    0x00, 0x00, 0x00,
+                ],
+                ".debug_line" => debug_line,
+                _ => &[],
+            })
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::util::{self, Engines};
+use goblin::elf::header::{EI_OSABI, ELFOSABI_NONE, EM_X86_64};
+use goblin::Object;
+use std::error::Error;
+
+/// page size the decoder unprotects code in
+const PAGE_SIZE: u64 = 0x1000;
+/// the OS/ABI Linux binaries are marked with when they aren't plain System V
+const ELFOSABI_GNU: u8 = 3;
+
+/// How bytes are obfuscated, both with a one byte key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Xor(u8),
+    Add(u8),
+}
+
+impl Encoding {
+    pub fn parse(kind: &str, key: &str) -> Result<Self, String> {
+        let key = util::parse_address(key)
+            .filter(|x| *x <= 0xff)
+            .ok_or_else(|| format!("{} isn't a byte", key))?;
+        match kind {
+            "xor" => Ok(Encoding::Xor(key as u8)),
+            "add" => Ok(Encoding::Add(key as u8)),
+            _ => Err(format!("unknown encoding {}, expected xor or add", kind)),
+        }
+    }
+
+    pub fn encode(self, bytes: &mut [u8]) {
+        for byte in bytes {
+            *byte = match self {
+                Encoding::Xor(key) => *byte ^ key,
+                Encoding::Add(key) => byte.wrapping_add(key),
+            };
+        }
+    }
+
+    /// Refuse binaries the decoder can't run in, it calls mprotect the way 64 bit Linux does.
+    pub fn check_target(data: &[u8]) -> Result<(), Box<dyn Error>> {
+        match Object::parse(data)? {
+            Object::Elf(elf)
+                if elf.header.e_machine == EM_X86_64
+                    && [ELFOSABI_NONE, ELFOSABI_GNU].contains(&elf.header.e_ident[EI_OSABI]) =>
+            {
+                Ok(())
+            }
+            _ => Err("the decoder only runs in x86-64 Linux ELF binaries".into()),
+        }
+    }
+
+    /// Assemble a routine at `va` which decodes `len` bytes at `start` in place the first time
+    /// it's called, returning the code and its entry point.  `flag` is a zeroed byte in writable
+    /// memory it uses to remember that, and the pages being decoded are made writable with
+    /// mprotect first.  Everything is addressed relative to rip so it works in position
+    /// independent binaries.
+    pub fn decoder(
+        self,
+        va: u64,
+        flag: u64,
+        start: u64,
+        len: u64,
+    ) -> Result<(Vec<u8>, u64), Box<dyn Error>> {
+        let page = start & !(PAGE_SIZE - 1);
+        let pages_len = (start + len - page + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        let mut asm = Assembler {
+            engines: Engines::default(),
+            code: vec![],
+            va,
+        };
+        // the ret comes first so every jump is backwards and assembles in one pass
+        let done = asm.emit("ret")?;
+        // a rip relative lea is always 7 bytes long
+        let entry = asm.emit(&format!("lea rax, [{}]", rip(flag, asm.va + 7)))?;
+        asm.emit("cmp byte ptr [rax], 0")?;
+        asm.emit(&format!("jne {:#x}", done))?;
+        asm.emit("mov byte ptr [rax], 1")?;
+        // mprotect(page, pages_len, PROT_READ | PROT_WRITE | PROT_EXEC)
+        asm.emit(&format!("lea rdi, [{}]", rip(page, asm.va + 7)))?;
+        asm.emit(&format!("mov esi, {:#x}", pages_len))?;
+        asm.emit("mov edx, 7")?;
+        asm.emit("mov eax, 10")?;
+        asm.emit("syscall")?;
+        asm.emit(&format!("lea rdi, [{}]", rip(start, asm.va + 7)))?;
+        asm.emit(&format!("mov ecx, {:#x}", len))?;
+        let top = asm.emit(&match self {
+            Encoding::Xor(key) => format!("xor byte ptr [rdi], {:#x}", key),
+            Encoding::Add(key) => format!("sub byte ptr [rdi], {:#x}", key),
+        })?;
+        asm.emit("inc rdi")?;
+        asm.emit("dec ecx")?;
+        asm.emit(&format!("jnz {:#x}", top))?;
+        asm.emit(&format!("jmp {:#x}", done))?;
+        Ok((asm.code, entry))
+    }
+}
+
+/// assembles one line at a time, keeping track of where the next one goes
+struct Assembler {
+    /// the decoder is written in 64 bit intel syntax whatever the user's engines read
+    engines: Engines,
+    code: Vec<u8>,
+    va: u64,
+}
+
+impl Assembler {
+    /// returns the address `line` was assembled at
+    fn emit(&mut self, line: &str) -> Result<u64, Box<dyn Error>> {
+        let va = self.va;
+        let bytes = self.engines.assemble(line.to_string(), va)?;
+        self.va += bytes.len() as u64;
+        self.code.extend(bytes);
+        Ok(va)
+    }
+}
+
+/// operand for `target` relative to rip, where `next` is the address of the next instruction
+fn rip(target: u64, next: u64) -> String {
+    if target >= next {
+        format!("rip + {:#x}", target - next)
+    } else {
+        format!("rip - {:#x}", next - target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let original = vec![0x55, 0x48, 0x89, 0xe5, 0xc3];
+        for encoding in &[Encoding::Xor(0x41), Encoding::Add(0xf0)] {
+            let mut bytes = original.clone();
+            encoding.encode(&mut bytes);
+            assert_ne!(original, bytes);
+        }
+        let mut bytes = original.clone();
+        Encoding::Add(0xf0).encode(&mut bytes);
+        Encoding::Add(0x10).encode(&mut bytes);
+        assert_eq!(original, bytes);
+        assert_eq!(Ok(Encoding::Xor(0x41)), Encoding::parse("xor", "0x41"));
+        assert!(Encoding::parse("xor", "0x141").is_err());
+    }
+
+    #[test]
+    fn test_check_target() {
+        let mut elf = crate::binary::test_elf(0x400000, &[0xc3]);
+        assert!(Encoding::check_target(&elf).is_ok());
+        elf[18] = 3; // EM_386
+        assert!(Encoding::check_target(&elf).is_err());
+        assert!(Encoding::check_target(b"MZ").is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use std::io;
+use std::io::{self, Read};
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync::mpsc;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -23,9 +24,39 @@ pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
     input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
     tick_handle: thread::JoinHandle<()>,
 }
 
+/// Keys read only while not paused, so a program run in the foreground gets the terminal's
+/// input to itself.
+struct Pausable {
+    input: Box<dyn Read>,
+    fd: RawFd,
+    paused: Arc<AtomicBool>,
+}
+
+impl Read for Pausable {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.paused.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            // wait for input with a timeout so pausing takes effect before the next key is read
+            let mut fds = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let ready = unsafe { libc::poll(&mut fds, 1, 50) };
+            if ready > 0 && !self.paused.load(Ordering::Relaxed) {
+                return self.input.read(buf);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
     pub exit_key: Key,
@@ -49,12 +80,29 @@ impl Events {
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
         let input_handle = {
             let tx = tx.clone();
             let ignore_exit_key = ignore_exit_key.clone();
+            let paused = paused.clone();
             thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
+                // the file being edited may have been piped in, in which case keys come from the tty
+                let (input, fd): (Box<dyn io::Read>, RawFd) = if termion::is_tty(&io::stdin()) {
+                    (Box::new(io::stdin()), io::stdin().as_raw_fd())
+                } else {
+                    match termion::get_tty() {
+                        Ok(tty) => {
+                            let fd = tty.as_raw_fd();
+                            (Box::new(tty), fd)
+                        }
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            return;
+                        }
+                    }
+                };
+                let input = Pausable { input, fd, paused };
+                for evt in input.keys() {
                     if let Ok(key) = evt {
                         if let Err(err) = tx.send(Event::Input(key)) {
                             eprintln!("{}", err);
@@ -78,6 +126,7 @@ impl Events {
         Events {
             rx,
             ignore_exit_key,
+            paused,
             input_handle,
             tick_handle,
         }
@@ -94,4 +143,13 @@ impl Events {
     pub fn enable_exit_key(&mut self) {
         self.ignore_exit_key.store(false, Ordering::Relaxed);
     }
+
+    /// stop reading keys until `resume`, for handing the terminal to another program
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
 }
@@ -0,0 +1,170 @@
+use crate::util::{run, TempDir};
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// Patch formats other tools understand, picked by the extension of the file being written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Ips,
+    Bps,
+    /// VCDIFF as produced by xdelta3
+    Vcdiff,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("ips") => Ok(Format::Ips),
+            Some("bps") => Ok(Format::Bps),
+            Some("vcdiff") | Some("xdelta") => Ok(Format::Vcdiff),
+            _ => Err("patches can be exported as .ips, .bps, .vcdiff or .xdelta".into()),
+        }
+    }
+
+    /// a patch which turns `original` into `patched`
+    pub fn encode(self, original: &[u8], patched: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Format::Ips => ips(original, patched),
+            Format::Bps => Ok(bps(original, patched)),
+            Format::Vcdiff => vcdiff(original, patched),
+        }
+    }
+}
+
+/// `(start, end)` of each run of bytes which differ, bytes past the end of `original` count as
+/// differing
+pub fn changed_runs(original: &[u8], patched: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for i in (0..patched.len()).filter(|i| original.get(*i) != patched.get(*i)) {
+        match runs.last_mut() {
+            Some((_, end)) if *end == i => *end = i + 1,
+            _ => runs.push((i, i + 1)),
+        }
+    }
+    runs
+}
+
+/// IPS: 24 bit offsets and 16 bit lengths, with the truncation extension for files which shrank
+pub fn ips(original: &[u8], patched: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    const EOF: usize = 0x454f46;
+    let mut out = b"PATCH".to_vec();
+    for (mut start, end) in changed_runs(original, patched) {
+        // a record at offset "EOF" would read as the end of the patch
+        if start == EOF {
+            start -= 1;
+        }
+        while start < end {
+            let len = (end - start).min(0xffff);
+            if start + len > 0xffffff {
+                return Err("IPS can't address past 16MiB".into());
+            }
+            out.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+            out.extend_from_slice(&patched[start..start + len]);
+            start += len;
+        }
+    }
+    out.extend_from_slice(b"EOF");
+    if patched.len() < original.len() {
+        out.extend_from_slice(&(patched.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(out)
+}
+
+/// BPS using only SourceRead and TargetRead actions, which is all an in place patch needs
+pub fn bps(original: &[u8], patched: &[u8]) -> Vec<u8> {
+    const SOURCE_READ: u64 = 0;
+    const TARGET_READ: u64 = 1;
+    let mut out = b"BPS1".to_vec();
+    bps_number(&mut out, original.len() as u64);
+    bps_number(&mut out, patched.len() as u64);
+    bps_number(&mut out, 0);
+    let mut at = 0;
+    for (start, end) in changed_runs(original, patched) {
+        if start > at {
+            bps_number(&mut out, ((start - at - 1) as u64) << 2 | SOURCE_READ);
+        }
+        bps_number(&mut out, ((end - start - 1) as u64) << 2 | TARGET_READ);
+        out.extend_from_slice(&patched[start..end]);
+        at = end;
+    }
+    if patched.len() > at {
+        bps_number(
+            &mut out,
+            ((patched.len() - at - 1) as u64) << 2 | SOURCE_READ,
+        );
+    }
+    out.extend_from_slice(&crc32(original).to_le_bytes());
+    out.extend_from_slice(&crc32(patched).to_le_bytes());
+    let checksum = crc32(&out);
+    out.extend_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+/// the variable length encoding BPS uses for sizes and actions
+fn bps_number(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let x = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | x);
+            break;
+        }
+        out.push(x);
+        value -= 1;
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (!(crc & 1)).wrapping_add(1))
+        })
+    })
+}
+
+/// VCDIFF is left to xdelta3, which has to be on the PATH
+fn vcdiff(original: &[u8], patched: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let directory = TempDir::new("vcdiff")?;
+    let source = directory.join("source");
+    let target = directory.join("target");
+    std::fs::write(&source, original)?;
+    std::fs::write(&target, patched)?;
+    run(Command::new("xdelta3")
+        .arg("-e")
+        .arg("-c")
+        .arg("-s")
+        .arg(&source)
+        .arg(&target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn test_ips() {
+        let patch = ips(&[0, 1, 2, 3, 4], &[0, 9, 9, 3]).unwrap();
+        assert_eq!(
+            b"PATCH\x00\x00\x01\x00\x02\x09\x09EOF\x00\x00\x04".to_vec(),
+            patch
+        );
+    }
+
+    #[test]
+    fn test_bps() {
+        let patch = bps(&[0, 1, 2, 3], &[0, 9, 2, 3, 4]);
+        // source size, target size, no metadata, SourceRead 1, TargetRead 1, SourceRead 2,
+        // TargetRead 1
+        assert_eq!(
+            b"BPS1\x84\x85\x80\x80\x81\x09\x84\x81\x04".to_vec(),
+            patch[..patch.len() - 12].to_vec()
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::util;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::cmp::Ordering;
+
+/// A Functions pane filter: words are fuzzy matched against the name and `key:value` or
+/// `size<op>value` terms narrow it down further, e.g. `size>0x100 section:.text modified:true
+/// parse`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// as typed, shown in the pane's title
+    pub query: String,
+    /// what's left of the query once the terms are taken out
+    text: String,
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    /// the size compares to the value as any of the orderings
+    Size(Vec<Ordering>, usize),
+    Section(String),
+    Modified(bool),
+}
+
+/// What a filter looks at in a function.
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    pub size: usize,
+    /// the section the function starts in, if any
+    pub section: Option<&'a str>,
+    pub modified: bool,
+}
+
+impl Filter {
+    pub fn parse(query: &str) -> Result<Filter, String> {
+        let mut text = vec![];
+        let mut terms = vec![];
+        for word in query.split_whitespace() {
+            if let Some(size) = word.strip_prefix("size") {
+                let (orderings, value) = if let Some(value) = size.strip_prefix(">=") {
+                    (vec![Ordering::Greater, Ordering::Equal], value)
+                } else if let Some(value) = size.strip_prefix("<=") {
+                    (vec![Ordering::Less, Ordering::Equal], value)
+                } else if let Some(value) = size.strip_prefix('>') {
+                    (vec![Ordering::Greater], value)
+                } else if let Some(value) = size.strip_prefix('<') {
+                    (vec![Ordering::Less], value)
+                } else if let Some(value) =
+                    size.strip_prefix('=').or_else(|| size.strip_prefix(':'))
+                {
+                    (vec![Ordering::Equal], value)
+                } else {
+                    text.push(word);
+                    continue;
+                };
+                let value =
+                    util::parse_address(value).ok_or_else(|| format!("{} isn't a size", value))?;
+                terms.push(Term::Size(orderings, value as usize));
+            } else if let Some(section) = word.strip_prefix("section:") {
+                terms.push(Term::Section(section.to_string()));
+            } else if let Some(modified) = word.strip_prefix("modified:") {
+                terms.push(Term::Modified(match modified {
+                    "true" | "yes" => true,
+                    "false" | "no" => false,
+                    _ => return Err("modified: takes true or false".to_string()),
+                }));
+            } else {
+                text.push(word);
+            }
+        }
+        Ok(Filter {
+            query: query.trim().to_string(),
+            text: text.join(" "),
+            terms,
+        })
+    }
+
+    pub fn matches(&self, function: &Candidate) -> bool {
+        let terms = self.terms.iter().all(|term| match term {
+            Term::Size(orderings, value) => orderings.contains(&function.size.cmp(value)),
+            Term::Section(section) => function.section == Some(section.as_str()),
+            Term::Modified(modified) => function.modified == *modified,
+        });
+        terms
+            && (self.text.is_empty()
+                || SkimMatcherV2::default()
+                    .fuzzy_match(function.name, &self.text)
+                    .is_some_and(|x| x > 5))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter::parse("size>=0x100 section:.text modified:true prs").unwrap();
+        assert_eq!("prs", filter.text);
+        let mut function = Candidate {
+            name: "parse_args",
+            size: 0x100,
+            section: Some(".text"),
+            modified: true,
+        };
+        assert!(filter.matches(&function));
+        function.size = 0xff;
+        assert!(!filter.matches(&function));
+        function.size = 0x200;
+        function.section = Some(".text.hot");
+        assert!(!filter.matches(&function));
+        function.section = Some(".text");
+        function.name = "main";
+        assert!(!filter.matches(&function));
+
+        assert!(Filter::parse("size>big").is_err());
+        assert!(Filter::parse("modified:maybe").is_err());
+        // only comparisons make it a size term
+        assert_eq!("sizeof", Filter::parse("sizeof").unwrap().text);
+    }
+}
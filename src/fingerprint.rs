@@ -0,0 +1,140 @@
+use crate::util::Engines;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Where a function was and a hash of its code, to find it again in another build of the file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub name: String,
+    /// file offset of the start
+    pub offset: u64,
+    pub size: u64,
+    /// see `hash`
+    pub hash: String,
+}
+
+/// How a function from one build was found in another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub name: String,
+    /// how far it moved
+    pub delta: i64,
+    /// found by its hash, so its code is the same and edits to it still apply, rather than only
+    /// by name
+    pub same_code: bool,
+}
+
+/// A position independent hash of the code at file offset `start`: the mnemonics of its
+/// instructions in order (an FNV-1a of them).  Moving the function or changing what its calls and
+/// jumps point at leaves the hash alone, changing its code doesn't.
+pub fn hash(engines: &Engines, code: &[u8], start: u64) -> String {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for (_, text, _) in engines.disassemble(code, start) {
+        let mnemonic = text.split_whitespace().next().unwrap_or_default();
+        for byte in mnemonic.bytes().chain(Some(b';')) {
+            hash = (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Where each function in `old` went among `new`, by the old name.  The function with the same
+/// hash is picked, preferring one with the same name if several have it, then one with the same
+/// name whose code changed.  Functions found neither way are left out.
+pub fn relocate(old: &[Fingerprint], new: &[Fingerprint]) -> BTreeMap<String, Match> {
+    let mut by_hash: BTreeMap<&str, Vec<&Fingerprint>> = BTreeMap::new();
+    for function in new {
+        by_hash.entry(&function.hash).or_default().push(function);
+    }
+    let mut matches = BTreeMap::new();
+    for function in old {
+        let same_hash = by_hash
+            .get(function.hash.as_str())
+            .map_or(&[][..], |x| &x[..]);
+        let found = match same_hash {
+            [only] => Some((*only, true)),
+            several => several
+                .iter()
+                .find(|x| x.name == function.name)
+                .map(|x| (*x, true))
+                .or_else(|| {
+                    let renamed = new.iter().find(|x| x.name == function.name)?;
+                    Some((renamed, false))
+                }),
+        };
+        if let Some((found, same_code)) = found {
+            let entry = Match {
+                name: found.name.clone(),
+                delta: found.offset as i64 - function.offset as i64,
+                same_code,
+            };
+            matches.insert(function.name.clone(), entry);
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(name: &str, offset: u64, hash: &str) -> Fingerprint {
+        Fingerprint {
+            name: name.to_string(),
+            offset,
+            size: 0x10,
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_hash() {
+        let engines = Engines::default();
+        // mov eax, 1; call +0; ret, then the same with another constant and call target
+        let a = hash(
+            &engines,
+            b"\xb8\x01\x00\x00\x00\xe8\x00\x00\x00\x00\xc3",
+            0x1000,
+        );
+        let b = hash(
+            &engines,
+            b"\xb8\x02\x00\x00\x00\xe8\x10\x00\x00\x00\xc3",
+            0x2000,
+        );
+        let c = hash(&engines, b"\xb8\x01\x00\x00\x00\x90\xc3", 0x1000);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_relocate() {
+        let old = [
+            fingerprint("fcn.00001139", 0x1139, "aa"),
+            fingerprint("main", 0x1200, "bb"),
+            fingerprint("helper", 0x1300, "cc"),
+            fingerprint("gone", 0x1400, "dd"),
+        ];
+        let new = [
+            fingerprint("fcn.00001149", 0x1149, "aa"),
+            fingerprint("main", 0x1210, "b2"),
+            fingerprint("helper", 0x1310, "cc"),
+            fingerprint("copy", 0x1500, "cc"),
+        ];
+        let matches = relocate(&old, &new);
+        let found = |name: &str| matches.get(name).cloned();
+        assert_eq!(
+            Some(Match {
+                name: "fcn.00001149".to_string(),
+                delta: 0x10,
+                same_code: true
+            }),
+            found("fcn.00001139")
+        );
+        assert_eq!(Some(false), found("main").map(|x| x.same_code));
+        assert_eq!(
+            Some("helper"),
+            found("helper").as_ref().map(|x| x.name.as_str())
+        );
+        assert_eq!(None, found("gone"));
+    }
+}
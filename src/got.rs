@@ -0,0 +1,235 @@
+use crate::cave;
+use crate::util;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use tui::widgets::ListState;
+
+/// An instruction of the code as the editor has it.
+#[derive(Debug, Clone, Copy)]
+pub struct Instruction<'a> {
+    pub function: &'a str,
+    /// file offset, which the text was disassembled at
+    pub offset: u64,
+    /// address it's loaded at
+    pub va: u64,
+    pub len: u64,
+    pub text: &'a str,
+}
+
+/// A call (or tail call) through a GOT or IAT slot, made by the instruction itself or by way of a
+/// PLT stub or import thunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSite {
+    pub function: String,
+    pub offset: u64,
+    pub va: u64,
+    pub instruction: String,
+    /// address of the slot
+    pub slot: u64,
+    /// the imported function the loader fills the slot in with
+    pub import: String,
+    /// file offset of the stub called, None when the instruction reads the slot itself
+    pub stub: Option<u64>,
+}
+
+/// the mnemonic and operand of a call or jmp, past prefixes
+fn branch(text: &str) -> Option<(&str, &str)> {
+    let words = text
+        .trim_start_matches("notrack ")
+        .trim_start_matches("bnd ")
+        .trim_start();
+    let (mnemonic, operand) = words.split_at(words.find(' ')?);
+    let mnemonic = match mnemonic {
+        "call" | "callq" | "calll" => "call",
+        "jmp" | "jmpq" | "jmpl" => "jmp",
+        _ => return None,
+    };
+    Some((mnemonic, operand.trim()))
+}
+
+/// The slot a call or jmp at `va`, `len` bytes long, reads its target from: rip relative or an
+/// absolute address, in Intel (`[rip + 0x2fe2]`, `[0x804a00c]`) or AT&T (`*0x2fe2(%rip)`,
+/// `*0x804a00c`) syntax.
+fn slot_read(text: &str, va: u64, len: u64) -> Option<u64> {
+    let operand = branch(text)?.1.trim_start_matches('*');
+    if let Some(displacement) = cave::rip_displacement(operand) {
+        return Some((va + len).wrapping_add(displacement as u64));
+    }
+    let absolute = match operand.find('[') {
+        Some(at) => operand[at + 1..].split(']').next()?,
+        // AT&T, where `*` marks an indirect branch
+        None if text.contains('*') => operand,
+        None => return None,
+    };
+    util::parse_address(absolute.trim())
+}
+
+/// where a direct call or jmp goes, in the offsets the text was disassembled at
+fn direct(text: &str) -> Option<u64> {
+    let (_, operand) = branch(text)?;
+    util::parse_address(operand)
+}
+
+/// Every call through an import's slot among `instructions`, given each slot's address and the
+/// import it holds, sorted by offset.  Jumps through a slot are PLT stubs or thunks where code
+/// calls them, and tail calls otherwise.
+pub fn find(instructions: &[Instruction], slots: &HashMap<u64, String>) -> Vec<CallSite> {
+    let through = |x: &Instruction| {
+        let slot = slot_read(x.text, x.va, x.len)?;
+        Some(slot).filter(|x| slots.contains_key(x))
+    };
+    // the slot and jmp of each stub by where it starts, at an endbr in front of the jmp if any
+    let mut stubs = HashMap::new();
+    for (i, instruction) in instructions.iter().enumerate() {
+        let slot = match through(instruction) {
+            Some(slot) if matches!(branch(instruction.text), Some(("jmp", _))) => slot,
+            _ => continue,
+        };
+        let stub = (slot, instruction.offset);
+        stubs.insert(instruction.offset, stub);
+        if let Some(previous) = i.checked_sub(1).map(|x| &instructions[x]) {
+            let adjacent = previous.offset + previous.len == instruction.offset;
+            if adjacent && previous.text.starts_with("endbr") {
+                stubs.insert(previous.offset, stub);
+            }
+        }
+    }
+    // stubs' jmps are reported as the calls to them
+    let called = instructions
+        .iter()
+        .filter_map(|x| stubs.get(&direct(x.text)?))
+        .map(|(_, jmp)| *jmp)
+        .collect::<HashSet<_>>();
+
+    let mut sites = vec![];
+    for instruction in instructions {
+        let (slot, stub) = match (through(instruction), direct(instruction.text)) {
+            (Some(_), _) if called.contains(&instruction.offset) => continue,
+            (Some(slot), _) => (slot, None),
+            (None, Some(target)) => match stubs.get(&target) {
+                Some((slot, _)) => (*slot, Some(target)),
+                None => continue,
+            },
+            (None, None) => continue,
+        };
+        sites.push(CallSite {
+            function: instruction.function.to_string(),
+            offset: instruction.offset,
+            va: instruction.va,
+            instruction: instruction.text.to_string(),
+            slot,
+            import: slots[&slot].clone(),
+            stub,
+        });
+    }
+    sites.sort_by_key(|x| x.offset);
+    sites
+}
+
+/// `sites` as a JSON array, for planning interposition outside the editor.
+pub fn to_json(sites: &[CallSite]) -> serde_json::Value {
+    sites
+        .iter()
+        .map(|x| {
+            json!({
+                "function": x.function,
+                "offset": x.offset,
+                "address": x.va,
+                "instruction": x.instruction,
+                "slot": x.slot,
+                "import": x.import,
+                "stub": x.stub,
+            })
+        })
+        .collect()
+}
+
+/// The calls through imports, a line for each, `:got`.
+#[derive(Debug)]
+pub struct GotMap {
+    pub sites: Vec<CallSite>,
+    pub state: ListState,
+}
+
+impl GotMap {
+    pub fn new(sites: Vec<CallSite>) -> GotMap {
+        let mut state = ListState::default();
+        state.select(Some(0));
+        GotMap { sites, state }
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.sites
+            .iter()
+            .map(|x| {
+                let via = x.stub.map_or(String::new(), |x| format!(" via {:#x}", x));
+                format!(
+                    "{:<24} <- {} {:#x} {}{} (slot {:#x})",
+                    x.import, x.function, x.va, x.instruction, via, x.slot
+                )
+            })
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<&CallSite> {
+        self.sites.get(self.state.selected()?)
+    }
+
+    /// move the selection by `delta` lines, stopping at either end
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.sites.len().saturating_sub(1) as isize;
+        let index = self.state.selected().unwrap_or(0) as isize + delta;
+        self.state.select(Some(index.max(0).min(last) as usize));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        // code at offset 0x1000 loaded at 0x401000, puts' slot at 0x404018
+        let at = |function, offset: u64, len, text| Instruction {
+            function,
+            offset,
+            va: offset + 0x400000,
+            len,
+            text,
+        };
+        let instructions = [
+            at("sym.imp.puts", 0x1030, 4, "endbr64"),
+            at(
+                "sym.imp.puts",
+                0x1034,
+                7,
+                "bnd jmp qword ptr [rip + 0x2fdd]",
+            ),
+            at("main", 0x1100, 5, "call 0x1030"),
+            at("main", 0x1105, 6, "call qword ptr [rip + 0x2f0d]"),
+            at("main", 0x110b, 5, "call 0x1200"),
+            at("main", 0x1110, 6, "jmp qword ptr [rip + 0x2f02]"),
+        ];
+        let slots = [(0x404018, "puts".to_string())].iter().cloned().collect();
+        let sites = find(&instructions, &slots);
+        assert_eq!(
+            vec![(0x1100, Some(0x1030)), (0x1105, None), (0x1110, None)],
+            sites.iter().map(|x| (x.offset, x.stub)).collect::<Vec<_>>()
+        );
+        assert!(sites
+            .iter()
+            .all(|x| x.import == "puts" && x.slot == 0x404018));
+        assert_eq!(
+            Some(0x804a00c),
+            slot_read("call dword ptr [0x804a00c]", 0, 6)
+        );
+        assert_eq!(Some(0x804a00c), slot_read("call *0x804a00c", 0, 6));
+        assert_eq!(None, slot_read("call eax", 0, 2));
+        assert_eq!(None, slot_read("call 0x804a00c", 0, 5));
+        assert_eq!(Some(0x3000), slot_read("jmpq *0x1ffa(%rip)", 0x1000, 6));
+
+        let json = to_json(&sites[..1]);
+        assert_eq!("puts", json[0]["import"]);
+        assert_eq!(0x1030, json[0]["stub"]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How a hook command ran.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub command: String,
+    /// exit code, None if it was killed by a signal or couldn't be started
+    pub status: Option<i32>,
+    /// stdout followed by stderr
+    pub output: String,
+    pub elapsed: Duration,
+}
+
+impl Outcome {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    pub fn summary(&self) -> String {
+        let status = match self.status {
+            Some(code) => format!("exited {}", code),
+            None => "didn't finish".to_string(),
+        };
+        format!(
+            "`{}` {} after {:.1}s",
+            self.command,
+            status,
+            self.elapsed.as_secs_f32()
+        )
+    }
+
+    /// the summary and then the output, indented
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![self.summary()];
+        lines.extend(self.output.lines().map(|x| format!("  {}", x)));
+        lines
+    }
+}
+
+/// Run `command` with `sh -c`, with the path of `file` as `$1`.  It gets no input, the
+/// terminal belongs to the editor.
+pub fn run(command: &str, file: &Path) -> Outcome {
+    let start = Instant::now();
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .arg("sh")
+        .arg(file)
+        .stdin(Stdio::null())
+        .output();
+    let (status, output) = match output {
+        Ok(output) => (
+            output.status.code(),
+            format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        ),
+        Err(e) => (None, format!("couldn't run sh: {}", e)),
+    };
+    Outcome {
+        command: command.to_string(),
+        status,
+        output,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Hooks running one after another in the background, so a slow test suite (or a slow check
+/// before a write) doesn't hold up editing.  The first one to fail stops the rest.
+#[derive(Debug)]
+pub struct Running {
+    receiver: Receiver<Outcome>,
+    remaining: usize,
+}
+
+impl Running {
+    pub fn spawn(commands: Vec<String>, file: PathBuf) -> Running {
+        let (sender, receiver) = mpsc::channel();
+        let remaining = commands.len();
+        std::thread::spawn(move || {
+            for command in commands {
+                let outcome = run(&command, &file);
+                let success = outcome.success();
+                // nobody's listening any more once another write started hooks of its own
+                if sender.send(outcome).is_err() || !success {
+                    break;
+                }
+            }
+        });
+        Running {
+            receiver,
+            remaining,
+        }
+    }
+
+    /// the hooks which have finished since the last call
+    pub fn finished(&mut self) -> Vec<Outcome> {
+        let outcomes = self.receiver.try_iter().collect::<Vec<_>>();
+        self.remaining -= outcomes.len();
+        if outcomes.iter().any(|x| !x.success()) {
+            self.remaining = 0;
+        }
+        outcomes
+    }
+
+    pub fn done(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks() {
+        let file = Path::new("/bin/true");
+        let outcome = run("echo \"$1\"; echo oops >&2; exit 3", file);
+        assert_eq!(Some(3), outcome.status);
+        assert_eq!("/bin/true\noops\n", outcome.output);
+        assert!(outcome
+            .summary()
+            .starts_with("`echo \"$1\"; echo oops >&2; exit 3` exited 3"));
+        // nothing to read
+        assert_eq!("", run("cat", file).output);
+
+        let commands = ["true", "false", "true"].iter().map(|x| x.to_string());
+        let mut running = Running::spawn(commands.collect(), file.to_path_buf());
+        let mut outcomes = vec![];
+        while !running.done() {
+            outcomes.extend(running.finished());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // the last one never runs
+        assert_eq!(
+            vec![Some(0), Some(1)],
+            outcomes.iter().map(|x| x.status).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,562 @@
+use crate::util;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// flagspaces r2 fills in itself, flags anywhere else were made by hand
+const R2_FLAGSPACES: &[&str] = &[
+    "classes",
+    "functions",
+    "imports",
+    "pointers",
+    "registers",
+    "relocs",
+    "sections",
+    "segments",
+    "sign",
+    "strings",
+    "symbols",
+    "symbols.sections",
+];
+
+/// Formats reverse engineering tools keep their work in, picked by the extension of the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Ghidra's XML export
+    GhidraXml,
+    /// Ghidra's SARIF export, only read
+    Sarif,
+    /// an IDC script, as IDA writes with File > Produce file > Dump database to IDC
+    Idc,
+    /// one `address name` per line, as IDA's names list is usually saved or Ghidra's
+    /// ImportSymbolsScript reads (`name address f`)
+    Names,
+    /// the `rc.r2` of an r2 project, only read
+    R2Project,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("xml") => Ok(Format::GhidraXml),
+            Some("sarif") => Ok(Format::Sarif),
+            Some("idc") => Ok(Format::Idc),
+            Some("txt") | Some("names") => Ok(Format::Names),
+            Some("r2") => Ok(Format::R2Project),
+            _ => Err(
+                "annotations can be .xml (Ghidra), .sarif, .idc (IDA), .txt names or an r2 project"
+                    .into(),
+            ),
+        }
+    }
+}
+
+/// Function names, comments and bookmarks by virtual address, as another tool knows them.
+#[derive(Debug, Default, PartialEq)]
+pub struct Notes {
+    /// function names by entry point
+    pub names: BTreeMap<u64, String>,
+    pub comments: BTreeMap<u64, String>,
+    /// bookmarks and their descriptions, which may be empty
+    pub bookmarks: BTreeMap<u64, String>,
+}
+
+/// Read `text` in `format`.  Addresses are moved from the image base the export was made at (if
+/// it records one) to `image_base`, as Ghidra loads position independent code at 0x100000.
+pub fn read(format: Format, text: &str, image_base: u64) -> Result<Notes, Box<dyn Error>> {
+    match format {
+        Format::GhidraXml => Ok(read_ghidra_xml(text, image_base)),
+        Format::Sarif => read_sarif(text, image_base),
+        Format::Idc => Ok(read_idc(text)),
+        Format::Names => Ok(read_names(text)),
+        Format::R2Project => Ok(read_r2_project(text)),
+    }
+}
+
+/// `notes` in `format`, for a file loaded at `image_base`
+pub fn write(format: Format, notes: &Notes, image_base: u64) -> Result<String, Box<dyn Error>> {
+    match format {
+        Format::GhidraXml => Ok(write_ghidra_xml(notes, image_base)),
+        Format::Sarif => Err("SARIF can only be imported, export .xml for Ghidra".into()),
+        Format::R2Project => Err("r2 projects can only be imported".into()),
+        Format::Idc => Ok(write_idc(notes)),
+        Format::Names => Ok(notes
+            .names
+            .iter()
+            .map(|(address, name)| format!("{} {:#x} f\n", name, address))
+            .collect()),
+    }
+}
+
+/// `00101139` or `ram:00101139`
+fn hex_address(text: &str) -> Option<u64> {
+    let text = text.rsplit(':').next()?.trim();
+    u64::from_str_radix(text.strip_prefix("0x").unwrap_or(text), 16).ok()
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Each `<tag ...>` element in `xml` as its attributes and the text up to `</tag>`, empty for
+/// elements closed with `/>`.  Enough of XML for what Ghidra writes, not a parser.
+fn elements(xml: &str, tag: &str) -> Vec<(BTreeMap<String, String>, String)> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut found = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // <FUNCTIONS> isn't a <FUNCTION
+        if !rest.starts_with(|x: char| x.is_whitespace() || x == '>' || x == '/') {
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let head = &rest[..end];
+        let attributes = head
+            .trim_end_matches('/')
+            .split('"')
+            .collect::<Vec<_>>()
+            .chunks(2)
+            .filter(|x| x.len() == 2)
+            .map(|x| {
+                let key = x[0].trim().trim_end_matches('=').trim();
+                (key.to_string(), unescape_xml(x[1]))
+            })
+            .collect();
+        rest = &rest[end + 1..];
+        let mut body = String::new();
+        if !head.ends_with('/') {
+            if let Some(end) = rest.find(&close) {
+                body = unescape_xml(&rest[..end]);
+                rest = &rest[end + close.len()..];
+            }
+        }
+        found.push((attributes, body));
+    }
+    found
+}
+
+fn read_ghidra_xml(xml: &str, image_base: u64) -> Notes {
+    let base = elements(xml, "PROGRAM")
+        .first()
+        .and_then(|(x, _)| hex_address(x.get("IMAGE_BASE")?))
+        .unwrap_or(image_base);
+    let rebase = |address: &str| {
+        Some(
+            hex_address(address)?
+                .wrapping_sub(base)
+                .wrapping_add(image_base),
+        )
+    };
+    let mut notes = Notes::default();
+    for (attributes, _) in elements(xml, "FUNCTION") {
+        let address = attributes.get("ENTRY_POINT").and_then(|x| rebase(x));
+        if let (Some(address), Some(name)) = (address, attributes.get("NAME")) {
+            notes.names.insert(address, name.clone());
+        }
+    }
+    for (attributes, body) in elements(xml, "COMMENT") {
+        if let Some(address) = attributes.get("ADDRESS").and_then(|x| rebase(x)) {
+            add_comment(&mut notes, address, body.trim());
+        }
+    }
+    for (attributes, _) in elements(xml, "BOOKMARK") {
+        if let Some(address) = attributes.get("ADDRESS").and_then(|x| rebase(x)) {
+            let description = attributes.get("DESCRIPTION").cloned().unwrap_or_default();
+            notes.bookmarks.insert(address, description);
+        }
+    }
+    notes
+}
+
+/// several comments on one address (pre, end of line, plate...) are joined
+fn add_comment(notes: &mut Notes, address: u64, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let comment = notes.comments.entry(address).or_default();
+    if !comment.is_empty() {
+        comment.push_str("; ");
+    }
+    comment.push_str(text);
+}
+
+/// Ghidra's SARIF export: a result per function, comment and bookmark, told apart by `ruleId`,
+/// with what they say in `properties.additionalProperties`
+fn read_sarif(text: &str, image_base: u64) -> Result<Notes, Box<dyn Error>> {
+    let json = serde_json::from_str::<serde_json::Value>(text)?;
+    let address = |x: &serde_json::Value| match x {
+        serde_json::Value::Number(x) => x.as_u64(),
+        serde_json::Value::String(x) => hex_address(x),
+        _ => None,
+    };
+    let mut notes = Notes::default();
+    for run in json["runs"].as_array().ok_or("no runs in the SARIF")? {
+        let base =
+            address(&run["properties"]["additionalProperties"]["imageBase"]).unwrap_or(image_base);
+        for result in run["results"].as_array().into_iter().flatten() {
+            let properties = &result["properties"]["additionalProperties"];
+            let location = &result["locations"][0]["physicalLocation"]["address"];
+            let at = match address(&location["absoluteAddress"]) {
+                Some(at) => at.wrapping_sub(base).wrapping_add(image_base),
+                None => continue,
+            };
+            let message = result["message"]["text"].as_str().unwrap_or_default();
+            let text = |key: &str| properties[key].as_str().unwrap_or(message).to_string();
+            match result["ruleId"].as_str().unwrap_or_default() {
+                "FUNCTIONS" => {
+                    notes.names.insert(at, text("name"));
+                }
+                "COMMENTS" => add_comment(&mut notes, at, &text("comment")),
+                "BOOKMARKS" => {
+                    notes.bookmarks.insert(at, text("comment"));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(notes)
+}
+
+/// The arguments of a call like `set_name(0x401000, "main");`, as strings and numbers.  None if
+/// `line` isn't a call to `name`.
+fn idc_call(line: &str, name: &str) -> Option<Vec<String>> {
+    let rest = line.trim_start().strip_prefix(name)?.trim_start();
+    let mut chars = rest.strip_prefix('(')?.chars();
+    let mut args = vec![];
+    let mut current = String::new();
+    loop {
+        match chars.next()? {
+            '"' => loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        'n' => current.push('\n'),
+                        't' => current.push('\t'),
+                        c => current.push(c),
+                    },
+                    c => current.push(c),
+                }
+            },
+            ',' => args.push(std::mem::take(&mut current).trim().to_string()),
+            ')' => {
+                args.push(current.trim().to_string());
+                return Some(args);
+            }
+            c => current.push(c),
+        }
+    }
+}
+
+/// names, comments and bookmarks an IDC script sets, with both the IDA 7 and older names
+fn read_idc(text: &str) -> Notes {
+    let mut notes = Notes::default();
+    let address = |args: &[String]| util::parse_address(args.first()?);
+    for line in text.lines() {
+        for name in &["set_name", "MakeNameEx", "MakeName"] {
+            if let Some(args) = idc_call(line, name) {
+                if let (Some(address), Some(name)) = (address(&args), args.get(1)) {
+                    notes.names.insert(address, name.clone());
+                }
+            }
+        }
+        for name in &["set_cmt", "MakeComm", "MakeRptCmt"] {
+            if let Some(args) = idc_call(line, name) {
+                if let (Some(address), Some(text)) = (address(&args), args.get(1)) {
+                    add_comment(&mut notes, address, text);
+                }
+            }
+        }
+        // put_bookmark(ea, lnnum, x, y, slot, comment)
+        for name in &["put_bookmark", "MarkPosition"] {
+            if let Some(args) = idc_call(line, name) {
+                if let Some(address) = address(&args) {
+                    let description = args.get(5).cloned().unwrap_or_default();
+                    notes.bookmarks.insert(address, description);
+                }
+            }
+        }
+    }
+    notes
+}
+
+/// `address name` lines, or `name address [f|l]` as Ghidra's ImportSymbolsScript takes them
+fn read_names(text: &str) -> Notes {
+    let mut notes = Notes::default();
+    for line in text.lines() {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let (address, name) = match words.as_slice() {
+            [name, address, ..] if address.starts_with("0x") => (hex_address(address), name),
+            [address, name, ..] => (hex_address(address), name),
+            _ => continue,
+        };
+        if let Some(address) = address {
+            notes.names.insert(address, name.to_string());
+        }
+    }
+    notes
+}
+
+/// The `rc.r2` of the r2 project `name`, which can also be the path of the project's directory
+/// or of the file itself.  Projects are kept in `$XDG_DATA_HOME/radare2/projects` (or
+/// `~/.local/share/...`) since r2 5 and in `~/.config/radare2/projects` before that.
+pub fn r2_project(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    if path.is_dir() {
+        return Ok(path.join("rc.r2"));
+    }
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| Some(home.as_ref()?.join(".local").join("share")));
+    let old = home.map(|x| x.join(".config"));
+    [data, old]
+        .iter()
+        .flatten()
+        .map(|x| x.join("radare2").join("projects").join(name).join("rc.r2"))
+        .find(|x| x.is_file())
+        .ok_or_else(|| format!("no r2 project called {}", name).into())
+}
+
+/// standard base64, None if it isn't or doesn't decode to UTF-8
+fn decode_base64(text: &str) -> Option<String> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut out = vec![];
+    for c in text.trim_end_matches('=').bytes() {
+        bits = bits << 6 | ALPHABET.iter().position(|x| *x == c)? as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// The function names, comments and flags the commands in an r2 project's `rc.r2` set.  Flags
+/// made by hand, rather than the ones r2 makes for symbols, strings and so on, become bookmarks
+/// described by their name.
+fn read_r2_project(text: &str) -> Notes {
+    let mut notes = Notes::default();
+    let mut flagspace = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let line = line
+            .strip_prefix('"')
+            .and_then(|x| x.strip_suffix('"'))
+            .unwrap_or(line);
+        // `command @ address` runs it at an address
+        let (command, at) = match line.rsplit_once(" @ ") {
+            Some((command, at)) => (command, util::parse_address(at)),
+            None => (line, None),
+        };
+        let words = command.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["fs"] | ["fs", "*"] => flagspace.clear(),
+            ["fs", space] => flagspace = space.to_string(),
+            // af+ address name [type] [diff]
+            ["af+", address, name, ..] => {
+                if let Some(address) = util::parse_address(address) {
+                    notes.names.insert(address, name.to_string());
+                }
+            }
+            ["afn", name, rest @ ..] => {
+                if let Some(address) = rest.first().and_then(|x| util::parse_address(x)).or(at) {
+                    notes.names.insert(address, name.to_string());
+                }
+            }
+            [name @ "CC", ..] | [name @ "CCu", ..] => {
+                let text = command[name.len()..].trim();
+                let text = match text.strip_prefix("base64:") {
+                    Some(encoded) => decode_base64(encoded),
+                    None => Some(text.to_string()),
+                };
+                if let (Some(address), Some(text)) = (at, text) {
+                    add_comment(&mut notes, address, &text);
+                }
+            }
+            // f name [size] [address]
+            ["f", name, rest @ ..] if !R2_FLAGSPACES.contains(&flagspace.as_str()) => {
+                if let Some(address) = rest.get(1).and_then(|x| util::parse_address(x)).or(at) {
+                    notes.bookmarks.insert(address, name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    notes
+}
+
+fn write_ghidra_xml(notes: &Notes, image_base: u64) -> String {
+    let mut xml = String::new();
+    let _ = writeln!(xml, "<?xml version=\"1.0\" standalone=\"yes\"?>");
+    let _ = writeln!(xml, "<PROGRAM IMAGE_BASE=\"{:08x}\">", image_base);
+    xml.push_str("    <FUNCTIONS>\n");
+    for (address, name) in &notes.names {
+        let _ = writeln!(
+            xml,
+            "        <FUNCTION ENTRY_POINT=\"{:08x}\" NAME=\"{}\" />",
+            address,
+            escape_xml(name)
+        );
+    }
+    xml.push_str("    </FUNCTIONS>\n    <COMMENTS>\n");
+    for (address, text) in &notes.comments {
+        let _ = writeln!(
+            xml,
+            "        <COMMENT ADDRESS=\"{:08x}\" TYPE=\"end-of-line\">{}</COMMENT>",
+            address,
+            escape_xml(text)
+        );
+    }
+    xml.push_str("    </COMMENTS>\n    <BOOKMARKS>\n");
+    for (address, description) in &notes.bookmarks {
+        let _ = writeln!(
+            xml,
+            "        <BOOKMARK ADDRESS=\"{:08x}\" TYPE=\"Note\" CATEGORY=\"transmogrify\" \
+             DESCRIPTION=\"{}\" />",
+            address,
+            escape_xml(description)
+        );
+    }
+    xml.push_str("    </BOOKMARKS>\n</PROGRAM>\n");
+    xml
+}
+
+fn write_idc(notes: &Notes) -> String {
+    let quote = |text: &str| format!("{:?}", text);
+    let mut idc = "#include <idc.idc>\n\nstatic main() {\n".to_string();
+    for (address, name) in &notes.names {
+        let _ = writeln!(idc, "    set_name({:#x}, {});", address, quote(name));
+    }
+    for (address, text) in &notes.comments {
+        let _ = writeln!(idc, "    set_cmt({:#x}, {}, 0);", address, quote(text));
+    }
+    for (slot, (address, description)) in notes.bookmarks.iter().enumerate() {
+        let _ = writeln!(
+            idc,
+            "    put_bookmark({:#x}, 0, 0, 0, {}, {});",
+            address,
+            slot + 1,
+            quote(description)
+        );
+    }
+    idc.push_str("}\n");
+    idc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ghidra_xml() {
+        let xml = r#"<?xml version="1.0" standalone="yes"?>
+<PROGRAM NAME="a.out" IMAGE_BASE="00100000">
+    <FUNCTIONS>
+        <FUNCTION ENTRY_POINT="ram:00101139" NAME="check_key" LIBRARY_FUNCTION="n">
+        </FUNCTION>
+    </FUNCTIONS>
+    <COMMENTS>
+        <COMMENT ADDRESS="00101140" TYPE="pre">a &lt; b</COMMENT>
+        <COMMENT ADDRESS="00101140" TYPE="end-of-line">bail</COMMENT>
+    </COMMENTS>
+    <BOOKMARKS>
+        <BOOKMARK ADDRESS="00101145" TYPE="Note" CATEGORY="" DESCRIPTION="patch me" />
+    </BOOKMARKS>
+</PROGRAM>"#;
+        let notes = read(Format::GhidraXml, xml, 0).unwrap();
+        assert_eq!(
+            Some("check_key"),
+            notes.names.get(&0x1139).map(|x| x.as_str())
+        );
+        assert_eq!(
+            Some("a < b; bail"),
+            notes.comments.get(&0x1140).map(|x| x.as_str())
+        );
+        assert_eq!(
+            Some("patch me"),
+            notes.bookmarks.get(&0x1145).map(|x| x.as_str())
+        );
+        let written = write(Format::GhidraXml, &notes, 0).unwrap();
+        assert_eq!(notes, read(Format::GhidraXml, &written, 0).unwrap());
+    }
+
+    #[test]
+    fn test_r2_project() {
+        let rc = "\
+e asm.arch = x86
+fs symbols
+f sym.main 1 0x00001139
+fs *
+f patch_here 1 0x00001145
+\"af+ 0x1139 check_key f n\"
+afn helper 0x1160
+\"CCu base64:cmV0dXJucyAw @ 0x1140\"
+CC plain @ 0x1141
+";
+        let notes = read(Format::R2Project, rc, 0).unwrap();
+        assert_eq!(
+            Some("check_key"),
+            notes.names.get(&0x1139).map(|x| x.as_str())
+        );
+        assert_eq!(Some("helper"), notes.names.get(&0x1160).map(|x| x.as_str()));
+        assert_eq!(
+            Some("returns 0"),
+            notes.comments.get(&0x1140).map(|x| x.as_str())
+        );
+        assert_eq!(
+            Some("plain"),
+            notes.comments.get(&0x1141).map(|x| x.as_str())
+        );
+        assert_eq!(1, notes.bookmarks.len());
+        assert_eq!(
+            Some("patch_here"),
+            notes.bookmarks.get(&0x1145).map(|x| x.as_str())
+        );
+    }
+
+    #[test]
+    fn test_idc() {
+        let idc = "static main() {\n    MakeName(0x401000, \"main\");\n    set_cmt(0x401004, \
+                   \"say \\\"hi\\\"\", 0);\n    put_bookmark(0x401008, 0, 0, 0, 1, \"here\");\n}";
+        let notes = read(Format::Idc, idc, 0).unwrap();
+        assert_eq!(Some("main"), notes.names.get(&0x401000).map(|x| x.as_str()));
+        assert_eq!(
+            Some("say \"hi\""),
+            notes.comments.get(&0x401004).map(|x| x.as_str())
+        );
+        assert_eq!(
+            Some("here"),
+            notes.bookmarks.get(&0x401008).map(|x| x.as_str())
+        );
+        let written = write(Format::Idc, &notes, 0).unwrap();
+        assert_eq!(notes, read(Format::Idc, &written, 0).unwrap());
+
+        let names = read(Format::Names, "401000 main\ncafe 0x401010 f\n", 0).unwrap();
+        assert_eq!(Some("cafe"), names.names.get(&0x401010).map(|x| x.as_str()));
+        assert_eq!(2, names.names.len());
+    }
+}
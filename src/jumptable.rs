@@ -0,0 +1,346 @@
+use crate::util::{self, from_hexstring, InstructionRow, Radix};
+use std::convert::TryInto;
+
+/// how many entries are read from a table with no bounds check in front of it
+const MAX_ENTRIES: usize = 512;
+/// how far back from the jmp the instructions computing its target are looked for
+const LOOKBACK: usize = 12;
+
+/// A switch statement compiled to an indirect jmp through a table of addresses.  Everything is
+/// in file offsets, like the rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JumpTable {
+    /// the indirect jmp
+    pub jump: u64,
+    pub table: u64,
+    /// 4 or 8
+    pub entry_size: u64,
+    /// where each case goes, in table order
+    pub targets: Vec<u64>,
+}
+
+impl JumpTable {
+    /// bytes the table takes
+    pub fn size(&self) -> u64 {
+        self.entry_size * self.targets.len() as u64
+    }
+
+    /// the case whose entry is at `offset`
+    pub fn entry_at(&self, offset: u64) -> Option<usize> {
+        if offset < self.table || offset >= self.table + self.size() {
+            return None;
+        }
+        Some(((offset - self.table) / self.entry_size) as usize)
+    }
+
+    /// the cases which go to `offset`
+    pub fn cases_to(&self, offset: u64) -> Vec<usize> {
+        (0..self.targets.len())
+            .filter(|&i| self.targets[i] == offset)
+            .collect()
+    }
+}
+
+/// How entries are stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Entry {
+    /// a signed 32 bit offset from the start of the table, as position independent code does
+    Relative,
+    /// an address of `size` bytes
+    Absolute(u64),
+}
+
+/// The file loaded as (file offset, size, virtual address) segments, see
+/// `binary::segments`.  A file without any is taken to be loaded as it is.
+pub struct Segments<'a>(pub &'a [(u64, u64, u64)]);
+
+impl Segments<'_> {
+    fn to_va(&self, offset: u64) -> Option<u64> {
+        if self.0.is_empty() {
+            return Some(offset);
+        }
+        self.0
+            .iter()
+            .find(|(start, size, _)| (*start..start + size).contains(&offset))
+            .map(|(start, _, va)| offset - start + va)
+    }
+
+    fn to_offset(&self, va: u64) -> Option<u64> {
+        if self.0.is_empty() {
+            return Some(va);
+        }
+        self.0
+            .iter()
+            .find(|(_, size, start)| (*start..start + size).contains(&va))
+            .map(|(offset, _, start)| va - start + offset)
+    }
+}
+
+/// `[base + index*scale + displacement]`
+#[derive(Debug, Default, PartialEq)]
+struct Memory {
+    base: Option<String>,
+    index: Option<(String, u64)>,
+    displacement: i64,
+}
+
+fn parse_memory(operand: &str) -> Option<Memory> {
+    let inside = &operand[operand.find('[')? + 1..operand.rfind(']')?];
+    let mut memory = Memory::default();
+    let mut sign = 1;
+    for term in inside.split_whitespace() {
+        match term {
+            "+" => sign = 1,
+            "-" => sign = -1,
+            term => {
+                if let Some(value) = util::parse_address(term) {
+                    memory.displacement += sign * value as i64;
+                } else if let Some(star) = term.find('*') {
+                    let scale = util::parse_address(&term[star + 1..])?;
+                    memory.index = Some((term[..star].to_string(), scale));
+                } else if memory.base.is_none() {
+                    memory.base = Some(term.to_string());
+                } else {
+                    memory.index = Some((term.to_string(), 1));
+                }
+            }
+        }
+    }
+    Some(memory)
+}
+
+/// a row's mnemonic and operands, with immediates in hex even if an edit typed them otherwise
+fn split(row: &InstructionRow) -> (String, Vec<String>) {
+    let text = util::format_immediates(&row.text, Radix::Hex);
+    let mut words = text.splitn(2, ' ');
+    let mnemonic = words.next().unwrap_or_default().to_string();
+    let operands = words
+        .next()
+        .unwrap_or_default()
+        .split(", ")
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect();
+    (mnemonic, operands)
+}
+
+fn end_of(row: &InstructionRow) -> u64 {
+    row.offset + from_hexstring(&row.bytes).len() as u64
+}
+
+/// the table address and entry layout behind the jmp at `rows[jump]`
+fn locate(rows: &[InstructionRow], jump: usize, segments: &Segments) -> Option<(u64, Entry)> {
+    let (_, operands) = split(&rows[jump]);
+    let operand = operands.first()?;
+    let before = || rows[jump.saturating_sub(LOOKBACK)..jump].iter().rev();
+
+    // jmp qword ptr [rax*8 + 0x4008f0]
+    let absolute = |operand: &str| {
+        let memory = parse_memory(operand)?;
+        match (&memory.base, &memory.index) {
+            (None, Some((_, scale))) if *scale == 4 || *scale == 8 => {
+                Some((memory.displacement as u64, Entry::Absolute(*scale)))
+            }
+            _ => None,
+        }
+    };
+    if operand.contains('[') {
+        return absolute(operand);
+    }
+
+    // mov rax, qword ptr [rax*8 + 0x4008f0] ; jmp rax
+    // or
+    // lea rdx, [rip + 0xe5c] ; movsxd rax, dword ptr [rdx + rax*4] ; add rax, rdx ; jmp rax
+    let target = operand.as_str();
+    let (index, definition) = before().enumerate().find_map(|(i, row)| {
+        let (mnemonic, operands) = split(row);
+        match operands.as_slice() {
+            [register, source] if register == target && mnemonic != "cmp" => {
+                Some((jump - 1 - i, (mnemonic, source.clone())))
+            }
+            _ => None,
+        }
+    })?;
+    match definition.0.as_str() {
+        "mov" => absolute(&definition.1),
+        "add" => {
+            let base = definition.1;
+            let load = rows[..index].iter().rev().take(LOOKBACK).find_map(|row| {
+                let (mnemonic, operands) = split(row);
+                match operands.as_slice() {
+                    [register, source] if register == target && mnemonic == "movsxd" => {
+                        Some(source.clone())
+                    }
+                    _ => None,
+                }
+            })?;
+            let memory = parse_memory(&load)?;
+            if !matches!(&memory.index, Some((_, 4))) {
+                return None;
+            }
+            let table = rows[..index].iter().rev().take(LOOKBACK).find_map(|row| {
+                let (mnemonic, operands) = split(row);
+                match operands.as_slice() {
+                    [register, source] if *register == base && mnemonic == "lea" => {
+                        let memory = parse_memory(source)?;
+                        if memory.base.as_deref() != Some("rip") || memory.index.is_some() {
+                            return None;
+                        }
+                        let next = segments.to_va(end_of(row))?;
+                        Some(next.wrapping_add(memory.displacement as u64))
+                    }
+                    _ => None,
+                }
+            })?;
+            Some((table, Entry::Relative))
+        }
+        _ => None,
+    }
+}
+
+/// the number of cases a `cmp x, n ; ja default` in front of the jmp allows, if there is one
+fn bound(rows: &[InstructionRow], jump: usize) -> Option<usize> {
+    let start = jump.saturating_sub(LOOKBACK);
+    (start..jump).rev().find_map(|i| {
+        let (mnemonic, operands) = split(&rows[i]);
+        if mnemonic != "cmp" {
+            return None;
+        }
+        let limit = util::parse_address(operands.get(1)?)? as usize;
+        rows[i + 1..jump]
+            .iter()
+            .take(2)
+            .find_map(|row| match split(row).0.as_str() {
+                "ja" => Some(limit + 1),
+                "jae" => Some(limit),
+                _ => None,
+            })
+    })
+}
+
+/// Find the jump tables of the function in `rows`, which spans the file offsets `start..end` of
+/// `data`.  Rows have to be in Intel syntax.  Without a bounds check entries are read until one
+/// points outside the function.
+pub fn detect(
+    rows: &[InstructionRow],
+    data: &[u8],
+    segments: &Segments,
+    (start, end): (u64, u64),
+) -> Vec<JumpTable> {
+    let mut tables = vec![];
+    for (jump, row) in rows.iter().enumerate() {
+        if split(row).0 != "jmp" || row.data {
+            continue;
+        }
+        let (table_va, entry) = match locate(rows, jump, segments) {
+            Some(found) => found,
+            None => continue,
+        };
+        let table = match segments.to_offset(table_va) {
+            Some(table) => table,
+            None => continue,
+        };
+        let bound = bound(rows, jump);
+        let entry_size = match entry {
+            Entry::Relative => 4,
+            Entry::Absolute(size) => size,
+        };
+        let mut targets = vec![];
+        for i in 0..bound.unwrap_or(MAX_ENTRIES).min(MAX_ENTRIES) {
+            let at = (table + i as u64 * entry_size) as usize;
+            let bytes = match data.get(at..at + entry_size as usize) {
+                Some(bytes) => bytes,
+                None => break,
+            };
+            let target_va = match entry {
+                Entry::Relative => {
+                    let offset = i32::from_le_bytes(bytes.try_into().unwrap_or_default());
+                    table_va.wrapping_add(offset as i64 as u64)
+                }
+                Entry::Absolute(4) => {
+                    u32::from_le_bytes(bytes.try_into().unwrap_or_default()) as u64
+                }
+                Entry::Absolute(_) => u64::from_le_bytes(bytes.try_into().unwrap_or_default()),
+            };
+            let target = match segments.to_offset(target_va) {
+                Some(target) => target,
+                None => break,
+            };
+            if bound.is_none() && !(start..end).contains(&target) {
+                break;
+            }
+            targets.push(target);
+        }
+        if !targets.is_empty() {
+            tables.push(JumpTable {
+                jump: row.offset,
+                table,
+                entry_size,
+                targets,
+            });
+        }
+    }
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(offset: u64, len: usize, text: &str) -> InstructionRow {
+        InstructionRow::new(offset, &vec![0x90; len], text.to_string())
+    }
+
+    #[test]
+    fn test_relative() {
+        // the table sits right after the function, its entries are relative to its start
+        let rows = vec![
+            row(0x0, 3, "cmp edi, 2"),
+            row(0x3, 2, "ja 0x20"),
+            row(0x5, 7, "lea rdx, [rip + 0x17]"),
+            row(0xc, 4, "movsxd rax, dword ptr [rdx + rdi*4]"),
+            row(0x10, 3, "add rax, rdx"),
+            row(0x13, 2, "jmp rax"),
+            row(0x15, 1, "ret"),
+        ];
+        let mut data = vec![0u8; 0x23];
+        for (i, target) in [0x15i32, 0x15, 0x0].iter().enumerate() {
+            let entry = (target - 0x23).to_le_bytes();
+            data.extend(&entry);
+            assert_eq!(0x23 + 4 * (i + 1), data.len());
+        }
+        let tables = detect(&rows, &data, &Segments(&[]), (0, 0x16));
+        assert_eq!(1, tables.len());
+        let table = &tables[0];
+        assert_eq!((0x13, 0x23, 4), (table.jump, table.table, table.entry_size));
+        assert_eq!(vec![0x15, 0x15, 0x0], table.targets);
+        assert_eq!(Some(1), table.entry_at(0x27));
+        assert_eq!(vec![0, 1], table.cases_to(0x15));
+    }
+
+    #[test]
+    fn test_absolute() {
+        // loaded at 0x400000, no bounds check so reading stops at the first entry outside
+        let segments = [(0, 0x100, 0x400000)];
+        let rows = vec![
+            row(0x10, 8, "jmp qword ptr [rax*8 + 0x400020]"),
+            row(0x18, 1, "ret"),
+        ];
+        let mut data = vec![0u8; 0x20];
+        data.extend(&0x400018u64.to_le_bytes());
+        data.extend(&0x400010u64.to_le_bytes());
+        data.extend(&0x400090u64.to_le_bytes());
+        let tables = detect(&rows, &data, &Segments(&segments), (0x10, 0x19));
+        assert_eq!(vec![0x18, 0x10], tables[0].targets);
+        assert_eq!(0x20, tables[0].table);
+
+        assert_eq!(
+            Some(Memory {
+                base: Some("rip".to_string()),
+                index: None,
+                displacement: -0x10,
+            }),
+            parse_memory("qword ptr [rip - 0x10]")
+        );
+    }
+}
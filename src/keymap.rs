@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use termion::event::Key;
+
+/// Which keys do what in Viewing mode, the `keymap` key of the config.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Keymap {
+    /// a/s/d pick the column, e edits, j/k move as well as the arrows
+    #[default]
+    Default,
+    /// hjkl move, i/a edit, dd nops, yy and p copy instructions and Tab moves between columns
+    /// in place of a/s/d.  The default keys h, l, i and p move to their capitals.
+    Vim,
+}
+
+/// What a key does once the keymap has been applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// whatever the key does in the default keymap
+    Key(Key),
+    /// start editing with the cursor after the last character
+    Append,
+    /// nop the current instruction and those after it
+    Delete,
+    /// copy the current instruction and those after it
+    Yank,
+    /// overwrite instructions from the current one with what was yanked
+    Paste,
+    /// move to the next (or with false, the previous) column
+    Column(bool),
+}
+
+impl Keymap {
+    /// Translate a key typed in Viewing mode.  None means it's the first of two (`dd`, `yy`) and
+    /// `pending` holds it until the next key.
+    pub fn action(self, pending: &mut Option<char>, key: Key) -> Option<Action> {
+        let first = pending.take();
+        let action = match (self, first, key) {
+            (Keymap::Vim, None, Key::Char(c @ 'd')) | (Keymap::Vim, None, Key::Char(c @ 'y')) => {
+                *pending = Some(c);
+                return None;
+            }
+            (Keymap::Vim, Some('d'), Key::Char('d')) => Action::Delete,
+            (Keymap::Vim, Some('y'), Key::Char('y')) => Action::Yank,
+            // anything else after a d or y cancels it, like Esc
+            (Keymap::Vim, Some(_), _) => return None,
+            (Keymap::Vim, None, key) => match key {
+                Key::Char('h') => Action::Key(Key::Left),
+                Key::Char('l') => Action::Key(Key::Right),
+                Key::Char('i') => Action::Key(Key::Char('e')),
+                Key::Char('a') => Action::Append,
+                Key::Char('p') => Action::Paste,
+                Key::Char('\t') => Action::Column(true),
+                Key::BackTab => Action::Column(false),
+                Key::Char('H') => Action::Key(Key::Char('h')),
+                Key::Char('L') => Action::Key(Key::Char('l')),
+                Key::Char('I') => Action::Key(Key::Char('i')),
+                Key::Char('P') => Action::Key(Key::Char('p')),
+                key => Action::Key(key),
+            },
+            (Keymap::Default, _, key) => Action::Key(key),
+        };
+        Some(match action {
+            Action::Key(Key::Char('j')) => Action::Key(Key::Down),
+            Action::Key(Key::Char('k')) => Action::Key(Key::Up),
+            action => action,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action() {
+        let mut pending = None;
+        let default = Keymap::Default;
+        assert_eq!(
+            Some(Action::Key(Key::Char('d'))),
+            default.action(&mut pending, Key::Char('d'))
+        );
+        assert_eq!(
+            Some(Action::Key(Key::Down)),
+            default.action(&mut pending, Key::Char('j'))
+        );
+
+        let vim = Keymap::Vim;
+        assert_eq!(
+            Some(Action::Key(Key::Left)),
+            vim.action(&mut pending, Key::Char('h'))
+        );
+        assert_eq!(None, vim.action(&mut pending, Key::Char('d')));
+        assert_eq!(
+            Some(Action::Delete),
+            vim.action(&mut pending, Key::Char('d'))
+        );
+        assert_eq!(None, vim.action(&mut pending, Key::Char('y')));
+        assert_eq!(None, vim.action(&mut pending, Key::Esc));
+        assert_eq!(None, pending);
+        assert_eq!(
+            Some(Action::Key(Key::Char('h'))),
+            vim.action(&mut pending, Key::Char('H'))
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use crate::literal;
+use crate::scalar;
+use crate::util;
+use std::fmt;
+
+/// What a field holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scalar {
+    Unsigned(usize),
+    Signed(usize),
+    Float(usize),
+    /// a `char`, shown as one and arrays of them as a string
+    Char,
+    Pointer(usize),
+}
+
+impl Scalar {
+    /// The C (or Rust) type `name`, `unsigned` if it was written so, on a target whose pointers
+    /// are `pointer` bytes.
+    fn parse(name: &str, unsigned: bool, pointer: usize) -> Option<Scalar> {
+        let width = match name {
+            "char" if !unsigned => return Some(Scalar::Char),
+            "f32" | "float" => return Some(Scalar::Float(4)),
+            "f64" | "double" => return Some(Scalar::Float(8)),
+            "char" | "_Bool" | "bool" | "int8_t" | "uint8_t" | "i8" | "u8" => 1,
+            "short" | "int16_t" | "uint16_t" | "i16" | "u16" => 2,
+            "int" | "int32_t" | "uint32_t" | "i32" | "u32" => 4,
+            "long long" | "int64_t" | "uint64_t" | "i64" | "u64" => 8,
+            "long" | "size_t" | "ssize_t" | "intptr_t" | "uintptr_t" => pointer,
+            _ => return None,
+        };
+        let unsigned =
+            unsigned || name.starts_with('u') || ["_Bool", "bool", "size_t"].contains(&name);
+        Some(if unsigned {
+            Scalar::Unsigned(width)
+        } else {
+            Scalar::Signed(width)
+        })
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Scalar::Char => 1,
+            Scalar::Unsigned(x) | Scalar::Signed(x) | Scalar::Float(x) | Scalar::Pointer(x) => x,
+        }
+    }
+
+    /// the value of the first `size` bytes of `bytes`, little endian
+    fn format(self, bytes: &[u8]) -> String {
+        let mut word = [0; 8];
+        word[..bytes.len()].copy_from_slice(bytes);
+        let value = u64::from_le_bytes(word);
+        let bits = 8 * bytes.len() as u32;
+        match self {
+            Scalar::Char => format!("'{}'", literal::escape(bytes)),
+            Scalar::Unsigned(_) => value.to_string(),
+            Scalar::Signed(_) => (((value << (64 - bits)) as i64) >> (64 - bits)).to_string(),
+            Scalar::Float(4) => f32::from_bits(value as u32).to_string(),
+            Scalar::Float(_) => f64::from_bits(value).to_string(),
+            Scalar::Pointer(_) => format!("{:#x}", value),
+        }
+    }
+
+    /// the bytes of `value` typed for a field of this type, see `scalar::encode`
+    fn encode(self, value: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Scalar::Char => match literal::unescape(value)?.as_slice() {
+                [byte] => Ok(vec![*byte]),
+                _ => scalar::encode("i8", value),
+            },
+            Scalar::Unsigned(x) | Scalar::Pointer(x) => {
+                scalar::encode(&format!("u{}", 8 * x), value)
+            }
+            Scalar::Signed(x) => scalar::encode(&format!("i{}", 8 * x), value),
+            Scalar::Float(x) => scalar::encode(&format!("f{}", 8 * x), value),
+        }
+    }
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scalar::Unsigned(x) => write!(f, "u{}", 8 * x),
+            Scalar::Signed(x) => write!(f, "i{}", 8 * x),
+            Scalar::Float(x) => write!(f, "f{}", 8 * x),
+            Scalar::Char => f.write_str("char"),
+            Scalar::Pointer(_) => f.write_str("ptr"),
+        }
+    }
+}
+
+/// A field of a struct, a struct inside it is flattened into fields named `outer.inner`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// bytes from the start of the struct
+    pub offset: usize,
+    pub scalar: Scalar,
+    /// how many there are, for arrays
+    pub count: Option<usize>,
+}
+
+impl Field {
+    pub fn size(&self) -> usize {
+        self.scalar.size() * self.count.unwrap_or(1)
+    }
+}
+
+/// The layout of a C struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub size: usize,
+    /// alignment of the whole, its largest field's unless packed
+    align: usize,
+}
+
+impl Struct {
+    /// A line for each field: where it is, its name and type, and its value in `bytes`, which
+    /// start where the struct does.  Fields past the end of `bytes` have no value.
+    pub fn describe(&self, bytes: &[u8]) -> Vec<String> {
+        self.fields
+            .iter()
+            .map(|field| {
+                let kind = match field.count {
+                    Some(count) => format!("{}[{}]", field.scalar, count),
+                    None => field.scalar.to_string(),
+                };
+                let value = match bytes.get(field.offset..field.offset + field.size()) {
+                    None => "?".to_string(),
+                    Some(bytes) if field.scalar == Scalar::Char && field.count.is_some() => {
+                        let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+                        format!("\"{}\"", literal::escape(&bytes[..end]))
+                    }
+                    Some(bytes) => {
+                        let values = bytes
+                            .chunks(field.scalar.size())
+                            .map(|x| field.scalar.format(x))
+                            .collect::<Vec<_>>();
+                        match field.count {
+                            Some(_) => format!("[{}]", values.join(", ")),
+                            None => values.join(""),
+                        }
+                    }
+                };
+                format!("+{:#06x} {} {} = {}", field.offset, field.name, kind, value)
+            })
+            .collect()
+    }
+
+    /// The offset and bytes to write to set the field `name` to `value`.  Elements of arrays are
+    /// named `name[index]`, a whole array of chars takes a string, NUL padded.
+    pub fn encode(&self, name: &str, value: &str) -> Result<(usize, Vec<u8>), String> {
+        let (name, index) = match name.strip_suffix(']').and_then(|x| x.split_once('[')) {
+            Some((name, index)) => (
+                name,
+                Some(
+                    index
+                        .parse::<usize>()
+                        .map_err(|_| format!("{} isn't an index", index))?,
+                ),
+            ),
+            None => (name, None),
+        };
+        let field = self
+            .fields
+            .iter()
+            .find(|x| x.name == name)
+            .ok_or_else(|| format!("{} has no field {}", self.name, name))?;
+        match (field.count, index) {
+            (Some(count), Some(index)) if index < count => {
+                let offset = field.offset + index * field.scalar.size();
+                Ok((offset, field.scalar.encode(value)?))
+            }
+            (Some(count), Some(index)) => Err(format!(
+                "{} is past the end of {}[{}]",
+                index, field.name, count
+            )),
+            (Some(count), None) if field.scalar == Scalar::Char => {
+                let mut bytes = literal::unescape(value)?;
+                if bytes.len() > count {
+                    return Err(format!("{} only has room for {} chars", field.name, count));
+                }
+                bytes.resize(count, 0);
+                Ok((field.offset, bytes))
+            }
+            (Some(_), None) => Err(format!("{} is an array, give an index", field.name)),
+            (None, Some(_)) => Err(format!("{} isn't an array", field.name)),
+            (None, None) => Ok((field.offset, field.scalar.encode(value)?)),
+        }
+    }
+}
+
+/// `source` without comments or preprocessor lines
+fn strip(source: &str) -> String {
+    let mut out = String::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = &after[after.find('\n').unwrap_or(after.len())..];
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = &after[after.find("*/").map_or(after.len(), |x| x + 2)..];
+            out.push(' ');
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out.lines()
+        .filter(|x| !x.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A declarator split into the type written before it (without any `*`), whether it's a
+/// pointer, its name and the length of the array it declares, if it does.
+fn declarator(text: &str) -> (String, bool, &str, Option<&str>) {
+    let (before, count) = match text.split_once('[') {
+        Some((before, count)) => (before, Some(count.trim().trim_end_matches(']').trim())),
+        None => (text, None),
+    };
+    let before = before.trim_end();
+    let at = before
+        .rfind(|x: char| !(x.is_alphanumeric() || x == '_'))
+        .map_or(0, |x| x + 1);
+    let kind = &before[..at];
+    (
+        kind.replace('*', " "),
+        kind.contains('*'),
+        &before[at..],
+        count,
+    )
+}
+
+/// One field declaration, `type name`, `type *name`, `type name[N]` or several names after one
+/// type separated by commas, laid out after `fields`, which end at `end`.
+fn declare(
+    declaration: &str,
+    known: &[Struct],
+    pointer: usize,
+    packed: bool,
+    fields: &mut Vec<Field>,
+    end: &mut usize,
+    align: &mut usize,
+) -> Result<(), String> {
+    if declaration.contains(':') {
+        return Err(format!(
+            "{} is a bit field, which isn't supported",
+            declaration.trim()
+        ));
+    }
+    let mut declarators = declaration.split(',').map(declarator).peekable();
+    let kind = match declarators.peek() {
+        Some((kind, ..)) => kind.clone(),
+        None => return Ok(()),
+    };
+    let mut words = kind
+        .split_whitespace()
+        .filter(|x| !["const", "volatile", "signed", "struct"].contains(x))
+        .collect::<Vec<_>>();
+    let unsigned = words.contains(&"unsigned");
+    words.retain(|x| *x != "unsigned");
+    // `short int`, `long int` and `long long int` are `short`, `long` and `long long`
+    let kind = match words.join(" ").as_str() {
+        "" => "int".to_string(),
+        x if x.starts_with("short") || x.starts_with("long") => {
+            x.trim_end_matches(" int").to_string()
+        }
+        x => x.to_string(),
+    };
+    let nested = known.iter().find(|x| x.name == kind);
+    for (_, is_pointer, name, count) in declarators {
+        if name.is_empty() {
+            return Err(format!("{} doesn't name a field", declaration.trim()));
+        }
+        let count = match count {
+            Some(count) => Some(
+                util::parse_address(count)
+                    .ok_or_else(|| format!("{} isn't an array length", count))?
+                    as usize,
+            ),
+            None => None,
+        };
+        let nested = nested.filter(|_| !is_pointer);
+        // a struct's fields are copied in under the field's name
+        let (members, size, member_align) = match nested {
+            _ if is_pointer => (
+                vec![(String::new(), 0, Scalar::Pointer(pointer), None)],
+                pointer,
+                pointer,
+            ),
+            Some(nested) => (
+                nested
+                    .fields
+                    .iter()
+                    .map(|x| (format!(".{}", x.name), x.offset, x.scalar, x.count))
+                    .collect(),
+                nested.size,
+                nested.align,
+            ),
+            None => {
+                let scalar = Scalar::parse(&kind, unsigned, pointer)
+                    .ok_or_else(|| format!("{} isn't a type", kind))?;
+                (
+                    vec![(String::new(), 0, scalar, None)],
+                    scalar.size(),
+                    scalar.size(),
+                )
+            }
+        };
+        let member_align = if packed { 1 } else { member_align };
+        *end = round_up(*end, member_align);
+        *align = (*align).max(member_align);
+        // arrays of structs are flattened an element at a time
+        let elements = match (nested, count) {
+            (Some(_), Some(count)) => (0..count).map(Some).collect::<Vec<_>>(),
+            _ => vec![None],
+        };
+        for element in elements {
+            let prefix = match element {
+                Some(index) => format!("{}[{}]", name, index),
+                None => name.to_string(),
+            };
+            for (suffix, offset, scalar, inner) in &members {
+                fields.push(Field {
+                    name: format!("{}{}", prefix, suffix),
+                    offset: *end + element.map_or(0, |x| x * size) + offset,
+                    scalar: *scalar,
+                    count: if nested.is_some() { *inner } else { count },
+                });
+            }
+        }
+        *end += size * count.unwrap_or(1);
+    }
+    Ok(())
+}
+
+/// The structs read from a header, see `parse`.
+#[derive(Debug)]
+pub struct Header {
+    pub structs: Vec<Struct>,
+    /// the structs which couldn't be laid out, by name with why
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Every struct defined in `source`, C as found in a header: `struct name { ... };` and
+/// `typedef struct [tag] { ... } name;`, laid out as a compiler for a target with `pointer`
+/// byte pointers would unless marked `packed`.  Fields are scalars, pointers, arrays of them and
+/// structs defined earlier.  Structs which can't be laid out are skipped and returned by name
+/// with why, the rest of the header is still read.  Structs without a name can't be used and
+/// are left out.
+pub fn parse(source: &str, pointer: usize) -> Result<Header, String> {
+    let source = strip(source);
+    let mut structs: Vec<Struct> = vec![];
+    let mut skipped = vec![];
+    let mut rest = source.as_str();
+    while let Some(at) = find_word(rest, "struct") {
+        let before = &rest[..at];
+        let typedef = before.trim_end().ends_with("typedef");
+        rest = &rest[at + "struct".len()..];
+        let open = match rest.find(['{', ';']) {
+            Some(open) if rest[open..].starts_with('{') => open,
+            // a declaration or a field of another struct's type
+            _ => continue,
+        };
+        let (head, packed) = attributes(&rest[..open]);
+        if head.contains([')', '=']) {
+            continue;
+        }
+        let close = rest[open..]
+            .find('}')
+            .map(|x| open + x)
+            .ok_or("a struct is missing its }")?;
+        let body = &rest[open + 1..close];
+        let tail_end = rest[close..].find(';').map_or(rest.len(), |x| close + x);
+        let (tail, packed_after) = attributes(&rest[close + 1..tail_end]);
+        rest = &rest[tail_end..];
+        let packed = packed || packed_after;
+        let tag = head.split_whitespace().next_back();
+        let alias = tail.split_whitespace().next_back().filter(|_| typedef);
+        let name = match alias.or(tag) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let (mut fields, mut end, mut align) = (vec![], 0, 1);
+        let laid = body
+            .split(';')
+            .filter(|x| !x.trim().is_empty())
+            .try_for_each(|declaration| {
+                declare(
+                    declaration,
+                    &structs,
+                    pointer,
+                    packed,
+                    &mut fields,
+                    &mut end,
+                    &mut align,
+                )
+            });
+        if let Err(e) = laid {
+            skipped.push((name, e));
+            continue;
+        }
+        let size = round_up(end, align);
+        let layout = Struct {
+            name,
+            fields,
+            size,
+            align,
+        };
+        // a typedef names the tag too, so both can be used
+        if let (Some(tag), Some(_)) = (tag, alias) {
+            if tag != layout.name {
+                structs.push(Struct {
+                    name: tag.to_string(),
+                    ..layout.clone()
+                });
+            }
+        }
+        structs.push(layout);
+    }
+    Ok(Header { structs, skipped })
+}
+
+/// `text` without any `__attribute__((...))`, and whether one of them was `packed`
+fn attributes(text: &str) -> (String, bool) {
+    let mut out = String::new();
+    let mut packed = false;
+    let mut rest = text;
+    while let Some(at) = rest.find("__attribute__") {
+        out.push_str(&rest[..at]);
+        rest = &rest[at + "__attribute__".len()..];
+        let mut depth = 0;
+        let len = rest
+            .char_indices()
+            .find_map(|(i, x)| {
+                match x {
+                    '(' => depth += 1,
+                    ')' if depth == 1 => return Some(i + 1),
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                None
+            })
+            .unwrap_or(rest.len());
+        packed |= rest[..len].contains("packed");
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    (out, packed)
+}
+
+/// `value` rounded up to a multiple of `align`
+fn round_up(value: usize, align: usize) -> usize {
+    match value % align {
+        0 => value,
+        x => value + align - x,
+    }
+}
+
+/// where the word `word` first appears in `text`, not as part of a longer name
+fn find_word(text: &str, word: &str) -> Option<usize> {
+    let name = |x: char| x.is_alphanumeric() || x == '_';
+    text.match_indices(word)
+        .map(|(at, _)| at)
+        .find(|&at| !text[..at].ends_with(name) && !text[at + word.len()..].starts_with(name))
+}
+
+/// A struct laid over the bytes at an address, `:struct`.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub layout: Struct,
+    pub address: u64,
+    /// file offset of `address`
+    pub offset: u64,
+    /// the struct's bytes as the file had them when it was laid, before pending edits
+    pub bytes: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let header = "
+            #include <stdint.h>
+            // a point
+            struct point { int x, y; };
+            typedef struct node {
+                unsigned char tag;   /* padded to 8 */
+                struct point at;
+                const char *name;
+                char label[6];
+                uint16_t weights[2];
+                long long int total;
+            } node_t;
+            struct __attribute__((packed)) wire { uint8_t kind; uint32_t len; };
+        ";
+        let Header { structs, skipped } = parse(header, 8).unwrap();
+        assert!(skipped.is_empty());
+        let names = structs.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["point", "node", "node_t", "wire"], names);
+
+        let node = &structs[2];
+        let fields = node
+            .fields
+            .iter()
+            .map(|x| (x.name.as_str(), x.offset, x.scalar))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("tag", 0, Scalar::Unsigned(1)),
+                ("at.x", 4, Scalar::Signed(4)),
+                ("at.y", 8, Scalar::Signed(4)),
+                ("name", 16, Scalar::Pointer(8)),
+                ("label", 24, Scalar::Char),
+                ("weights", 30, Scalar::Unsigned(2)),
+                ("total", 40, Scalar::Signed(8)),
+            ],
+            fields
+        );
+        assert_eq!(48, node.size);
+        assert_eq!(5, structs[3].size);
+        assert_eq!(16, parse(header, 4).unwrap().structs[2].fields[4].offset);
+
+        // what can't be laid out is skipped, and the rest still defined
+        let Header { structs, skipped } = parse(
+            "struct bits { int x : 3; }; struct { int y; } unnamed; struct ok { int z; };
+             struct uses { widget w; };",
+            8,
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["ok"],
+            structs.iter().map(|x| x.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["bits", "uses"],
+            skipped.iter().map(|x| x.0.as_str()).collect::<Vec<_>>()
+        );
+        assert!(parse("struct open { int x;", 8).is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        let point = &parse("struct point { short x; char name[4]; float f; };", 8)
+            .unwrap()
+            .structs[0];
+        let mut bytes = vec![0xfe, 0xff, b'a', b'b', 0, 0, 0, 0];
+        bytes.extend_from_slice(&1.5f32.to_le_bytes());
+        assert_eq!(
+            vec![
+                "+0x0000 x i16 = -2",
+                "+0x0002 name char[4] = \"ab\"",
+                "+0x0008 f f32 = 1.5",
+            ],
+            point.describe(&bytes)
+        );
+        assert_eq!(Ok((2, b"xyz\0".to_vec())), point.encode("name", "xyz"));
+        assert_eq!(Ok((3, vec![b'q'])), point.encode("name[1]", "q"));
+        assert_eq!(Ok((0, vec![7, 0])), point.encode("x", "7"));
+        assert!(point.encode("name", "toolong").is_err());
+        assert!(point.encode("z", "1").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::util::{to_hexstring, Engines, Function, InstructionRow};
+use std::collections::HashMap;
+use tui::widgets::ListState;
+
+/// A line of the linear view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    /// where a function starts
+    Header(String),
+    /// an instruction, `function` is None between functions
+    Row {
+        function: Option<String>,
+        va: u64,
+        bytes: String,
+        text: String,
+    },
+}
+
+/// The whole code section disassembled in address order instead of function by function.
+/// Functions are shown as they're being edited, the bytes between them are swept linearly.
+#[derive(Debug)]
+pub struct Linear {
+    pub lines: Vec<Line>,
+    pub state: ListState,
+    /// the first line on screen, only the lines on screen are formatted
+    top: usize,
+}
+
+impl Linear {
+    /// `data` is the file, `start..end` the part of it to show.
+    pub fn new(
+        data: &[u8],
+        (start, end): (u64, u64),
+        functions: &[Function],
+        rows: &HashMap<String, Vec<InstructionRow>>,
+        engines: &Engines,
+    ) -> Linear {
+        let mut functions = functions
+            .iter()
+            .filter(|x| !x.outside_file && (start..end).contains(&(x.offset as u64)))
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|x| x.offset);
+
+        let mut lines = vec![];
+        let mut cursor = start;
+        for function in functions {
+            let offset = function.offset as u64;
+            // overlapping functions are left to the Functions pane
+            if offset < cursor {
+                continue;
+            }
+            sweep(&mut lines, data, cursor, offset, engines);
+            lines.push(Line::Header(function.name.clone()));
+            for row in rows.get(&function.name).into_iter().flatten() {
+                lines.push(Line::Row {
+                    function: Some(function.name.clone()),
+                    va: row.offset,
+                    bytes: row.bytes.clone(),
+                    text: row.text.clone(),
+                });
+            }
+            cursor = offset + function.size as u64;
+        }
+        sweep(&mut lines, data, cursor, end, engines);
+        let mut state = ListState::default();
+        state.select(Some(0));
+        Linear {
+            lines,
+            state,
+            top: 0,
+        }
+    }
+
+    /// The labels of a pane `height` lines tall scrolled as little as needed to show the
+    /// selected line, and the selection among them.
+    pub fn window(&mut self, height: usize) -> (Vec<String>, ListState) {
+        let selected = self.state.selected().unwrap_or(0);
+        if selected < self.top {
+            self.top = selected;
+        } else if selected >= self.top + height {
+            self.top = (selected + 1).saturating_sub(height);
+        }
+        let labels = self.lines.iter().skip(self.top).take(height);
+        let mut state = ListState::default();
+        state.select(Some(selected - self.top));
+        (labels.map(label).collect(), state)
+    }
+
+    pub fn selected_label(&self) -> Option<String> {
+        self.lines.get(self.state.selected()?).map(label)
+    }
+
+    /// the function and address of the selected line, a header counts as its first row
+    pub fn selected(&self) -> Option<(Option<&str>, u64)> {
+        let index = self.state.selected()?;
+        self.lines[index..].iter().find_map(|line| match line {
+            Line::Header(_) => None,
+            Line::Row { function, va, .. } => Some((function.as_deref(), *va)),
+        })
+    }
+
+    /// select the line at (or the last one before) `va`
+    pub fn select_address(&mut self, va: u64) {
+        let index = self
+            .lines
+            .iter()
+            .rposition(|line| matches!(line, Line::Row { va: x, .. } if *x <= va))
+            .unwrap_or(0);
+        self.state.select(Some(index));
+    }
+
+    /// move the selection by `delta` lines, stopping at either end
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.lines.len().saturating_sub(1) as isize;
+        let index = self.state.selected().unwrap_or(0) as isize + delta;
+        self.state.select(Some(index.max(0).min(last) as usize));
+    }
+}
+
+/// How many bytes from `start` make up a function, when nobody said: up to and including the
+/// first `ret`, or up to `end` (where the next function starts) if there isn't one before it.
+/// Decoding stops at the first byte which isn't an instruction.
+pub fn function_size(data: &[u8], start: u64, end: u64, engines: &Engines) -> u64 {
+    let end = end.min(data.len() as u64);
+    if start >= end {
+        return 0;
+    }
+    let mut size = 0;
+    for (len, returns) in engines.returns(&data[start as usize..end as usize], start) {
+        size += len as u64;
+        if returns {
+            break;
+        }
+    }
+    size
+}
+
+fn label(line: &Line) -> String {
+    match line {
+        Line::Header(name) => format!("{}:", name),
+        Line::Row {
+            va, bytes, text, ..
+        } => format!("  {:#010x}  {:<24} {}", va, bytes, text.trim_end()),
+    }
+}
+
+/// disassemble `data[start..end]` one instruction after another, a byte at a time where it
+/// doesn't decode
+fn sweep(lines: &mut Vec<Line>, data: &[u8], start: u64, end: u64, engines: &Engines) {
+    let end = end.min(data.len() as u64);
+    let mut va = start;
+    while va < end {
+        let decoded = engines.disassemble(&data[va as usize..end as usize], va);
+        let before = va;
+        for (bytes, text, _) in decoded {
+            let len = bytes.len() as u64;
+            lines.push(Line::Row {
+                function: None,
+                va,
+                bytes: to_hexstring(&bytes),
+                text,
+            });
+            va += len;
+        }
+        if va == before {
+            lines.push(Line::Row {
+                function: None,
+                va,
+                bytes: to_hexstring(&data[va as usize..=va as usize]),
+                text: format!(".byte {:#04x}", data[va as usize]),
+            });
+            va += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{EngineOptions, Syntax};
+
+    #[test]
+    fn test_linear() {
+        // padding, then a function of one nop and a ret after it
+        let data = [0xcc, 0x90, 0xc3];
+        let functions = vec![Function {
+            name: "f".to_string(),
+            offset: 1,
+            size: 1,
+            nbbs: 1,
+            callrefs: vec![],
+            outside_file: false,
+        }];
+        let mut rows = HashMap::new();
+        rows.insert(
+            "f".to_string(),
+            vec![InstructionRow::new(1, &[0x90], "nop".to_string())],
+        );
+        let mut linear = Linear::new(&data, (0, 3), &functions, &rows, &Engines::default());
+        let (labels, state) = linear.window(10);
+        assert_eq!(4, labels.len());
+        assert_eq!("f:", labels[1]);
+        assert!(labels[3].ends_with("ret"));
+        assert_eq!(Some(0), state.selected());
+
+        assert_eq!(Some((None, 0)), linear.selected());
+        linear.move_selection(1);
+        assert_eq!(Some((Some("f"), 1)), linear.selected());
+        linear.move_selection(10);
+        assert_eq!(Some((None, 2)), linear.selected());
+        let (labels, state) = linear.window(2);
+        assert_eq!(2, labels.len());
+        assert!(labels[1].ends_with("ret"));
+        assert_eq!(Some(1), state.selected());
+        linear.select_address(1);
+        assert_eq!(Some(2), linear.state.selected());
+        // already on screen, so it doesn't scroll
+        assert_eq!(Some(0), linear.window(2).1.selected());
+        linear.move_selection(-1);
+        let (labels, state) = linear.window(2);
+        assert_eq!("f:", labels[0]);
+        assert_eq!(Some(0), state.selected());
+    }
+
+    #[test]
+    fn test_function_size() {
+        let engines = Engines::default();
+        // nop, ret, then the next function
+        let data = [0x90, 0xc3, 0x90, 0x90];
+        assert_eq!(2, function_size(&data, 0, 4, &engines));
+        assert_eq!(2, function_size(&data, 2, 4, &engines));
+        assert_eq!(1, function_size(&data, 0, 1, &engines));
+        assert_eq!(0, function_size(&data, 4, 8, &engines));
+        // repz ret, ret 0x10
+        let data = [0xf3, 0xc3, 0x90, 0xc2, 0x10, 0x00, 0x90];
+        assert_eq!(2, function_size(&data, 0, 7, &engines));
+        assert_eq!(4, function_size(&data, 2, 7, &engines));
+        // retq in AT&T syntax
+        let att = Engines::new(EngineOptions {
+            syntax: Syntax::Att,
+            ..EngineOptions::default()
+        })
+        .unwrap();
+        assert_eq!(2, function_size(&[0x90, 0xc3, 0x90], 0, 3, &att));
+        let plain = Engines::new(EngineOptions {
+            detail: false,
+            ..EngineOptions::default()
+        })
+        .unwrap();
+        assert_eq!(2, function_size(&data, 0, 7, &plain));
+        assert_eq!(4, function_size(&data, 2, 7, &plain));
+    }
+}
@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Formats a function can be captured in, picked by the extension of the file being written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// text with ANSI color escapes, for `cat` or `less -R`
+    Ansi,
+    /// a `<pre>` block with inline styles, so it can be pasted into a page as it is
+    Html,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("ans") | Some("ansi") | Some("txt") => Ok(Format::Ansi),
+            Some("html") | Some("htm") => Ok(Format::Html),
+            _ => Err("functions can be captured as .ans or .html".into()),
+        }
+    }
+}
+
+/// How a row is highlighted in the Disasm column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Highlight {
+    Normal,
+    /// where a trace is stopped
+    Pc,
+    /// doesn't assemble (or disassemble)
+    Invalid,
+    /// uses an instruction set extension the target lacks
+    Forbidden,
+}
+
+impl Highlight {
+    fn ansi(self) -> &'static str {
+        match self {
+            Highlight::Normal => "",
+            Highlight::Pc => "\x1b[30;43m",
+            Highlight::Invalid => "\x1b[4;31m",
+            Highlight::Forbidden => "\x1b[35m",
+        }
+    }
+
+    fn css(self) -> &'static str {
+        match self {
+            Highlight::Normal => "",
+            Highlight::Pc => "color:#000;background:#cc0",
+            Highlight::Invalid => "color:#c00;text-decoration:underline",
+            Highlight::Forbidden => "color:#c0c",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub address: u64,
+    /// hex
+    pub bytes: String,
+    pub text: String,
+    /// bookmarks, comments and the like, as the Disasm column shows them after the `;`
+    pub notes: Option<String>,
+    pub highlight: Highlight,
+}
+
+/// A function as the Hex and Disasm columns show it.
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub title: String,
+    pub rows: Vec<Row>,
+}
+
+impl Listing {
+    pub fn render(&self, format: Format) -> String {
+        let width = self.rows.iter().map(|x| x.bytes.len()).max().unwrap_or(0);
+        let mut out = String::new();
+        match format {
+            Format::Ansi => {
+                let _ = writeln!(out, "\x1b[1m{}\x1b[0m", self.title);
+                for row in &self.rows {
+                    let _ = write!(
+                        out,
+                        "{:#010x}  {:<width$}  {}{}\x1b[0m",
+                        row.address,
+                        row.bytes,
+                        row.highlight.ansi(),
+                        row.text,
+                        width = width
+                    );
+                    if let Some(notes) = &row.notes {
+                        let _ = write!(out, "  \x1b[90m; {}\x1b[0m", notes);
+                    }
+                    out.push('\n');
+                }
+            }
+            Format::Html => {
+                let _ = writeln!(
+                    out,
+                    "<pre style=\"color:#ddd;background:#111;padding:1em\"><b>{}</b>",
+                    escape(&self.title)
+                );
+                for row in &self.rows {
+                    let _ = write!(
+                        out,
+                        "{:#010x}  {:<width$}  <span style=\"{}\">{}</span>",
+                        row.address,
+                        row.bytes,
+                        row.highlight.css(),
+                        escape(&row.text),
+                        width = width
+                    );
+                    if let Some(notes) = &row.notes {
+                        let _ = write!(
+                            out,
+                            "  <span style=\"color:#888\">; {}</span>",
+                            escape(notes)
+                        );
+                    }
+                    out.push('\n');
+                }
+                out.push_str("</pre>\n");
+            }
+        }
+        out
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing() -> Listing {
+        Listing {
+            title: "main".to_string(),
+            rows: vec![
+                Row {
+                    address: 0x1000,
+                    bytes: "55".to_string(),
+                    text: "push rbp".to_string(),
+                    notes: Some("<entry>".to_string()),
+                    highlight: Highlight::Normal,
+                },
+                Row {
+                    address: 0x1001,
+                    bytes: "0f 0b".to_string(),
+                    text: "ud2".to_string(),
+                    notes: None,
+                    highlight: Highlight::Invalid,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let ansi = listing().render(Format::Ansi);
+        assert!(ansi.contains("0x00001000  55     push rbp\x1b[0m  \x1b[90m; <entry>"));
+        assert!(ansi.contains("0x00001001  0f 0b  \x1b[4;31mud2\x1b[0m\n"));
+
+        let html = listing().render(Format::Html);
+        assert!(html.contains("; &lt;entry&gt;</span>"));
+        assert!(html.contains("text-decoration:underline\">ud2</span>"));
+        assert!(html.ends_with("</pre>\n"));
+    }
+}
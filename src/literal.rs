@@ -0,0 +1,144 @@
+use crate::cave;
+use crate::util;
+use std::convert::TryFrom;
+
+/// The bytes of `text` with C style escapes (`\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\xNN`)
+/// turned into what they stand for.
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('r') => b'\r',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('x') => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                u8::from_str_radix(&hex, 16).map_err(|_| format!("\\x{} isn't a byte", hex))?
+            }
+            Some(c) => return Err(format!("\\{} isn't an escape", c)),
+            None => return Err("a \\ at the end escapes nothing".to_string()),
+        });
+    }
+    Ok(bytes)
+}
+
+/// `bytes` as text, what `unescape` takes back: printable ASCII as it is, anything else escaped.
+pub fn escape(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for byte in bytes {
+        match byte {
+            b'\n' => text.push_str("\\n"),
+            b'\t' => text.push_str("\\t"),
+            b'\r' => text.push_str("\\r"),
+            0 => text.push_str("\\0"),
+            b'\\' => text.push_str("\\\\"),
+            b'"' => text.push_str("\\\""),
+            x if x.is_ascii_graphic() || *x == b' ' => text.push(*x as char),
+            x => text.push_str(&format!("\\x{:02x}", x)),
+        }
+    }
+    text
+}
+
+/// The NUL terminated string at `offset` in `data`, without its NUL, if it's printable text.
+pub fn read(data: &[u8], offset: u64) -> Option<&[u8]> {
+    let rest = data.get(offset as usize..)?;
+    let len = rest.iter().position(|x| *x == 0)?;
+    let text = &rest[..len];
+    let printable = |x: &u8| x.is_ascii_graphic() || b" \t\r\n".contains(x);
+    Some(text).filter(|x| !x.is_empty() && x.iter().all(printable))
+}
+
+/// `bytes`, the instruction at `va` disassembled as `text`, changed to refer to `to` wherever it
+/// referred to `from`, through a rip relative operand or as an absolute address.  None if it
+/// doesn't refer to `from`.
+pub fn repoint(bytes: &[u8], text: &str, va: u64, from: u64, to: u64) -> Option<Vec<u8>> {
+    let mut bytes = bytes.to_vec();
+    let next = va + bytes.len() as u64;
+    let patterns = match cave::rip_displacement(text) {
+        Some(displacement) if next.wrapping_add(displacement as u64) == from => {
+            let moved = i32::try_from(displacement + to as i64 - from as i64).ok()?;
+            vec![(
+                (displacement as i32).to_le_bytes().to_vec(),
+                moved.to_le_bytes().to_vec(),
+            )]
+        }
+        Some(_) => return None,
+        None => {
+            let mut mentioned = false;
+            util::map_immediates(text, |literal, value| {
+                mentioned |= value == from;
+                literal.to_string()
+            });
+            if !mentioned {
+                return None;
+            }
+            let mut patterns = vec![(from.to_le_bytes().to_vec(), to.to_le_bytes().to_vec())];
+            if let (Ok(from), Ok(to)) = (u32::try_from(from), u32::try_from(to)) {
+                patterns.push((from.to_le_bytes().to_vec(), to.to_le_bytes().to_vec()));
+            }
+            patterns
+        }
+    };
+    // past the opcode, like `cave::relocate`
+    let (at, replacement) = patterns.iter().find_map(|(pattern, replacement)| {
+        let at = bytes
+            .windows(pattern.len())
+            .skip(1)
+            .position(|x| x == &pattern[..])?;
+        Some((at + 1, replacement))
+    })?;
+    bytes[at..at + replacement.len()].copy_from_slice(replacement);
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(Ok(b"a\nb\x00\xff\\".to_vec()), unescape(r"a\nb\0\xff\\"));
+        assert!(unescape(r"\q").is_err());
+        assert!(unescape("\\").is_err());
+        let bytes = b"say \"hi\"\n\x00\x7f\\";
+        assert_eq!(r#"say \"hi\"\n\0\x7f\\"#, escape(bytes));
+        assert_eq!(Ok(bytes.to_vec()), unescape(&escape(bytes)));
+    }
+
+    #[test]
+    fn test_read() {
+        let data = b"\x01\x02hello\x00\xff\x00";
+        assert_eq!(Some(&b"hello"[..]), read(data, 2));
+        assert_eq!(None, read(data, 0));
+        assert_eq!(None, read(data, 7));
+    }
+
+    #[test]
+    fn test_repoint() {
+        // lea rdi, [rip + 0xff9] at 0x1000 refers to 0x2000
+        let lea = b"\x48\x8d\x3d\xf9\x0f\x00\x00";
+        let text = "lea rdi, [rip + 0xff9]";
+        assert_eq!(
+            Some(b"\x48\x8d\x3d\xf9\x1f\x00\x00".to_vec()),
+            repoint(lea, text, 0x1000, 0x2000, 0x3000)
+        );
+        assert_eq!(None, repoint(lea, text, 0x1000, 0x2010, 0x3000));
+        assert_eq!(None, repoint(b"\x90", "nop", 0x1000, 0x2000, 0x3000));
+        // push 0x804a008
+        let push = b"\x68\x08\xa0\x04\x08";
+        assert_eq!(
+            Some(b"\x68\x00\xb0\x04\x08".to_vec()),
+            repoint(push, "push 0x804a008", 0x8049000, 0x804a008, 0x804b000)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// An advisory lock held on a file while it's being edited, so a second session on the same file
+/// is refused instead of the two silently writing over each other.
+#[derive(Debug)]
+pub struct Lock {
+    /// canonical path of the locked file
+    pub path: PathBuf,
+    file: File,
+}
+
+impl Lock {
+    pub fn acquire(path: &Path) -> Result<Lock, Box<dyn Error>> {
+        let path = path.canonicalize()?;
+        let file = lock(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock => format!(
+                "{} is already being edited in another session",
+                path.display()
+            ),
+            _ => format!("couldn't lock {}: {}", path.display(), e),
+        })?;
+        Ok(Lock { path, file })
+    }
+
+    /// Hold the lock on `copy` instead, for when it's about to be renamed over the locked file.
+    /// The lock belongs to the file rather than its path, so it'd be lost with the old file.
+    pub fn transfer(&mut self, copy: &Path) -> io::Result<()> {
+        self.file = lock(copy)?;
+        Ok(())
+    }
+}
+
+fn lock(path: &Path) -> io::Result<File> {
+    let file = File::open(path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire() {
+        let path = std::env::temp_dir().join(format!("transmogrify-{}-lock", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let lock = Lock::acquire(&path).unwrap();
+        assert!(Lock::acquire(&path).is_err());
+        drop(lock);
+        assert!(Lock::acquire(&path).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
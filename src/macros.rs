@@ -0,0 +1,162 @@
+use std::collections::{HashMap, VecDeque};
+use termion::event::Key;
+
+/// How many keys one `@` can replay, nested replays included, before it's assumed to be a
+/// macro which replays itself and stopped.
+const MAX_REPLAYED: usize = 100_000;
+
+/// What the key after `Q` or `@` means.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prompt {
+    Record,
+    Replay,
+}
+
+/// Vim style keyboard macros: `Q<reg>` records every key pressed until the next `Q` into a
+/// register, `@<reg>` presses them all again (`@@` repeats the last one replayed).  Unlike vim
+/// it's `Q` rather than `q`, which quits.
+#[derive(Debug, Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<Key>>,
+    recording: Option<char>,
+    /// set after `Q` or `@` until the register is typed
+    pub prompt: Option<Prompt>,
+    last: Option<char>,
+    /// keys still to be replayed, taken before any from the terminal
+    pending: VecDeque<Key>,
+    replayed: usize,
+}
+
+/// registers are letters and digits, like vim's named and numbered ones
+pub fn is_register(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+}
+
+impl Macros {
+    /// the register being recorded into
+    pub fn recording(&self) -> Option<char> {
+        self.recording
+    }
+
+    pub fn start(&mut self, register: char) {
+        self.registers.insert(register, vec![]);
+        self.recording = Some(register);
+    }
+
+    /// Stop recording, returning the register and how many keys went into it.  The `Q` which
+    /// stopped it was recorded like any other key, so it's taken back out.
+    pub fn stop(&mut self) -> Option<(char, usize)> {
+        let register = self.recording.take()?;
+        let keys = self.registers.entry(register).or_default();
+        keys.pop();
+        Some((register, keys.len()))
+    }
+
+    /// Add a key pressed at the terminal to the register being recorded.  Replayed keys aren't
+    /// recorded again, an `@` typed while recording is kept as `@` instead.
+    pub fn record(&mut self, key: &Key) {
+        if let Some(register) = self.recording {
+            self.registers.entry(register).or_default().push(*key);
+        }
+    }
+
+    /// Queue the keys in `register` (or the last one replayed for `@`) ahead of anything
+    /// already queued, so a macro replaying another finishes that one before carrying on.
+    pub fn replay(&mut self, register: char) -> Result<usize, String> {
+        let register = match register {
+            '@' => self.last.ok_or("no macro has been replayed yet")?,
+            register => register,
+        };
+        let keys = self
+            .registers
+            .get(&register)
+            .filter(|x| !x.is_empty())
+            .ok_or_else(|| format!("nothing recorded in @{}", register))?;
+        if self.replayed + keys.len() > MAX_REPLAYED {
+            self.pending.clear();
+            self.replayed = 0;
+            return Err(format!(
+                "stopped @{} after {} keys, does it replay itself?",
+                register, MAX_REPLAYED
+            ));
+        }
+        for key in keys.iter().rev() {
+            self.pending.push_front(*key);
+        }
+        self.replayed += keys.len();
+        self.last = Some(register);
+        Ok(keys.len())
+    }
+
+    /// the next replayed key, if a macro is being replayed
+    pub fn next(&mut self) -> Option<Key> {
+        let key = self.pending.pop_front();
+        if key.is_none() {
+            self.replayed = 0;
+        }
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(macros: &mut Macros) -> Vec<Key> {
+        std::iter::from_fn(|| macros.next()).collect()
+    }
+
+    #[test]
+    fn test_macros() {
+        let mut macros = Macros::default();
+        assert!(macros.replay('a').is_err());
+        assert!(macros.replay('@').is_err());
+
+        macros.start('a');
+        for key in &[Key::Char('e'), Key::Esc, Key::Down, Key::Char('Q')] {
+            macros.record(key);
+        }
+        assert_eq!(Some(('a', 3)), macros.stop());
+        assert_eq!(None, macros.recording());
+
+        macros.start('b');
+        for key in &[
+            Key::Char('@'),
+            Key::Char('a'),
+            Key::Char('x'),
+            Key::Char('Q'),
+        ] {
+            macros.record(key);
+        }
+        macros.stop();
+        assert_eq!(Ok(3), macros.replay('b'));
+        assert_eq!(Some(Key::Char('@')), macros.next());
+        assert_eq!(Some(Key::Char('a')), macros.next());
+        // the nested macro runs before the rest of the outer one
+        assert_eq!(Ok(3), macros.replay('a'));
+        assert_eq!(
+            vec![Key::Char('e'), Key::Esc, Key::Down, Key::Char('x')],
+            keys(&mut macros)
+        );
+        assert_eq!(Ok(3), macros.replay('@'));
+        assert_eq!(3, keys(&mut macros).len());
+
+        // a macro which replays itself is stopped rather than looping forever
+        macros.start('c');
+        for key in &[Key::Char('@'), Key::Char('c'), Key::Char('Q')] {
+            macros.record(key);
+        }
+        macros.stop();
+        let mut result = macros.replay('c');
+        while result.is_ok() {
+            // the `@`, then the register it names
+            macros.next();
+            let register = match macros.next() {
+                Some(Key::Char(c)) => c,
+                key => panic!("{:?}", key),
+            };
+            result = macros.replay(register);
+        }
+        assert_eq!(None, macros.next());
+    }
+}
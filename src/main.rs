@@ -1,15 +1,90 @@
+mod analysis;
+mod annotations;
 mod application;
+mod archive;
+mod baseline;
+mod binary;
+mod branch;
+mod bundle;
+mod byteclass;
+mod cave;
+mod command;
+mod completion;
+mod compressed;
+mod config;
+mod container;
+mod csvpatch;
+mod debugger;
+mod diff;
+mod dwarf;
+mod encoder;
 mod event;
+mod export;
+mod filter;
+mod fingerprint;
+mod got;
+mod hooks;
+mod interop;
+mod jumptable;
+mod keymap;
+mod layout;
+mod linear;
+mod listing;
+mod literal;
+mod lock;
+mod macros;
+mod minimap;
+mod overlay;
+mod overview;
+mod palette;
+mod plain;
+mod pointer;
+mod procmaps;
+mod project;
+mod remote;
+mod report;
+mod rewrite;
+mod runner;
+mod scalar;
+mod scratchpad;
+mod session;
+mod signatures;
+mod snippet;
+mod source;
+mod stats;
+mod symbols;
+mod syscalls;
+mod theme;
+mod trace;
 mod util;
+mod vtable;
+mod wizard;
+mod yara;
 
 use crate::event::{Event, Events};
-use crate::util::{Mode, Column, Function};
+use crate::util::{Mode, Column, Engines, Function, Grouping, InstructionRow, TempDir};
 
+use crate::analysis::BackendKind;
 use crate::application::Application;
-use r2pipe::{open_pipe, R2Pipe};
-use serde::{Deserialize, Serialize};
+use crate::archive::Member;
+use crate::bundle::{Bundle, Placement};
+use crate::byteclass::Class;
+use crate::compressed::Compressed;
+use crate::config::Settings;
+use crate::container::Image;
+use crate::diff::DiffLine;
+use crate::keymap::Action;
+use crate::lock::Lock;
+use crate::macros::Prompt;
+use crate::minimap::{Cell, Mark};
+use crate::overview::Overview;
+use crate::plain::Announcer;
+use crate::remote::Remote;
+use crate::session::Recorder;
+use crate::source::Source;
+use crate::theme::Theme;
 use std::error::Error;
-use std::io;
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
 use structopt::StructOpt;
 use termion::event::Key;
@@ -17,63 +92,575 @@ use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 use tui::backend::TermionBackend;
-use tui::layout::{Alignment, Constraint, Direction, Layout};
-use tui::style::{Color, Modifier, Style};
-use tui::text::Spans;
-use tui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::Modifier;
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
 use tui::Terminal;
 
 #[derive(StructOpt, Debug)]
 #[structopt(about, author)]
 struct Opt {
+    /// File to edit, - to read it from stdin, archive:member to edit a member of an ar, zip or cpio
+    /// archive or [user@]host:path to edit it over ssh
     #[structopt(name = "FILE", parse(from_os_str))]
-    file: PathBuf,
-}
+    file: Option<PathBuf>,
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct FunctionDisassembly {
-    name: String,
-    ops: Vec<Instruction>,
-}
+    /// Write the result here instead of editing FILE in place (or uploading it again), required
+    /// when FILE is - or a pipe
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Edit FILE inside this container image, writes commit it to a new image tagged
+    /// <tag>-patched
+    #[structopt(long)]
+    image: Option<String>,
+
+    /// Path to a config file, defaults to $XDG_CONFIG_HOME/transmogrify/config.json
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Function name, address or source file:line to select on startup
+    #[structopt(long)]
+    seek: Option<String>,
+
+    /// Milliseconds between rebuilds of edited rows, overrides the config file
+    #[structopt(long)]
+    tick_rate: Option<u64>,
+
+    /// PDB to name functions and globals from, defaults to FILE with a .pdb extension if there is
+    /// one
+    #[structopt(long, parse(from_os_str))]
+    pdb: Option<PathBuf>,
+
+    /// r2 project (a name or its directory) to take function names, comments and flags from
+    #[structopt(long)]
+    r2_project: Option<String>,
+
+    /// Log every edit to this file so the session can be replayed later
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// Restore pending edits, comments and the cursor from this file if it exists and keep
+    /// saving them there, so a crash doesn't lose unwritten work
+    #[structopt(long, parse(from_os_str))]
+    project: Option<PathBuf>,
+
+    /// Write what changes as plain lines for screen readers instead of drawing the interface
+    #[structopt(long)]
+    plain: bool,
+
+    /// Only keep the current function's disassembly in memory, reading the rest from the file as
+    /// they're visited, for firmware images too big to hold all of it
+    #[structopt(long)]
+    low_memory: bool,
+
+    /// What finds the functions: r2, rizin, ghidra, objdump or native (no tools needed).  Falls
+    /// back to native when it isn't installed
+    #[structopt(long)]
+    backend: Option<BackendKind>,
+
+    /// Print how long finding the functions and disassembling them took once the session ends
+    #[structopt(long)]
+    timings: bool,
+
+    /// Redo the edits logged by --record against FILE (e.g. a fresh copy) and exit
+    #[structopt(long, parse(from_os_str), conflicts_with = "record")]
+    replay: Option<PathBuf>,
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Instruction {
-    bytes: String,
-    disasm: String,
+    #[structopt(subcommand)]
+    command: Option<Subcommand>,
 }
 
-fn get_functions<P: AsRef<str>>(program: P) -> Vec<Function> {
-    // using r2 so we can pull functions from stripped binaries -- is there a better way to do this?
-    let mut r2p = open_pipe!(Some(program)).unwrap();
-    r2p.cmd("aaa").unwrap();
-    let x = r2p.cmd("aflj").unwrap();
-    if let Ok(json) = serde_json::from_str::<Vec<Function>>(&x) {
-        json
-    } else {
-        vec![]
-    }
+#[derive(StructOpt, Debug)]
+enum Subcommand {
+    /// Apply a .tmogpatch bundle to TARGET, refusing if TARGET isn't the file it was made from
+    Apply {
+        #[structopt(parse(from_os_str))]
+        bundle: PathBuf,
+        #[structopt(parse(from_os_str))]
+        target: PathBuf,
+        /// Write the patched file here instead of patching TARGET in place
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Write each offset,hex-bytes row of CSV into TARGET, checking every row before writing any
+    ApplyCsv {
+        #[structopt(parse(from_os_str))]
+        csv: PathBuf,
+        #[structopt(parse(from_os_str))]
+        target: PathBuf,
+        /// Write the patched file here instead of patching TARGET in place
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Move a .tmogpatch bundle onto TARGET, another build of the file it was made from, finding
+    /// each hunk again by its function and the bytes around it
+    Rebase {
+        #[structopt(parse(from_os_str))]
+        bundle: PathBuf,
+        #[structopt(parse(from_os_str))]
+        target: PathBuf,
+        /// Write the rebased bundle here instead of next to BUNDLE as .rebased.tmogpatch
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let opt = Opt::from_args();
+    if let Some(Subcommand::Apply {
+        bundle,
+        target,
+        output,
+    }) = &opt.command
+    {
+        let bundle = Bundle::load(bundle)?;
+        let patched = bundle.apply(&std::fs::read(target)?)?;
+        let output = output.as_ref().unwrap_or(target);
+        std::fs::write(output, patched)?;
+        println!(
+            "applied {} hunk(s) to {}",
+            bundle.hunks.len(),
+            output.display()
+        );
+        if !bundle.notes.is_empty() {
+            println!("{}", bundle.notes);
+        }
+        return Ok(());
+    }
+    if let Some(Subcommand::ApplyCsv {
+        csv,
+        target,
+        output,
+    }) = &opt.command
+    {
+        let rows = csvpatch::parse(&std::fs::read_to_string(csv)?)
+            .map_err(|e| format!("{}:\n{}", csv.display(), e))?;
+        let mut data = std::fs::read(target)?;
+        let summary = csvpatch::apply(&rows, &mut data)
+            .map_err(|e| format!("nothing was written:\n{}", e))?;
+        let output = output.as_ref().unwrap_or(target);
+        if !output.exists() {
+            // starts as a copy so it keeps the target's permissions
+            std::fs::copy(target, output)?;
+        }
+        // locked like any other file written back to, so it isn't patched under a session
+        let mut lock = Lock::acquire(output)?;
+        util::replace_atomically(output, |copy| {
+            std::fs::write(copy, &data)?;
+            lock.transfer(copy)?;
+            Ok(())
+        })?;
+        println!(
+            "applied {} row(s) to {}, {} byte(s) changed, {} row(s) were already applied",
+            summary.rows,
+            output.display(),
+            summary.changed,
+            summary.unchanged
+        );
+        return Ok(());
+    }
+    if let Some(Subcommand::Rebase {
+        bundle: path,
+        target,
+        output,
+    }) = &opt.command
+    {
+        let bundle = Bundle::load(path)?;
+        let data = std::fs::read(target)?;
+        // finding functions means analysing the whole file, skip it when nothing needs them
+        let functions = if bundle.hunks.iter().any(|x| x.function.is_some()) {
+            let settings = Settings::load(opt.config.as_deref())?;
+            let engines = Engines::new(settings.engines.clone())?;
+            let backend = opt.backend.unwrap_or(settings.backend);
+            Application::new(target.to_string_lossy(), engines, backend, true)?
+                .fingerprints(|_| true)
+        } else {
+            vec![]
+        };
+        let (rebased, placements) = bundle.rebase(&data, &functions);
+        for (hunk, placement) in bundle.hunks.iter().zip(&placements) {
+            let outcome = match placement {
+                Placement::Function { name, offset } => format!("{:#x} in {}", offset, name),
+                Placement::Context(offset) => format!("{:#x} by the bytes around it", offset),
+                Placement::Unchanged(offset) => format!("{:#x} unmoved", offset),
+                Placement::Lost => "not found".to_string(),
+            };
+            println!("{:#010x}  {}", hunk.offset, outcome);
+        }
+        let output = output
+            .clone()
+            .unwrap_or_else(|| path.with_extension("rebased.tmogpatch"));
+        std::fs::write(&output, serde_json::to_string_pretty(&rebased)?)?;
+        println!(
+            "rebased {} of {} hunk(s) into {}",
+            rebased.hunks.len(),
+            bundle.hunks.len(),
+            output.display()
+        );
+        if rebased.hunks.len() < bundle.hunks.len() {
+            return Err("some hunks couldn't be placed and were left out".into());
+        }
+        return Ok(());
+    }
+    let input = opt.file.clone().ok_or("FILE is required")?;
+    let mut settings = Settings::load(opt.config.as_deref())?;
+    if let Some(tick_rate) = opt.tick_rate {
+        settings.tick_rate = tick_rate;
+    }
+    settings.plain |= opt.plain;
+    if let Some(backend) = opt.backend {
+        settings.backend = backend;
+    }
+    // something has to be left to edit in
+    settings.show_disasm |= !settings.show_hex;
+
+    // stdin and pipes can't be reopened by r2 or written back to, so edit a copy
+    let stdin = input.as_os_str() == "-";
+    let seekable = !stdin
+        && std::fs::metadata(&input)
+            .map(|x| x.is_file())
+            .unwrap_or(false);
+    // remote files, members of archives, files in images and compressed files are edited as a
+    // local copy which is stored again on write
+    let source = match &opt.image {
+        Some(image) => Some(Source::Image(Image {
+            runtime: settings.container_runtime.clone(),
+            image: image.clone(),
+            path: input.to_string_lossy().into_owned(),
+        })),
+        None if seekable => Compressed::detect(&input).map(Source::Compressed),
+        None => Some(&input)
+            .filter(|x| !stdin && !x.exists())
+            .and_then(|x| x.to_str())
+            .and_then(|x| {
+                Member::parse(x)
+                    .map(Source::Archive)
+                    .or_else(|| Remote::parse(x).map(Source::Remote))
+            }),
+    };
+    // files written back to are locked so a second session can't silently undo this one's writes
+    let lock = if seekable && opt.output.is_none() {
+        Some(Lock::acquire(&input)?)
+    } else {
+        None
+    };
+    let working_copy = match (seekable || source.is_some(), &opt.output, &source) {
+        (true, None, None) => None,
+        (false, None, _) => return Err("reading from stdin or a pipe needs --output".into()),
+        _ => {
+            let directory = TempDir::new("copy")?;
+            let copy = directory.join("copy");
+            if let Some(source) = &source {
+                source.fetch(&copy)?;
+            } else {
+                let mut input: Box<dyn Read> = if stdin {
+                    Box::new(io::stdin())
+                } else {
+                    Box::new(std::fs::File::open(&input)?)
+                };
+                io::copy(&mut input, &mut std::fs::File::create(&copy)?)?;
+            }
+            Some((directory, copy))
+        }
+    };
+    let source = source.filter(|_| opt.output.is_none());
+    let pdb = symbols::Found::new(&input, opt.pdb.clone());
+    let file = working_copy
+        .as_ref()
+        .map_or(input, |(_, copy)| copy.clone());
+
+    if let Some(replay) = &opt.replay {
+        let entries = session::load(replay)?;
+        let engines = Engines::new(settings.engines.clone())?;
+        let mut app = Application::new(
+            file.to_string_lossy(),
+            engines,
+            settings.backend,
+            opt.low_memory,
+        )?;
+        app.output = opt.output.clone();
+        app.source = source;
+        app.lock = lock;
+        if let Some(pdb) = &pdb {
+            pdb.report(app.load_pdb(&pdb.path))?;
+        }
+        if let Some(project) = &opt.r2_project {
+            app.import_annotations(&interop::r2_project(project)?)?;
+        }
+        let replayed = app.replay(&entries);
+        drop(working_copy);
+        let replayed = replayed?;
+        println!(
+            "replayed {} operation(s) from {}",
+            replayed,
+            replay.display()
+        );
+        let unwritten = session::unwritten(&entries).len();
+        if unwritten > 0 {
+            eprintln!(
+                "warning: the last {} operation(s) were never written in the recorded session, \
+                 so they weren't written here either",
+                unwritten
+            );
+        }
+        if opt.timings {
+            eprintln!("{}", app.timings);
+        }
+        return Ok(());
+    }
 
     // Terminal initialization
+    // kept to give the terminal back to a debugger in the state it was in
+    let mut cooked: libc::termios = unsafe { std::mem::zeroed() };
+    unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut cooked) };
     let stdout = io::stdout().into_raw_mode()?;
-    let stdout = MouseTerminal::from(stdout);
-    let stdout = AlternateScreen::from(stdout);
-    let backend = TermionBackend::new(stdout);
+    // plain mode writes lines of its own, the interface is still laid out (column widths come
+    // from it) but drawn into nothing
+    let (screen, _raw): (Box<dyn Write>, _) = if settings.plain {
+        (Box::new(io::sink()), Some(stdout))
+    } else {
+        let stdout = MouseTerminal::from(stdout);
+        (Box::new(AlternateScreen::from(stdout)), None)
+    };
+    let backend = TermionBackend::new(screen);
     let mut terminal = Terminal::new(backend)?;
 
-    let events = Events::new();
+    let mut events = Events::with_config(event::Config {
+        tick_rate: settings.tick_rate(),
+        ..event::Config::default()
+    });
+    // 'q' can be typed into edits and commands, quitting is handled below
+    events.disable_exit_key();
 
     // App
 
-    let mut app = Application::new(opt.file.to_string_lossy());
+    let engines = Engines::new(settings.engines.clone())?;
+    let mut app = Application::new(
+        file.to_string_lossy(),
+        engines,
+        settings.backend,
+        opt.low_memory,
+    )?;
+    app.output = opt.output.clone();
+    app.source = source;
+    app.lock = lock;
+    app.settings = settings;
+    app.config = opt.config.clone();
+    if let Some(pdb) = &pdb {
+        app.message = Some(pdb.report(app.load_pdb(&pdb.path))?);
+    }
+    if let Some(project) = &opt.r2_project {
+        app.message = Some(app.import_annotations(&interop::r2_project(project)?)?);
+    }
     app.editor_state.select(Some(0));
-    app.function_state.select(Some(0));
+    app.select_function(Some(0));
+    if let Some(record) = &opt.record {
+        app.recorder = Some(Recorder::create(record)?);
+    }
+    if let Some(project) = &opt.project {
+        app.message = Some(app.open_project(project)?);
+    }
+    if let Some(seek) = &opt.seek {
+        if !app.seek(seek) {
+            return Err(format!("couldn't find a function named or containing {}", seek).into());
+        }
+    }
+    // asking for somewhere to start means skipping straight to the editor
+    app.show_overview = app.settings.overview
+        && opt.seek.is_none()
+        && app.overview.is_some()
+        && !app.settings.plain;
+    let mut announcer = Some(Announcer::default()).filter(|_| app.settings.plain);
+    let theme = app.settings.theme;
 
+    // typed before a key to repeat it, e.g. 10j.  Only keys which make sense repeated take one:
+    // moving up and down (j, k and the arrows) in any list, left and right along a row, nop (n
+    // or dd), yank (yy) and stepping a trace (n).  Any other key drops it.
+    let mut count: Option<usize> = None;
+    // the first key of a two key command, e.g. the d of dd
+    let mut pending = None;
     loop {
+        if let Some(launch) = app.launch.take() {
+            events.pause();
+            let status = debugger::run_in_terminal(&launch.command, &cooked, app.settings.plain);
+            events.resume();
+            // the copy it ran, if any
+            drop(launch.copy);
+            terminal.clear()?;
+            app.message = Some(match status {
+                Ok(status) => format!("debugger {}", status),
+                Err(e) => format!("error: {}", e),
+            });
+        }
         terminal.draw(|f| {
+            if let (true, Some(overview)) = (app.show_overview, &app.overview) {
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                let chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)].as_ref())
+                    .split(vchunks[0]);
+                let (summary, sections) = overview_items(overview, theme);
+                let title = app.file.display().to_string();
+                f.render_widget(list(summary, &title, false, theme), chunks[0]);
+                f.render_widget(list(sections, "Sections", false, theme), chunks[1]);
+                f.render_widget(
+                    Paragraph::new(
+                        "press any key to start editing, h to come back here, q to quit",
+                    ),
+                    vchunks[1],
+                );
+                return;
+            }
+
+            if let Some(stats) = &app.stats {
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                let chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Ratio(1, 3); 3].as_ref())
+                    .split(vchunks[0]);
+                let title = format!("Mnemonics ({} instructions)", stats.instructions);
+                f.render_widget(
+                    make_list(stats.mnemonic_labels(), &title, false, theme),
+                    chunks[0],
+                );
+                let calls = make_list(stats.call_labels(), "Most called", false, theme);
+                f.render_widget(calls, chunks[1]);
+                let title = format!("Function sizes ({} functions)", stats.functions);
+                f.render_widget(
+                    make_list(stats.size_labels(), &title, false, theme),
+                    chunks[2],
+                );
+                f.render_widget(Paragraph::new("press any key to go back"), vchunks[1]);
+                return;
+            }
+
+            if app.show_hooks {
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                f.render_widget(
+                    make_list(app.hook_lines(), "Write hooks", false, theme),
+                    vchunks[0],
+                );
+                f.render_widget(Paragraph::new("press any key to go back"), vchunks[1]);
+                return;
+            }
+
+            if app.palette.is_some() {
+                let bar = app.get_bar();
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                if let Some(palette) = &mut app.palette {
+                    let title = format!("Goto ({} matches, Esc goes back)", palette.total);
+                    f.render_stateful_widget(
+                        make_list(palette.labels(), &title, true, theme),
+                        vchunks[0],
+                        &mut palette.state,
+                    );
+                }
+                f.render_widget(Paragraph::new(bar).style(theme.text()), vchunks[1]);
+                return;
+            }
+
+            if let Some(runner) = &app.runner {
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                let height = vchunks[0].height.saturating_sub(2) as usize;
+                let lines = if runner.transcript.is_empty() {
+                    vec!["(nothing printed yet)".to_string()]
+                } else {
+                    runner.transcript.all()
+                };
+                let shown = lines.into_iter().skip(runner.top(height)).take(height);
+                let title = format!(
+                    "{} ({}, Esc goes back)",
+                    runner.command,
+                    runner.describe_status()
+                );
+                f.render_widget(make_list(shown, &title, true, theme), vchunks[0]);
+                let bar = app.get_bar();
+                f.render_widget(Paragraph::new(bar).style(theme.text()), vchunks[1]);
+                return;
+            }
+
+            if app.linear.is_some() {
+                let bar = app.get_bar();
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                if let Some(linear) = &mut app.linear {
+                    let height = vchunks[0].height.saturating_sub(2) as usize;
+                    let (labels, mut state) = linear.window(height);
+                    f.render_stateful_widget(
+                        make_list(labels, "Linear (Esc goes back)", true, theme),
+                        vchunks[0],
+                        &mut state,
+                    );
+                }
+                f.render_widget(Paragraph::new(bar).style(theme.text()), vchunks[1]);
+                return;
+            }
+
+            if app.got.is_some() {
+                let bar = app.get_bar();
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                if let Some(got) = &mut app.got {
+                    f.render_stateful_widget(
+                        make_list(
+                            got.labels(),
+                            "Calls through the GOT/IAT (Enter goes to the call, Esc goes back)",
+                            true,
+                            theme,
+                        ),
+                        vchunks[0],
+                        &mut got.state,
+                    );
+                }
+                f.render_widget(Paragraph::new(bar).style(theme.text()), vchunks[1]);
+                return;
+            }
+
+            if app.vtables.is_some() {
+                let bar = app.get_bar();
+                let vchunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                    .split(f.size());
+                let names = app.function_names();
+                if let Some(vtables) = &mut app.vtables {
+                    let labels = vtables.labels(|va| names.get(&va).cloned().unwrap_or_default());
+                    f.render_stateful_widget(
+                        make_list(
+                            labels,
+                            "Vtables (Enter goes to the function, Esc goes back)",
+                            true,
+                            theme,
+                        ),
+                        vchunks[0],
+                        &mut vtables.state,
+                    );
+                }
+                f.render_widget(Paragraph::new(bar).style(theme.text()), vchunks[1]);
+                return;
+            }
+
             // this solves for the correct proportions of the bar/main in a responsive way
             let (main_size, bar_size) = {
                 let (_, rows) = termion::terminal_size().unwrap_or((0, 0));
@@ -87,7 +674,25 @@ fn main() -> Result<(), Box<dyn Error>> {
                 )
             };
 
-            let (functions, hex, disasm_view, _bar) = {
+            let split = app.split.is_some();
+            let comparing = app.comparison.is_some();
+            let tracing = app.trace.is_some();
+            let showing_lines = app.show_lines;
+            let scratching = app.show_scratchpad;
+            let laying = app.struct_view.is_some();
+            let (
+                functions,
+                hex,
+                disasm_view,
+                split_view,
+                compare_view,
+                calls_view,
+                registers_view,
+                lines_view,
+                scratch_view,
+                struct_view,
+                _bar,
+            ) = {
                 let vchunks = Layout::default()
                     .direction(Direction::Vertical)
                     .margin(0)
@@ -99,183 +704,900 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .as_ref(),
                     )
                     .split(f.size());
+                // the split pane shows the same editor columns as the main one
+                let editors = app.settings.show_hex as u32 + app.settings.show_disasm as u32;
+                let columns = 1
+                    + editors * (1 + split as u32)
+                    + comparing as u32
+                    + app.show_calls as u32
+                    + tracing as u32
+                    + showing_lines as u32
+                    + scratching as u32
+                    + laying as u32;
                 let chunks = Layout::default()
                     .direction(Direction::Horizontal)
-                    .constraints(
-                        [
-                            Constraint::Percentage(33),
-                            Constraint::Percentage(33),
-                            Constraint::Percentage(33),
-                        ]
-                        .as_ref(),
-                    )
+                    .constraints(vec![Constraint::Ratio(1, columns); columns as usize])
                     .split(vchunks[0]);
-                (chunks[0], chunks[1], chunks[2], vchunks[1])
+                // Hex and Disasm take the columns after Functions, optional panels the ones after
+                // them in this order
+                let mut rest = chunks[1..].iter().copied();
+                let (show_hex, show_disasm) = (app.settings.show_hex, app.settings.show_disasm);
+                let mut editor = |shown: bool| if shown { rest.next() } else { None };
+                let hex = editor(show_hex);
+                let disasm_view = editor(show_disasm);
+                let split_view = if split {
+                    Some((editor(show_hex), editor(show_disasm)))
+                } else {
+                    None
+                };
+                let compare_view = if comparing { rest.next() } else { None };
+                let calls_view = if app.show_calls { rest.next() } else { None };
+                let registers_view = if tracing { rest.next() } else { None };
+                let lines_view = if showing_lines { rest.next() } else { None };
+                let scratch_view = if scratching { rest.next() } else { None };
+                let struct_view = if laying { rest.next() } else { None };
+                (
+                    chunks[0],
+                    hex,
+                    disasm_view,
+                    split_view,
+                    compare_view,
+                    calls_view,
+                    registers_view,
+                    lines_view,
+                    scratch_view,
+                    struct_view,
+                    vchunks[1],
+                )
             };
-            app.column_width = hex.width as isize;
+            app.column_width = hex.or(disasm_view).map_or(0, |x| x.width) as isize;
             {
-                f.render_stateful_widget(
-                    make_list(
-                        app.get_functions(""),
-                        "Functions",
-                        app.selected == Column::Function,
-                    ),
-                    functions,
-                    &mut app.function_state,
+                let mut title = match &app.attached {
+                    Some(attached) => {
+                        format!("Functions (by {}, in pid {})", app.sort, attached.pid)
+                    }
+                    None => format!("Functions (by {})", app.sort),
+                };
+                if let Some(filter) = &app.filter {
+                    title = format!("{} matching {}", title, filter.query);
+                }
+                let list = make_list(
+                    app.function_labels(),
+                    &title,
+                    app.selected == Column::Function,
+                    theme,
                 );
+                if app.filter.is_some() {
+                    // only matches are listed, so the selection is found among them
+                    let visible = app.visible_functions();
+                    let selected = app.function_state.selected();
+                    let mut state = ListState::default();
+                    state.select(selected.and_then(|x| visible.iter().position(|y| *y == x)));
+                    f.render_stateful_widget(list, functions, &mut state);
+                } else {
+                    f.render_stateful_widget(list, functions, &mut app.function_state);
+                }
             }
 
-            let func = app.get_current_function();
-
-            match app.selected {
-                Column::Hex => {
-                    f.set_cursor(
-                        hex.x + app.get_cursor() as u16 + 1 + (app.mode == Mode::Editing) as u16,
-                        hex.y + 1u16 + app.editor_state.selected().unwrap_or(0) as u16,
-                    );
+            let grouped = app.grouping == Grouping::Instruction;
+            // lines too long for their column are scrolled to the cursor and cut off with an
+            // ellipsis
+            let hex_labels = app.hex_labels();
+            let disasm = app.disasm_labels(app.get_current_function().map_or("", |x| &x.name));
+            let index = app.editor_state.selected().unwrap_or(0);
+            let current = match app.selected {
+                Column::Hex => hex_labels.get(index),
+                Column::Disasm => disasm.get(index),
+                _ => None,
+            };
+            app.follow_cursor(current.map_or(0, |x| util::grapheme_count(x)));
+            let clip = |labels: Vec<String>, column, area: Option<Rect>| {
+                let width = area.map_or(0, |x| x.width.saturating_sub(2));
+                let scroll = app.scroll_of(column);
+                labels
+                    .iter()
+                    .map(|x| util::clip(x, scroll, width as usize))
+                    .collect::<Vec<_>>()
+            };
+            let hex_labels = if app.show_classes {
+                let width = hex.map_or(0, |x| x.width.saturating_sub(2)) as usize;
+                let scroll = app.scroll_of(Column::Hex);
+                hex_labels
+                    .iter()
+                    .zip(app.hex_classes())
+                    .map(|(label, classes)| class_spans(label, &classes, scroll, width, theme))
+                    .collect::<Vec<_>>()
+            } else {
+                let labels = clip(hex_labels, Column::Hex, hex);
+                labels.into_iter().map(Spans::from).collect()
+            };
+            let disasm = clip(disasm, Column::Disasm, disasm_view);
+            match (app.selected, hex, disasm_view) {
+                (Column::Hex, Some(hex), _) if grouped => {
+                    f.set_cursor(hex.x + app.cursor_column() + 1, hex.y + 1u16 + index as u16);
                 }
-                Column::Disasm => {
+                (Column::Disasm, _, Some(disasm_view)) => {
                     f.set_cursor(
-                        disasm_view.x
-                            + app.get_cursor() as u16
-                            + 1
-                            + (app.mode == Mode::Editing) as u16,
-                        disasm_view.y + 1u16 + app.editor_state.selected().unwrap_or(0) as u16,
+                        disasm_view.x + app.cursor_column() + 1,
+                        disasm_view.y + 1u16 + index as u16,
                     );
                 }
                 _ => {}
             }
 
-            {
-                let hex_bytes = app.bytes.get(&func.name).unwrap().clone();
+            let pc = app.trace.as_ref().map(|x| x.pc);
+            match hex {
+                Some(hex) if grouped => {
+                    f.render_widget(
+                        list(
+                            row_items(
+                                hex_labels,
+                                app.current_rows(),
+                                pc,
+                                &app.settings.forbidden_extensions,
+                                theme,
+                            ),
+                            "Hex",
+                            app.selected == Column::Hex,
+                            theme,
+                        ),
+                        hex,
+                    );
+                }
+                Some(hex) => {
+                    let title = format!("Hex ({})", app.grouping);
+                    let items = hex_labels
+                        .into_iter()
+                        .map(|x| ListItem::new(x).style(theme.text()))
+                        .collect();
+                    f.render_widget(list(items, &title, app.selected == Column::Hex, theme), hex);
+                }
+                None => {}
+            }
 
+            if let Some(disasm_view) = disasm_view {
                 f.render_widget(
-                    make_list(hex_bytes, "Hex", app.selected == Column::Hex),
-                    hex,
+                    list(
+                        row_items(
+                            disasm,
+                            app.current_rows(),
+                            pc,
+                            &app.settings.forbidden_extensions,
+                            theme,
+                        ),
+                        "Disasm",
+                        app.selected == Column::Disasm,
+                        theme,
+                    ),
+                    disasm_view,
                 );
+                // over the right border, inside the corners
+                let height = disasm_view.height.saturating_sub(2);
+                if app.settings.minimap && disasm_view.width > 2 && height > 0 {
+                    let marks = app.minimap_marks();
+                    // the column isn't scrolled
+                    let shown = 0..(height as usize).min(marks.len());
+                    let lines = minimap::cells(&marks, height as usize, shown, index)
+                        .iter()
+                        .map(|x| Spans::from(minimap_span(x, theme)))
+                        .collect::<Vec<_>>();
+                    let stripe = Rect {
+                        x: disasm_view.x + disasm_view.width - 1,
+                        y: disasm_view.y + 1,
+                        width: 1,
+                        height,
+                    };
+                    f.render_widget(Paragraph::new(lines), stripe);
+                }
             }
 
-            {
-                let disasm = app.disasm.get(&func.name).unwrap().clone();
+            if let Some((hex, disasm_view)) = split_view {
+                let function = app
+                    .split
+                    .as_ref()
+                    .map(|x| x.function.clone())
+                    .unwrap_or_default();
+                let hex_bytes = app
+                    .rows
+                    .get(&function)
+                    .map(|rows| rows.iter().map(|x| x.bytes.clone()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let disasm = app.disasm_labels(&function);
+                if let Some(pane) = app.split.as_mut() {
+                    if let Some(hex) = hex {
+                        f.render_stateful_widget(
+                            make_list(hex_bytes, "Hex", false, theme),
+                            hex,
+                            &mut pane.editor_state,
+                        );
+                    }
+                    if let Some(disasm_view) = disasm_view {
+                        f.render_stateful_widget(
+                            make_list(disasm, &function, false, theme),
+                            disasm_view,
+                            &mut pane.editor_state,
+                        );
+                    }
+                }
+            }
 
+            if let Some(compare_view) = compare_view {
+                let title = app
+                    .comparison
+                    .as_ref()
+                    .map(|x| format!("vs {}", x.file.display()))
+                    .unwrap_or_default();
+                let items = match app.compare_current() {
+                    Some(lines) => lines
+                        .into_iter()
+                        .map(|line| match line {
+                            DiffLine::Same(text) => (format!("  {}", text), theme.text()),
+                            DiffLine::Removed(text) => (format!("- {}", text), theme.bad()),
+                            DiffLine::Added(text) => (format!("+ {}", text), theme.good()),
+                            DiffLine::Changed(_, text) => (format!("~ {}", text), theme.changed()),
+                        })
+                        .map(|(text, style)| ListItem::new(Spans::from(text)).style(style))
+                        .collect(),
+                    None => vec![ListItem::new(Spans::from("no function with this name"))],
+                };
+                f.render_widget(list(items, &title, false, theme), compare_view);
+            }
+
+            if let Some(calls_view) = calls_view {
+                let labels = app.call_tree().into_iter().map(|x| x.label);
+                f.render_stateful_widget(
+                    make_list(labels, "Calls", app.selected == Column::Calls, theme),
+                    calls_view,
+                    &mut app.call_state,
+                );
+            }
+
+            if let (Some(registers_view), Some(trace)) = (registers_view, &app.trace) {
+                let registers = trace
+                    .registers
+                    .iter()
+                    .map(|(name, value)| format!("{:>6} {:#018x}", name, value));
+                let title = format!("Registers (step {})", trace.steps);
+                f.render_widget(make_list(registers, &title, false, theme), registers_view);
+            }
+
+            if let Some(lines_view) = lines_view {
+                let (title, text) = app.source_panel();
+                f.render_stateful_widget(
+                    make_list(text, &title, false, theme),
+                    lines_view,
+                    &mut app.line_state,
+                );
+            }
+
+            if let Some(scratch_view) = scratch_view {
+                let title = format!("Scratchpad at {:#x}", app.scratchpad.address);
                 f.render_widget(
-                    make_list(disasm, "Disasm", app.selected == Column::Disasm),
-                    disasm_view,
+                    make_list(
+                        app.scratchpad.labels(&app.engines),
+                        &title,
+                        app.mode == Mode::Scratch,
+                        theme,
+                    ),
+                    scratch_view,
                 );
             }
 
-            let paragraph = Paragraph::new(app.get_bar())
-                .style(Style::default().fg(Color::White))
+            if let (Some(struct_view), Some((title, fields))) = (struct_view, app.struct_panel()) {
+                f.render_widget(make_list(fields, &title, false, theme), struct_view);
+            }
+
+            let mut bar = vec![Span::raw(app.get_bar())];
+            if let Some((size, original)) = app.byte_budget() {
+                let style = if size > original {
+                    theme.bad()
+                } else {
+                    theme.text()
+                };
+                bar.push(Span::styled(
+                    format!(" | {} bytes (orig {})", size, original),
+                    style,
+                ));
+            }
+            if let Some((va, _, name)) = app.pointer_at_cursor() {
+                bar.push(Span::raw(format!(" | -> {} ({:#x})", name, va)));
+            }
+            let paragraph = Paragraph::new(Spans::from(bar))
+                .style(theme.text())
                 .block(Block::default().borders(Borders::NONE));
             f.render_widget(paragraph, _bar);
         })?;
+        if let Some(announcer) = &mut announcer {
+            announcer.announce(app.announcements(), &mut io::stdout())?;
+        }
 
-        match events.next()? {
+        let event = match app.macros.next() {
+            Some(key) => {
+                // rows are rebuilt on ticks, which don't come between replayed keys
+                if app.mode == Mode::Viewing && app.selected.editable() && app.is_dirty() {
+                    app.rebuild();
+                }
+                Event::Input(key)
+            }
+            None => {
+                let event = events.next()?;
+                if let Event::Input(key) = &event {
+                    app.macros.record(key);
+                }
+                event
+            }
+        };
+        match event {
             Event::Input(input) => {
+                if app.show_overview {
+                    match input {
+                        Key::Char('q') => break,
+                        _ => app.show_overview = false,
+                    }
+                    continue;
+                }
+                if app.stats.take().is_some() {
+                    continue;
+                }
+                if app.show_hooks {
+                    app.show_hooks = false;
+                    continue;
+                }
+                if let Some(prompt) = app.macros.prompt.take() {
+                    app.message = None;
+                    match (prompt, input) {
+                        (Prompt::Record, Key::Char(c)) if macros::is_register(c) => {
+                            app.macros.start(c)
+                        }
+                        (Prompt::Replay, Key::Char(c)) if c == '@' || macros::is_register(c) => {
+                            if let Err(e) = app.macros.replay(c) {
+                                app.message = Some(e);
+                            }
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.mode == Mode::Viewing {
+                    if let Key::Char(c @ '0'..='9') = input {
+                        // a leading 0 isn't a count
+                        if c != '0' || count.is_some() {
+                            let digit = c as usize - '0' as usize;
+                            // small enough to add to a cursor or row index without overflowing
+                            let value = count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                            count = Some(value.min(u32::MAX as usize));
+                            continue;
+                        }
+                    }
+                }
+                let action = match app.mode {
+                    Mode::Viewing => match app.settings.keymap.action(&mut pending, input) {
+                        Some(action) => action,
+                        None => continue,
+                    },
+                    _ => Action::Key(input),
+                };
+                let count = count.take().unwrap_or(1);
+                let input = match action {
+                    Action::Key(key) => key,
+                    Action::Append => {
+                        app.cursor_to_end();
+                        Key::Char('e')
+                    }
+                    Action::Column(forward) => {
+                        app.cycle_column(forward);
+                        continue;
+                    }
+                    _ if !app.selected.editable() => continue,
+                    action => {
+                        let result = match action {
+                            Action::Delete => app.nop(count),
+                            Action::Yank => Ok(app.yank(count)),
+                            _ => app.paste(),
+                        };
+                        app.message = Some(result.unwrap_or_else(|e| format!("error: {}", e)));
+                        continue;
+                    }
+                };
+                if let (Some(runner), Mode::Viewing) = (&mut app.runner, &app.mode) {
+                    let page = termion::terminal_size().map_or(20, |(_, rows)| rows as isize - 3);
+                    let height = page.max(1) as usize;
+                    match input {
+                        Key::Esc => app.runner = None,
+                        Key::Down => runner.scroll(count as isize, height),
+                        Key::Up => runner.scroll(-(count as isize), height),
+                        Key::PageDown => runner.scroll(page, height),
+                        Key::PageUp => runner.scroll(-page, height),
+                        Key::Home => runner.scroll(isize::MIN / 2, height),
+                        Key::End => runner.scroll(isize::MAX / 2, height),
+                        Key::Char('!') => {
+                            app.message = Some(match app.run_target(None) {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char(':') => {
+                            app.command.clear();
+                            app.mode = Mode::Command;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let (Some(linear), Mode::Viewing) = (&mut app.linear, &app.mode) {
+                    let page = termion::terminal_size().map_or(20, |(_, rows)| rows as isize - 3);
+                    match input {
+                        Key::Esc => app.linear = None,
+                        Key::Down => linear.move_selection(count as isize),
+                        Key::Up => linear.move_selection(-(count as isize)),
+                        Key::PageDown => linear.move_selection(page),
+                        Key::PageUp => linear.move_selection(-page),
+                        Key::Home => linear.move_selection(isize::MIN / 2),
+                        Key::End => linear.move_selection(isize::MAX / 2),
+                        Key::Char('\n') => {
+                            app.message = Some(match app.leave_linear() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char(':') => {
+                            app.command.clear();
+                            app.mode = Mode::Command;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let (Some(got), Mode::Viewing) = (&mut app.got, &app.mode) {
+                    let page = termion::terminal_size().map_or(20, |(_, rows)| rows as isize - 3);
+                    match input {
+                        Key::Esc => app.got = None,
+                        Key::Down => got.move_selection(count as isize),
+                        Key::Up => got.move_selection(-(count as isize)),
+                        Key::PageDown => got.move_selection(page),
+                        Key::PageUp => got.move_selection(-page),
+                        Key::Home => got.move_selection(isize::MIN / 2),
+                        Key::End => got.move_selection(isize::MAX / 2),
+                        Key::Char('\n') => {
+                            app.message = Some(match app.follow_got() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char(':') => {
+                            app.command.clear();
+                            app.mode = Mode::Command;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let (Some(vtables), Mode::Viewing) = (&mut app.vtables, &app.mode) {
+                    let page = termion::terminal_size().map_or(20, |(_, rows)| rows as isize - 3);
+                    match input {
+                        Key::Esc => app.vtables = None,
+                        Key::Down => vtables.move_selection(count as isize),
+                        Key::Up => vtables.move_selection(-(count as isize)),
+                        Key::PageDown => vtables.move_selection(page),
+                        Key::PageUp => vtables.move_selection(-page),
+                        Key::Home => vtables.move_selection(isize::MIN / 2),
+                        Key::End => vtables.move_selection(isize::MAX / 2),
+                        Key::Char('\n') => {
+                            app.message = Some(match app.follow_slot() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char(':') => {
+                            app.command.clear();
+                            app.mode = Mode::Command;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
                 // handle mode specific operations
                 match app.mode {
                     Mode::Viewing => match input {
                         Key::Char('q') => {
                             break;
                         }
+                        Key::Char('Q') => match app.macros.stop() {
+                            Some((register, keys)) => {
+                                app.message =
+                                    Some(format!("recorded {} key(s) into @{}", keys, register))
+                            }
+                            None => {
+                                app.macros.prompt = Some(Prompt::Record);
+                                app.message = Some("register to record into".to_string())
+                            }
+                        },
+                        Key::Char('@') => {
+                            app.macros.prompt = Some(Prompt::Replay);
+                            app.message = Some("register to replay, @ for the last one".to_string())
+                        }
                         Key::Char('w') => {
-                            app.write();
+                            app.message = Some(match app.signing_warning() {
+                                Some(warning) => warning,
+                                None => match app.save() {
+                                    Ok(message) => message,
+                                    Err(e) => format!("error: {}", e),
+                                },
+                            });
                         }
                         Key::Char('a') => app.select(Column::Function),
                         Key::Char('s') => app.select(Column::Hex),
                         Key::Char('d') => app.select(Column::Disasm),
-                        Key::Char('e') if app.selected != Column::Function => {
-                            app.mode = Mode::Editing
+                        Key::Char('c') if app.show_calls => {
+                            app.select(Column::Calls);
+                            app.call_state.select(Some(0));
+                        }
+                        Key::Char('t') => app.toggle_calls(),
+                        Key::Char(c @ 'S') | Key::Char(c @ 'D') => {
+                            let column = match c {
+                                'S' => Column::Hex,
+                                _ => Column::Disasm,
+                            };
+                            app.message = Some(match app.toggle_column(column) {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('l') => app.toggle_lines(),
+                        Key::Char('h') => app.toggle_overview(),
+                        Key::Char('\n') if app.selected == Column::Calls => app.follow_call(),
+                        Key::Char('\n') if app.pointer_at_cursor().is_some() => {
+                            app.message = Some(match app.follow_pointer() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('\n') if app.selected.editable() => {
+                            app.message = Some(match app.follow_jump_table() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Backspace => {
+                            app.message = Some(match app.go_back() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('o') => app.cycle_sort(),
+                        Key::Char('b') => app.cycle_radix(),
+                        Key::Char('!') => {
+                            app.message = Some(match app.run_target(None) {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('x') => app.cycle_grouping(),
+                        Key::Char('A') => app.toggle_ascii(),
+                        Key::Char('B') => app.toggle_classes(),
+                        Key::Char('z') => app.open_scratchpad(),
+                        Key::Char('Z') => app.show_scratchpad = false,
+                        Key::Char('r') if app.changed_on_disk => {
+                            app.message = Some(match app.reload() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('v') => app.toggle_split(),
+                        Key::Ctrl('w') => app.switch_pane(),
+                        Key::Ctrl('p') => {
+                            app.message = Some(match app.open_palette() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('m') if app.selected.editable() => app.toggle_bookmark(),
+                        Key::Char('+') if app.selected.editable() => app.toggle_cursor(),
+                        Key::Char('u') if app.selected.editable() => {
+                            app.message = Some(match app.undo() {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('p') if app.selected.editable() => app.toggle_breakpoint(),
+                        // as a command so it's recorded like one
+                        Key::Char('C') if app.selected.editable() => {
+                            app.command = "duplicate".to_string();
+                            app.run_command();
+                        }
+                        Key::Char('i') if app.selected.editable() => {
+                            app.message = Some(match app.invert_branch() {
+                                Ok(text) => format!("inverted to {}", text),
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('n') if app.trace.is_some() => {
+                            for _ in 0..count {
+                                app.advance_trace(false)
+                            }
+                        }
+                        Key::Char('n') if app.selected.editable() => {
+                            app.message = Some(match app.nop(count) {
+                                Ok(message) => message,
+                                Err(e) => format!("error: {}", e),
+                            });
+                        }
+                        Key::Char('g') if app.trace.is_some() => app.advance_trace(true),
+                        Key::Char(':') => {
+                            app.command.clear();
+                            app.mode = Mode::Command;
+                        }
+                        Key::Char('e') if app.selected.editable() => {
+                            if app.selected == Column::Hex && app.grouping != Grouping::Instruction
+                            {
+                                app.message =
+                                    Some("hex is only editable grouped by instruction".to_string())
+                            } else if app.editable() {
+                                app.mode = Mode::Editing
+                            } else {
+                                app.message =
+                                    Some("this function isn't all in the file".to_string())
+                            }
                         }
                         _ => {}
                     },
                     Mode::Editing => match input {
-                        Key::Esc => {
-                            app.mode = Mode::Viewing;
+                        Key::Esc => app.stop_editing(),
+                        Key::Char('\t') => app.complete(),
+                        Key::Up | Key::Down => {}
+                        // readline keys, moving within the row included
+                        _ => {
+                            app.apply_key(input);
+                            continue;
                         }
-                        Key::Char(_) | Key::Delete | Key::Backspace | Key::Home | Key::End => {
-                            app.apply_key(input)
-                        }
-                        _ => {}
                     },
+                    Mode::Command => {
+                        match input {
+                            Key::Esc => app.mode = Mode::Viewing,
+                            Key::Char('\n') => app.run_command(),
+                            Key::Char(c) => app.command.push(c),
+                            Key::Backspace => {
+                                app.command.pop();
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    Mode::Wizard => {
+                        match input {
+                            Key::Esc => {
+                                app.wizard = None;
+                                app.command.clear();
+                                app.message = Some("no trampoline added".to_string());
+                                app.mode = Mode::Viewing;
+                            }
+                            Key::Char('\n') => app.answer_wizard(),
+                            Key::Char(c) => app.command.push(c),
+                            Key::Backspace => {
+                                app.command.pop();
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    Mode::Scratch => {
+                        match input {
+                            Key::Esc => app.mode = Mode::Viewing,
+                            Key::Char(c) => app.scratchpad.push(c),
+                            Key::Backspace => app.scratchpad.pop(),
+                            Key::Ctrl('l') => app.scratchpad.clear(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    Mode::Palette => {
+                        let page =
+                            termion::terminal_size().map_or(20, |(_, rows)| rows as isize - 3);
+                        match (input, &mut app.palette) {
+                            (Key::Esc, _) => {
+                                app.palette = None;
+                                app.mode = Mode::Viewing;
+                            }
+                            (Key::Char('\n'), _) => {
+                                app.message = Some(match app.leave_palette() {
+                                    Ok(message) => message,
+                                    Err(e) => format!("error: {}", e),
+                                });
+                            }
+                            (Key::Char(c), Some(palette)) => palette.push(c),
+                            (Key::Backspace, Some(palette)) => palette.pop(),
+                            (Key::Down, Some(palette)) => palette.move_selection(1),
+                            (Key::Up, Some(palette)) => palette.move_selection(-1),
+                            (Key::PageDown, Some(palette)) => palette.move_selection(page),
+                            (Key::PageUp, Some(palette)) => palette.move_selection(-page),
+                            _ => {}
+                        }
+                        continue;
+                    }
                 }
 
                 // handle cursor movement or list select state
                 match app.selected {
+                    Column::Calls => match input {
+                        Key::Down => app.next_column(count),
+                        Key::Up => app.previous_column(count),
+                        _ => {}
+                    },
                     Column::Function => match input {
                         Key::Down => {
-                            app.next_column();
+                            app.next_column(count);
                             app.editor_state.select(Some(0));
                         }
                         Key::Up => {
-                            app.previous_column();
+                            app.previous_column(count);
                             app.editor_state.select(Some(0));
                         }
                         _ => {}
                     },
                     Column::Hex | Column::Disasm => match input {
                         Key::Down => {
-                            app.next_column();
+                            app.next_column(count);
                         }
                         Key::Up => {
-                            app.previous_column();
+                            app.previous_column(count);
                         }
-                        Key::Left => app.set_cursor(app.get_cursor() - 1),
-                        Key::Right => app.set_cursor(app.get_cursor() + 1),
+                        Key::Left => app.set_cursor(app.get_cursor() - count as isize),
+                        Key::Right => app.set_cursor(app.get_cursor() + count as isize),
                         Key::Home => app.set_cursor(0),
-                        Key::End => {
-                            let len = app
-                                .get(
-                                    app.get_current_function().clone().name,
-                                    app.editor_state.selected().unwrap_or(0),
-                                )
-                                .map(|x| match app.selected {
-                                    Column::Disasm => x.1.len(),
-                                    Column::Hex => x.0.len(),
-                                    _ => 0,
-                                })
-                                .unwrap_or(0) as isize;
-                            app.set_cursor(len - 1)
-                        }
+                        Key::End => app.cursor_to_end(),
                         _ => {}
                     },
                 }
             }
 
             Event::Tick => {
-                let editable = app.selected.editable();
-                if editable {
+                app.check_file();
+                if app.selected.editable() && app.is_dirty() {
                     app.rebuild();
                 }
+                app.autosave(false);
+                app.poll_hooks();
+                app.poll_runner();
             }
         }
     }
+    if app.selected.editable() && app.is_dirty() {
+        app.rebuild();
+    }
+    app.autosave(true);
 
+    drop(working_copy);
+    if opt.timings {
+        // after the terminal is back to normal so it isn't drawn over
+        drop(terminal);
+        drop(_raw);
+        eprintln!("{}", app.timings);
+    }
     Ok(())
 }
 
-fn make_list(items: impl IntoIterator<Item = String>, title: &str, selected: bool) -> List {
-    List::new(
+fn make_list(
+    items: impl IntoIterator<Item = String>,
+    title: &str,
+    selected: bool,
+    theme: Theme,
+) -> List {
+    list(
         items
             .into_iter()
             .map(|i| {
                 let lines = vec![Spans::from(i)];
-                ListItem::new(lines).style(Style::default().fg(Color::White))
+                ListItem::new(lines).style(theme.text())
             })
             .collect::<Vec<_>>(),
+        title,
+        selected,
+        theme,
     )
-    .block(if selected {
-        Block::default()
-            .borders(Borders::ALL)
-            .title(title)
-            .border_style(Style::default().fg(Color::LightGreen))
-    } else {
-        Block::default().borders(Borders::ALL).title(title)
-    })
-    .highlight_style(
-        Style::default()
-            .bg(Color::LightGreen)
-            .fg(Color::Black)
-            .add_modifier(Modifier::BOLD),
-    )
+}
+
+/// One item per row, rows which don't assemble (or disassemble) are underlined in red, rows
+/// using `forbidden` instruction set extensions are magenta and the row at `pc` is highlighted.
+fn row_items<'a>(
+    labels: impl IntoIterator<Item = impl Into<Spans<'a>>>,
+    rows: &'a [InstructionRow],
+    pc: Option<u64>,
+    forbidden: &[String],
+    theme: Theme,
+) -> Vec<ListItem<'a>> {
+    labels
+        .into_iter()
+        .zip(rows)
+        .map(|(label, row)| {
+            let style = if pc == Some(row.offset) {
+                theme.pc()
+            } else if !row.valid {
+                theme.invalid()
+            } else if row.uses(forbidden).is_some() {
+                theme.forbidden()
+            } else {
+                theme.text()
+            };
+            ListItem::new(label.into()).style(style)
+        })
+        .collect()
+}
+
+/// A line of the minimap: a heavier line where rows are on screen, a square where a row is
+/// marked, and reversed at the cursor.
+fn minimap_span(cell: &Cell, theme: Theme) -> Span<'static> {
+    let (symbol, mut style) = match cell.mark {
+        Some(Mark::Bookmark) => ("■", theme.good()),
+        Some(Mark::Hit) => ("■", theme.forbidden()),
+        Some(Mark::Modified) => ("■", theme.changed()),
+        None if cell.shown => ("┃", theme.text()),
+        None => ("│", theme.text()),
+    };
+    if cell.cursor {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+    Span::styled(symbol, style)
+}
+
+/// A Hex column row with its bytes colored by class, clipped to `width` from `scroll`.
+fn class_spans(
+    label: &str,
+    classes: &[Class],
+    scroll: usize,
+    width: usize,
+    theme: Theme,
+) -> Spans<'static> {
+    let spans = byteclass::runs(label, classes, scroll, width)
+        .into_iter()
+        .map(|(text, class)| match class {
+            Some(class) if class != Class::Other => Span::styled(text, theme.byte(class)),
+            _ => Span::raw(text),
+        })
+        .collect::<Vec<_>>();
+    Spans::from(spans)
+}
+
+/// The left side of the overview (format, entry point and mitigations, green when they're in
+/// effect and red when they aren't) and the sections on the right.
+fn overview_items(overview: &Overview, theme: Theme) -> (Vec<ListItem<'_>>, Vec<ListItem<'_>>) {
+    let text = theme.text();
+    let mut summary = vec![
+        ListItem::new(Spans::from(overview.format.clone())).style(text),
+        ListItem::new(Spans::from(format!("arch   {}", overview.arch))).style(text),
+        ListItem::new(Spans::from(format!("entry  {:#x}", overview.entry))).style(text),
+        ListItem::new(Spans::from("")),
+    ];
+    summary.extend(overview.mitigations.iter().map(|x| {
+        let style = if x.enabled { theme.good() } else { theme.bad() };
+        ListItem::new(Spans::from(vec![
+            Span::styled(format!("{:<16}", x.name), text),
+            Span::styled(x.state.clone(), style),
+        ]))
+    }));
+    let sections = overview
+        .sections
+        .iter()
+        .map(|x| {
+            ListItem::new(Spans::from(format!(
+                "{:<20} {:#012x} {:>#10x} {}",
+                x.name, x.va, x.size, x.permissions
+            )))
+            .style(text)
+        })
+        .collect();
+    (summary, sections)
+}
+
+/// a bordered list of already styled items, its border highlighted if `selected`
+fn list<'a>(items: Vec<ListItem<'a>>, title: &'a str, selected: bool, theme: Theme) -> List<'a> {
+    List::new(items)
+        .block(if selected {
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(theme.focused())
+        } else {
+            Block::default().borders(Borders::ALL).title(title)
+        })
+        .highlight_style(theme.highlight())
 }
@@ -0,0 +1,90 @@
+use std::ops::Range;
+
+/// What the minimap marks a row for, the more important last since a cell standing for several
+/// rows shows the most important of their marks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Mark {
+    Modified,
+    /// matched by a `:yara` rule
+    Hit,
+    Bookmark,
+}
+
+/// A line of the minimap on the right edge of the Disasm column, standing for a run of rows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub mark: Option<Mark>,
+    /// whether any of its rows is on screen
+    pub shown: bool,
+    /// whether the selected row is one of them
+    pub cursor: bool,
+}
+
+/// The minimap of a function whose rows have `marks`, `height` lines tall, with the rows
+/// `shown` on screen and the `cursor` on one.  A function shorter than the minimap is stretched
+/// over it.
+pub fn cells(
+    marks: &[Option<Mark>],
+    height: usize,
+    shown: Range<usize>,
+    cursor: usize,
+) -> Vec<Cell> {
+    let rows = marks.len();
+    (0..height)
+        .map(|i| {
+            let start = i * rows / height;
+            let end = ((i + 1) * rows / height).max(start + 1).min(rows);
+            Cell {
+                mark: marks
+                    .get(start..end)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .max()
+                    .copied(),
+                shown: start < end && start < shown.end && shown.start < end,
+                cursor: (start..end).contains(&cursor),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells() {
+        // 8 rows in 4 lines, the first 4 on screen
+        let mut marks = vec![None; 8];
+        marks[1] = Some(Mark::Modified);
+        marks[5] = Some(Mark::Modified);
+        marks[4] = Some(Mark::Bookmark);
+        let map = cells(&marks, 4, 0..4, 7);
+        assert_eq!(
+            vec![Some(Mark::Modified), None, Some(Mark::Bookmark), None],
+            map.iter().map(|x| x.mark).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![true, true, false, false],
+            map.iter().map(|x| x.shown).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![false, false, false, true],
+            map.iter().map(|x| x.cursor).collect::<Vec<_>>()
+        );
+
+        // 2 rows stretched over 4 lines
+        let stretched = cells(&[Some(Mark::Hit), None], 4, 0..2, 0);
+        assert_eq!(
+            vec![Some(Mark::Hit), Some(Mark::Hit), None, None],
+            stretched.iter().map(|x| x.mark).collect::<Vec<_>>()
+        );
+        assert!(cells(&[], 3, 0..0, 0).iter().all(|x| *x
+            == Cell {
+                mark: None,
+                shown: false,
+                cursor: false
+            }));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::config::Settings;
+use crate::export;
+use crate::util::{self, TempDir};
+use goblin::elf::program_header::{PF_R, PF_W, PF_X, PT_LOAD};
+use goblin::elf::Elf;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+use std::process::Command;
+
+/// One run of changed bytes and where it ends up in memory.
+struct Hunk {
+    /// relative to the load bias, i.e. the virtual address in the file
+    va: u64,
+    bytes: Vec<u8>,
+    /// mprotect flags of the segment, restored once the bytes are copied in
+    prot: u32,
+}
+
+/// C source for a shared object which, preloaded with LD_PRELOAD, copies the difference between
+/// `original` and `patched` into memory as the process starts, leaving the file on disk (and its
+/// signature) untouched.  `name` is the file's name, used to find it among the loaded objects
+/// when it's a library rather than the executable.
+pub fn source(original: &[u8], patched: &[u8], name: &str) -> Result<String, Box<dyn Error>> {
+    if original.len() != patched.len() {
+        return Err("the file changed size, only edits in place can be overlaid".into());
+    }
+    let elf = Elf::parse(original).map_err(|_| "only ELF files can be overlaid")?;
+    let hunks = export::changed_runs(original, patched)
+        .into_iter()
+        .map(|(start, end)| {
+            let (start, end) = (start as u64, end as u64);
+            let segment = elf
+                .program_headers
+                .iter()
+                .filter(|x| x.p_type == PT_LOAD)
+                .find(|x| x.p_offset <= start && end <= x.p_offset + x.p_filesz)
+                .ok_or_else(|| format!("the edit at {:#x} isn't in a loaded segment", start))?;
+            let flag = |flag, prot| if segment.p_flags & flag != 0 { prot } else { 0 };
+            Ok(Hunk {
+                va: start - segment.p_offset + segment.p_vaddr,
+                bytes: patched[start as usize..end as usize].to_vec(),
+                prot: flag(PF_R, 1) | flag(PF_W, 2) | flag(PF_X, 4),
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    if hunks.is_empty() {
+        return Err("there are no edits to overlay".into());
+    }
+    // executables are always the first object, libraries have to be found by name
+    let library = elf.header.e_type == goblin::elf::header::ET_DYN && elf.interpreter.is_none();
+
+    let mut c = String::new();
+    writeln!(
+        c,
+        "/* generated by transmogrify, overlays edits to {} */",
+        name
+    )?;
+    c.push_str(PRELUDE);
+    for (i, hunk) in hunks.iter().enumerate() {
+        let bytes = hunk
+            .bytes
+            .iter()
+            .map(|x| format!("{:#04x}", x))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            c,
+            "static const unsigned char bytes{}[] = {{{}}};",
+            i, bytes
+        )?;
+    }
+    writeln!(c, "static const struct hunk hunks[] = {{")?;
+    for (i, hunk) in hunks.iter().enumerate() {
+        writeln!(
+            c,
+            "    {{{:#x}, sizeof(bytes{}), bytes{}, {}}},",
+            hunk.va, i, i, hunk.prot
+        )?;
+    }
+    writeln!(c, "}};")?;
+    if library {
+        let name = name.escape_default();
+        writeln!(c, "static const char *target = \"{}\";", name)?;
+    } else {
+        writeln!(c, "static const char *target = NULL;")?;
+    }
+    c.push_str(APPLY);
+    Ok(c)
+}
+
+/// write the overlay to `path`, as C source if it ends in `.c` and compiled with the configured
+/// compiler otherwise
+pub fn build(source: &str, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+    if path.extension().is_some_and(|x| x == "c") {
+        std::fs::write(path, source)?;
+        return Ok(());
+    }
+    let directory = TempDir::new("overlay")?;
+    let c = directory.join("overlay.c");
+    std::fs::write(&c, source)?;
+    util::run(
+        Command::new(&settings.cc)
+            .args(["-shared", "-fPIC", "-O2", "-o"])
+            .arg(path)
+            .arg(&c),
+    )?;
+    Ok(())
+}
+
+const PRELUDE: &str = r#"#define _GNU_SOURCE
+#include <link.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+struct hunk {
+    unsigned long va;
+    unsigned long len;
+    const unsigned char *bytes;
+    int prot;
+};
+
+"#;
+
+const APPLY: &str = r#"
+static int find(struct dl_phdr_info *info, size_t size, void *bias) {
+    const char *name = strrchr(info->dlpi_name, '/');
+    name = name ? name + 1 : info->dlpi_name;
+    if (target ? strcmp(name, target) == 0 : info->dlpi_name[0] == '\0') {
+        *(ElfW(Addr) *)bias = info->dlpi_addr;
+        return 1;
+    }
+    return 0;
+}
+
+__attribute__((constructor)) static void overlay(void) {
+    ElfW(Addr) bias = 0;
+    if (!dl_iterate_phdr(find, &bias)) {
+        return;
+    }
+    unsigned long page = sysconf(_SC_PAGESIZE);
+    for (size_t i = 0; i < sizeof(hunks) / sizeof(*hunks); i++) {
+        unsigned long start = bias + hunks[i].va;
+        unsigned long first = start & -page;
+        unsigned long len = ((start + hunks[i].len + page - 1) & -page) - first;
+        mprotect((void *)first, len, PROT_READ | PROT_WRITE | PROT_EXEC);
+        memcpy((void *)start, hunks[i].bytes, hunks[i].len);
+        mprotect((void *)first, len, hunks[i].prot);
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary;
+
+    #[test]
+    fn test_source() {
+        let original = binary::test_elf(0x400000, &[0x90; 0x10]);
+        let mut patched = original.clone();
+        patched[0x1004..0x1006].copy_from_slice(&[0xeb, 0xfe]);
+        let c = source(&original, &patched, "a.out").unwrap();
+        assert!(c.contains("bytes0[] = {0xeb, 0xfe};"));
+        assert!(c.contains("{0x401004, sizeof(bytes0), bytes0, 5},"));
+        assert!(c.contains("target = NULL;"));
+
+        assert!(source(&original, &original, "a.out").is_err());
+        assert!(source(&original, &patched[..0x1008], "a.out").is_err());
+        assert!(source(&[0; 0x40], &[1; 0x40], "a.out").is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        let directory = TempDir::new("test").unwrap();
+        let path = directory.join("overlay.c");
+        build("int x;", &path, &Settings::default()).unwrap();
+        assert_eq!("int x;", std::fs::read_to_string(&path).unwrap());
+    }
+}
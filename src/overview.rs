@@ -0,0 +1,334 @@
+use crate::binary::{
+    self, DYNAMIC_BASE, GUARD_CF, HIGH_ENTROPY_VA, NO_SEH, NX_COMPAT, RELOCS_STRIPPED,
+};
+use goblin::elf::dynamic::{DF_1_NOW, DF_BIND_NOW};
+use goblin::elf::header::{ET_CORE, ET_DYN, ET_EXEC, ET_REL};
+use goblin::elf::program_header::{PF_X, PT_GNU_RELRO, PT_GNU_STACK, PT_LOAD};
+use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE};
+use goblin::elf::Elf;
+use goblin::pe::PE;
+use goblin::Object;
+use std::error::Error;
+
+/// What's shown before the editor opens, roughly what `file` and checksec would say.
+#[derive(Debug, Clone)]
+pub struct Overview {
+    /// e.g. "ELF64, position independent executable"
+    pub format: String,
+    pub arch: String,
+    pub entry: u64,
+    pub sections: Vec<Section>,
+    pub mitigations: Vec<Mitigation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub va: u64,
+    pub size: u64,
+    /// rwx, dashes for what's missing
+    pub permissions: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mitigation {
+    pub name: &'static str,
+    /// e.g. "partial" for RELRO
+    pub state: String,
+    pub enabled: bool,
+}
+
+impl Mitigation {
+    fn new(name: &'static str, enabled: bool, state: impl Into<String>) -> Mitigation {
+        Mitigation {
+            name,
+            state: state.into(),
+            enabled,
+        }
+    }
+}
+
+pub fn read(data: &[u8]) -> Result<Overview, Box<dyn Error>> {
+    let arch = binary::arch(data)?;
+    match Object::parse(data)? {
+        Object::Elf(elf) => Ok(elf_overview(&elf, arch)),
+        Object::PE(pe) => Ok(pe_overview(&pe, data, arch)),
+        _ => Err("only ELF and PE files are supported".into()),
+    }
+}
+
+fn permissions(read: bool, write: bool, execute: bool) -> String {
+    [(read, 'r'), (write, 'w'), (execute, 'x')]
+        .iter()
+        .map(|&(set, flag)| if set { flag } else { '-' })
+        .collect()
+}
+
+fn elf_overview(elf: &Elf, arch: String) -> Overview {
+    let kind = match elf.header.e_type {
+        ET_EXEC => "executable",
+        ET_DYN if elf.interpreter.is_some() => "position independent executable",
+        ET_DYN => "shared object",
+        ET_REL => "relocatable object",
+        ET_CORE => "core dump",
+        _ => "unknown type",
+    };
+    let sections = elf
+        .section_headers
+        .iter()
+        .filter(|x| x.sh_size != 0)
+        .map(|x| Section {
+            name: elf
+                .shdr_strtab
+                .get(x.sh_name)
+                .and_then(|x| x.ok())
+                .unwrap_or_default()
+                .to_string(),
+            va: x.sh_addr,
+            size: x.sh_size,
+            permissions: permissions(
+                x.sh_flags & SHF_ALLOC as u64 != 0,
+                x.sh_flags & SHF_WRITE as u64 != 0,
+                x.sh_flags & SHF_EXECINSTR as u64 != 0,
+            ),
+        })
+        .collect();
+
+    let stack = elf
+        .program_headers
+        .iter()
+        .find(|x| x.p_type == PT_GNU_STACK);
+    let nx = match stack {
+        Some(stack) if stack.p_flags & PF_X != 0 => {
+            Mitigation::new("NX", false, "executable stack")
+        }
+        Some(_) => Mitigation::new("NX", true, "enabled"),
+        // the kernel defaults to an executable stack without the header
+        None => Mitigation::new("NX", false, "no GNU_STACK header"),
+    };
+    let pie = match elf.header.e_type {
+        ET_EXEC => {
+            let base = elf
+                .program_headers
+                .iter()
+                .filter(|x| x.p_type == PT_LOAD)
+                .map(|x| x.p_vaddr & !0xfff)
+                .min()
+                .unwrap_or_default();
+            Mitigation::new("PIE", false, format!("loads at {:#x}", base))
+        }
+        ET_DYN => Mitigation::new("PIE", true, "enabled"),
+        _ => Mitigation::new("PIE", false, "not loadable"),
+    };
+    let bind_now = elf
+        .dynamic
+        .as_ref()
+        .is_some_and(|x| x.info.flags & DF_BIND_NOW != 0 || x.info.flags_1 & DF_1_NOW != 0);
+    let relro = match (
+        elf.program_headers.iter().any(|x| x.p_type == PT_GNU_RELRO),
+        bind_now,
+    ) {
+        (true, true) => Mitigation::new("RELRO", true, "full"),
+        (true, false) => Mitigation::new("RELRO", false, "partial, the GOT stays writable"),
+        (false, _) => Mitigation::new("RELRO", false, "none"),
+    };
+    let symbols = elf
+        .dynsyms
+        .iter()
+        .filter_map(|x| elf.dynstrtab.get(x.st_name).and_then(|x| x.ok()))
+        .chain(
+            elf.syms
+                .iter()
+                .filter_map(|x| elf.strtab.get(x.st_name).and_then(|x| x.ok())),
+        )
+        .collect::<Vec<_>>();
+    let canary = symbols
+        .iter()
+        .any(|x| x.starts_with("__stack_chk_") || *x == "__intel_security_cookie");
+    let canary = if canary {
+        Mitigation::new("Canary", true, "found")
+    } else {
+        Mitigation::new("Canary", false, "no __stack_chk_fail")
+    };
+    let mut fortified = symbols
+        .iter()
+        .filter(|x| x.starts_with("__") && x.ends_with("_chk") && !x.starts_with("__stack_chk"))
+        .collect::<Vec<_>>();
+    fortified.sort();
+    fortified.dedup();
+    let fortify = if fortified.is_empty() {
+        Mitigation::new("Fortify", false, "no fortified functions")
+    } else {
+        Mitigation::new(
+            "Fortify",
+            true,
+            format!("{} fortified function(s)", fortified.len()),
+        )
+    };
+
+    Overview {
+        format: format!("ELF{}, {}", if elf.is_64 { 64 } else { 32 }, kind),
+        arch,
+        entry: elf.entry,
+        sections,
+        mitigations: vec![nx, pie, relro, canary, fortify],
+    }
+}
+
+fn pe_overview(pe: &PE, data: &[u8], arch: String) -> Overview {
+    let image_base = pe.image_base as u64;
+    let sections = pe
+        .sections
+        .iter()
+        .map(|x| Section {
+            name: x.name().unwrap_or_default().to_string(),
+            va: image_base + x.virtual_address as u64,
+            size: x.virtual_size as u64,
+            permissions: permissions(
+                x.characteristics & 0x4000_0000 != 0,
+                x.characteristics & 0x8000_0000 != 0,
+                x.characteristics & 0x2000_0000 != 0,
+            ),
+        })
+        .collect();
+    let characteristics = pe
+        .header
+        .optional_header
+        .map_or(0, |x| x.windows_fields.dll_characteristics);
+    let set = |flag| characteristics & flag != 0;
+
+    let mut mitigations = vec![Mitigation::new(
+        "NX",
+        set(NX_COMPAT),
+        if set(NX_COMPAT) {
+            "enabled"
+        } else {
+            "not NX_COMPAT"
+        },
+    )];
+    mitigations.push(
+        if pe.header.coff_header.characteristics & RELOCS_STRIPPED != 0 {
+            Mitigation::new("ASLR", false, "relocations stripped")
+        } else if set(DYNAMIC_BASE) {
+            Mitigation::new("ASLR", true, "enabled")
+        } else {
+            Mitigation::new("ASLR", false, "not DYNAMIC_BASE")
+        },
+    );
+    if pe.is_64 {
+        mitigations.push(Mitigation::new(
+            "High entropy VA",
+            set(HIGH_ENTROPY_VA),
+            if set(HIGH_ENTROPY_VA) {
+                "enabled"
+            } else {
+                "disabled"
+            },
+        ));
+    }
+    mitigations.push(Mitigation::new(
+        "CFG",
+        set(GUARD_CF),
+        if set(GUARD_CF) { "enabled" } else { "disabled" },
+    ));
+    let load_config = load_config(pe, data);
+    let cookie = load_config.and_then(|x| {
+        if pe.is_64 {
+            x.get(0x58..0x60)
+                .map(|x| u64::from_le_bytes([x[0], x[1], x[2], x[3], x[4], x[5], x[6], x[7]]))
+        } else {
+            x.get(0x3c..0x40)
+                .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as u64)
+        }
+    });
+    mitigations.push(match cookie {
+        Some(cookie) if cookie != 0 => Mitigation::new("GS", true, "security cookie"),
+        _ => Mitigation::new("GS", false, "no security cookie"),
+    });
+    if !pe.is_64 {
+        let handlers = load_config.and_then(|x| x.get(0x44..0x48));
+        mitigations.push(if set(NO_SEH) {
+            Mitigation::new("SafeSEH", true, "no SEH")
+        } else if handlers.is_some_and(|x| x != [0; 4]) {
+            Mitigation::new("SafeSEH", true, "handler table")
+        } else {
+            Mitigation::new("SafeSEH", false, "no handler table")
+        });
+    }
+
+    Overview {
+        format: format!(
+            "PE32{}, {}",
+            if pe.is_64 { "+" } else { "" },
+            if pe.is_lib { "DLL" } else { "executable" }
+        ),
+        arch,
+        entry: image_base + pe.entry as u64,
+        sections,
+        mitigations,
+    }
+}
+
+/// the load configuration directory's bytes, which start with their own size
+fn load_config<'a>(pe: &PE, data: &'a [u8]) -> Option<&'a [u8]> {
+    let optional_header = pe.header.optional_header?;
+    let table = (*optional_header.data_directories.get_load_config_table())?;
+    let start = goblin::pe::utils::find_offset(
+        table.virtual_address as usize,
+        &pe.sections,
+        optional_header.windows_fields.file_alignment,
+    )?;
+    let size = data.get(start..start + 4)?;
+    let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+    data.get(start..start + size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::program_header::PT_GNU_STACK;
+
+    fn states(overview: &Overview) -> Vec<(&str, bool, &str)> {
+        overview
+            .mitigations
+            .iter()
+            .map(|x| (x.name, x.enabled, x.state.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_elf_overview() {
+        let mut data = binary::test_elf(0x400000, &[0xc3]);
+        let overview = read(&data).unwrap();
+        assert_eq!("ELF64, executable", overview.format);
+        assert_eq!(0x401000, overview.entry);
+        assert!(overview.sections.is_empty());
+        assert_eq!(
+            vec![
+                ("NX", false, "no GNU_STACK header"),
+                ("PIE", false, "loads at 0x400000"),
+                ("RELRO", false, "none"),
+                ("Canary", false, "no __stack_chk_fail"),
+                ("Fortify", false, "no fortified functions"),
+            ],
+            states(&overview)
+        );
+
+        // a shared object whose only segment is a non-executable stack
+        data[16] = 3;
+        data[64..68].copy_from_slice(&PT_GNU_STACK.to_le_bytes());
+        data[68..72].copy_from_slice(&6u32.to_le_bytes());
+        let overview = read(&data).unwrap();
+        assert_eq!("ELF64, shared object", overview.format);
+        assert_eq!(
+            vec![("NX", true, "enabled"), ("PIE", true, "enabled")],
+            states(&overview)[..2].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_permissions() {
+        assert_eq!("r-x", permissions(true, false, true));
+        assert_eq!("---", permissions(false, false, false));
+    }
+}
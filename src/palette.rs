@@ -0,0 +1,201 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use tui::widgets::ListState;
+
+/// shortest run of printable bytes taken for a string
+const MIN_STRING: usize = 5;
+/// most matches listed, the rest are a longer query away
+const MAX_MATCHES: usize = 200;
+
+/// What a palette entry is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Function,
+    Symbol,
+    String,
+    Section,
+    Bookmark,
+}
+
+impl Kind {
+    fn tag(self) -> &'static str {
+        match self {
+            Kind::Function => "fn",
+            Kind::Symbol => "sym",
+            Kind::String => "str",
+            Kind::Section => "sect",
+            Kind::Bookmark => "mark",
+        }
+    }
+}
+
+/// Where going to an entry ends up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Place {
+    /// the row at a file offset in a function
+    Row(String, u64),
+    /// a virtual address, which may be outside every function
+    Address(u64),
+}
+
+/// Something the palette can go to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub kind: Kind,
+    pub name: String,
+    pub place: Place,
+}
+
+impl Target {
+    pub fn new(kind: Kind, name: String, place: Place) -> Target {
+        Target { kind, name, place }
+    }
+}
+
+/// A Ctrl-P style list of every function, symbol, string, section and bookmark, narrowed down by
+/// fuzzy matching as a query is typed.
+#[derive(Debug)]
+pub struct Palette {
+    pub query: String,
+    targets: Vec<Target>,
+    /// indices into `targets`, best match first
+    matches: Vec<usize>,
+    /// how many targets match, which can be more than are listed
+    pub total: usize,
+    pub state: ListState,
+}
+
+impl Palette {
+    pub fn new(targets: Vec<Target>) -> Palette {
+        let mut palette = Palette {
+            query: String::new(),
+            targets,
+            matches: vec![],
+            total: 0,
+            state: ListState::default(),
+        };
+        palette.refresh();
+        palette
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.refresh();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.refresh();
+    }
+
+    /// match the query again, selecting the best match
+    fn refresh(&mut self) {
+        let matcher = SkimMatcherV2::default();
+        let mut scored = self
+            .targets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, x)| Some((matcher.fuzzy_match(&x.name, &self.query)?, i)))
+            .collect::<Vec<_>>();
+        // ties keep the order targets came in, functions first
+        scored.sort_by_key(|&(score, i)| (-score, i));
+        self.total = scored.len();
+        self.matches = scored
+            .into_iter()
+            .take(MAX_MATCHES)
+            .map(|(_, i)| i)
+            .collect();
+        self.state
+            .select(Some(0).filter(|_| !self.matches.is_empty()));
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.matches
+            .iter()
+            .map(|&i| {
+                let target = &self.targets[i];
+                format!("{:<4} {}", target.kind.tag(), target.name)
+            })
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<&Target> {
+        self.targets.get(*self.matches.get(self.state.selected()?)?)
+    }
+
+    /// move the selection by `delta` matches, stopping at either end
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as isize - 1;
+        let index = self.state.selected().unwrap_or(0) as isize + delta;
+        self.state.select(Some(index.max(0).min(last) as usize));
+    }
+}
+
+/// Runs of at least `MIN_STRING` printable ASCII characters in `data`, as the offset of each
+/// into `data` and its text.
+pub fn strings(data: &[u8]) -> Vec<(u64, String)> {
+    let mut strings = vec![];
+    let printable = |x: &u8| x.is_ascii_graphic() || *x == b' ' || *x == b'\t';
+    let mut start = 0;
+    while start < data.len() {
+        let len = data[start..].iter().take_while(|x| printable(x)).count();
+        if len >= MIN_STRING {
+            let text = String::from_utf8_lossy(&data[start..start + len]).to_string();
+            strings.push((start as u64, text));
+        }
+        start += len + 1;
+    }
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette() {
+        let targets = vec![
+            Target::new(
+                Kind::Function,
+                "main".to_string(),
+                Place::Row("main".to_string(), 0),
+            ),
+            Target::new(Kind::Symbol, "maintain".to_string(), Place::Address(0x10)),
+            Target::new(Kind::String, "usage: %s".to_string(), Place::Address(0x20)),
+        ];
+        let mut palette = Palette::new(targets);
+        assert_eq!(3, palette.labels().len());
+        assert_eq!("fn   main", palette.labels()[0]);
+
+        "usg".chars().for_each(|c| palette.push(c));
+        assert_eq!(1, palette.total);
+        assert_eq!(
+            Some(&Place::Address(0x20)),
+            palette.selected().map(|x| &x.place)
+        );
+        palette.push('z');
+        assert_eq!(None, palette.selected());
+        palette.pop();
+        palette.pop();
+        palette.pop();
+        palette.pop();
+        "main".chars().for_each(|c| palette.push(c));
+        palette.move_selection(5);
+        assert_eq!(Some(Kind::Symbol), palette.selected().map(|x| x.kind));
+    }
+
+    #[test]
+    fn test_strings() {
+        let data = b"\x00hello world\x01abc\x00\x7fusage: %s\n";
+        assert_eq!(
+            vec![
+                (1, "hello world".to_string()),
+                (18, "usage: %s".to_string())
+            ],
+            strings(data)
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use std::io::{self, Write};
+
+/// Line by line output for screen readers.  Nothing is drawn, instead each line of what would
+/// be read out (see `Application::announcements`) is written whenever it changes, so a reader
+/// following the terminal only hears what a key actually did.
+#[derive(Debug, Default)]
+pub struct Announcer {
+    last: Vec<String>,
+}
+
+impl Announcer {
+    pub fn announce(&mut self, lines: Vec<String>, out: &mut impl Write) -> io::Result<()> {
+        for (i, line) in lines.iter().enumerate() {
+            if !line.is_empty() && self.last.get(i) != Some(line) {
+                // the terminal is in raw mode, so a newline doesn't return the cursor
+                write!(out, "{}\r\n", line)?;
+            }
+        }
+        self.last = lines;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announce() {
+        let mut announcer = Announcer::default();
+        let mut out = vec![];
+        let lines = |x: &[&str]| x.iter().map(|x| x.to_string()).collect();
+        announcer
+            .announce(
+                lines(&["Mode: Viewing", "disasm main 1 of 2: nop"]),
+                &mut out,
+            )
+            .unwrap();
+        announcer
+            .announce(
+                lines(&["Mode: Viewing", "disasm main 2 of 2: ret"]),
+                &mut out,
+            )
+            .unwrap();
+        assert_eq!(
+            "Mode: Viewing\r\ndisasm main 1 of 2: nop\r\ndisasm main 2 of 2: ret\r\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
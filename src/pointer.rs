@@ -0,0 +1,113 @@
+use crate::binary;
+
+/// What pointers in the file can point at: where it's loaded, and the sections and symbols there
+/// to name a target by.
+#[derive(Debug, Default)]
+pub struct Targets {
+    /// size of a pointer in bytes
+    width: usize,
+    /// see `binary::segments`
+    segments: Vec<(u64, u64, u64)>,
+    /// see `binary::sections`
+    sections: Vec<(String, u64, u64)>,
+    /// see `binary::symbols`, by address
+    symbols: Vec<(u64, String)>,
+}
+
+impl Targets {
+    pub fn load(data: &[u8]) -> Targets {
+        let width = match binary::arch(data).as_deref() {
+            Ok("x86") => 4,
+            _ => 8,
+        };
+        let mut symbols = binary::symbols(data)
+            .into_iter()
+            .map(|(name, va)| (va, name))
+            .collect::<Vec<_>>();
+        symbols.sort();
+        Targets {
+            width,
+            segments: binary::segments(data),
+            sections: binary::sections(data),
+            symbols,
+        }
+    }
+
+    /// size of a pointer in bytes
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The pointer `bytes` starts with, if it points into the file as loaded, as the address and
+    /// the file offset it points at.  Null is never a pointer.
+    pub fn decode(&self, bytes: &[u8]) -> Option<(u64, u64)> {
+        let mut word = [0; 8];
+        word[..self.width].copy_from_slice(bytes.get(..self.width)?);
+        let va = u64::from_le_bytes(word);
+        let offset = self
+            .segments
+            .iter()
+            .find(|(_, size, start)| va != 0 && (*start..start + size).contains(&va))
+            .map(|(offset, _, start)| va - start + offset)?;
+        Some((va, offset))
+    }
+
+    /// `va`, loaded from file `offset`, as the closest symbol at or before it in its section and
+    /// how far past that it is, or else how far into the section it is.
+    pub fn name(&self, va: u64, offset: u64) -> String {
+        let plus = |name: &str, delta: u64| match delta {
+            0 => name.to_string(),
+            _ => format!("{}+{:#x}", name, delta),
+        };
+        let section = self
+            .sections
+            .iter()
+            .find(|(_, start, size)| (*start..start + size).contains(&offset));
+        // where the section is loaded, without one only a symbol right at `va` names it
+        let floor = section.map_or(va, |(_, start, _)| va - (offset - start));
+        let symbol = self
+            .symbols
+            .iter()
+            .rev()
+            .find(|(at, _)| (floor..=va).contains(at));
+        match (symbol, section) {
+            (Some((at, name)), _) => plus(name, va - at),
+            (None, Some((name, start, _))) => plus(name, offset - start),
+            (None, None) => format!("{:#x}", va),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_targets() {
+        let targets = Targets {
+            width: 8,
+            segments: vec![(0, 0x1000, 0x400000), (0x1000, 0x100, 0x601000)],
+            sections: vec![
+                (".text".to_string(), 0x100, 0x200),
+                (".data".to_string(), 0x1000, 0x100),
+            ],
+            symbols: vec![
+                (0x400100, "main".to_string()),
+                (0x601010, "table".to_string()),
+            ],
+        };
+        assert_eq!(
+            Some((0x601018, 0x1018)),
+            targets.decode(&0x601018u64.to_le_bytes())
+        );
+        assert_eq!(None, targets.decode(&0x602000u64.to_le_bytes()));
+        assert_eq!(None, targets.decode(&[0; 8]));
+        assert_eq!(None, targets.decode(&[0x10, 0x10, 0x60]));
+
+        assert_eq!("table+0x8", targets.name(0x601018, 0x1018));
+        assert_eq!(".data+0x8", targets.name(0x601008, 0x1008));
+        assert_eq!("main", targets.name(0x400100, 0x100));
+        // past the end of .text, so not main's
+        assert_eq!("0x400300", targets.name(0x400300, 0x300));
+    }
+}
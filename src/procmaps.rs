@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// One line of `/proc/<pid>/maps`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub start: u64,
+    pub end: u64,
+    /// offset into the mapped file
+    pub offset: u64,
+    pub path: Option<PathBuf>,
+}
+
+pub fn read(pid: u32) -> Result<Vec<Mapping>, Box<dyn Error>> {
+    let path = format!("/proc/{}/maps", pid);
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(parse(&text))
+}
+
+/// `start-end perms offset dev inode [path]`, lines which don't look like that are skipped
+pub fn parse(text: &str) -> Vec<Mapping> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(6, ' ');
+            let (start, end) = fields.next()?.split_at(line.find('-')?);
+            let _perms = fields.next()?;
+            let offset = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let path = fields.nth(2).map(|x| x.trim()).filter(|x| !x.is_empty());
+            Some(Mapping {
+                start: u64::from_str_radix(start, 16).ok()?,
+                end: u64::from_str_radix(&end[1..], 16).ok()?,
+                offset,
+                path: path.map(PathBuf::from),
+            })
+        })
+        .collect()
+}
+
+/// where the start of `file` is mapped, None if the process doesn't have it mapped
+pub fn load_base(mappings: &[Mapping], file: &Path) -> Option<u64> {
+    let file = file.canonicalize().ok()?;
+    mappings
+        .iter()
+        .filter(|x| x.offset == 0)
+        .find(|x| x.path.as_deref() == Some(file.as_path()))
+        .map(|x| x.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let maps = "\
+55d0c2a00000-55d0c2a01000 r--p 00000000 fd:01 1234                       /usr/bin/cat
+55d0c2a01000-55d0c2a05000 r-xp 00001000 fd:01 1234                       /usr/bin/cat
+7ffd1c8e0000-7ffd1c901000 rw-p 00000000 00:00 0                          [stack]
+7ffd1c9f0000-7ffd1c9f2000 rw-p 00000000 00:00 0 ";
+        let mappings = parse(maps);
+        assert_eq!(4, mappings.len());
+        assert_eq!(
+            Mapping {
+                start: 0x55d0c2a01000,
+                end: 0x55d0c2a05000,
+                offset: 0x1000,
+                path: Some(PathBuf::from("/usr/bin/cat")),
+            },
+            mappings[1]
+        );
+        assert_eq!(None, mappings[3].path);
+    }
+}
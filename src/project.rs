@@ -0,0 +1,140 @@
+use crate::annotations::Location;
+use crate::bundle::Checksum;
+use crate::fingerprint::Fingerprint;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Work on a file which hasn't been written yet, saved now and then so a crash or a killed
+/// terminal doesn't lose it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Project {
+    /// the file the edits were made against, a project is only restored onto the same file
+    pub target: Checksum,
+    /// instructions whose bytes were changed
+    pub edits: Vec<Edit>,
+    /// edits outside any function, as (file offset, hex)
+    #[serde(default)]
+    pub patches: Vec<(u64, String)>,
+    /// what the file had under each of `patches` (hex), to find them again in a rebuilt file
+    #[serde(default)]
+    pub originals: Vec<String>,
+    #[serde(default)]
+    pub comments: Vec<(Location, String)>,
+    #[serde(default)]
+    pub bookmarks: Vec<Location>,
+    /// byte ranges marked as data, see `Application::data`
+    #[serde(default)]
+    pub data: Vec<(u64, u64)>,
+    /// the selected function and row
+    pub cursor: Option<Location>,
+    /// the functions everything above is in, to find them again in a rebuilt file
+    #[serde(default)]
+    pub functions: Vec<Fingerprint>,
+}
+
+/// the instruction at `va` in `function` was changed to `bytes` (hex)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub function: String,
+    pub va: u64,
+    pub bytes: String,
+    /// the instruction's bytes in the file, empty in projects saved before they were kept
+    #[serde(default)]
+    pub original: String,
+}
+
+impl Project {
+    pub fn load(path: &Path) -> Result<Project, Box<dyn Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?)
+    }
+
+    /// Write the project next to `path` and rename it over, so the last snapshot survives a
+    /// crash part way through saving the next one.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temporary =
+            path.with_file_name(format!(".{}.transmogrify-{}", name, std::process::id()));
+        std::fs::write(&temporary, serde_json::to_string_pretty(self)?)?;
+        if let Err(e) = std::fs::rename(&temporary, path) {
+            let _ = std::fs::remove_file(&temporary);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+/// Where and how often a session is snapshotted.
+#[derive(Debug)]
+pub struct Autosave {
+    pub path: PathBuf,
+    /// the file as it was last loaded or written
+    pub target: Checksum,
+    /// what's on disk, so nothing is written while the session sits idle
+    saved: Option<Project>,
+    last: Instant,
+}
+
+impl Autosave {
+    pub fn new(path: PathBuf, target: Checksum) -> Autosave {
+        Autosave {
+            path,
+            target,
+            saved: None,
+            last: Instant::now(),
+        }
+    }
+
+    /// whether `interval` seconds have passed since the last snapshot, 0 never autosaves
+    pub fn due(&self, interval: u64) -> bool {
+        interval > 0 && self.last.elapsed().as_secs() >= interval
+    }
+
+    /// Save `project` unless it's what was saved last time.
+    pub fn save(&mut self, project: Project) -> Result<(), Box<dyn Error>> {
+        self.last = Instant::now();
+        if self.saved.as_ref() == Some(&project) {
+            return Ok(());
+        }
+        project.save(&self.path)?;
+        self.saved = Some(project);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save() {
+        let directory = crate::util::TempDir::new("test").unwrap();
+        let path = directory.join("project");
+        let project = Project {
+            target: Checksum::of(b"\x7fELF"),
+            edits: vec![Edit {
+                function: "main".to_string(),
+                va: 0x1004,
+                bytes: "90 90".to_string(),
+                original: "31 c0".to_string(),
+            }],
+            patches: vec![(0x3c, "40".to_string())],
+            originals: vec!["00".to_string()],
+            comments: vec![(("main".to_string(), 4), "nopped out".to_string())],
+            bookmarks: vec![],
+            data: vec![(0x1010, 0x1018)],
+            cursor: Some(("main".to_string(), 4)),
+            functions: vec![],
+        };
+        let mut autosave = Autosave::new(path.clone(), project.target.clone());
+        assert!(!autosave.due(0));
+        autosave.save(project.clone()).unwrap();
+        assert_eq!(project, Project::load(&path).unwrap());
+        // unchanged snapshots aren't written again
+        std::fs::remove_file(&path).unwrap();
+        autosave.save(project).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::util::run;
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// A file on another machine, `[user@]host:path` as scp spells it.  It's copied down to edit
+/// and back up on write, both through the system's scp/ssh so their config and agent apply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remote {
+    pub host: String,
+    pub path: String,
+}
+
+impl Remote {
+    /// None if `spec` doesn't look like `host:path`
+    pub fn parse(spec: &str) -> Option<Remote> {
+        let (host, path) = spec.split_at(spec.find(':')?);
+        let path = &path[1..];
+        if host.is_empty() || host.contains('/') || path.is_empty() {
+            return None;
+        }
+        Some(Remote {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    pub fn fetch(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        run(Command::new("scp")
+            .arg("-pq")
+            .arg(self.to_string())
+            .arg(local))?;
+        Ok(())
+    }
+
+    /// Upload `local` next to the remote file and rename it over the top, so the remote file is
+    /// never half written.
+    pub fn upload(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        let temporary = format!("{}.transmogrify", self.path);
+        run(Command::new("scp")
+            .arg("-pq")
+            .arg(local)
+            .arg(format!("{}:{}", self.host, temporary)))?;
+        run(Command::new("ssh").arg(&self.host).arg(format!(
+            "mv -f -- {} {}",
+            quote(&temporary),
+            quote(&self.path)
+        )))?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.host, self.path)
+    }
+}
+
+/// single quote `text` for the remote shell
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Some(Remote {
+                host: "root@router".to_string(),
+                path: "/usr/bin/httpd".to_string(),
+            }),
+            Remote::parse("root@router:/usr/bin/httpd")
+        );
+        assert_eq!(None, Remote::parse("./a:b"));
+        assert_eq!(None, Remote::parse("/tmp/binary"));
+        assert_eq!(None, Remote::parse("host:"));
+    }
+}
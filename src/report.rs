@@ -0,0 +1,249 @@
+use serde_json::json;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Report formats for reviewing a patch, picked by the extension of the file being written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    /// SARIF 2.1.0, which code review tools can show inline
+    Sarif,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("md") | Some("markdown") => Ok(Format::Markdown),
+            Some("sarif") | Some("json") => Ok(Format::Sarif),
+            _ => Err("reports can be written as .md or .sarif".into()),
+        }
+    }
+}
+
+/// One line of a hunk's disassembly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A run of changed instructions in one function, with a little unchanged code around it.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub function: String,
+    /// address of the first changed instruction
+    pub va: u64,
+    /// bytes from `va` to the end of the last changed instruction
+    pub len: u64,
+    pub lines: Vec<Line>,
+    /// comments on instructions in the hunk, by address
+    pub comments: Vec<(u64, String)>,
+}
+
+/// A comment on an instruction which wasn't changed.
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub function: String,
+    pub va: u64,
+    pub instruction: String,
+    pub text: String,
+}
+
+/// Everything a reviewer needs to judge a patch: what changed and what's been said about it.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub file: String,
+    /// who wrote the report, `$USER`
+    pub author: String,
+    pub hunks: Vec<Hunk>,
+    pub comments: Vec<Comment>,
+    /// header and data patches outside any function, as (file offset, bytes)
+    pub patches: Vec<(u64, Vec<u8>)>,
+}
+
+impl Report {
+    pub fn render(&self, format: Format) -> Result<String, Box<dyn Error>> {
+        match format {
+            Format::Markdown => self.markdown(),
+            Format::Sarif => Ok(serde_json::to_string_pretty(&self.sarif())?),
+        }
+    }
+
+    fn markdown(&self) -> Result<String, Box<dyn Error>> {
+        let mut out = String::new();
+        writeln!(out, "# Review of {}", self.file)?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{} hunk(s), {} comment(s) and {} other patch(es), by {}",
+            self.hunks.len(),
+            self.comments.len() + self.hunks.iter().map(|x| x.comments.len()).sum::<usize>(),
+            self.patches.len(),
+            self.author
+        )?;
+        for hunk in &self.hunks {
+            writeln!(out)?;
+            writeln!(out, "## {} at {:#x}", hunk.function, hunk.va)?;
+            writeln!(out)?;
+            writeln!(out, "```diff")?;
+            for line in &hunk.lines {
+                match line {
+                    Line::Context(text) => writeln!(out, "  {}", text)?,
+                    Line::Removed(text) => writeln!(out, "- {}", text)?,
+                    Line::Added(text) => writeln!(out, "+ {}", text)?,
+                }
+            }
+            writeln!(out, "```")?;
+            for (va, text) in &hunk.comments {
+                writeln!(out)?;
+                writeln!(out, "> **{:#x}**: {}", va, text)?;
+            }
+        }
+        if !self.comments.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "## Comments")?;
+            writeln!(out)?;
+            for comment in &self.comments {
+                writeln!(
+                    out,
+                    "- **{} at {:#x}** `{}`: {}",
+                    comment.function, comment.va, comment.instruction, comment.text
+                )?;
+            }
+        }
+        if !self.patches.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "## Other patches")?;
+            writeln!(out)?;
+            for (offset, bytes) in &self.patches {
+                writeln!(
+                    out,
+                    "- offset {:#x}: `{}`",
+                    offset,
+                    crate::util::to_hexstring(bytes)
+                )?;
+            }
+        }
+        Ok(out)
+    }
+
+    fn sarif(&self) -> serde_json::Value {
+        let location = |va: u64, len: u64| {
+            json!([{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": self.file },
+                    "address": { "absoluteAddress": va, "length": len },
+                }
+            }])
+        };
+        let hunks = self.hunks.iter().map(|hunk| {
+            let diff = hunk
+                .lines
+                .iter()
+                .map(|line| match line {
+                    Line::Context(text) => format!("  {}", text),
+                    Line::Removed(text) => format!("- {}", text),
+                    Line::Added(text) => format!("+ {}", text),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            json!({
+                "ruleId": "patch",
+                "level": "note",
+                "message": { "text": format!("{} changed:\n{}", hunk.function, diff) },
+                "locations": location(hunk.va, hunk.len),
+                "properties": {
+                    "function": hunk.function,
+                    "comments": hunk.comments.iter().map(|(va, text)| {
+                        json!({ "address": va, "text": text })
+                    }).collect::<Vec<_>>(),
+                },
+            })
+        });
+        let comments = self.comments.iter().map(|comment| {
+            json!({
+                "ruleId": "comment",
+                "level": "note",
+                "message": { "text": comment.text },
+                "locations": location(comment.va, 0),
+                "properties": {
+                    "function": comment.function,
+                    "instruction": comment.instruction,
+                },
+            })
+        });
+        let patches = self.patches.iter().map(|(offset, bytes)| {
+            json!({
+                "ruleId": "patch",
+                "level": "note",
+                "message": { "text": crate::util::to_hexstring(bytes) },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": self.file },
+                        "region": { "byteOffset": offset, "byteLength": bytes.len() },
+                    }
+                }],
+            })
+        });
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": { "driver": { "name": "transmogrify", "rules": [
+                    { "id": "patch", "shortDescription": { "text": "changed code or data" } },
+                    { "id": "comment", "shortDescription": { "text": "review comment" } },
+                ] } },
+                "properties": { "author": self.author },
+                "results": hunks.chain(comments).chain(patches).collect::<Vec<_>>(),
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            file: "a.out".to_string(),
+            author: "reviewer".to_string(),
+            hunks: vec![Hunk {
+                function: "main".to_string(),
+                va: 0x1004,
+                len: 2,
+                lines: vec![
+                    Line::Context("push rbp".to_string()),
+                    Line::Removed("jne 0x1010".to_string()),
+                    Line::Added("je 0x1010".to_string()),
+                ],
+                comments: vec![(0x1004, "inverted the check".to_string())],
+            }],
+            comments: vec![],
+            patches: vec![(0x3c, vec![0x40, 0x81])],
+        }
+    }
+
+    #[test]
+    fn test_markdown() {
+        let markdown = report().render(Format::Markdown).unwrap();
+        assert!(markdown.contains("## main at 0x1004"));
+        assert!(markdown.contains("```diff\n  push rbp\n- jne 0x1010\n+ je 0x1010\n```"));
+        assert!(markdown.contains("> **0x1004**: inverted the check"));
+        assert!(markdown.contains("- offset 0x3c: `40 81`"));
+    }
+
+    #[test]
+    fn test_sarif() {
+        let sarif: serde_json::Value =
+            serde_json::from_str(&report().render(Format::Sarif).unwrap()).unwrap();
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(2, results.len());
+        assert_eq!(
+            0x1004,
+            results[0]["locations"][0]["physicalLocation"]["address"]["absoluteAddress"]
+        );
+    }
+}
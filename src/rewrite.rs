@@ -0,0 +1,965 @@
+//! Structural changes to the binary which go beyond overwriting bytes in place.
+
+use crate::binary::elf_va_to_offset;
+use crate::util::Patch;
+use goblin::elf::dynamic::{DT_NEEDED, DT_NULL, DT_RELA, DT_RELASZ, DT_STRSZ, DT_STRTAB};
+use goblin::elf::dynamic::{DT_SYMTAB, DT_VERSYM};
+use goblin::elf::program_header::{ProgramHeader, PF_R, PF_W, PF_X};
+use goblin::elf::program_header::{PT_DYNAMIC, PT_LOAD, PT_NOTE};
+use goblin::elf::reloc::R_X86_64_GLOB_DAT;
+use goblin::elf::section_header::{SectionHeader, SHT_STRTAB, SHT_SYMTAB};
+use goblin::elf::Elf;
+use goblin::pe::PE;
+use goblin::Object;
+use std::error::Error;
+
+/// how much room a new segment/section gets, detours are allocated from it until it runs out
+pub const EXTENSION_SIZE: usize = 0x1000;
+const PAGE_SIZE: u64 = 0x1000;
+const SECTION_NAME: &[u8; 8] = b".tmog\0\0\0";
+const SECTION_CHARACTERISTICS: u32 = 0x6000_0020; // CODE | MEM_EXECUTE | MEM_READ
+const WRITABLE_SECTION_NAME: &[u8; 8] = b".tmogw\0\0";
+const WRITABLE_SECTION_CHARACTERISTICS: u32 = 0xc000_0040; // INITIALIZED_DATA | MEM_READ | MEM_WRITE
+
+/// Space appended to the end of the file as a new ELF PT_LOAD segment or PE section, readable
+/// and executable, for code and the tables of injected imports.  Anything the program writes to
+/// (import slots, flags) goes in a second, writable segment right after it, which is only added
+/// once something is allocated there.  The addresses are decided up front so code can be
+/// assembled against them, the segments themselves only get created on the first write.
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub va: u64,
+    pub code: Vec<u8>,
+    /// file offset of the segment once it has been written
+    pub offset: Option<u64>,
+    /// contents of the writable segment at `writable_va()`
+    pub writable: Vec<u8>,
+    /// file offset of the writable segment once it has been written
+    pub writable_offset: Option<u64>,
+}
+
+impl Extension {
+    pub fn plan(data: &[u8]) -> Result<Extension, Box<dyn Error>> {
+        let va = match Object::parse(data)? {
+            Object::Elf(elf) => {
+                let end = elf
+                    .program_headers
+                    .iter()
+                    .filter(|x| x.p_type == PT_LOAD)
+                    .map(|x| x.p_vaddr + x.p_memsz)
+                    .max()
+                    .ok_or("no loadable segments")?;
+                align(end, PAGE_SIZE)
+            }
+            Object::PE(pe) => {
+                let (section_alignment, _) = pe_alignment(&pe)?;
+                let end = pe
+                    .sections
+                    .iter()
+                    .map(|x| (x.virtual_address + x.virtual_size) as u64)
+                    .max()
+                    .ok_or("no sections")?;
+                pe.image_base as u64 + align(end, section_alignment)
+            }
+            _ => return Err("only ELF and PE files can be extended".into()),
+        };
+        Ok(Extension {
+            va,
+            code: vec![],
+            offset: None,
+            writable: vec![],
+            writable_offset: None,
+        })
+    }
+
+    /// address of the writable segment, right after the code one
+    pub fn writable_va(&self) -> u64 {
+        self.va + EXTENSION_SIZE as u64
+    }
+
+    /// address the next allocation will land at
+    pub fn next_va(&self) -> u64 {
+        self.va + self.code.len() as u64
+    }
+
+    /// append `code`, returning the address it will live at
+    pub fn push(&mut self, code: &[u8]) -> Result<u64, Box<dyn Error>> {
+        if self.code.len() + code.len() > EXTENSION_SIZE {
+            return Err("out of space in the extension segment".into());
+        }
+        let va = self.next_va();
+        self.code.extend_from_slice(code);
+        Ok(va)
+    }
+
+    /// like `push` but aligns the start of `bytes` to `alignment`
+    pub fn push_aligned(&mut self, bytes: &[u8], alignment: usize) -> Result<u64, Box<dyn Error>> {
+        let padding = (alignment - self.code.len() % alignment) % alignment;
+        self.push(&vec![0; padding])?;
+        self.push(bytes)
+    }
+
+    /// like `push_aligned` but in the writable segment
+    pub fn push_writable(&mut self, bytes: &[u8], alignment: usize) -> Result<u64, Box<dyn Error>> {
+        let start = align(self.writable.len() as u64, alignment as u64) as usize;
+        if start + bytes.len() > EXTENSION_SIZE {
+            return Err("out of space in the writable extension segment".into());
+        }
+        self.writable.resize(start, 0);
+        self.writable.extend_from_slice(bytes);
+        Ok(self.writable_va() + start as u64)
+    }
+
+    /// where the segments are in the file and what they hold, None until everything allocated
+    /// has a segment in the file
+    pub fn written(&self) -> Option<Vec<(u64, Vec<u8>)>> {
+        let mut written = vec![(self.offset?, self.contents())];
+        if !self.writable.is_empty() {
+            written.push((self.writable_offset?, self.writable_contents()));
+        }
+        Some(written)
+    }
+
+    /// the full contents of the segment, unused space is filled with int3
+    fn contents(&self) -> Vec<u8> {
+        let mut contents = self.code.clone();
+        contents.resize(EXTENSION_SIZE, 0xcc);
+        contents
+    }
+
+    fn writable_contents(&self) -> Vec<u8> {
+        let mut contents = self.writable.clone();
+        contents.resize(EXTENSION_SIZE, 0);
+        contents
+    }
+
+    /// add the segments to `data` if they aren't there yet, otherwise refresh their contents
+    pub fn apply(&mut self, data: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => add_segment(data, self.va, false)?,
+        };
+        self.offset = Some(offset);
+        fill(data, offset, &self.contents());
+        if self.writable.is_empty() {
+            return Ok(());
+        }
+        let offset = match self.writable_offset {
+            Some(offset) => offset,
+            None => add_segment(data, self.writable_va(), true)?,
+        };
+        self.writable_offset = Some(offset);
+        fill(data, offset, &self.writable_contents());
+        Ok(())
+    }
+}
+
+/// overwrite `data` at `offset` with `contents`, growing it if need be
+fn fill(data: &mut Vec<u8>, offset: u64, contents: &[u8]) {
+    let (start, end) = (offset as usize, offset as usize + contents.len());
+    if data.len() < end {
+        data.resize(end, 0);
+    }
+    data[start..end].copy_from_slice(contents);
+}
+
+/// Append a segment at `va` to `data`, returning its file offset.
+fn add_segment(data: &mut Vec<u8>, va: u64, writable: bool) -> Result<u64, Box<dyn Error>> {
+    let file_len = data.len() as u64;
+    let (offset, headers) = match Object::parse(data)? {
+        Object::Elf(elf) => add_elf_segment(&elf, va, file_len, writable)?,
+        Object::PE(pe) => add_pe_section(&pe, va, file_len, writable)?,
+        _ => return Err("only ELF and PE files can be extended".into()),
+    };
+    for (at, bytes) in headers {
+        data[at..at + bytes.len()].copy_from_slice(&bytes);
+    }
+    data.resize(offset as usize + EXTENSION_SIZE, 0);
+    Ok(offset)
+}
+
+type HeaderWrites = Vec<(usize, Vec<u8>)>;
+
+/// Repurpose a PT_NOTE program header as a PT_LOAD for the new segment.  Notes aren't needed to
+/// run the binary and there is no room to grow the program header table in place, so a binary
+/// needs a second one for the writable segment.
+fn add_elf_segment(
+    elf: &Elf,
+    va: u64,
+    file_len: u64,
+    writable: bool,
+) -> Result<(u64, HeaderWrites), Box<dyn Error>> {
+    if !elf.little_endian {
+        return Err("big endian ELF files aren't supported".into());
+    }
+    let offset = align(file_len, PAGE_SIZE);
+
+    let mut headers = elf.program_headers.clone();
+    let note = headers
+        .iter()
+        .position(|x| x.p_type == PT_NOTE)
+        .ok_or("no PT_NOTE program header left to repurpose")?;
+    headers.remove(note);
+    let segment = ProgramHeader {
+        p_type: PT_LOAD,
+        p_flags: if writable { PF_R | PF_W } else { PF_R | PF_X },
+        p_offset: offset,
+        p_vaddr: va,
+        p_paddr: va,
+        p_filesz: EXTENSION_SIZE as u64,
+        p_memsz: EXTENSION_SIZE as u64,
+        p_align: PAGE_SIZE,
+    };
+    // loaders expect PT_LOAD entries sorted by address, ours is the highest so it goes last
+    let last_load = headers
+        .iter()
+        .rposition(|x| x.p_type == PT_LOAD)
+        .map(|x| x + 1)
+        .unwrap_or(headers.len());
+    headers.insert(last_load, segment);
+
+    let table = headers
+        .iter()
+        .flat_map(|x| encode_program_header(x, elf.is_64))
+        .collect();
+    Ok((offset, vec![(elf.header.e_phoff as usize, table)]))
+}
+
+fn encode_program_header(header: &ProgramHeader, is_64: bool) -> Vec<u8> {
+    if is_64 {
+        [
+            &header.p_type.to_le_bytes()[..],
+            &header.p_flags.to_le_bytes()[..],
+            &header.p_offset.to_le_bytes()[..],
+            &header.p_vaddr.to_le_bytes()[..],
+            &header.p_paddr.to_le_bytes()[..],
+            &header.p_filesz.to_le_bytes()[..],
+            &header.p_memsz.to_le_bytes()[..],
+            &header.p_align.to_le_bytes()[..],
+        ]
+        .concat()
+    } else {
+        [
+            header.p_type,
+            header.p_offset as u32,
+            header.p_vaddr as u32,
+            header.p_paddr as u32,
+            header.p_filesz as u32,
+            header.p_memsz as u32,
+            header.p_flags,
+            header.p_align as u32,
+        ]
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .collect()
+    }
+}
+
+/// Append a section header after the existing ones, which works as long as there is slack
+/// between the end of the section table and the first section's raw data.
+fn add_pe_section(
+    pe: &PE,
+    va: u64,
+    file_len: u64,
+    writable: bool,
+) -> Result<(u64, HeaderWrites), Box<dyn Error>> {
+    let (section_alignment, file_alignment) = pe_alignment(pe)?;
+    let optional_header = pe
+        .header
+        .optional_header
+        .ok_or("PE file has no optional header")?;
+    let coff = pe.header.dos_header.pe_pointer as usize + 4;
+    let optional = coff + 20;
+    let table = optional + pe.header.coff_header.size_of_optional_header as usize;
+    let count = pe.sections.len();
+    let header_at = table + count * 40;
+    let size_of_headers = optional_header.windows_fields.size_of_headers as usize;
+    let first_data = pe
+        .sections
+        .iter()
+        .map(|x| x.pointer_to_raw_data as usize)
+        .filter(|x| *x != 0)
+        .min()
+        .unwrap_or(size_of_headers);
+    if header_at + 40 > first_data.min(size_of_headers) {
+        return Err("no room for another section header".into());
+    }
+
+    let offset = align(file_len, file_alignment);
+    let rva = (va - pe.image_base as u64) as u32;
+    let raw_size = align(EXTENSION_SIZE as u64, file_alignment) as u32;
+    let size_of_image = align(rva as u64 + EXTENSION_SIZE as u64, section_alignment) as u32;
+
+    let (name, characteristics) = if writable {
+        (WRITABLE_SECTION_NAME, WRITABLE_SECTION_CHARACTERISTICS)
+    } else {
+        (SECTION_NAME, SECTION_CHARACTERISTICS)
+    };
+    let header = [
+        &name[..],
+        &(EXTENSION_SIZE as u32).to_le_bytes()[..],
+        &rva.to_le_bytes()[..],
+        &raw_size.to_le_bytes()[..],
+        &(offset as u32).to_le_bytes()[..],
+        &[0u8; 12][..], // relocations, line numbers and their counts
+        &characteristics.to_le_bytes()[..],
+    ]
+    .concat();
+
+    Ok((
+        offset,
+        vec![
+            (header_at, header),
+            (coff + 2, ((count + 1) as u16).to_le_bytes().to_vec()),
+            (optional + 56, size_of_image.to_le_bytes().to_vec()),
+        ],
+    ))
+}
+
+fn pe_alignment(pe: &PE) -> Result<(u64, u64), Box<dyn Error>> {
+    let windows = pe
+        .header
+        .optional_header
+        .ok_or("PE file has no optional header")?
+        .windows_fields;
+    Ok((
+        windows.section_alignment as u64,
+        windows.file_alignment as u64,
+    ))
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+/// Add `library` to the binary's imports, and if given `function` from it as well.  The rebuilt
+/// tables are allocated in `extension` and the returned patches repoint the headers at them.
+/// Returns the address of the slot the loader will fill in with the address of `function`.
+/// Nothing is allocated in `extension` unless it succeeds.
+pub fn inject_import(
+    data: &[u8],
+    extension: &mut Extension,
+    library: &str,
+    function: Option<&str>,
+) -> Result<(Vec<Patch>, Option<u64>), Box<dyn Error>> {
+    let mut grown = extension.clone();
+    let injected = match Object::parse(data)? {
+        Object::Elf(elf) => inject_elf_import(&elf, data, &mut grown, library, function)?,
+        Object::PE(pe) => {
+            let function = function.ok_or("PE imports need a function")?;
+            inject_pe_import(&pe, data, &mut grown, library, function)?
+        }
+        _ => return Err("only ELF and PE files are supported".into()),
+    };
+    *extension = grown;
+    Ok(injected)
+}
+
+/// Copies .dynstr (and for a function .dynsym, the version table and .rela.dyn) into the
+/// extension with the new entries appended, then adds a DT_NEEDED in a spare DT_NULL slot.
+fn inject_elf_import(
+    elf: &Elf,
+    data: &[u8],
+    extension: &mut Extension,
+    library: &str,
+    function: Option<&str>,
+) -> Result<(Vec<Patch>, Option<u64>), Box<dyn Error>> {
+    if !elf.is_64 || !elf.little_endian {
+        return Err("only 64 bit little endian ELF files are supported".into());
+    }
+    let dynamic = elf
+        .program_headers
+        .iter()
+        .find(|x| x.p_type == PT_DYNAMIC)
+        .ok_or("not a dynamically linked binary")?;
+    let entries = (0..dynamic.p_filesz as usize / 16)
+        .map(|i| {
+            let at = dynamic.p_offset as usize + i * 16;
+            (at, read_u64(data, at), read_u64(data, at + 8))
+        })
+        .collect::<Vec<_>>();
+    let terminator = entries
+        .iter()
+        .position(|(_, tag, _)| *tag == DT_NULL)
+        .ok_or("dynamic section has no DT_NULL")?;
+    if terminator + 1 >= entries.len() {
+        return Err("no spare room in the dynamic section for a DT_NEEDED".into());
+    }
+    let entry = |tag: u64| {
+        entries[..terminator]
+            .iter()
+            .find(|(_, x, _)| *x == tag)
+            .map(|(at, _, val)| (*at, *val))
+    };
+    let table = |tag: u64| -> Result<(usize, u64), Box<dyn Error>> {
+        let (at, va) = entry(tag).ok_or("missing dynamic entry")?;
+        let offset = elf_va_to_offset(elf, va).ok_or("dynamic table outside the file")?;
+        Ok((at, offset))
+    };
+    let (strtab_at, strtab) = table(DT_STRTAB)?;
+    let (strsz_at, strsz) = entry(DT_STRSZ).ok_or("missing DT_STRSZ")?;
+    // look everything up before allocating any of the copies
+    let tables = match function {
+        Some(_) => Some((
+            table(DT_SYMTAB)?,
+            table(DT_VERSYM).ok(),
+            table(DT_RELA)?,
+            entry(DT_RELASZ).ok_or("missing DT_RELASZ")?,
+        )),
+        None => None,
+    };
+    let mut patches = vec![];
+    let mut repoint = |at: usize, value: u64| {
+        patches.push(Patch::new(at as u64 + 8, value.to_le_bytes().to_vec()))
+    };
+
+    let mut strings = data[strtab as usize..(strtab + strsz) as usize].to_vec();
+    let library_name = strings.len() as u64;
+    strings.extend(library.as_bytes());
+    strings.push(0);
+    let function_name = strings.len() as u32;
+    if let Some(function) = function {
+        strings.extend(function.as_bytes());
+        strings.push(0);
+    }
+    repoint(strtab_at, extension.push(&strings)?);
+    repoint(strsz_at, strings.len() as u64);
+
+    let slot = match tables {
+        Some(((symtab_at, symtab), versym, (rela_at, rela), (relasz_at, relasz))) => {
+            let count = elf.dynsyms.len();
+            let mut symbols = data[symtab as usize..symtab as usize + count * 24].to_vec();
+            symbols.extend(&function_name.to_le_bytes());
+            symbols.push(0x12); // STB_GLOBAL, STT_FUNC
+            symbols.extend(&[0; 19]); // st_other, st_shndx, st_value, st_size
+            repoint(symtab_at, extension.push_aligned(&symbols, 8)?);
+
+            if let Some((versym_at, versym)) = versym {
+                let mut versions = data[versym as usize..versym as usize + count * 2].to_vec();
+                versions.extend(&1u16.to_le_bytes()); // VER_NDX_GLOBAL
+                repoint(versym_at, extension.push_aligned(&versions, 2)?);
+            }
+
+            let slot = extension.push_writable(&[0; 8], 8)?;
+            let mut relocs = data[rela as usize..(rela + relasz) as usize].to_vec();
+            relocs.extend(&slot.to_le_bytes());
+            relocs.extend(&(((count as u64) << 32) | R_X86_64_GLOB_DAT as u64).to_le_bytes());
+            relocs.extend(&0u64.to_le_bytes());
+            repoint(rela_at, extension.push_aligned(&relocs, 8)?);
+            repoint(relasz_at, relocs.len() as u64);
+            Some(slot)
+        }
+        None => None,
+    };
+
+    let needed = entries[terminator].0 as u64;
+    patches.push(Patch::new(
+        needed,
+        [DT_NEEDED.to_le_bytes(), library_name.to_le_bytes()].concat(),
+    ));
+    Ok((patches, slot))
+}
+
+/// Rebuilds the import directory in the extension with a new descriptor for `library` importing
+/// just `function` and points the import data directory at it.
+fn inject_pe_import(
+    pe: &PE,
+    data: &[u8],
+    extension: &mut Extension,
+    library: &str,
+    function: &str,
+) -> Result<(Vec<Patch>, Option<u64>), Box<dyn Error>> {
+    let (_, file_alignment) = pe_alignment(pe)?;
+    let optional_header = pe
+        .header
+        .optional_header
+        .ok_or("PE file has no optional header")?;
+    let rva = |va: u64| (va - pe.image_base as u64) as u32;
+    let thunk = if pe.is_64 { 8 } else { 4 };
+
+    let mut descriptors = vec![];
+    if let Some(imports) = optional_header.data_directories.get_import_table() {
+        let offset = goblin::pe::utils::find_offset(
+            imports.virtual_address as usize,
+            &pe.sections,
+            file_alignment as u32,
+        )
+        .ok_or("import directory outside the file")?;
+        for descriptor in data[offset..].chunks(20) {
+            if descriptor.len() < 20 || descriptor.iter().all(|x| *x == 0) {
+                break;
+            }
+            descriptors.extend(descriptor);
+        }
+    }
+
+    let dll_name = rva(extension.push(&[library.as_bytes(), &[0][..]].concat())?);
+    let hint_name = [&[0u8, 0][..], function.as_bytes(), &[0][..]].concat();
+    let hint_name = rva(extension.push_aligned(&hint_name, 2)?);
+    let mut thunks = hint_name.to_le_bytes().to_vec();
+    thunks.resize(thunk * 2, 0);
+    let lookup = rva(extension.push_aligned(&thunks, thunk)?);
+    let slot = extension.push_writable(&thunks, thunk)?;
+
+    for field in &[lookup, 0, 0, dll_name, rva(slot)] {
+        descriptors.extend(&field.to_le_bytes());
+    }
+    descriptors.extend(&[0; 20]);
+    let directory = rva(extension.push_aligned(&descriptors, 4)?);
+
+    let data_directories =
+        pe.header.dos_header.pe_pointer as usize + 24 + if pe.is_64 { 112 } else { 96 };
+    Ok((
+        vec![
+            Patch::new(
+                data_directories as u64 + 8,
+                [
+                    directory.to_le_bytes(),
+                    (descriptors.len() as u32).to_le_bytes(),
+                ]
+                .concat(),
+            ),
+            // bound imports would describe the old import table
+            Patch::new(data_directories as u64 + 11 * 8, vec![0; 8]),
+        ],
+        Some(slot),
+    ))
+}
+
+/// A function to describe in a synthetic symbol table.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub va: u64,
+    pub size: u64,
+}
+
+/// Changes to the ELF symbol table, applied to the whole file as the last step of a write.
+#[derive(Debug, Clone)]
+pub enum SymbolTableEdit {
+    Strip,
+    Add(Vec<Symbol>),
+}
+
+impl SymbolTableEdit {
+    pub fn apply(&self, data: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        match self {
+            SymbolTableEdit::Strip => strip_symbols(data),
+            SymbolTableEdit::Add(symbols) => add_symbols(data, symbols),
+        }
+    }
+}
+
+/// Empty out .symtab and its string table and mark both sections as unused.
+fn strip_symbols(data: &mut [u8]) -> Result<(), Box<dyn Error>> {
+    let elf = Elf::parse(data)?;
+    let (shoff, shentsize) = (elf.header.e_shoff, elf.header.e_shentsize as u64);
+    let sections = elf
+        .section_headers
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| x.sh_type == SHT_SYMTAB)
+        .flat_map(|(i, x)| {
+            let link = x.sh_link as usize;
+            let mut sections = vec![(i, x.clone())];
+            // some linkers keep section names and symbol names in one table, which has to stay
+            if link != elf.header.e_shstrndx as usize {
+                sections.extend(elf.section_headers.get(link).map(|x| (link, x.clone())));
+            }
+            sections
+        })
+        .collect::<Vec<_>>();
+    if sections.is_empty() {
+        return Err("there is no symbol table to strip".into());
+    }
+    for (i, section) in sections {
+        let start = section.sh_offset as usize;
+        let header = (shoff + i as u64 * shentsize) as usize;
+        // keep the name but otherwise turn it into a SHT_NULL section
+        let ranges = [
+            start..start + section.sh_size as usize,
+            header + 4..header + shentsize as usize,
+        ];
+        for range in ranges.iter().cloned() {
+            for byte in &mut data[range] {
+                *byte = 0;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Append .symtab/.strtab sections describing `symbols`, along with a copy of the section
+/// header table and its string table with room for the new entries.
+fn add_symbols(data: &mut Vec<u8>, symbols: &[Symbol]) -> Result<(), Box<dyn Error>> {
+    let elf = Elf::parse(data)?;
+    if !elf.is_64 || !elf.little_endian {
+        return Err("only 64 bit little endian ELF files are supported".into());
+    }
+    if elf.section_headers.iter().any(|x| x.sh_type == SHT_SYMTAB) {
+        return Err("the binary already has a symbol table".into());
+    }
+    let mut sections = elf.section_headers.clone();
+    let shstrndx = elf.header.e_shstrndx as usize;
+    let shstrtab = sections
+        .get(shstrndx)
+        .ok_or("no section name table")?
+        .clone();
+
+    let mut strings = vec![0u8];
+    let mut table = vec![0u8; 24];
+    for symbol in symbols {
+        let section = sections
+            .iter()
+            .position(|x| x.sh_addr != 0 && (x.sh_addr..x.sh_addr + x.sh_size).contains(&symbol.va))
+            .unwrap_or(0);
+        table.extend(&(strings.len() as u32).to_le_bytes());
+        table.push(0x12); // STB_GLOBAL, STT_FUNC
+        table.push(0);
+        table.extend(&(section as u16).to_le_bytes());
+        table.extend(&symbol.va.to_le_bytes());
+        table.extend(&symbol.size.to_le_bytes());
+        strings.extend(symbol.name.as_bytes());
+        strings.push(0);
+    }
+
+    let mut names = data
+        [shstrtab.sh_offset as usize..(shstrtab.sh_offset + shstrtab.sh_size) as usize]
+        .to_vec();
+    let symtab_name = names.len();
+    names.extend(b".symtab\0");
+    let strtab_name = names.len();
+    names.extend(b".strtab\0");
+
+    let mut append = |bytes: &[u8], alignment: usize| {
+        data.resize(align(data.len() as u64, alignment as u64) as usize, 0);
+        let offset = data.len() as u64;
+        data.extend_from_slice(bytes);
+        offset
+    };
+    sections[shstrndx].sh_offset = append(&names, 1);
+    sections[shstrndx].sh_size = names.len() as u64;
+    let strtab = SectionHeader {
+        sh_name: strtab_name,
+        sh_type: SHT_STRTAB,
+        sh_offset: append(&strings, 1),
+        sh_size: strings.len() as u64,
+        sh_addralign: 1,
+        ..Default::default()
+    };
+    let symtab = SectionHeader {
+        sh_name: symtab_name,
+        sh_type: SHT_SYMTAB,
+        sh_offset: append(&table, 8),
+        sh_size: table.len() as u64,
+        sh_link: sections.len() as u32 + 1,
+        sh_info: 1,
+        sh_addralign: 8,
+        sh_entsize: 24,
+        ..Default::default()
+    };
+    sections.push(symtab);
+    sections.push(strtab);
+    let headers = sections
+        .iter()
+        .flat_map(encode_section_header)
+        .collect::<Vec<_>>();
+    let shoff = append(&headers, 8);
+
+    data[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+    data[0x3c..0x3e].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+    Ok(())
+}
+
+fn encode_section_header(header: &SectionHeader) -> Vec<u8> {
+    [
+        &(header.sh_name as u32).to_le_bytes()[..],
+        &header.sh_type.to_le_bytes()[..],
+        &header.sh_flags.to_le_bytes()[..],
+        &header.sh_addr.to_le_bytes()[..],
+        &header.sh_offset.to_le_bytes()[..],
+        &header.sh_size.to_le_bytes()[..],
+        &header.sh_link.to_le_bytes()[..],
+        &header.sh_info.to_le_bytes()[..],
+        &header.sh_addralign.to_le_bytes()[..],
+        &header.sh_entsize.to_le_bytes()[..],
+    ]
+    .concat()
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 0x200 byte ELF loaded as a whole at 0x400000, with a PT_NOTE and `extra` program headers.
+    fn tiny_elf(extra: &[ProgramHeader]) -> Vec<u8> {
+        let mut data = vec![0x7f, b'E', b'L', b'F', 2, 1, 1];
+        data.resize(16, 0);
+        data.extend(&2u16.to_le_bytes()); // ET_EXEC
+        data.extend(&0x3eu16.to_le_bytes()); // x86-64
+        data.extend(&1u32.to_le_bytes());
+        data.extend(&0x400100u64.to_le_bytes()); // entry
+        data.extend(&64u64.to_le_bytes()); // program headers
+        data.extend(&0u64.to_le_bytes()); // no section headers
+        data.extend(&0u32.to_le_bytes());
+        let count = 2 + extra.len() as u16;
+        for half in &[64u16, 56, count, 64, 0, 0] {
+            data.extend(&half.to_le_bytes());
+        }
+        let load = ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: PF_R | PF_X,
+            p_offset: 0,
+            p_vaddr: 0x400000,
+            p_paddr: 0x400000,
+            p_filesz: 0x200,
+            p_memsz: 0x200,
+            p_align: PAGE_SIZE,
+        };
+        data.extend(encode_program_header(&load, true));
+        data.extend(encode_program_header(&note(0x100), true));
+        for header in extra {
+            data.extend(encode_program_header(header, true));
+        }
+        data.resize(0x200, 0);
+        data
+    }
+
+    fn note(offset: u64) -> ProgramHeader {
+        ProgramHeader {
+            p_type: PT_NOTE,
+            p_flags: PF_R,
+            p_offset: offset,
+            p_vaddr: 0x400000 + offset,
+            p_paddr: 0x400000 + offset,
+            p_filesz: 0x20,
+            p_memsz: 0x20,
+            p_align: 4,
+        }
+    }
+
+    #[test]
+    fn test_push() {
+        let mut extension = Extension {
+            va: 0x401000,
+            code: vec![],
+            offset: None,
+            writable: vec![],
+            writable_offset: None,
+        };
+        assert_eq!(0x401000, extension.push(&[0x90]).unwrap());
+        assert_eq!(0x401008, extension.push_aligned(&[1; 8], 8).unwrap());
+        assert_eq!(&[0; 7], &extension.code[1..8]);
+        assert_eq!(0x401010, extension.next_va());
+        assert!(extension.push(&[0; EXTENSION_SIZE]).is_err());
+        assert_eq!(0x10, extension.code.len());
+        assert_eq!(None, extension.written());
+        assert_eq!(0xcc, extension.contents()[EXTENSION_SIZE - 1]);
+        assert_eq!(0x2000, align(0x1001, PAGE_SIZE));
+        assert_eq!(0x1000, align(0x1000, PAGE_SIZE));
+    }
+
+    #[test]
+    fn test_encode_program_header() {
+        let header = ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: PF_R | PF_X,
+            p_offset: 0x1000,
+            p_vaddr: 0x8049000,
+            p_paddr: 0x8049000,
+            p_filesz: 0x10,
+            p_memsz: 0x20,
+            p_align: PAGE_SIZE,
+        };
+        let wide = encode_program_header(&header, true);
+        assert_eq!(56, wide.len());
+        assert_eq!(&5u32.to_le_bytes(), &wide[4..8]);
+        assert_eq!(&0x8049000u64.to_le_bytes(), &wide[16..24]);
+        // 32 bit headers move the flags after the sizes
+        let narrow = encode_program_header(&header, false);
+        assert_eq!(32, narrow.len());
+        assert_eq!(&0x8049000u32.to_le_bytes(), &narrow[8..12]);
+        assert_eq!(&5u32.to_le_bytes(), &narrow[24..28]);
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut data = tiny_elf(&[]);
+        let mut extension = Extension::plan(&data).unwrap();
+        assert_eq!(0x401000, extension.va);
+        extension.push(&[0xc3]).unwrap();
+        extension.apply(&mut data).unwrap();
+        assert_eq!(Some(0x1000), extension.offset);
+        assert_eq!(0x1000 + EXTENSION_SIZE, data.len());
+        assert_eq!(&[0xc3, 0xcc], &data[0x1000..0x1002]);
+
+        let elf = Elf::parse(&data).unwrap();
+        assert!(elf.program_headers.iter().all(|x| x.p_type != PT_NOTE));
+        let segment = &elf.program_headers[1];
+        assert_eq!(
+            (PT_LOAD, 0x1000, 0x401000, PF_R | PF_X),
+            (
+                segment.p_type,
+                segment.p_offset,
+                segment.p_vaddr,
+                segment.p_flags
+            )
+        );
+
+        // applying again only refreshes the contents
+        extension.push(&[0x90]).unwrap();
+        extension.apply(&mut data).unwrap();
+        assert_eq!(0x1000 + EXTENSION_SIZE, data.len());
+        assert_eq!(&[0xc3, 0x90, 0xcc], &data[0x1000..0x1003]);
+    }
+
+    #[test]
+    fn test_writable_segment() {
+        let mut data = tiny_elf(&[note(0x120)]);
+        let mut extension = Extension::plan(&data).unwrap();
+        extension.push(&[0xc3]).unwrap();
+        assert_eq!(0x402000, extension.push_writable(&[2], 1).unwrap());
+        assert_eq!(0x402008, extension.push_writable(&[1], 8).unwrap());
+        assert_eq!(None, extension.written());
+        extension.apply(&mut data).unwrap();
+        assert_eq!(Some(0x2000), extension.writable_offset);
+        assert_eq!(&[2, 0, 0, 0, 0, 0, 0, 0, 1, 0], &data[0x2000..0x200a]);
+        assert_eq!(2, extension.written().unwrap().len());
+
+        let elf = Elf::parse(&data).unwrap();
+        let loads = elf
+            .program_headers
+            .iter()
+            .filter(|x| x.p_type == PT_LOAD)
+            .map(|x| (x.p_vaddr, x.p_flags))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (0x400000, PF_R | PF_X),
+                (0x401000, PF_R | PF_X),
+                (0x402000, PF_R | PF_W)
+            ],
+            loads
+        );
+
+        // without a second PT_NOTE there's nowhere to describe the writable segment
+        let mut data = tiny_elf(&[]);
+        let mut extension = Extension::plan(&data).unwrap();
+        extension.push_writable(&[0], 1).unwrap();
+        assert!(extension.apply(&mut data).is_err());
+    }
+
+    #[test]
+    fn test_inject_import_failure() {
+        // a dynamic section with a string table but no symbol table to add a function to
+        let dynamic = ProgramHeader {
+            p_type: PT_DYNAMIC,
+            p_flags: PF_R,
+            p_offset: 0x180,
+            p_vaddr: 0x400180,
+            p_paddr: 0x400180,
+            p_filesz: 0x40,
+            p_memsz: 0x40,
+            p_align: 8,
+        };
+        let mut data = tiny_elf(&[dynamic]);
+        for (i, value) in [DT_STRTAB, 0x4001c0, DT_STRSZ, 1].iter().enumerate() {
+            data[0x180 + i * 8..0x188 + i * 8].copy_from_slice(&value.to_le_bytes());
+        }
+        let mut extension = Extension::plan(&data).unwrap();
+        assert!(inject_import(&data, &mut extension, "libc.so.6", Some("puts")).is_err());
+        assert!(extension.code.is_empty() && extension.writable.is_empty());
+        let (patches, slot) = inject_import(&data, &mut extension, "libc.so.6", None).unwrap();
+        assert_eq!((3, None), (patches.len(), slot));
+        assert_eq!(b"\0libc.so.6\0", &extension.code[..]);
+    }
+
+    /// `data` with a section header table appended: the null section, a .shstrtab and `sections`
+    fn with_sections(mut data: Vec<u8>, sections: &[SectionHeader]) -> Vec<u8> {
+        let names = b"\0.shstrtab\0.symtab\0.strtab\0";
+        let shstrtab = SectionHeader {
+            sh_name: 1,
+            sh_type: SHT_STRTAB,
+            sh_offset: data.len() as u64,
+            sh_size: names.len() as u64,
+            sh_addralign: 1,
+            ..Default::default()
+        };
+        data.extend(&names[..]);
+        let shoff = data.len() as u64;
+        let mut headers = vec![SectionHeader::default(), shstrtab];
+        headers.extend(sections.iter().cloned());
+        for header in &headers {
+            data.extend(encode_section_header(header));
+        }
+        data[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        data[0x3c..0x3e].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+        data[0x3e..0x40].copy_from_slice(&1u16.to_le_bytes());
+        data
+    }
+
+    fn names(data: &[u8]) -> Vec<String> {
+        let elf = Elf::parse(data).unwrap();
+        elf.section_headers
+            .iter()
+            .filter_map(|x| elf.shdr_strtab.get(x.sh_name).and_then(|x| x.ok()))
+            .map(|x| x.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut data = with_sections(tiny_elf(&[]), &[]);
+        let symbols = [Symbol {
+            name: "main".to_string(),
+            va: 0x400100,
+            size: 0x10,
+        }];
+        SymbolTableEdit::Add(symbols.to_vec())
+            .apply(&mut data)
+            .unwrap();
+        let elf = Elf::parse(&data).unwrap();
+        assert_eq!(
+            Some("main"),
+            elf.strtab
+                .get(elf.syms.get(1).unwrap().st_name)
+                .and_then(|x| x.ok())
+        );
+        assert_eq!(0x400100, elf.syms.get(1).unwrap().st_value);
+        assert!(SymbolTableEdit::Add(symbols.to_vec())
+            .apply(&mut data)
+            .is_err());
+
+        SymbolTableEdit::Strip.apply(&mut data).unwrap();
+        let elf = Elf::parse(&data).unwrap();
+        assert!(elf.section_headers.iter().all(|x| x.sh_type != SHT_SYMTAB));
+        assert!(SymbolTableEdit::Strip.apply(&mut data).is_err());
+    }
+
+    #[test]
+    fn test_strip_shared_string_table() {
+        // the symbol table's names are in .shstrtab, which must survive for the section names
+        let symtab = SectionHeader {
+            sh_name: 11,
+            sh_type: SHT_SYMTAB,
+            sh_offset: 0x1c0,
+            sh_size: 0x30,
+            sh_link: 1,
+            sh_entsize: 24,
+            ..Default::default()
+        };
+        let mut data = with_sections(tiny_elf(&[]), &[symtab]);
+        assert_eq!(vec!["", ".shstrtab", ".symtab"], names(&data));
+        SymbolTableEdit::Strip.apply(&mut data).unwrap();
+        assert_eq!(vec!["", ".shstrtab", ".symtab"], names(&data));
+        let elf = Elf::parse(&data).unwrap();
+        assert_eq!(SHT_STRTAB, elf.section_headers[1].sh_type);
+        assert_eq!(0, elf.section_headers[2].sh_type);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::util::TempDir;
+use std::error::Error;
+use std::ffi::{CStr, OsStr};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver};
+
+/// What a program printed, as lines with terminal escapes dropped.
+#[derive(Debug, Default)]
+pub struct Transcript {
+    pub lines: Vec<String>,
+    /// the line still being written
+    partial: Vec<u8>,
+}
+
+impl Transcript {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.partial.extend_from_slice(bytes);
+        while let Some(end) = self.partial.iter().position(|&x| x == b'\n') {
+            let line = self.partial.drain(..=end).collect::<Vec<_>>();
+            self.lines.push(clean(&line[..end]));
+        }
+    }
+
+    /// every line, including one which hasn't been finished yet
+    pub fn all(&self) -> Vec<String> {
+        let mut lines = self.lines.clone();
+        if !self.partial.is_empty() {
+            lines.push(clean(&self.partial));
+        }
+        lines
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len() + usize::from(!self.partial.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// a line as a terminal would leave it: what follows the last carriage return, without escapes
+fn clean(line: &[u8]) -> String {
+    let line = String::from_utf8_lossy(line);
+    // the pty turns each newline into \r\n
+    let line = line.strip_suffix('\r').unwrap_or(&line);
+    let line = line.rsplit('\r').next().unwrap_or_default();
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI, up to a final byte
+                Some('[') => {
+                    chars.find(|x| ('@'..='~').contains(x));
+                }
+                // OSC, up to BEL or ST
+                Some(']') => {
+                    chars.find(|x| *x == '\x07' || *x == '\\');
+                }
+                _ => {}
+            },
+            '\t' => out.push_str("    "),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A program running in a pseudo terminal, so it behaves (line buffering, colors) as it would
+/// when run by hand.
+#[derive(Debug)]
+pub struct Runner {
+    /// how it was run, for the title
+    pub command: String,
+    pub transcript: Transcript,
+    pub status: Option<ExitStatus>,
+    /// first line shown, None follows the output as it comes
+    scroll: Option<usize>,
+    child: Child,
+    receiver: Receiver<Vec<u8>>,
+    /// where a copy with pending edits applied is, removed once the runner is
+    copy: Option<TempDir>,
+}
+
+impl Runner {
+    /// Run `program` with `args`.  `copy` is deleted along with the runner.
+    pub fn spawn(
+        program: &Path,
+        args: &[String],
+        copy: Option<TempDir>,
+    ) -> Result<Runner, Box<dyn Error>> {
+        let (mut master, slave) = open_pty()?;
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        // a session of its own with the pty as its controlling terminal, so ^C and friends
+        // behave and it doesn't touch the editor's terminal
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                libc::ioctl(0, libc::TIOCSCTTY as _, 0);
+                Ok(())
+            });
+        }
+        let child = command
+            .spawn()
+            .map_err(|e| format!("couldn't run {}: {}", program.display(), e))?;
+        // reading the master only ends once every copy of the slave is closed
+        drop(command);
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            // the read fails with EIO once the program and its children have exited
+            while let Ok(len) = master.read(&mut buffer) {
+                if len == 0 || sender.send(buffer[..len].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut command = program.display().to_string();
+        for arg in args {
+            command.push(' ');
+            command.push_str(arg);
+        }
+        Ok(Runner {
+            command,
+            transcript: Transcript::default(),
+            status: None,
+            scroll: None,
+            child,
+            receiver,
+            copy,
+        })
+    }
+
+    /// Take in whatever's been printed since the last call, returning true when the program has
+    /// just exited.
+    pub fn poll(&mut self) -> bool {
+        for chunk in self.receiver.try_iter() {
+            self.transcript.push(&chunk);
+        }
+        if self.status.is_some() {
+            return false;
+        }
+        self.status = self.child.try_wait().ok().flatten();
+        self.status.is_some()
+    }
+
+    pub fn describe_status(&self) -> String {
+        match self.status {
+            None => "running".to_string(),
+            Some(status) => match (status.code(), status.signal()) {
+                (Some(code), _) => format!("exited {}", code),
+                (None, Some(signal)) => format!("killed by signal {}", signal),
+                (None, None) => "exited".to_string(),
+            },
+        }
+    }
+
+    /// the first line shown in a pane `height` lines tall
+    pub fn top(&self, height: usize) -> usize {
+        let last = self.transcript.len().saturating_sub(height);
+        self.scroll.map_or(last, |x| x.min(last))
+    }
+
+    /// scroll by `delta` lines, scrolling to the end follows the output again
+    pub fn scroll(&mut self, delta: isize, height: usize) {
+        let last = self.transcript.len().saturating_sub(height) as isize;
+        let top = self.top(height) as isize + delta;
+        self.scroll = if top >= last {
+            None
+        } else {
+            Some(top.max(0) as usize)
+        };
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        if self.status.is_none() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+        // the copy can only go once it's stopped running
+        drop(self.copy.take());
+    }
+}
+
+/// a new pseudo terminal's master and slave, 80x24
+fn open_pty() -> io::Result<(File, File)> {
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let master = unsafe { File::from_raw_fd(fd) };
+    let mut name = [0 as libc::c_char; 128];
+    let ok = unsafe {
+        libc::grantpt(fd) == 0
+            && libc::unlockpt(fd) == 0
+            && libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) == 0
+    };
+    if !ok {
+        return Err(io::Error::last_os_error());
+    }
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(OsStr::from_bytes(name.to_bytes()))?;
+    let size = libc::winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSWINSZ, &size) };
+    Ok((master, slave))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript() {
+        let mut transcript = Transcript::default();
+        transcript.push(b"\x1b[1;31mred\x1b[0m\r\n10%\r50");
+        transcript.push(b"%\r100%\r\n\x1b]0;title\x07tail");
+        assert_eq!(vec!["red", "100%"], transcript.lines);
+        assert_eq!(vec!["red", "100%", "tail"], transcript.all());
+        assert_eq!(3, transcript.len());
+    }
+
+    #[test]
+    fn test_runner() {
+        let args = ["-c".to_string(), "echo hi; exit 4".to_string()];
+        let mut runner = Runner::spawn(Path::new("/bin/sh"), &args, None).unwrap();
+        // what's left in the pty can arrive after the exit
+        for _ in 0..500 {
+            runner.poll();
+            if runner.status.is_some() && !runner.transcript.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!("exited 4", runner.describe_status());
+        assert_eq!(vec!["hi"], runner.transcript.all());
+        assert_eq!(0, runner.top(10));
+    }
+}
@@ -0,0 +1,68 @@
+use crate::util;
+
+/// The bytes of `value` as the type `kind` names: `u8`/`i8` up to `u64`/`i64`, `f32` or `f64`,
+/// little endian unless it ends in `be` (`u32be`, `f64be`...).  Integers can be given in hex or
+/// decimal, and as anything which fits the width signed or unsigned.
+pub fn encode(kind: &str, value: &str) -> Result<Vec<u8>, String> {
+    let (kind, big_endian) = match (kind.strip_suffix("be"), kind.strip_suffix("le")) {
+        (Some(kind), _) => (kind, true),
+        (_, Some(kind)) => (kind, false),
+        _ => (kind, false),
+    };
+    let float = |e: std::num::ParseFloatError| format!("{} isn't a number: {}", value, e);
+    let mut bytes = match kind {
+        "f32" => value.parse::<f32>().map_err(float)?.to_le_bytes().to_vec(),
+        "f64" => value.parse::<f64>().map_err(float)?.to_le_bytes().to_vec(),
+        _ => {
+            let width = match kind {
+                "u8" | "i8" => 1,
+                "u16" | "i16" => 2,
+                "u32" | "i32" => 4,
+                "u64" | "i64" => 8,
+                _ => {
+                    return Err(format!(
+                        "{} isn't a type, try u8 to u64, i8 to i64, f32 or f64",
+                        kind
+                    ))
+                }
+            };
+            let number = match value.strip_prefix('-') {
+                Some(value) => util::parse_address(value).map(|x| -(x as i128)),
+                None => util::parse_address(value).map(|x| x as i128),
+            }
+            .ok_or_else(|| format!("{} isn't a number", value))?;
+            let bits = 8 * width as u32;
+            if number < -(1 << (bits - 1)) || number >= 1 << bits {
+                return Err(format!("{} doesn't fit in {} byte(s)", value, width));
+            }
+            number.to_le_bytes()[..width].to_vec()
+        }
+    };
+    if big_endian {
+        bytes.reverse();
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            Ok(vec![0xef, 0xbe, 0xad, 0xde]),
+            encode("u32", "0xdeadbeef")
+        );
+        assert_eq!(Ok(vec![0xff, 0xfe]), encode("i16be", "-2"));
+        assert_eq!(Ok(vec![0xff]), encode("i8", "255"));
+        assert_eq!(Ok(vec![0, 0, 0xc0, 0x3f]), encode("f32", "1.5"));
+        assert_eq!(
+            Ok(vec![0x3f, 0xf8, 0, 0, 0, 0, 0, 0]),
+            encode("f64be", "1.5")
+        );
+        assert!(encode("u8", "256").is_err());
+        assert!(encode("i8", "-129").is_err());
+        assert!(encode("u24", "1").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::util::{from_hexstring, to_hexstring, Engines};
+
+/// Lines typed into the scratchpad, each translated on its own: assembly is encoded and hex is
+/// disassembled, so instructions can be tried out without touching any function.
+#[derive(Debug, Clone)]
+pub struct Scratchpad {
+    pub lines: Vec<String>,
+    /// where instructions are placed, which matters for relative jumps and calls
+    pub address: u64,
+}
+
+impl Default for Scratchpad {
+    fn default() -> Self {
+        Scratchpad {
+            lines: vec![String::new()],
+            address: 0,
+        }
+    }
+}
+
+impl Scratchpad {
+    pub fn push(&mut self, c: char) {
+        match c {
+            '\n' => self.lines.push(String::new()),
+            c => self
+                .lines
+                .last_mut()
+                .expect("there's always a line")
+                .push(c),
+        }
+    }
+
+    /// delete the last character, joining the last two lines if the last one is empty
+    pub fn pop(&mut self) {
+        let last = self.lines.last_mut().expect("there's always a line");
+        if last.pop().is_none() && self.lines.len() > 1 {
+            self.lines.pop();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Scratchpad {
+            address: self.address,
+            ..Scratchpad::default()
+        };
+    }
+
+    /// Each line followed by what it translates to.  Instructions assembled on a line are placed
+    /// after the ones on the lines before it.
+    pub fn labels(&self, engines: &Engines) -> Vec<String> {
+        let mut address = self.address;
+        let mut labels = vec![];
+        for line in &self.lines {
+            labels.push(format!("> {}", line));
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (translation, len) = translate(engines, line, address);
+            labels.push(format!("  {}", translation));
+            address += len as u64;
+        }
+        labels
+    }
+}
+
+/// The hex digits of `line` if it's bytes rather than an instruction.  Mnemonics like `add`,
+/// `fadd` and `cbw` are all hex digits too, so bytes have to start with a digit or `0x`.
+fn hex_digits(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let digits = match line.strip_prefix("0x") {
+        Some(digits) => digits,
+        None if line.starts_with(|x: char| x.is_ascii_digit()) => line,
+        None => return None,
+    };
+    let count = digits.chars().filter(|x| !x.is_whitespace()).count();
+    let hex = digits
+        .chars()
+        .all(|x| x.is_whitespace() || x.is_ascii_hexdigit());
+    Some(digits).filter(|_| count != 0 && count % 2 == 0 && hex)
+}
+
+/// What `line` encodes or decodes to at `address`, and how many bytes that is.
+fn translate(engines: &Engines, line: &str, address: u64) -> (String, usize) {
+    if let Some(digits) = hex_digits(line) {
+        let bytes = from_hexstring(digits);
+        let text = engines
+            .disassemble(&bytes, address)
+            .into_iter()
+            .map(|(_, text, _)| text.trim().to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        (text, bytes.len())
+    } else {
+        match engines.assemble(line.to_string(), address) {
+            Ok(bytes) if bytes.is_empty() => ("doesn't assemble".to_string(), 0),
+            Ok(bytes) => (to_hexstring(&bytes), bytes.len()),
+            Err(e) => (format!("error: {}", e), 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_digits() {
+        assert_eq!(Some("90 c3"), hex_digits("90 c3"));
+        assert_eq!(Some("4889e5"), hex_digits(" 4889e5"));
+        assert_eq!(Some("c3"), hex_digits("0xc3"));
+        for mnemonic in &["add", "fadd", "dec", "cbw", "c3"] {
+            assert_eq!(None, hex_digits(mnemonic));
+        }
+        assert_eq!(None, hex_digits("0xdead beef c"));
+        assert_eq!(None, hex_digits("0x"));
+        assert_eq!(None, hex_digits("nop"));
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut scratchpad = Scratchpad::default();
+        for c in "90 c3\nx".chars() {
+            scratchpad.push(c);
+        }
+        scratchpad.pop();
+        scratchpad.pop();
+        assert_eq!(vec!["90 c3".to_string()], scratchpad.lines);
+        assert_eq!(
+            vec!["> 90 c3", "  nop; ret"],
+            scratchpad.labels(&Engines::default())
+        );
+    }
+}
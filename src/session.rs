@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Something done to the binary, in enough detail to do it again against a fresh copy.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    /// the instruction at `va` in `function` was changed to `bytes` (hex)
+    Edit {
+        function: String,
+        va: u64,
+        bytes: String,
+    },
+    /// a command line was run with the instruction at `va` in `function` selected, and for ones
+    /// run from the Hex column the `byte` of it the cursor was on
+    Command {
+        function: String,
+        va: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        byte: Option<usize>,
+        line: String,
+    },
+    Write,
+    /// the same change made at several cursors at once, as an `Edit` each
+    Batch {
+        edits: Vec<Operation>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    /// milliseconds since the unix epoch
+    pub time: u64,
+    #[serde(flatten)]
+    pub operation: Operation,
+}
+
+/// Appends every operation to a log as one JSON object per line, so a session which ends badly
+/// is still on disk up to its last edit.
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> std::io::Result<Recorder> {
+        Ok(Recorder {
+            file: File::create(path)?,
+        })
+    }
+
+    pub fn record(&mut self, operation: Operation) -> Result<(), Box<dyn Error>> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_millis() as u64)
+            .unwrap_or(0);
+        let entry = Entry { time, operation };
+        writeln!(self.file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// The operations after the last write, which the recorded session never saved.
+pub fn unwritten(entries: &[Entry]) -> &[Entry] {
+    let written = entries
+        .iter()
+        .rposition(|x| x.operation == Operation::Write)
+        .map_or(0, |x| x + 1);
+    &entries[written..]
+}
+
+/// read back a log written by a `Recorder`
+pub fn load(path: &Path) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut entries = vec![];
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(
+            serde_json::from_str(&line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))?,
+        );
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unwritten() {
+        let entry = |operation| Entry { time: 0, operation };
+        let edit = Operation::Edit {
+            function: "main".to_string(),
+            va: 0x1000,
+            bytes: "90".to_string(),
+        };
+        let mut entries = vec![entry(edit.clone()), entry(Operation::Write)];
+        assert!(unwritten(&entries).is_empty());
+        entries.push(entry(edit.clone()));
+        assert_eq!(1, unwritten(&entries).len());
+        assert_eq!(1, unwritten(&entries[..1]).len());
+    }
+}
@@ -0,0 +1,268 @@
+//! Well known constants which give away what a function does, mostly crypto and checksums.
+
+use crate::binary;
+use crate::util::{self, Function, InstructionRow};
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Bytes to look for and how many bytes from the start of a match a function may point to and
+/// still be considered a user of it, so tables are found from references into their middle.
+pub struct Signature {
+    pub name: &'static str,
+    pub bytes: &'static [u8],
+    pub extent: u64,
+}
+
+/// constants are stored little endian, as they are in x86 immediates and tables
+pub const SIGNATURES: &[Signature] = &[
+    Signature {
+        name: "AES",
+        bytes: &[
+            0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7,
+            0xab, 0x76,
+        ],
+        extent: 256,
+    },
+    Signature {
+        name: "AES",
+        bytes: &[
+            0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3,
+            0xd7, 0xfb,
+        ],
+        extent: 256,
+    },
+    Signature {
+        name: "MD5/SHA-1",
+        bytes: &[0x01, 0x23, 0x45, 0x67],
+        extent: 20,
+    },
+    Signature {
+        name: "SHA-1",
+        bytes: &[0x99, 0x79, 0x82, 0x5a],
+        extent: 16,
+    },
+    Signature {
+        name: "SHA-256",
+        bytes: &[0x67, 0xe6, 0x09, 0x6a],
+        extent: 32,
+    },
+    Signature {
+        name: "SHA-256",
+        bytes: &[0x98, 0x2f, 0x8a, 0x42],
+        extent: 256,
+    },
+    Signature {
+        name: "SHA-512",
+        bytes: &[0x08, 0xc9, 0xbc, 0xf3, 0x67, 0xe6, 0x09, 0x6a],
+        extent: 64,
+    },
+    Signature {
+        name: "CRC-32",
+        bytes: &[0x20, 0x83, 0xb8, 0xed],
+        extent: 4,
+    },
+    Signature {
+        name: "CRC-32",
+        bytes: &[0xb7, 0x1d, 0xc1, 0x04],
+        extent: 4,
+    },
+    Signature {
+        name: "CRC-32",
+        bytes: &[
+            0x00, 0x00, 0x00, 0x00, 0x96, 0x30, 0x07, 0x77, 0x2c, 0x61, 0x0e, 0xee, 0xba, 0x51,
+            0x09, 0x99,
+        ],
+        extent: 1024,
+    },
+    Signature {
+        name: "CRC-32C",
+        bytes: &[0x78, 0x3b, 0xf6, 0x82],
+        extent: 4,
+    },
+    Signature {
+        name: "TEA",
+        bytes: &[0xb9, 0x79, 0x37, 0x9e],
+        extent: 4,
+    },
+    Signature {
+        name: "Blowfish",
+        bytes: &[0x88, 0x6a, 0x3f, 0x24],
+        extent: 4168,
+    },
+    Signature {
+        name: "ChaCha/Salsa20",
+        bytes: b"nd 3",
+        extent: 16,
+    },
+    // % 65521 compiles down to a multiply by this
+    Signature {
+        name: "Adler-32",
+        bytes: &[0x71, 0x80, 0x07, 0x80],
+        extent: 4,
+    },
+    Signature {
+        name: "Adler-32",
+        bytes: &[0xf1, 0xff, 0x00, 0x00],
+        extent: 4,
+    },
+    Signature {
+        name: "zlib",
+        bytes: b" inflate 1.",
+        extent: 64,
+    },
+    Signature {
+        name: "zlib",
+        bytes: b" deflate 1.",
+        extent: 64,
+    },
+];
+
+/// Names of the signatures each function contains or references, keyed by function name.
+pub fn scan(
+    data: &[u8],
+    functions: &[Function],
+    rows: &HashMap<String, Vec<InstructionRow>>,
+) -> HashMap<String, Vec<&'static str>> {
+    let hits = SIGNATURES
+        .par_iter()
+        .flat_map(|signature| {
+            data.par_windows(signature.bytes.len())
+                .enumerate()
+                .filter(move |(_, window)| *window == signature.bytes)
+                .map(move |(offset, _)| (signature, offset as u64))
+        })
+        .collect::<Vec<_>>();
+    let vas = binary::offsets_to_vas(data, &hits.iter().map(|x| x.1).collect::<Vec<_>>());
+
+    let mut found: HashMap<String, Vec<&'static str>> = HashMap::new();
+    let mut tag = |function: &str, name: &'static str| {
+        let names = found.entry(function.to_string()).or_default();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    };
+    // hits outside of any function are data, which gets attributed to whoever points at it.
+    // Rows are disassembled at file offsets, so a rip relative reference resolves to the file
+    // offset of what it points at while an absolute one is its address.
+    let mut data_hits = vec![];
+    for ((signature, offset), va) in hits.iter().zip(vas) {
+        let va = match va {
+            Some(va) => va,
+            None => continue,
+        };
+        match functions
+            .iter()
+            .find(|x| (x.offset as u64..(x.offset + x.size) as u64).contains(offset))
+        {
+            Some(function) => tag(&function.name, signature.name),
+            None => data_hits.push((
+                [
+                    *offset..offset + signature.extent,
+                    va..va + signature.extent,
+                ],
+                signature.name,
+            )),
+        }
+    }
+    if !data_hits.is_empty() {
+        for function in functions {
+            for target in references(
+                rows.get(&function.name)
+                    .map(|x| x.as_slice())
+                    .unwrap_or(&[]),
+            ) {
+                for (ranges, name) in &data_hits {
+                    if ranges.iter().any(|x| x.contains(&target)) {
+                        tag(&function.name, name);
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+/// every address an instruction mentions, rip relative operands resolved
+pub fn references(rows: &[InstructionRow]) -> Vec<u64> {
+    let mut addresses = vec![];
+    for row in rows {
+        let next = row.offset + util::from_hexstring(&row.bytes).len() as u64;
+        let text = row.text.replace(' ', "");
+        if let Some(at) = text.find("[rip+") {
+            let displacement = text[at + 5..].split(']').next().unwrap_or("");
+            if let Some(x) = util::parse_address(displacement) {
+                addresses.push(next.wrapping_add(x));
+            }
+        }
+        util::map_immediates(&row.text, |literal, value| {
+            addresses.push(value);
+            literal.to_string()
+        });
+    }
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, offset: usize, size: usize) -> Function {
+        Function {
+            name: name.to_string(),
+            offset,
+            size,
+            nbbs: 0,
+            callrefs: vec![],
+            outside_file: false,
+        }
+    }
+
+    #[test]
+    fn test_references() {
+        let rows = [
+            InstructionRow::new(0x1000, &[0; 7], "lea rax, [rip + 0x10]".to_string()),
+            InstructionRow::new(0x1007, &[0; 5], "mov eax, 0x402000".to_string()),
+        ];
+        assert_eq!(vec![0x1017, 0x10, 0x402000], references(&rows));
+    }
+
+    #[test]
+    fn test_scan() {
+        // loaded at 0x400000: a function at offset 0x1000 with an MD5 constant in it, and one
+        // at 0x1010 pointing at the CRC-32 table after it, once rip relative and once absolute
+        let mut code = vec![0x90; 0x20];
+        code[4..8].copy_from_slice(&[0x01, 0x23, 0x45, 0x67]);
+        code.extend(SIGNATURES[9].bytes);
+        let data = binary::test_elf(0x400000, &code);
+        let functions = [
+            function("md5", 0x1000, 0x10),
+            function("crc", 0x1010, 0x10),
+            function("absolute", 0x2000, 0x10),
+        ];
+        let rows = [
+            (
+                "crc".to_string(),
+                vec![InstructionRow::new(
+                    0x1010,
+                    &[0; 7],
+                    "lea rax, [rip + 0x19]".to_string(),
+                )],
+            ),
+            (
+                "absolute".to_string(),
+                vec![InstructionRow::new(
+                    0x2000,
+                    &[0; 5],
+                    "mov eax, 0x401024".to_string(),
+                )],
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let found = scan(&data, &functions, &rows);
+        assert_eq!(Some(&vec!["MD5/SHA-1"]), found.get("md5"));
+        assert_eq!(Some(&vec!["CRC-32"]), found.get("crc"));
+        assert_eq!(Some(&vec!["CRC-32"]), found.get("absolute"));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::config::Settings;
+use crate::util::{self, TempDir};
+use goblin::elf::sym::STB_GLOBAL;
+use goblin::elf::Elf;
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// Machine code compiled from a C snippet, ready to be copied anywhere in the binary.
+pub struct Snippet {
+    pub code: Vec<u8>,
+    /// offset of the function to call within `code`
+    pub entry: u64,
+}
+
+/// Compile the C file at `source` with the configured compiler and pull `.text` out of the
+/// object, `function` defaults to the first global one.  Anything which needs relocating (calls to libc, globals, string literals) is refused
+/// since there's nothing to resolve it against once the code is in the binary.
+pub fn compile(
+    source: &Path,
+    function: Option<&str>,
+    settings: &Settings,
+) -> Result<Snippet, Box<dyn Error>> {
+    let directory = TempDir::new("snippet")?;
+    let object = directory.join("snippet.o");
+    util::run(
+        Command::new(&settings.cc)
+            .args(&settings.cflags)
+            .arg("-c")
+            .arg(source)
+            .arg("-o")
+            .arg(&object),
+    )?;
+    let data = std::fs::read(&object)?;
+
+    let elf = Elf::parse(&data)?;
+    let text = elf
+        .section_headers
+        .iter()
+        .position(|x| elf.shdr_strtab.get(x.sh_name).and_then(|x| x.ok()) == Some(".text"))
+        .ok_or("the snippet has no .text section")?;
+    let relocated = elf.shdr_relocs.iter().any(|(index, relocs)| {
+        elf.section_headers[*index].sh_info as usize == text && !relocs.is_empty()
+    });
+    if relocated {
+        return Err(
+            "the snippet refers to code or data outside itself, which can't be injected".into(),
+        );
+    }
+    let entry = elf
+        .syms
+        .iter()
+        .filter(|x| x.is_function() && x.st_shndx == text)
+        .find(|x| match function {
+            Some(function) => elf.strtab.get(x.st_name).and_then(|x| x.ok()) == Some(function),
+            None => x.st_bind() == STB_GLOBAL,
+        })
+        .ok_or_else(|| match function {
+            Some(function) => format!("the snippet has no function named {}", function),
+            None => "the snippet has no functions".to_string(),
+        })?
+        .st_value;
+    let header = &elf.section_headers[text];
+    let code = data
+        .get(header.sh_offset as usize..(header.sh_offset + header.sh_size) as usize)
+        .ok_or(".text runs past the end of the object")?
+        .to_vec();
+    Ok(Snippet { code, entry })
+}
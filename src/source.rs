@@ -0,0 +1,58 @@
+use crate::archive::Member;
+use crate::compressed::Compressed;
+use crate::container::Image;
+use crate::remote::Remote;
+use std::error::Error;
+use std::path::Path;
+
+/// Somewhere the file lives other than the local filesystem.  It's fetched into a local working
+/// copy to be edited and stored back on write.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Remote(Remote),
+    Image(Image),
+    Archive(Member),
+    Compressed(Compressed),
+}
+
+impl Source {
+    pub fn fetch(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        match self {
+            Source::Remote(remote) => remote.fetch(local),
+            Source::Image(image) => image.fetch(local),
+            Source::Archive(member) => member.fetch(local),
+            Source::Compressed(compressed) => compressed.fetch(local),
+        }
+        .map_err(|e| format!("couldn't fetch {}: {}", self, e).into())
+    }
+
+    pub fn store(&self, local: &Path) -> Result<(), Box<dyn Error>> {
+        match self {
+            Source::Remote(remote) => remote.upload(local),
+            Source::Image(image) => image.commit(local),
+            Source::Archive(member) => member.store(local),
+            Source::Compressed(compressed) => compressed.store(local),
+        }
+    }
+
+    /// where `store` puts the file, for telling the user
+    pub fn destination(&self) -> String {
+        match self {
+            Source::Remote(remote) => remote.to_string(),
+            Source::Image(image) => format!("{} in {}", image.path, image.patched()),
+            Source::Archive(member) => member.to_string(),
+            Source::Compressed(compressed) => compressed.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Remote(remote) => remote.fmt(f),
+            Source::Image(image) => image.fmt(f),
+            Source::Archive(member) => member.fmt(f),
+            Source::Compressed(compressed) => compressed.fmt(f),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::util::{Function, InstructionRow};
+use std::collections::HashMap;
+
+/// prefixes which are counted with the instruction they're on rather than on their own
+const PREFIXES: &[&str] = &[
+    "lock", "rep", "repe", "repz", "repne", "repnz", "bnd", "notrack",
+];
+
+/// How many rows of each histogram are shown
+const SHOWN: usize = 40;
+
+/// Instruction, call and size statistics for the whole binary, for getting a feel for a file
+/// before reading any of it.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub functions: usize,
+    pub instructions: usize,
+    /// how often each mnemonic is used, most used first
+    pub mnemonics: Vec<(String, usize)>,
+    /// how many call sites each function has, most called first
+    pub calls: Vec<(String, usize)>,
+    /// how many functions there are of each size, as the smallest size in a power of two bucket
+    pub sizes: Vec<(usize, usize)>,
+}
+
+impl Stats {
+    pub fn new(functions: &[Function], rows: &HashMap<String, Vec<InstructionRow>>) -> Stats {
+        let mut mnemonics = HashMap::new();
+        let mut instructions = 0;
+        for row in rows.values().flatten().filter(|x| !x.data) {
+            instructions += 1;
+            let mut words = row.text.split_whitespace();
+            let mnemonic = words
+                .find(|x| !PREFIXES.contains(x))
+                .unwrap_or_default()
+                .to_string();
+            *mnemonics.entry(mnemonic).or_insert(0) += 1;
+        }
+
+        let names = functions
+            .iter()
+            .map(|x| (x.offset as u64, x.name.as_str()))
+            .collect::<HashMap<_, _>>();
+        let mut calls = HashMap::new();
+        for target in functions.iter().flat_map(|x| x.calls()) {
+            let name = names
+                .get(&target)
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| format!("{:#x}", target));
+            *calls.entry(name).or_insert(0) += 1;
+        }
+
+        let mut sizes = HashMap::new();
+        for function in functions {
+            let bucket = match function.size {
+                size if size.is_power_of_two() => size,
+                size => size.next_power_of_two() / 2,
+            };
+            *sizes.entry(bucket).or_insert(0) += 1;
+        }
+        let mut sizes = sizes.into_iter().collect::<Vec<_>>();
+        sizes.sort_unstable();
+
+        Stats {
+            functions: functions.len(),
+            instructions,
+            mnemonics: by_count(mnemonics),
+            calls: by_count(calls),
+            sizes,
+        }
+    }
+
+    pub fn mnemonic_labels(&self) -> Vec<String> {
+        histogram(
+            self.mnemonics
+                .iter()
+                .map(|(name, count)| (name.clone(), *count)),
+        )
+    }
+
+    pub fn call_labels(&self) -> Vec<String> {
+        histogram(
+            self.calls
+                .iter()
+                .map(|(name, count)| (name.clone(), *count)),
+        )
+    }
+
+    pub fn size_labels(&self) -> Vec<String> {
+        histogram(self.sizes.iter().map(|&(size, count)| {
+            let label = match size {
+                0 => "empty".to_string(),
+                size => format!("{:#x}-{:#x}", size, size * 2 - 1),
+            };
+            (label, count)
+        }))
+    }
+}
+
+/// most common first, ties by name so the order doesn't change between runs
+fn by_count(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// a row for each of the first `SHOWN` counts, with a bar scaled to the largest of them
+fn histogram(counts: impl Iterator<Item = (String, usize)>) -> Vec<String> {
+    let counts = counts.take(SHOWN).collect::<Vec<_>>();
+    let max = counts.iter().map(|x| x.1).max().unwrap_or(1);
+    counts
+        .into_iter()
+        .map(|(label, count)| {
+            let bar = (count * 20).div_ceil(max);
+            format!("{:<16} {:>7} {}", label, count, "#".repeat(bar))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, offset: usize, size: usize, calls: &[u64]) -> Function {
+        Function {
+            name: name.to_string(),
+            offset,
+            size,
+            nbbs: 1,
+            callrefs: calls
+                .iter()
+                .map(|&addr| crate::util::Reference {
+                    addr,
+                    kind: "CALL".to_string(),
+                    at: 0,
+                })
+                .collect(),
+            outside_file: false,
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let functions = vec![
+            function("main", 0x10, 0x30, &[0x40, 0x40, 0x99]),
+            function("helper", 0x40, 0x8, &[]),
+        ];
+        let mut rows = HashMap::new();
+        rows.insert(
+            "main".to_string(),
+            vec![
+                InstructionRow::new(0x10, &[0x90], "nop".to_string()),
+                InstructionRow::new(0x11, &[0xf3, 0xa4], "rep movsb".to_string()),
+                InstructionRow::new(0x13, &[0x90], "nop".to_string()),
+            ],
+        );
+        let stats = Stats::new(&functions, &rows);
+        assert_eq!(3, stats.instructions);
+        assert_eq!(
+            vec![("nop".to_string(), 2), ("movsb".to_string(), 1)],
+            stats.mnemonics
+        );
+        assert_eq!(
+            vec![("helper".to_string(), 2), ("0x99".to_string(), 1)],
+            stats.calls
+        );
+        assert_eq!(vec![(0x8, 1), (0x20, 1)], stats.sizes);
+        assert_eq!(
+            "0x8-0xf                1 ####################",
+            stats.size_labels()[0]
+        );
+    }
+}
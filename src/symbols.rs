@@ -0,0 +1,126 @@
+use pdb::{FallibleIterator, SymbolData, PDB};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// A function or global named by a PDB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdbSymbol {
+    pub name: String,
+    pub function: bool,
+}
+
+/// A PDB to load, given with --pdb or found next to the binary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub path: PathBuf,
+    /// given with --pdb, so failing to load it is fatal rather than a warning
+    pub explicit: bool,
+}
+
+impl Found {
+    /// `explicit` if there is one, otherwise `file` with a .pdb extension if there's such a file
+    pub fn new(file: &Path, explicit: Option<PathBuf>) -> Option<Found> {
+        match explicit {
+            Some(path) => Some(Found {
+                path,
+                explicit: true,
+            }),
+            None => Some(file.with_extension("pdb"))
+                .filter(|x| x.is_file())
+                .map(|path| Found {
+                    path,
+                    explicit: false,
+                }),
+        }
+    }
+
+    /// what to tell the user about having `loaded` it, where a PDB which just happened to be
+    /// lying next to the binary only gets a warning if it couldn't be
+    pub fn report(&self, loaded: Result<String, Box<dyn Error>>) -> Result<String, Box<dyn Error>> {
+        match loaded {
+            Err(e) if !self.explicit => Ok(format!(
+                "warning: couldn't load {}: {}",
+                self.path.display(),
+                e
+            )),
+            loaded => loaded,
+        }
+    }
+}
+
+/// Every function and global in the PDB at `path`, by RVA.  Procedure names from the modules win
+/// over the (usually mangled) public names at the same address.
+pub fn load(path: &Path) -> Result<BTreeMap<u32, PdbSymbol>, Box<dyn Error>> {
+    let mut pdb = PDB::open(std::fs::File::open(path)?)?;
+    let address_map = pdb.address_map()?;
+    let mut symbols = BTreeMap::new();
+
+    let globals = pdb.global_symbols()?;
+    let mut iter = globals.iter();
+    while let Some(symbol) = iter.next()? {
+        let (offset, name, function) = match symbol.parse() {
+            Ok(SymbolData::Public(x)) => (x.offset, x.name, x.function),
+            Ok(SymbolData::Data(x)) => (x.offset, x.name, false),
+            _ => continue,
+        };
+        if let Some(rva) = offset.to_rva(&address_map) {
+            symbols.entry(rva.0).or_insert(PdbSymbol {
+                name: name.to_string().into_owned(),
+                function,
+            });
+        }
+    }
+
+    let debug_information = pdb.debug_information()?;
+    let mut modules = debug_information.modules()?;
+    while let Some(module) = modules.next()? {
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let mut iter = info.symbols()?;
+        while let Some(symbol) = iter.next()? {
+            if let Ok(SymbolData::Procedure(x)) = symbol.parse() {
+                if let Some(rva) = x.offset.to_rva(&address_map) {
+                    symbols.insert(
+                        rva.0,
+                        PdbSymbol {
+                            name: x.name.to_string().into_owned(),
+                            function: true,
+                        },
+                    );
+                }
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    #[test]
+    fn test_found() {
+        let directory = TempDir::new("test").unwrap();
+        let file = directory.join("a.exe");
+        assert_eq!(None, Found::new(&file, None));
+
+        let beside = directory.join("a.pdb");
+        std::fs::write(&beside, b"not a pdb").unwrap();
+        let found = Found::new(&file, None).unwrap();
+        assert_eq!(beside, found.path);
+        assert!(!found.explicit);
+        let warning = found.report(load(&found.path).map(|_| String::new()));
+        assert!(warning.unwrap().starts_with("warning: couldn't load"));
+
+        let explicit = Found::new(&file, Some(beside.clone())).unwrap();
+        assert!(explicit.explicit);
+        assert!(explicit
+            .report(load(&explicit.path).map(|_| String::new()))
+            .is_err());
+        assert_eq!("loaded", explicit.report(Ok("loaded".to_string())).unwrap());
+    }
+}
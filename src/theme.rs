@@ -0,0 +1,111 @@
+use crate::byteclass::Class;
+use serde::{Deserialize, Serialize};
+use tui::style::{Color, Modifier, Style};
+
+/// Colors the interface is drawn in, the `theme` key of the config.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Default,
+    /// bright colors on black, with everything which is only a color made bold as well
+    HighContrast,
+}
+
+impl Theme {
+    fn emphasis(self, style: Style) -> Style {
+        match self {
+            Theme::Default => style,
+            Theme::HighContrast => style.add_modifier(Modifier::BOLD),
+        }
+    }
+
+    pub fn text(self) -> Style {
+        match self {
+            Theme::Default => Style::default().fg(Color::White),
+            Theme::HighContrast => Style::default().fg(Color::White).bg(Color::Black),
+        }
+    }
+
+    /// border of the focused column
+    pub fn focused(self) -> Style {
+        match self {
+            Theme::Default => Style::default().fg(Color::LightGreen),
+            Theme::HighContrast => self.emphasis(Style::default().fg(Color::Yellow)),
+        }
+    }
+
+    /// the selected item of a list
+    pub fn highlight(self) -> Style {
+        let background = match self {
+            Theme::Default => Color::LightGreen,
+            Theme::HighContrast => Color::Yellow,
+        };
+        Style::default()
+            .bg(background)
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    /// the row a trace is stopped at
+    pub fn pc(self) -> Style {
+        let background = match self {
+            Theme::Default => Color::Yellow,
+            Theme::HighContrast => Color::Cyan,
+        };
+        self.emphasis(Style::default().fg(Color::Black).bg(background))
+    }
+
+    /// rows which don't assemble
+    pub fn invalid(self) -> Style {
+        self.bad().add_modifier(Modifier::UNDERLINED)
+    }
+
+    /// rows needing an instruction set extension the target lacks
+    pub fn forbidden(self) -> Style {
+        match self {
+            Theme::Default => Style::default().fg(Color::Magenta),
+            Theme::HighContrast => self.emphasis(Style::default().fg(Color::LightMagenta)),
+        }
+    }
+
+    /// added lines and mitigations in effect
+    pub fn good(self) -> Style {
+        match self {
+            Theme::Default => Style::default().fg(Color::Green),
+            Theme::HighContrast => self.emphasis(Style::default().fg(Color::LightGreen)),
+        }
+    }
+
+    /// removed lines, missing mitigations and going over budget
+    pub fn bad(self) -> Style {
+        match self {
+            Theme::Default => Style::default().fg(Color::Red),
+            Theme::HighContrast => self.emphasis(Style::default().fg(Color::LightRed)),
+        }
+    }
+
+    /// changed lines
+    pub fn changed(self) -> Style {
+        match self {
+            Theme::Default => Style::default().fg(Color::Yellow),
+            Theme::HighContrast => self.emphasis(Style::default().fg(Color::LightYellow)),
+        }
+    }
+
+    /// bytes in the Hex column when they're colored by class
+    pub fn byte(self, class: Class) -> Style {
+        let color = match (self, class) {
+            (Theme::Default, Class::Zero) => Color::DarkGray,
+            (Theme::HighContrast, Class::Zero) => Color::Gray,
+            (Theme::Default, Class::Printable) => Color::Cyan,
+            (Theme::HighContrast, Class::Printable) => Color::LightCyan,
+            (Theme::Default, Class::High) => Color::Yellow,
+            (Theme::HighContrast, Class::High) => Color::LightYellow,
+            (Theme::Default, Class::Prefix) => Color::Magenta,
+            (Theme::HighContrast, Class::Prefix) => Color::LightMagenta,
+            (_, Class::Other) => return self.text(),
+        };
+        self.emphasis(Style::default().fg(color))
+    }
+}
@@ -0,0 +1,95 @@
+use crate::util::{self, TempDir};
+use r2pipe::{open_pipe, R2Pipe};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+/// registers shown in the sidebar, in this order, when the emulated architecture has them
+const REGISTERS: &[&str] = &[
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip", "rflags",
+];
+
+/// Emulation of the patched code with r2's ESIL, one instruction at a time.  r2 runs against a
+/// copy of the file with the pending edits applied, so what gets traced is what would be written.
+pub struct Trace {
+    r2: R2Pipe,
+    /// holds the copy r2 has open, removed with the trace
+    _directory: TempDir,
+    /// address of the next instruction to execute
+    pub pc: u64,
+    pub registers: Vec<(String, u64)>,
+    /// instructions executed so far
+    pub steps: usize,
+}
+
+impl Trace {
+    /// start emulating `data` at `pc` with fresh registers and stack
+    pub fn start(data: &[u8], pc: u64) -> Result<Trace, Box<dyn Error>> {
+        let directory = TempDir::new("trace")?;
+        let file = directory.join("patched");
+        std::fs::write(&file, data)?;
+        let mut r2 = open_pipe!(Some(file.to_string_lossy())).map_err(|e| e.to_string())?;
+        // memory writes by the emulated code go to a cache instead of failing
+        for command in &["e io.cache=true", "aei", "aeim", &format!("aepc {:#x}", pc)] {
+            r2.cmd(command)?;
+        }
+        let mut trace = Trace {
+            r2,
+            _directory: directory,
+            pc,
+            registers: vec![],
+            steps: 0,
+        };
+        trace.refresh()?;
+        Ok(trace)
+    }
+
+    /// execute one instruction
+    pub fn step(&mut self) -> Result<(), Box<dyn Error>> {
+        self.r2.cmd("aes")?;
+        self.steps += 1;
+        self.refresh()
+    }
+
+    /// Step until the pc lands on one of `breakpoints`, emulation gets stuck or `limit`
+    /// instructions have run.  Returns why it stopped.
+    pub fn run(
+        &mut self,
+        breakpoints: &BTreeSet<u64>,
+        limit: usize,
+    ) -> Result<String, Box<dyn Error>> {
+        for _ in 0..limit {
+            let pc = self.pc;
+            self.step()?;
+            if breakpoints.contains(&self.pc) {
+                return Ok(format!("breakpoint at {:#x}", self.pc));
+            }
+            if self.pc == pc {
+                return Ok(format!("emulation stuck at {:#x}", self.pc));
+            }
+        }
+        Ok(format!("stopped at {:#x} after {} steps", self.pc, limit))
+    }
+
+    fn refresh(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut registers = serde_json::from_str::<HashMap<String, u64>>(&self.r2.cmd("aerj")?)?;
+        self.pc = util::parse_address(&self.r2.cmd("aer PC")?).ok_or("couldn't read the pc")?;
+        self.registers = REGISTERS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), registers.remove(*name)?)))
+            .collect();
+        // anything but x86_64 just gets every register in name order
+        if self.registers.is_empty() {
+            self.registers = registers.into_iter().collect();
+            self.registers.sort();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Trace {
+    fn drop(&mut self) {
+        // before the directory goes
+        self.r2.close();
+    }
+}
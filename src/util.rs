@@ -1,21 +1,27 @@
 use capstone::prelude::*;
-use capstone::Capstone;
+use capstone::{Capstone, InsnGroupType};
 
 use keystone::OptionValue;
 use serde::export::Formatter;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use termion::event::Key;
+use unicode_segmentation::UnicodeSegmentation;
 
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Clone, Copy)]
 pub enum Column {
     Function,
     Hex,
     Disasm,
+    /// the call tree panel
+    Calls,
 }
 
 impl Column {
     pub fn editable(&self) -> bool {
         match self {
-            Self::Function => false,
+            Self::Function | Self::Calls => false,
             Self::Hex | Self::Disasm => true,
         }
     }
@@ -25,53 +31,518 @@ impl Column {
 pub enum Mode {
     Viewing,
     Editing,
+    Command,
+    /// typing into the scratchpad
+    Scratch,
+    /// typing into the goto palette
+    Palette,
+    /// answering the trampoline wizard
+    Wizard,
 }
 
 impl std::fmt::Display for Mode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
             Mode::Viewing => "Viewing",
-            Mode::Editing => "Editing"
+            Mode::Editing => "Editing",
+            Mode::Command => "Command",
+            Mode::Scratch => "Scratchpad",
+            Mode::Palette => "Goto",
+            Mode::Wizard => "Trampoline",
+        })
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Copy)]
+pub enum SortOrder {
+    Name,
+    Address,
+    Size,
+    /// by source file and line, from DWARF line info
+    File,
+}
+
+impl SortOrder {
+    pub fn next(self) -> Self {
+        match self {
+            SortOrder::Name => SortOrder::Address,
+            SortOrder::Address => SortOrder::Size,
+            SortOrder::Size => SortOrder::File,
+            SortOrder::File => SortOrder::Name,
+        }
+    }
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SortOrder::Name => "name",
+            SortOrder::Address => "address",
+            SortOrder::Size => "size",
+            SortOrder::File => "source file",
+        })
+    }
+}
+
+/// How immediates are shown in the disassembly.
+#[derive(Eq, PartialEq, Clone, Copy)]
+pub enum Radix {
+    Hex,
+    Decimal,
+    /// printable ascii as a character literal, anything else in hex
+    Char,
+}
+
+impl Radix {
+    pub fn next(self) -> Self {
+        match self {
+            Radix::Hex => Radix::Decimal,
+            Radix::Decimal => Radix::Char,
+            Radix::Char => Radix::Hex,
+        }
+    }
+
+    pub fn format(self, value: u64) -> String {
+        match self {
+            Radix::Decimal => value.to_string(),
+            Radix::Char if (0x20..0x7f).contains(&value) && value != b'\'' as u64 => {
+                format!("'{}'", value as u8 as char)
+            }
+            Radix::Hex | Radix::Char => format!("{:#x}", value),
+        }
+    }
+}
+
+impl std::fmt::Display for Radix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Radix::Hex => "hex",
+            Radix::Decimal => "decimal",
+            Radix::Char => "char",
         })
     }
 }
 
-pub fn assemble(instr: String) -> Result<Vec<u8>, keystone::Error> {
-    use keystone::{Arch, Keystone, OptionType};
+/// How bytes are split into rows in the Hex column.
+#[derive(Eq, PartialEq, Clone, Copy)]
+pub enum Grouping {
+    /// one row per instruction, lined up with Disasm and editable
+    Instruction,
+    /// this many bytes per row behind an address gutter, for reading data
+    Fixed(usize),
+}
+
+impl Grouping {
+    pub fn next(self) -> Self {
+        match self {
+            Grouping::Instruction => Grouping::Fixed(8),
+            Grouping::Fixed(8) => Grouping::Fixed(16),
+            Grouping::Fixed(_) => Grouping::Instruction,
+        }
+    }
+}
+
+impl std::fmt::Display for Grouping {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Grouping::Instruction => f.write_str("instruction"),
+            Grouping::Fixed(width) => write!(f, "{} bytes", width),
+        }
+    }
+}
 
-    let engine = Keystone::new(
-        Arch::X86,
-        keystone::Mode::LITTLE_ENDIAN | keystone::Mode::MODE_64,
-    )?;
-    engine.option(OptionType::SYNTAX, OptionValue::SYNTAX_INTEL)?;
-    engine.asm(instr, 0x1000).map(|x| x.bytes)
+/// `bytes` loaded at `va` as rows of `width` bytes, each prefixed with its address
+pub fn fixed_rows(bytes: &[u8], va: u64, width: usize) -> Vec<String> {
+    bytes
+        .chunks(width)
+        .enumerate()
+        .map(|(i, chunk)| format!("{:08x}  {}", va + (i * width) as u64, to_hexstring(chunk)))
+        .collect()
 }
 
+/// how many grapheme clusters (what's seen as one character) `text` has
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
 
-pub fn disassemble(bytes: &[u8]) -> Vec<(Vec<u8>, String)> {
-    let cs = Capstone::new()
-        .x86()
-        .mode(arch::x86::ArchMode::Mode64)
-        .syntax(arch::x86::ArchSyntax::Intel)
-        .detail(true)
-        .build()
-        .expect("failed to create capstone object");
-    let insns = cs.disasm_all(bytes, 0x0).expect("disasm to work?");
-    insns
+/// byte offset of grapheme cluster `index` of `text`, its length if there aren't that many
+pub fn grapheme_offset(text: &str, index: usize) -> usize {
+    text.grapheme_indices(true)
+        .nth(index)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+/// Apply a key typed while editing `text` with the insertion point before grapheme cluster
+/// `at`, readline style: Home/Ctrl-a and End/Ctrl-e go to either end, Left/Right and
+/// Alt-b/Alt-f move by cluster and by word, Ctrl-w, Ctrl-u and Ctrl-k cut the word before the
+/// point, everything before it and everything after it into `killed`, which Ctrl-y pastes.
+/// Returns where the point ends up, None if the key did nothing.
+pub fn edit_text(text: &mut String, at: usize, key: Key, killed: &mut String) -> Option<usize> {
+    let len = grapheme_count(text);
+    // clamped so an emptied row can still be typed into
+    let at = at.min(len);
+    // words are mnemonics, registers, numbers and symbols, anything else is skipped over
+    let (word_start, word_end) = {
+        let clusters = text.graphemes(true).collect::<Vec<_>>();
+        let is_word = |x: &str| {
+            x.chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        };
+        let mut start = at;
+        while start > 0 && !is_word(clusters[start - 1]) {
+            start -= 1;
+        }
+        while start > 0 && is_word(clusters[start - 1]) {
+            start -= 1;
+        }
+        let mut end = at;
+        while end < len && !is_word(clusters[end]) {
+            end += 1;
+        }
+        while end < len && is_word(clusters[end]) {
+            end += 1;
+        }
+        (start, end)
+    };
+    let remove = |text: &mut String, start: usize, end: usize| {
+        let range = grapheme_offset(text, start)..grapheme_offset(text, end);
+        let removed = text[range.clone()].to_string();
+        text.replace_range(range, "");
+        removed
+    };
+    match key {
+        Key::Char(c) => {
+            text.insert(grapheme_offset(text, at), c);
+            // a combining mark joins the cluster before it rather than adding one
+            Some((at + grapheme_count(text)).saturating_sub(len))
+        }
+        Key::Ctrl('y') if !killed.is_empty() => {
+            text.insert_str(grapheme_offset(text, at), killed);
+            Some((at + grapheme_count(text)).saturating_sub(len))
+        }
+        Key::Delete if at < len => {
+            remove(text, at, at + 1);
+            Some(at)
+        }
+        Key::Backspace if at > 0 => {
+            remove(text, at - 1, at);
+            Some(at - 1)
+        }
+        Key::Ctrl('w') if at > 0 => {
+            *killed = remove(text, word_start, at);
+            Some(word_start)
+        }
+        Key::Ctrl('u') if at > 0 => {
+            *killed = remove(text, 0, at);
+            Some(0)
+        }
+        Key::Ctrl('k') if at < len => {
+            *killed = remove(text, at, len);
+            Some(at)
+        }
+        Key::Home | Key::Ctrl('a') => Some(0),
+        Key::End | Key::Ctrl('e') => Some(len),
+        Key::Left => Some(at.saturating_sub(1)),
+        Key::Right => Some((at + 1).min(len)),
+        Key::Alt('b') => Some(word_start),
+        Key::Alt('f') => Some(word_end),
+        _ => None,
+    }
+}
+
+/// The part of `text` from grapheme cluster `scroll` on which fits in `width` of them, with an
+/// ellipsis standing in for whatever is cut off on either side.
+pub fn clip(text: &str, scroll: usize, width: usize) -> String {
+    let len = grapheme_count(text);
+    let mut shown = text
+        .graphemes(true)
+        .skip(scroll)
+        .take(width)
+        .collect::<Vec<_>>();
+    if scroll > 0 && !shown.is_empty() {
+        shown[0] = "…";
+    }
+    if width > 0 && scroll + width < len {
+        shown[width - 1] = "…";
+    }
+    shown.concat()
+}
+
+/// Where to scroll a cell `width` characters wide holding `len` of them so that character
+/// `column` (which can be `len`, just past the end) is shown and not under an ellipsis from
+/// `clip`, moving as little as possible from `scroll`.
+pub fn scroll_to(column: usize, scroll: usize, width: usize, len: usize) -> usize {
+    if len < width || width < 3 {
+        return 0;
+    }
+    let mut scroll = scroll;
+    let left = if scroll > 0 { scroll + 1 } else { 0 };
+    if column < left {
+        scroll = column.saturating_sub(1);
+    }
+    let right = if scroll + width < len {
+        scroll + width - 1
+    } else {
+        scroll + width
+    };
+    if column >= right {
+        scroll = column + 2 - width;
+    }
+    scroll.min(len + 1 - width)
+}
+
+/// `bytes` as printable ascii, anything else as a dot
+pub fn printable(bytes: &[u8]) -> String {
+    bytes
         .iter()
-        .map(|x| {
-            (
-                x.bytes().to_vec(),
-                format!(
-                    "{} {}",
-                    x.mnemonic().unwrap_or(""),
-                    x.op_str().unwrap_or("")
-                ),
-            )
+        .map(|&x| {
+            if (0x20..0x7f).contains(&x) {
+                x as char
+            } else {
+                '.'
+            }
         })
         .collect()
 }
 
+/// Which syntax instructions are written in.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Syntax {
+    Intel,
+    Att,
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "intel" => Ok(Syntax::Intel),
+            "att" => Ok(Syntax::Att),
+            _ => Err(format!("{} isn't a syntax, try intel or att", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Syntax {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Syntax::Intel => "intel",
+            Syntax::Att => "att",
+        })
+    }
+}
+
+/// How capstone disassembles and keystone assembles, the `engines` key of the config.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+pub struct EngineOptions {
+    /// syntax capstone writes instructions in
+    pub syntax: Syntax,
+    /// syntax keystone reads instructions in, which should usually match `syntax`
+    pub asm_syntax: Syntax,
+    /// decode details, instruction set extensions aren't known without them
+    pub detail: bool,
+    /// turn bytes which aren't an instruction into `.byte` rows instead of stopping there
+    pub skipdata: bool,
+    /// 32 or 64 bit code
+    pub bits: u32,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            syntax: Syntax::Intel,
+            asm_syntax: Syntax::Intel,
+            detail: true,
+            skipdata: true,
+            bits: 64,
+        }
+    }
+}
+
+/// One option changed by `:engine`.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum EngineSetting {
+    Syntax(Syntax),
+    AsmSyntax(Syntax),
+    Detail(bool),
+    Skipdata(bool),
+    Bits(u32),
+}
+
+impl EngineSetting {
+    pub fn parse(option: &str, value: &str) -> Result<Self, String> {
+        let flag = || match value {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(format!("{} takes on or off", option)),
+        };
+        match option {
+            "syntax" => Ok(EngineSetting::Syntax(value.parse()?)),
+            "asm-syntax" => Ok(EngineSetting::AsmSyntax(value.parse()?)),
+            "detail" => Ok(EngineSetting::Detail(flag()?)),
+            "skipdata" => Ok(EngineSetting::Skipdata(flag()?)),
+            "bits" => match value {
+                "32" => Ok(EngineSetting::Bits(32)),
+                "64" => Ok(EngineSetting::Bits(64)),
+                _ => Err("bits takes 32 or 64".to_string()),
+            },
+            _ => Err(format!(
+                "{} isn't an option, try syntax, asm-syntax, detail, skipdata or bits",
+                option
+            )),
+        }
+    }
+
+    pub fn apply(self, options: &mut EngineOptions) {
+        match self {
+            EngineSetting::Syntax(syntax) => options.syntax = syntax,
+            EngineSetting::AsmSyntax(syntax) => options.asm_syntax = syntax,
+            EngineSetting::Detail(detail) => options.detail = detail,
+            EngineSetting::Skipdata(skipdata) => options.skipdata = skipdata,
+            EngineSetting::Bits(bits) => options.bits = bits,
+        }
+    }
+}
+
+/// A capstone and a keystone built from the same options, made once and used for every
+/// instruction rather than set up again each time.
+pub struct Engines {
+    pub options: EngineOptions,
+    capstone: Capstone,
+    keystone: keystone::Keystone,
+}
+
+impl Engines {
+    /// Engines with the default options for `bits` bit code, reading and writing Intel syntax,
+    /// for code the editor writes itself whatever syntax the user's engines are set to.
+    pub fn intel(bits: u32) -> Result<Engines, Box<dyn std::error::Error>> {
+        Engines::new(EngineOptions {
+            bits,
+            ..EngineOptions::default()
+        })
+    }
+
+    pub fn new(options: EngineOptions) -> Result<Engines, Box<dyn std::error::Error>> {
+        use keystone::{Arch, Keystone, OptionType};
+
+        let (mode, ks_mode) = match options.bits {
+            32 => (arch::x86::ArchMode::Mode32, keystone::Mode::MODE_32),
+            64 => (arch::x86::ArchMode::Mode64, keystone::Mode::MODE_64),
+            bits => return Err(format!("{} bit code isn't supported", bits).into()),
+        };
+        let mut capstone = Capstone::new()
+            .x86()
+            .mode(mode)
+            .syntax(match options.syntax {
+                Syntax::Intel => arch::x86::ArchSyntax::Intel,
+                Syntax::Att => arch::x86::ArchSyntax::Att,
+            })
+            .detail(options.detail)
+            .build()
+            .map_err(|e| format!("capstone: {}", e))?;
+        // without skipdata disassembly stops at the first byte which isn't an instruction
+        // (padding, jump tables...), this turns each of those into a `.byte` row and carries on
+        capstone
+            .set_skipdata(options.skipdata)
+            .map_err(|e| format!("capstone: {}", e))?;
+
+        let keystone = Keystone::new(Arch::X86, keystone::Mode::LITTLE_ENDIAN | ks_mode)?;
+        keystone.option(
+            OptionType::SYNTAX,
+            match options.asm_syntax {
+                Syntax::Intel => OptionValue::SYNTAX_INTEL,
+                Syntax::Att => OptionValue::SYNTAX_ATT,
+            },
+        )?;
+        Ok(Engines {
+            options,
+            capstone,
+            keystone,
+        })
+    }
+
+    pub fn assemble(&self, instr: String, address: u64) -> Result<Vec<u8>, keystone::Error> {
+        // keystone takes hex and decimal but not character literals
+        let instr = map_immediates(&instr, |literal, value| {
+            if literal.starts_with('\'') {
+                format!("{:#x}", value)
+            } else {
+                literal.to_string()
+            }
+        });
+        self.keystone.asm(instr, address).map(|x| x.bytes)
+    }
+
+    /// Each instruction's bytes, text and the instruction set extensions it needs (`avx2`,
+    /// `sse41`...), as capstone names them.
+    pub fn disassemble(&self, bytes: &[u8], address: u64) -> Vec<(Vec<u8>, String, Vec<String>)> {
+        let cs = &self.capstone;
+        let insns = cs.disasm_all(bytes, address).expect("disasm to work?");
+        insns
+            .iter()
+            .map(|x| {
+                // generic groups (jump, call...) are numbered below 128
+                let extensions = cs
+                    .insn_detail(&x)
+                    .map(|detail| {
+                        detail
+                            .groups()
+                            .filter(|group| group.0 >= 128)
+                            .filter_map(|group| cs.group_name(group))
+                            .filter(|name| !NOT_EXTENSIONS.contains(&name.as_str()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (
+                    x.bytes().to_vec(),
+                    format!(
+                        "{} {}",
+                        x.mnemonic().unwrap_or(""),
+                        x.op_str().unwrap_or("")
+                    ),
+                    extensions,
+                )
+            })
+            .collect()
+    }
+
+    /// The length of each instruction `bytes` disassemble to, and whether it returns, whichever
+    /// way it's spelled (`retq`, `ret 0x10`, `repz ret`...).  That's capstone's ret group when
+    /// details are on.
+    pub fn returns(&self, bytes: &[u8], address: u64) -> Vec<(usize, bool)> {
+        let cs = &self.capstone;
+        let ret = InsnGroupId(InsnGroupType::CS_GRP_RET as u8);
+        let insns = cs.disasm_all(bytes, address).expect("disasm to work?");
+        insns
+            .iter()
+            .map(|x| {
+                let returns = match cs.insn_detail(&x) {
+                    Ok(detail) => detail.groups().any(|group| group == ret),
+                    // without details, going by the mnemonic past any prefix
+                    Err(_) => x
+                        .mnemonic()
+                        .and_then(|x| x.split_whitespace().last())
+                        .is_some_and(|x| x.starts_with("ret")),
+                };
+                (x.bytes().len(), returns)
+            })
+            .collect()
+    }
+}
+
+impl Default for Engines {
+    fn default() -> Self {
+        Engines::new(EngineOptions::default()).expect("the default engine options work")
+    }
+}
+
+/// capstone groups which describe what kind of instruction it is or which modes it's valid in
+/// rather than an instruction set extension it needs
+const NOT_EXTENSIONS: &[&str] = &["mode32", "mode64", "16bitmode", "not64bitmode", "novlx"];
+
 pub fn to_hexstring(bytes: &[u8]) -> String {
     bytes
         .iter()
@@ -89,11 +560,375 @@ pub fn from_hexstring(str: &str) -> Vec<u8> {
 }
 
 
+/// parse a `0x` prefixed hex or plain decimal address
+pub fn parse_address(str: &str) -> Option<u64> {
+    let str = str.trim();
+    if let Some(hex) = str.strip_prefix("0x").or_else(|| str.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        str.parse().ok()
+    }
+}
+
+/// Map `path` into memory instead of reading it, so only the pages which are looked at get
+/// loaded.  The file mustn't be truncated while the map is alive.
+pub fn map(path: &std::path::Path) -> Result<memmap2::Mmap, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // mapping nothing fails with an unhelpful EINVAL
+    if file.metadata()?.len() == 0 {
+        return Err(format!("{} is empty", path.display()).into());
+    }
+    Ok(unsafe { memmap2::Mmap::map(&file)? })
+}
+
+/// Replace `path` by copying it next to itself, handing the copy to `write` and renaming it over
+/// the original, so a crash part way through leaves either the old or the new file and never
+/// half of each.  Permissions (setuid and setgid included) and, where allowed, ownership carry
+/// over, symlinks are followed.
+///
+/// The copy costs a pass over the whole file however little `write` changes, which is the price
+/// of never leaving a half written file: there is no renaming over the original without a
+/// complete file to rename.  It goes through `copy_file_range`, so filesystems which can (btrfs,
+/// xfs) share the data rather than duplicating it and the copy is then close to free, and it
+/// never holds the file in memory.
+pub fn replace_atomically<T>(
+    path: &std::path::Path,
+    write: impl FnOnce(&std::path::Path) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+    use std::os::unix::io::AsRawFd;
+    let path = path.canonicalize()?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.transmogrify-{}", name, std::process::id()));
+    let metadata = std::fs::metadata(&path)?;
+    // never anything already there, a symlink planted at the name would have the copy written
+    // wherever it points
+    let mut copy = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temporary)?;
+    let result = (|| {
+        std::io::copy(&mut std::fs::File::open(&path)?, &mut copy)?;
+        // only root can give files away, anyone else already owns the copy
+        unsafe { libc::fchown(copy.as_raw_fd(), metadata.uid(), metadata.gid()) };
+        drop(copy);
+        let value = write(&temporary)?;
+        // last, changing the owner or writing to the file clears setuid and setgid
+        std::fs::set_permissions(&temporary, metadata.permissions())?;
+        std::fs::File::open(&temporary)?.sync_all()?;
+        std::fs::rename(&temporary, &path)?;
+        Ok(value)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+        return result;
+    }
+    // the rename itself is only durable once the directory is
+    if let Some(directory) = path.parent() {
+        std::fs::File::open(directory)?.sync_all()?;
+    }
+    result
+}
+
+/// A directory in the temp dir with a name nobody can guess up front (so nobody can have put
+/// anything there first) which only this user can get into.  It's removed with everything in it
+/// when dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    pub path: std::path::PathBuf,
+}
+
+impl TempDir {
+    /// a new directory named after what it's for
+    pub fn new(purpose: &str) -> Result<TempDir, Box<dyn std::error::Error>> {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        let template = std::env::temp_dir().join(format!("transmogrify-{}-XXXXXX", purpose));
+        let mut template =
+            std::ffi::CString::new(template.as_os_str().as_bytes())?.into_bytes_with_nul();
+        // mkdtemp fills in the Xs and makes the directory 0700
+        if unsafe { libc::mkdtemp(template.as_mut_ptr() as *mut libc::c_char) }.is_null() {
+            return Err(format!(
+                "couldn't make a temporary directory: {}",
+                std::io::Error::last_os_error()
+            )
+            .into());
+        }
+        template.pop();
+        Ok(TempDir {
+            path: std::ffi::OsString::from_vec(template).into(),
+        })
+    }
+
+    pub fn join(&self, name: &str) -> std::path::PathBuf {
+        self.path.join(name)
+    }
+
+    /// A new file `name` in it which only this user can read, write or run.  Anything already
+    /// there (a symlink included) is an error rather than followed.
+    pub fn create(
+        &self,
+        name: &str,
+    ) -> Result<(std::path::PathBuf, std::fs::File), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::OpenOptionsExt;
+        let path = self.join(name);
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o700)
+            .open(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok((path, file))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// stdout of `command`, or its stderr as the error if it fails
+pub fn run(command: &mut std::process::Command) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let output = command
+        .output()
+        .map_err(|e| format!("couldn't run {:?}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()
+            .into());
+    }
+    Ok(output.stdout)
+}
+
+/// Replace every numeric literal in `text` (`0x10`, `16` or `'a'`) with `f(literal, value)`.
+/// Digits which are part of a name, like the 8 in r8, are left alone.
+pub fn map_immediates(text: &str, mut f: impl FnMut(&str, u64) -> String) -> String {
+    let chars = text.char_indices().collect::<Vec<_>>();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let in_word = i > 0 && (chars[i - 1].1.is_ascii_alphanumeric() || chars[i - 1].1 == '_');
+        // length in chars and value of a literal starting here
+        let literal = if in_word {
+            None
+        } else if c == '\'' && i + 2 < chars.len() && chars[i + 2].1 == '\'' {
+            Some((3, chars[i + 1].1 as u64))
+        } else if c.is_ascii_digit() {
+            let len = chars[i..]
+                .iter()
+                .take_while(|(_, x)| x.is_ascii_alphanumeric())
+                .count();
+            let end = chars.get(i + len).map(|x| x.0).unwrap_or(text.len());
+            parse_address(&text[start..end]).map(|value| (len, value))
+        } else {
+            None
+        };
+        match literal {
+            Some((len, value)) => {
+                let end = chars.get(i + len).map(|x| x.0).unwrap_or(text.len());
+                out.push_str(&f(&text[start..end], value));
+                i += len;
+            }
+            None => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// rewrite the immediates in `text` in `radix`
+pub fn format_immediates(text: &str, radix: Radix) -> String {
+    map_immediates(text, |_, value| radix.format(value))
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Function {
     pub name: String,
     pub offset: usize,
     pub size: usize,
+    /// number of basic blocks, as reported by r2
+    #[serde(default)]
+    pub nbbs: usize,
+    /// calls and jumps out of the function, as reported by r2
+    #[serde(default)]
+    pub callrefs: Vec<Reference>,
+    /// some of the function lies outside the file (imports, .bss...) so it can't be edited
+    #[serde(skip)]
+    pub outside_file: bool,
+}
+
+impl Function {
+    /// addresses this function calls
+    pub fn calls(&self) -> impl Iterator<Item = u64> + '_ {
+        self.callrefs
+            .iter()
+            .filter(|x| x.kind == "CALL" || x.kind == "C")
+            .map(|x| x.addr)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reference {
+    pub addr: u64,
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// address of the referencing instruction
+    #[serde(default)]
+    pub at: u64,
+}
+
+/// Raw bytes to be written at a file offset, for edits which don't belong to any function.
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+impl Patch {
+    pub fn new(offset: u64, bytes: Vec<u8>) -> Self {
+        Patch { offset, bytes }
+    }
+}
+
+/// how many bytes a row of data holds at most
+pub const DATA_ROW_SIZE: usize = 8;
+
+/// `bytes` as a `db` directive
+pub fn data_text(bytes: &[u8]) -> String {
+    let bytes = bytes
+        .iter()
+        .map(|x| format!("{:#04x}", x))
+        .collect::<Vec<_>>();
+    format!("db {}", bytes.join(", "))
+}
+
+/// the bytes of a `db` directive, in any radix immediates are shown in
+fn parse_data(text: &str) -> Option<Vec<u8>> {
+    let list = format_immediates(text.trim().strip_prefix("db")?, Radix::Hex);
+    if list.trim().is_empty() {
+        return None;
+    }
+    list.split(',')
+        .map(|x| parse_address(x).and_then(|x| u8::try_from(x).ok()))
+        .collect()
+}
+
+/// A single instruction of a function as shown in the Hex and Disasm columns.
+/// `bytes` and `text` are the user editable representations, `dirty` marks a row
+/// which has been edited since its other column was last rebuilt.
+#[derive(Debug, Clone)]
+pub struct InstructionRow {
+    /// where the instruction is in the file, which is also the address it's disassembled and
+    /// assembled at
+    pub offset: u64,
+    pub bytes: String,
+    pub text: String,
+    /// the bytes as they were when the file was loaded
+    pub original: String,
+    pub dirty: bool,
+    pub valid: bool,
+    /// instruction set extensions the instruction needs, see `disassemble`
+    pub extensions: Vec<String>,
+    /// bytes marked as data, shown as a `db` and never assembled or disassembled
+    pub data: bool,
+}
+
+impl InstructionRow {
+    pub fn new(offset: u64, bytes: &[u8], text: String) -> Self {
+        InstructionRow {
+            offset,
+            bytes: to_hexstring(bytes),
+            original: to_hexstring(bytes),
+            text,
+            dirty: false,
+            valid: true,
+            extensions: vec![],
+            data: false,
+        }
+    }
+
+    /// a row of bytes marked as data
+    pub fn data(offset: u64, bytes: &[u8]) -> Self {
+        InstructionRow {
+            data: true,
+            ..InstructionRow::new(offset, bytes, data_text(bytes))
+        }
+    }
+
+    /// whether the instruction needs any of the `forbidden` extensions, which are matched
+    /// ignoring case and dots so `SSE4.1` forbids `sse41`
+    pub fn uses(&self, forbidden: &[String]) -> Option<&str> {
+        self.extensions
+            .iter()
+            .find(|x| {
+                forbidden
+                    .iter()
+                    .any(|y| y.replace('.', "").eq_ignore_ascii_case(x))
+            })
+            .map(|x| x.as_str())
+    }
+
+    pub fn modified(&self) -> bool {
+        from_hexstring(&self.bytes) != from_hexstring(&self.original)
+    }
+
+    /// re-disassemble `text` from the edited `bytes`
+    pub fn rebuild_text(&mut self, engines: &Engines) {
+        if self.data {
+            self.text = data_text(&from_hexstring(&self.bytes));
+            self.valid = true;
+            self.dirty = false;
+            return;
+        }
+        match engines
+            .disassemble(&from_hexstring(&self.bytes), self.offset)
+            .first()
+        {
+            Some((_, text, extensions)) => {
+                self.text = text.clone();
+                self.extensions = extensions.clone();
+                self.valid = true;
+            }
+            None => {
+                self.text = "INVALID".to_string();
+                self.valid = false;
+            }
+        }
+        self.dirty = false;
+    }
+
+    /// re-assemble `bytes` from the edited `text`, leaving the old bytes alone if it doesn't assemble
+    pub fn rebuild_bytes(&mut self, engines: &Engines) {
+        if self.data {
+            match parse_data(&self.text) {
+                Some(bytes) => {
+                    self.bytes = to_hexstring(&bytes);
+                    self.valid = true;
+                }
+                None => self.valid = false,
+            }
+            self.dirty = false;
+            return;
+        }
+        match engines.assemble(self.text.clone(), self.offset) {
+            Ok(bytes) => {
+                self.extensions = engines
+                    .disassemble(&bytes, self.offset)
+                    .first()
+                    .map(|x| x.2.clone())
+                    .unwrap_or_default();
+                self.bytes = to_hexstring(&bytes);
+                self.valid = true;
+            }
+            Err(_) => self.valid = false,
+        }
+        self.dirty = false;
+    }
 }
 
 
@@ -105,12 +940,208 @@ mod tests {
 
     #[test]
     fn test_assemble() {
-        assert_eq!(vec![0x55], assemble("push rbp".to_string()).unwrap());
+        assert_eq!(
+            vec![0x55],
+            Engines::default()
+                .assemble("push rbp".to_string(), 0x1000)
+                .unwrap()
+        );
     }
 
     #[test]
     fn test_disassembles() {
-        assert_eq!("push rbp", disassemble(&[0x55]).first().unwrap().1);
+        let engines = Engines::default();
+        assert_eq!(
+            "push rbp",
+            engines.disassemble(&[0x55], 0x0).first().unwrap().1
+        );
+    }
+
+    #[test]
+    fn test_disassembles_data() {
+        let rows = Engines::default().disassemble(&[0x06, 0xc3], 0x0);
+        assert_eq!(2, rows.len());
+        assert_eq!(".byte 0x06", rows[0].1);
+    }
+
+    #[test]
+    fn test_data_rows() {
+        let engines = Engines::default();
+        let mut row = InstructionRow::data(0x10, &[0x41, 0xc3]);
+        assert_eq!("db 0x41, 0xc3", row.text);
+        row.text = "db 'B', 195, 0x00".to_string();
+        row.rebuild_bytes(&engines);
+        assert!(row.valid);
+        assert_eq!("42 c3 00", row.bytes);
+        row.text = "db 0x100".to_string();
+        row.rebuild_bytes(&engines);
+        assert!(!row.valid);
+        row.rebuild_text(&engines);
+        assert_eq!("db 0x42, 0xc3, 0x00", row.text);
+    }
+
+    #[test]
+    fn test_clip() {
+        assert_eq!("mov rax…", clip("mov rax, rbx", 0, 8));
+        assert_eq!("…ax, rbx", clip("mov rax, rbx", 4, 8));
+        assert_eq!("…rax…", clip("mov rax, rbx", 3, 5));
+        assert_eq!("nop", clip("nop", 0, 8));
+
+        assert_eq!(0, scroll_to(3, 0, 20, 12));
+        // the end of the line, where typing would add to it
+        assert_eq!(5, scroll_to(12, 0, 8, 12));
+        assert_eq!(5, scroll_to(11, 0, 8, 12));
+        assert_eq!(0, scroll_to(6, 0, 8, 12));
+        assert_eq!(1, scroll_to(2, 5, 8, 12));
+        assert_eq!(0, scroll_to(1, 5, 8, 12));
+        // a shorter line doesn't stay scrolled
+        assert_eq!(0, scroll_to(0, 5, 8, 4));
+        assert_eq!("…é…", clip("café au lait", 2, 3));
+    }
+
+    #[test]
+    fn test_edit_text() {
+        let mut killed = String::new();
+        let mut edit = |text: &mut String, at, key| edit_text(text, at, key, &mut killed);
+        let mut text = "mov rax, 0 ; ü".to_string();
+        assert_eq!(Some(15), edit(&mut text, 14, Key::Char('x')));
+        assert_eq!("mov rax, 0 ; üx", text);
+        assert_eq!(Some(13), edit(&mut text, 14, Key::Backspace));
+        assert_eq!("mov rax, 0 ; x", text);
+        assert_eq!(Some(12), edit(&mut text, 12, Key::Delete));
+        assert_eq!("mov rax, 0 ;x", text);
+
+        // e followed by a combining acute accent is one cluster
+        let mut text = "sym.cafe\u{301}".to_string();
+        assert_eq!(8, grapheme_count(&text));
+        assert_eq!(Some(8), edit(&mut text, 8, Key::Char('\u{308}')));
+        assert_eq!(8, grapheme_count(&text));
+        assert_eq!(Some(7), edit(&mut text, 8, Key::Backspace));
+        assert_eq!("sym.caf", text);
+        assert_eq!(None, edit(&mut text, 0, Key::Backspace));
+        assert_eq!(Some(8), edit(&mut text, 7, Key::Char('→')));
+        assert_eq!("sym.caf→", text);
+        assert_eq!(7, grapheme_offset(&text, 7));
+    }
+
+    #[test]
+    fn test_readline_keys() {
+        let mut killed = String::new();
+        let mut text = "mov rax, qword [rbp - 8]".to_string();
+        let mut edit = |text: &mut String, at, key| edit_text(text, at, key, &mut killed);
+        assert_eq!(Some(0), edit(&mut text, 5, Key::Ctrl('a')));
+        assert_eq!(Some(24), edit(&mut text, 5, Key::End));
+        assert_eq!(Some(22), edit(&mut text, 24, Key::Alt('b')));
+        assert_eq!(Some(4), edit(&mut text, 7, Key::Alt('b')));
+        assert_eq!(Some(14), edit(&mut text, 7, Key::Alt('f')));
+        assert_eq!(None, edit(&mut text, 24, Key::Ctrl('k')));
+        assert_eq!(Some(9), edit(&mut text, 14, Key::Ctrl('w')));
+        assert_eq!("mov rax,  [rbp - 8]", text);
+        assert_eq!(Some(14), edit(&mut text, 9, Key::Ctrl('y')));
+        assert_eq!("mov rax, qword [rbp - 8]", text);
+        assert_eq!(Some(0), edit(&mut text, 4, Key::Ctrl('u')));
+        assert_eq!(Some(24), edit(&mut text, 24, Key::Ctrl('y')));
+        assert_eq!("rax, qword [rbp - 8]mov ", text);
+    }
+
+    #[test]
+    fn test_temp_dir() {
+        use std::os::unix::fs::PermissionsExt;
+        let (a, b) = (TempDir::new("test").unwrap(), TempDir::new("test").unwrap());
+        assert_ne!(a.path, b.path);
+        let mode = std::fs::metadata(&a.path).unwrap().permissions().mode();
+        assert_eq!(0o700, mode & 0o777);
+        std::fs::write(a.join("file"), b"").unwrap();
+        assert!(a.create("file").is_err());
+        let (created, _) = a.create("created").unwrap();
+        let mode = std::fs::metadata(&created).unwrap().permissions().mode();
+        assert_eq!(0o700, mode & 0o777);
+        let path = a.path.clone();
+        drop(a);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_replace_atomically() {
+        use std::os::unix::fs::PermissionsExt;
+        let directory = TempDir::new("test").unwrap();
+        let path = directory.join("setuid");
+        std::fs::write(&path, b"old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o4755)).unwrap();
+        replace_atomically(&path, |copy| Ok(std::fs::write(copy, b"new")?)).unwrap();
+        assert_eq!(b"new", &std::fs::read(&path).unwrap()[..]);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o4755, mode & 0o7777);
+
+        // a symlink where the copy goes isn't followed
+        let elsewhere = directory.join("elsewhere");
+        let name = format!(".setuid.transmogrify-{}", std::process::id());
+        std::os::unix::fs::symlink(&elsewhere, directory.join(&name)).unwrap();
+        assert!(replace_atomically(&path, |_| Ok(())).is_err());
+        assert!(!elsewhere.exists());
+        assert_eq!(b"new", &std::fs::read(&path).unwrap()[..]);
+    }
+
+    #[test]
+    fn test_map() {
+        let directory = TempDir::new("test").unwrap();
+        let empty = directory.join("empty");
+        std::fs::write(&empty, b"").unwrap();
+        let error = map(&empty).err().unwrap().to_string();
+        assert!(error.ends_with("empty is empty"));
+        assert!(map(&directory.join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_extensions() {
+        // vaddps ymm0, ymm1, ymm2
+        let engines = Engines::default();
+        let rows = engines.disassemble(&[0xc5, 0xf4, 0x58, 0xc2], 0x0);
+        assert_eq!(vec!["avx".to_string()], rows[0].2);
+        assert!(engines.disassemble(&[0x55], 0x0)[0].2.is_empty());
+
+        let mut row = InstructionRow::new(0, &[], String::new());
+        row.extensions = rows[0].2.clone();
+        assert_eq!(Some("avx"), row.uses(&["AVX".to_string()]));
+        assert_eq!(None, row.uses(&["sse4.1".to_string()]));
+    }
+
+    #[test]
+    fn test_engine_settings() {
+        let mut options = EngineOptions::default();
+        EngineSetting::parse("syntax", "att")
+            .unwrap()
+            .apply(&mut options);
+        EngineSetting::parse("detail", "off")
+            .unwrap()
+            .apply(&mut options);
+        assert_eq!(Syntax::Att, options.syntax);
+        assert!(!options.detail);
+        assert!(EngineSetting::parse("bits", "16").is_err());
+        assert!(EngineSetting::parse("skipdata", "maybe").is_err());
+
+        let engines = Engines::new(options.clone()).unwrap();
+        assert_eq!("pushq %rbp", engines.disassemble(&[0x55], 0x0)[0].1);
+        // without details there's nothing to say which extensions are needed
+        let rows = engines.disassemble(&[0xc5, 0xf4, 0x58, 0xc2], 0x0);
+        assert!(rows[0].2.is_empty());
+
+        options.bits = 16;
+        assert!(Engines::new(options).is_err());
+    }
+
+    #[test]
+    fn tests_fixed_rows() {
+        assert_eq!(
+            vec!["00401000  00 01 02 03", "00401004  04"],
+            fixed_rows(&[0, 1, 2, 3, 4], 0x401000, 4)
+        );
+    }
+
+    #[test]
+    fn tests_printable() {
+        assert_eq!("/bin/sh.", printable(b"/bin/sh\0"));
+        assert_eq!(".~..", printable(&[0x1f, 0x7e, 0x7f, 0xff]));
     }
 
     #[test]
@@ -118,6 +1149,27 @@ mod tests {
         assert_eq!("01 02 03 fa", to_hexstring(&[0x1,0x2,0x3,0xfa]));
     }
 
+    #[test]
+    fn tests_parse_address() {
+        assert_eq!(Some(0x401000), parse_address("0x401000"));
+        assert_eq!(Some(4096), parse_address("4096"));
+        assert_eq!(None, parse_address("main"));
+    }
+
+    #[test]
+    fn tests_format_immediates() {
+        assert_eq!(
+            "mov r8d, 65",
+            format_immediates("mov r8d, 0x41", Radix::Decimal)
+        );
+        assert_eq!("cmp al, 'A'", format_immediates("cmp al, 65", Radix::Char));
+        assert_eq!("cmp al, 0x41", format_immediates("cmp al, 'A'", Radix::Hex));
+        assert_eq!(
+            "mov qword ptr [rbp - 16], 0",
+            format_immediates("mov qword ptr [rbp - 0x10], 0", Radix::Decimal)
+        );
+    }
+
     #[test]
     fn tests_from_hexstring() {
         assert_eq!(vec![0x1, 0x3, 0x5, 0xba], from_hexstring("01 03 05 ba"));
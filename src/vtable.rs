@@ -0,0 +1,305 @@
+use crate::literal;
+use std::collections::{BTreeSet, HashMap};
+use tui::widgets::ListState;
+
+/// A C++ class's table of virtual functions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vtable {
+    /// named by its RTTI or a `_ZTV` symbol
+    pub class: Option<String>,
+    /// address of the first slot, where objects of the class point
+    pub address: u64,
+    /// where each slot points
+    pub slots: Vec<u64>,
+}
+
+/// The file as it's loaded, to read pointers out of.
+pub struct Image<'a> {
+    pub data: &'a [u8],
+    /// see `binary::segments`
+    pub segments: &'a [(u64, u64, u64)],
+    /// size of a pointer
+    pub width: usize,
+    /// set for PE files, whose RTTI is MSVC's and holds addresses relative to it
+    pub pe_base: Option<u64>,
+    /// what relative relocations put at each address, the file holds 0 there in position
+    /// independent files
+    pub relocated: &'a HashMap<u64, u64>,
+}
+
+impl Image<'_> {
+    pub fn to_offset(&self, va: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|(_, size, start)| (*start..start + size).contains(&va))
+            .map(|(offset, _, start)| va - start + offset)
+    }
+
+    pub fn to_va(&self, offset: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|(start, size, _)| (*start..start + size).contains(&offset))
+            .map(|(start, _, va)| offset - start + va)
+    }
+
+    fn bytes(&self, va: u64, len: usize) -> Option<&[u8]> {
+        let offset = self.to_offset(va)? as usize;
+        self.data.get(offset..offset + len)
+    }
+
+    /// the pointer at `va` once loaded
+    pub fn pointer(&self, va: u64) -> Option<u64> {
+        if let Some(target) = self.relocated.get(&va) {
+            return Some(*target);
+        }
+        let mut word = [0; 8];
+        word[..self.width].copy_from_slice(self.bytes(va, self.width)?);
+        Some(u64::from_le_bytes(word))
+    }
+
+    fn u32_at(&self, va: u64) -> Option<u32> {
+        let mut word = [0; 4];
+        word.copy_from_slice(self.bytes(va, 4)?);
+        Some(u32::from_le_bytes(word))
+    }
+
+    fn string(&self, va: u64) -> Option<String> {
+        let text = literal::read(self.data, self.to_offset(va)?)?;
+        Some(String::from_utf8_lossy(text).into_owned())
+    }
+
+    /// The class named by the RTTI in front of a vtable whose slots start at `address`: an
+    /// Itanium typeinfo, or an MSVC complete object locator in PE files.
+    fn rtti(&self, address: u64) -> Option<String> {
+        let width = self.width as u64;
+        let header = self.pointer(address.checked_sub(width)?)?;
+        let name = match self.pe_base {
+            Some(base) => {
+                // the signature is 1 where the locator's addresses are relative, on x86_64
+                let descriptor = match self.u32_at(header)? {
+                    0 => self.u32_at(header + 12)? as u64,
+                    1 => base + self.u32_at(header + 12)? as u64,
+                    _ => return None,
+                };
+                self.string(descriptor + 2 * width)?
+            }
+            None => self.string(self.pointer(header + width)?)?,
+        };
+        demangle(&name)
+    }
+
+    /// whether `address` has an Itanium vtable's header in front of it: an offset to the top
+    /// of the object (0 or a little below) and a typeinfo pointer, null without RTTI
+    fn itanium_header(&self, address: u64) -> bool {
+        let width = self.width as u64;
+        let (typeinfo, top) = match (
+            address.checked_sub(width).and_then(|x| self.pointer(x)),
+            address.checked_sub(2 * width).and_then(|x| self.pointer(x)),
+        ) {
+            (Some(typeinfo), Some(top)) => (typeinfo, top),
+            _ => return false,
+        };
+        let bits = 64 - 8 * self.width as u32;
+        let top = ((top << bits) as i64) >> bits;
+        (-0x10000..=0).contains(&top) && (typeinfo == 0 || self.to_offset(typeinfo).is_some())
+    }
+}
+
+/// The class name in an Itanium (`4Base`, `N2ns4BaseE`) or MSVC (`.?AVBase@ns@@`) mangled type
+/// name, with namespaces joined by `::`.  Templates and the like aren't taken apart.
+pub fn demangle(name: &str) -> Option<String> {
+    if let Some(rest) = name
+        .strip_prefix(".?AV")
+        .or_else(|| name.strip_prefix(".?AU"))
+    {
+        let parts = rest.split("@@").next()?.split('@').rev();
+        return Some(parts.collect::<Vec<_>>().join("::"));
+    }
+    let mut rest = name.strip_prefix('N').unwrap_or(name);
+    let mut parts = vec![];
+    while let Some(len_end) = rest.find(|x: char| !x.is_ascii_digit()).filter(|x| *x > 0) {
+        let len = rest[..len_end].parse::<usize>().ok()?;
+        parts.push(rest.get(len_end..len_end + len)?);
+        rest = &rest[len_end + len..];
+    }
+    Some(parts.join("::")).filter(|x| !x.is_empty() && matches!(rest, "" | "E"))
+}
+
+/// Every vtable in `ranges` (file offsets and sizes of data sections): runs of pointers to the
+/// starts of `functions` (file offsets) behind an Itanium header or MSVC RTTI, or where a `_ZTV`
+/// symbol among `symbols` says one is.
+pub fn detect(
+    image: &Image,
+    ranges: &[(u64, u64)],
+    functions: &BTreeSet<u64>,
+    symbols: &[(String, u64)],
+) -> Vec<Vtable> {
+    let width = image.width as u64;
+    let named = symbols
+        .iter()
+        .filter_map(|(name, va)| Some((va + 2 * width, demangle(name.strip_prefix("_ZTV")?)?)))
+        .collect::<HashMap<_, _>>();
+    let code = |va: u64| {
+        let target = image.pointer(va)?;
+        Some(target).filter(|x| image.to_offset(*x).is_some_and(|x| functions.contains(&x)))
+    };
+    let mut tables = vec![];
+    for &(offset, size) in ranges {
+        let start = match image.to_va(offset) {
+            Some(start) => start,
+            None => continue,
+        };
+        let end = start + size;
+        // vtables are pointer aligned
+        let mut va = match start % width {
+            0 => start,
+            x => start + width - x,
+        };
+        while va + width <= end {
+            let slots = (0..)
+                .map(|i| va + i * width)
+                .take_while(|x| x + width <= end)
+                .map_while(&code)
+                .collect::<Vec<_>>();
+            let len = slots.len() as u64;
+            if len == 0 {
+                va += width;
+                continue;
+            }
+            let rtti = image.rtti(va);
+            let header = rtti.is_some() || (image.pe_base.is_none() && image.itanium_header(va));
+            if header || named.contains_key(&va) {
+                tables.push(Vtable {
+                    class: rtti.or_else(|| named.get(&va).cloned()),
+                    address: va,
+                    slots,
+                });
+            }
+            va += len * width;
+        }
+    }
+    tables
+}
+
+/// The vtables found, a line for each and one for each of its slots, `:vtables`.
+#[derive(Debug)]
+pub struct Explorer {
+    pub tables: Vec<Vtable>,
+    pub state: ListState,
+}
+
+impl Explorer {
+    pub fn new(tables: Vec<Vtable>) -> Explorer {
+        let mut state = ListState::default();
+        state.select(Some(0));
+        Explorer { tables, state }
+    }
+
+    /// the table and slot on each line, None on a table's own line
+    fn lines(&self) -> Vec<(usize, Option<usize>)> {
+        self.tables
+            .iter()
+            .enumerate()
+            .flat_map(|(i, table)| {
+                std::iter::once((i, None)).chain((0..table.slots.len()).map(move |x| (i, Some(x))))
+            })
+            .collect()
+    }
+
+    /// a line for each table and slot, slots pointing at what `name` calls their target
+    pub fn labels(&self, name: impl Fn(u64) -> String) -> Vec<String> {
+        self.lines()
+            .into_iter()
+            .map(|(i, slot)| {
+                let table = &self.tables[i];
+                match slot {
+                    None => format!(
+                        "vtable for {} at {:#x}, {} slot(s)",
+                        table.class.as_deref().unwrap_or("?"),
+                        table.address,
+                        table.slots.len()
+                    ),
+                    Some(slot) => format!(
+                        "  [{}] {:#x} {}",
+                        slot,
+                        table.slots[slot],
+                        name(table.slots[slot])
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// the table and slot selected, the first slot when a table's own line is
+    pub fn selected(&self) -> Option<(&Vtable, usize)> {
+        let (table, slot) = *self.lines().get(self.state.selected()?)?;
+        Some((&self.tables[table], slot.unwrap_or(0)))
+    }
+
+    /// move the selection by `delta` lines, stopping at either end
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.lines().len().saturating_sub(1) as isize;
+        let index = self.state.selected().unwrap_or(0) as isize + delta;
+        self.state.select(Some(index.max(0).min(last) as usize));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle() {
+        assert_eq!(Some("Base".to_string()), demangle("4Base"));
+        assert_eq!(Some("ns::Derived".to_string()), demangle("N2ns7DerivedE"));
+        assert_eq!(
+            Some("ns::Derived".to_string()),
+            demangle(".?AVDerived@ns@@")
+        );
+        assert_eq!(None, demangle("main"));
+    }
+
+    #[test]
+    fn test_detect() {
+        // one segment loaded at 0x1000: code at 0x1000 and 0x1010, then at 0x1040 a vtable
+        // (offset to top, typeinfo, two slots) whose typeinfo at 0x1080 names "4Base"
+        let mut data = vec![0; 0x100];
+        let words = [
+            (0x40, 0),
+            (0x48, 0x1080),
+            (0x50, 0x1000),
+            (0x58, 0x1010),
+            (0x88, 0x1090),
+        ];
+        for (at, word) in words.iter() {
+            data[*at..at + 8].copy_from_slice(&(*word as u64).to_le_bytes());
+        }
+        data[0x90..0x95].copy_from_slice(b"4Base");
+        // a function pointer without a header, like .init_array
+        data[0xc0..0xc8].copy_from_slice(&0x1000u64.to_le_bytes());
+        data[0xb8..0xc0].copy_from_slice(&0x1234u64.to_le_bytes());
+        let relocated = HashMap::new();
+        let image = Image {
+            data: &data,
+            segments: &[(0, 0x100, 0x1000)],
+            width: 8,
+            pe_base: None,
+            relocated: &relocated,
+        };
+        let functions = [0, 0x10].iter().copied().collect();
+        let tables = detect(&image, &[(0x20, 0xe0)], &functions, &[]);
+        assert_eq!(
+            vec![Vtable {
+                class: Some("Base".to_string()),
+                address: 0x1050,
+                slots: vec![0x1000, 0x1010],
+            }],
+            tables
+        );
+
+        let explorer = Explorer::new(tables);
+        let labels = explorer.labels(|x| format!("fcn.{:x}", x));
+        assert_eq!("vtable for Base at 0x1050, 2 slot(s)", labels[0]);
+        assert_eq!("  [1] 0x1010 fcn.1010", labels[2]);
+    }
+}
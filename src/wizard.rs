@@ -0,0 +1,193 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How a hook saves the state of the code it's spliced into around the call to the stub.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Template {
+    /// x86_64 System V: the caller saved registers (xmm0-15 included) and flags, stepping over
+    /// the red zone first
+    SysV64,
+    /// x86_64 Windows: the caller saved registers (xmm0-5 included) and flags, with shadow
+    /// space for the callee
+    Win64,
+    /// 32 bit x86: every general purpose register and the flags
+    X86,
+}
+
+impl Template {
+    /// the usual template for code built for `arch` (see `binary::arch`), `pe` for Windows
+    pub fn detect(arch: &str, pe: bool) -> Template {
+        match (arch, pe) {
+            ("x86", _) => Template::X86,
+            (_, true) => Template::Win64,
+            _ => Template::SysV64,
+        }
+    }
+
+    /// The instructions which call `stub` and put everything back afterwards.  The stack is
+    /// 16 byte aligned at the call.
+    pub fn call(self, stub: u64) -> Vec<String> {
+        let mut lines = vec![];
+        match self {
+            Template::SysV64 | Template::Win64 => {
+                // rsi and rdi are only callee saved on Windows
+                let saved: &[&str] = match self {
+                    Template::SysV64 => {
+                        &["rax", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11"]
+                    }
+                    _ => &["rax", "rcx", "rdx", "r8", "r9", "r10", "r11"],
+                };
+                let xmm = if self == Template::SysV64 { 16 } else { 6 };
+                // the shadow space goes under the saved xmm registers
+                let shadow = if self == Template::Win64 { 0x20 } else { 0 };
+                if self == Template::SysV64 {
+                    // the code around the hook may be using the red zone
+                    lines.push("lea rsp, [rsp - 0x80]".to_string());
+                }
+                lines.push("pushfq".to_string());
+                lines.extend(saved.iter().map(|x| format!("push {}", x)));
+                lines.push("push rbp".to_string());
+                lines.push("mov rbp, rsp".to_string());
+                lines.push("and rsp, -16".to_string());
+                lines.push(format!("sub rsp, {:#x}", shadow + xmm * 0x10));
+                let slot = |i: usize| format!("[rsp + {:#x}]", shadow + i * 0x10);
+                lines.extend((0..xmm).map(|i| format!("movdqu {}, xmm{}", slot(i), i)));
+                lines.push(format!("call {:#x}", stub));
+                lines.extend((0..xmm).map(|i| format!("movdqu xmm{}, {}", i, slot(i))));
+                lines.push("mov rsp, rbp".to_string());
+                lines.push("pop rbp".to_string());
+                lines.extend(saved.iter().rev().map(|x| format!("pop {}", x)));
+                lines.push("popfq".to_string());
+                if self == Template::SysV64 {
+                    lines.push("lea rsp, [rsp + 0x80]".to_string());
+                }
+            }
+            Template::X86 => {
+                lines.push("pushfd".to_string());
+                lines.push("pushad".to_string());
+                lines.push("mov ebp, esp".to_string());
+                lines.push("and esp, -16".to_string());
+                lines.push(format!("call {:#x}", stub));
+                lines.push("mov esp, ebp".to_string());
+                lines.push("popad".to_string());
+                lines.push("popfd".to_string());
+            }
+        }
+        lines
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Template::SysV64 => "sysv64",
+            Template::Win64 => "win64",
+            Template::X86 => "x86",
+        })
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sysv64" => Ok(Template::SysV64),
+            "win64" => Ok(Template::Win64),
+            "x86" => Ok(Template::X86),
+            _ => Err(format!("{} isn't a template, try sysv64, win64 or x86", s)),
+        }
+    }
+}
+
+/// What the wizard is asking.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Stub,
+    Template,
+    Confirm,
+}
+
+/// The questions `:trampoline` asks before hooking the entry of a function: which stub to call,
+/// how to save state around it, and whether the result looks right.
+#[derive(Debug)]
+pub struct Wizard {
+    /// the function being hooked
+    pub function: String,
+    pub step: Step,
+    /// the address the hook calls, once it's been given
+    pub stub: Option<u64>,
+    pub template: Template,
+}
+
+impl Wizard {
+    pub fn new(function: String, template: Template) -> Wizard {
+        Wizard {
+            function,
+            step: Step::Stub,
+            stub: None,
+            template,
+        }
+    }
+
+    pub fn question(&self) -> String {
+        match self.step {
+            Step::Stub => format!("hook {}, call (function or address):", self.function),
+            Step::Template => format!("save state as (sysv64, win64, x86) [{}]:", self.template),
+            Step::Confirm => "hook it? (y/n):".to_string(),
+        }
+    }
+
+    /// Take `stub`, resolved from what was typed, and move on to the template.
+    pub fn set_stub(&mut self, stub: u64) {
+        self.stub = Some(stub);
+        self.step = Step::Template;
+    }
+
+    /// Take the template typed, or keep the suggested one if nothing was, and move on to
+    /// confirming.
+    pub fn set_template(&mut self, answer: &str) -> Result<(), String> {
+        if !answer.trim().is_empty() {
+            self.template = answer.trim().parse()?;
+        }
+        self.step = Step::Confirm;
+        Ok(())
+    }
+
+    /// the saving and calling part of the hook, once the stub is known
+    pub fn prelude(&self) -> Option<Vec<String>> {
+        Some(self.template.call(self.stub?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wizard() {
+        assert_eq!(Template::Win64, Template::detect("x86_64", true));
+        assert_eq!(Template::X86, Template::detect("x86", false));
+
+        let mut wizard = Wizard::new("main".to_string(), Template::SysV64);
+        assert_eq!(None, wizard.prelude());
+        wizard.set_stub(0x401000);
+        assert!(wizard.set_template("arm").is_err());
+        assert_eq!(Step::Template, wizard.step);
+        wizard.set_template("").unwrap();
+        assert_eq!(Step::Confirm, wizard.step);
+        let prelude = wizard.prelude().unwrap();
+        assert_eq!("lea rsp, [rsp - 0x80]", prelude[0]);
+        assert!(prelude.contains(&"call 0x401000".to_string()));
+        assert!(prelude.contains(&"sub rsp, 0x100".to_string()));
+        assert!(prelude.contains(&"movdqu [rsp + 0xf0], xmm15".to_string()));
+        assert!(prelude.contains(&"movdqu xmm0, [rsp + 0x0]".to_string()));
+
+        wizard.set_template("win64").unwrap();
+        let prelude = wizard.prelude().unwrap();
+        assert!(!prelude.contains(&"push rsi".to_string()));
+        assert!(prelude.contains(&"sub rsp, 0x80".to_string()));
+        assert!(prelude.contains(&"movdqu [rsp + 0x70], xmm5".to_string()));
+        assert!(!prelude.iter().any(|x| x.contains("xmm6")));
+    }
+}
@@ -0,0 +1,75 @@
+//! Running YARA rules over the binary with the `yara` command line tool.
+
+use crate::util::run;
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// A string of a rule matching at a file offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub rule: String,
+    /// the string's identifier, e.g. `$key`
+    pub identifier: String,
+    pub offset: u64,
+}
+
+/// every string match of the rules in `rules` against `file`
+pub fn scan(rules: &Path, file: &Path) -> Result<Vec<Match>, Box<dyn Error>> {
+    let output = run(Command::new("yara")
+        .arg("--print-strings")
+        .arg(rules)
+        .arg(file))?;
+    Ok(parse(&String::from_utf8_lossy(&output)))
+}
+
+/// `yara -s` prints a `<rule> <file>` line for each matching rule followed by an
+/// `<offset>:<identifier>: <data>` line for each string which matched
+fn parse(output: &str) -> Vec<Match> {
+    let mut matches = vec![];
+    let mut rule = None;
+    for line in output.lines() {
+        if !line.starts_with("0x") {
+            rule = line.split_whitespace().next();
+            continue;
+        }
+        let mut fields = line.splitn(3, ':');
+        let (offset, identifier) = match (fields.next(), fields.next()) {
+            (Some(offset), Some(identifier)) => (offset, identifier),
+            _ => continue,
+        };
+        if let (Some(rule), Some(offset)) = (rule, crate::util::parse_address(offset)) {
+            matches.push(Match {
+                rule: rule.to_string(),
+                identifier: identifier.to_string(),
+                offset,
+            });
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let output = "Packed ./a.out\n0x1040:$upx: UPX!\n0x2000:$stub: { 60 BE }\nOther ./a.out\n";
+        assert_eq!(
+            vec![
+                Match {
+                    rule: "Packed".to_string(),
+                    identifier: "$upx".to_string(),
+                    offset: 0x1040
+                },
+                Match {
+                    rule: "Packed".to_string(),
+                    identifier: "$stub".to_string(),
+                    offset: 0x2000
+                },
+            ],
+            parse(output)
+        );
+    }
+}